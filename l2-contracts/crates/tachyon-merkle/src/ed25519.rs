@@ -0,0 +1,175 @@
+//! Reading consensus vote signatures out of Ed25519 program instructions.
+//!
+//! Programs that accept signed votes require the transaction to carry Ed25519
+//! program instructions for them and read those instructions back through the
+//! instructions sysvar. Only self-contained entries are accepted: every offset
+//! must point into the Ed25519 instruction itself, so the bytes returned here
+//! are exactly the bytes the Ed25519 program verified.
+//!
+//! Votes on the same root sign the same message, so the node packs all of them
+//! into one instruction that stores the message once.
+
+use crate::VOTE_MESSAGE_LEN;
+#[cfg(feature = "solana-program")]
+use solana_program::{account_info::AccountInfo, ed25519_program, program_error::ProgramError, sysvar::instructions};
+
+const OFFSETS_START: usize = 2;
+const OFFSETS_LEN: usize = 14;
+const PUBKEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+
+/// Instruction index meaning "this instruction" in Ed25519 signature offsets
+const THIS_INSTRUCTION: u16 = u16::MAX;
+
+/// A vote signature checked by the Ed25519 program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedVote {
+    pub pubkey: [u8; 32],
+    pub signature: [u8; 64],
+    pub message: [u8; VOTE_MESSAGE_LEN],
+}
+
+impl SignedVote {
+    pub fn matches(&self, pubkey: &[u8; 32], signature: &[u8; 64], message: &[u8; VOTE_MESSAGE_LEN]) -> bool {
+        self.pubkey == *pubkey && self.signature == *signature && self.message == *message
+    }
+}
+
+/// Data of one Ed25519 program instruction verifying each `(pubkey, signature)`
+/// over the shared `message`. Returns `None` for more than 255 signers or data
+/// too long for 16-bit offsets.
+pub fn vote_signatures_data(message: &[u8; VOTE_MESSAGE_LEN], signers: &[([u8; 32], [u8; 64])]) -> Option<Vec<u8>> {
+    let count = u8::try_from(signers.len()).ok()?;
+    let message_offset = OFFSETS_START + signers.len() * OFFSETS_LEN;
+    let signers_offset = message_offset + VOTE_MESSAGE_LEN;
+    let len = signers_offset + signers.len() * (PUBKEY_LEN + SIGNATURE_LEN);
+    u16::try_from(len).ok()?;
+
+    let mut data = Vec::with_capacity(len);
+    data.extend_from_slice(&[count, 0]);
+    for i in 0..signers.len() {
+        let pubkey_offset = signers_offset + i * (PUBKEY_LEN + SIGNATURE_LEN);
+        let signature_offset = pubkey_offset + PUBKEY_LEN;
+        for field in [
+            signature_offset as u16,
+            THIS_INSTRUCTION,
+            pubkey_offset as u16,
+            THIS_INSTRUCTION,
+            message_offset as u16,
+            VOTE_MESSAGE_LEN as u16,
+            THIS_INSTRUCTION,
+        ] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+    data.extend_from_slice(message);
+    for (pubkey, signature) in signers {
+        data.extend_from_slice(pubkey);
+        data.extend_from_slice(signature);
+    }
+
+    Some(data)
+}
+
+/// Parse the data of one Ed25519 program instruction and append every
+/// vote-sized signature it verifies. Returns `None` if the data is malformed
+/// or any entry references another instruction.
+pub fn parse_vote_signatures(data: &[u8], votes: &mut Vec<SignedVote>) -> Option<()> {
+    let read_u16 = |at: usize| data.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let read = |at: u16, len: usize| data.get(at as usize..at as usize + len);
+
+    let count = *data.first()? as usize;
+    for i in 0..count {
+        let base = OFFSETS_START + i * OFFSETS_LEN;
+        let signature_offset = read_u16(base)?;
+        let signature_ix = read_u16(base + 2)?;
+        let pubkey_offset = read_u16(base + 4)?;
+        let pubkey_ix = read_u16(base + 6)?;
+        let message_offset = read_u16(base + 8)?;
+        let message_len = read_u16(base + 10)?;
+        let message_ix = read_u16(base + 12)?;
+
+        if signature_ix != THIS_INSTRUCTION || pubkey_ix != THIS_INSTRUCTION || message_ix != THIS_INSTRUCTION {
+            return None;
+        }
+        if message_len as usize != VOTE_MESSAGE_LEN {
+            continue;
+        }
+
+        votes.push(SignedVote {
+            pubkey: read(pubkey_offset, 32)?.try_into().ok()?,
+            signature: read(signature_offset, 64)?.try_into().ok()?,
+            message: read(message_offset, VOTE_MESSAGE_LEN)?.try_into().ok()?,
+        });
+    }
+
+    Some(())
+}
+
+/// Collect every vote signature verified by the Ed25519 instructions that
+/// precede the current one, read from the instructions sysvar. Returns
+/// `Ok(None)` if any of them is malformed.
+#[cfg(feature = "solana-program")]
+pub fn load_vote_signatures(instructions_sysvar: &AccountInfo) -> Result<Option<Vec<SignedVote>>, ProgramError> {
+    let current = instructions::load_current_index_checked(instructions_sysvar)?;
+    let mut votes = Vec::new();
+
+    for index in 0..current {
+        let ix = instructions::load_instruction_at_checked(index as usize, instructions_sysvar)?;
+        if ix.program_id == ed25519_program::ID && parse_vote_signatures(&ix.data, &mut votes).is_none() {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(votes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vote_message;
+    use solana_ed25519_program::new_ed25519_instruction_with_signature;
+
+    #[test]
+    fn test_parses_instruction_built_by_node() {
+        let message = vote_message(9, &[7u8; 32]);
+        let ix = new_ed25519_instruction_with_signature(&message, &[3u8; 64], &[5u8; 32]);
+
+        let mut votes = Vec::new();
+        parse_vote_signatures(&ix.data, &mut votes).unwrap();
+
+        assert_eq!(votes.len(), 1);
+        assert!(votes[0].matches(&[5u8; 32], &[3u8; 64], &message));
+    }
+
+    #[test]
+    fn test_parses_packed_votes() {
+        let message = vote_message(9, &[7u8; 32]);
+        let signers: Vec<_> = (0..10u8).map(|i| ([i; 32], [i + 100; 64])).collect();
+        let data = vote_signatures_data(&message, &signers).unwrap();
+
+        // The message is stored once, not once per signer
+        assert_eq!(data.len(), OFFSETS_START + 10 * (OFFSETS_LEN + PUBKEY_LEN + SIGNATURE_LEN) + VOTE_MESSAGE_LEN);
+
+        let mut votes = Vec::new();
+        parse_vote_signatures(&data, &mut votes).unwrap();
+        assert_eq!(votes.len(), 10);
+        for (vote, (pubkey, signature)) in votes.iter().zip(&signers) {
+            assert!(vote.matches(pubkey, signature, &message));
+        }
+
+        assert!(vote_signatures_data(&message, &vec![([0; 32], [0; 64]); 256]).is_none());
+    }
+
+    #[test]
+    fn test_rejects_foreign_offsets() {
+        let message = vote_message(9, &[7u8; 32]);
+        let mut data = new_ed25519_instruction_with_signature(&message, &[3u8; 64], &[5u8; 32]).data;
+
+        // Point the message at instruction 0 instead of the Ed25519 instruction
+        data[OFFSETS_START + 12..OFFSETS_START + 14].copy_from_slice(&0u16.to_le_bytes());
+        assert!(parse_vote_signatures(&data, &mut Vec::new()).is_none());
+
+        assert!(parse_vote_signatures(&data[..5], &mut Vec::new()).is_none());
+    }
+}
@@ -0,0 +1,271 @@
+//! Canonical Merkle commitment shared by the Tachyon oracle node and the L2 programs.
+//!
+//! Leaf layout (60 bytes, little-endian integers):
+//!
+//! ```text
+//! asset_id (32) || price (i64) || confidence (i64) || expo (i32) || timestamp (i64)
+//! ```
+//!
+//! `price` and `confidence` are mantissas of the same [`Price`] exponent, `expo`.
+//!
+//! Tree rules:
+//! - leaves and interior nodes are hashed with keccak256
+//! - every pair is hashed smaller-first, so proofs carry no direction bits
+//! - an unpaired node at the end of a level is promoted to the next level unchanged
+//! - the root of an empty tree is all zeroes
+//!
+//! Consensus votes sign [`vote_message`] for the batch they endorse.
+
+use solana_keccak_hasher::hashv;
+
+pub mod ed25519;
+pub mod price;
+
+pub use price::{Price, PriceError};
+
+pub type Hash = [u8; 32];
+
+/// Size of a serialized price leaf
+pub const LEAF_LEN: usize = 60;
+
+/// Root committed for a batch with no feeds
+pub const EMPTY_ROOT: Hash = [0u8; 32];
+
+/// Derive the 32-byte asset id for a feed symbol such as "BTC/USD"
+pub fn asset_id(symbol: &str) -> Hash {
+    hashv(&[symbol.as_bytes()]).to_bytes()
+}
+
+/// A single price commitment as stored in the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceLeaf {
+    pub asset_id: Hash,
+    pub price: i64,
+    pub confidence: i64,
+    pub expo: i32,
+    pub timestamp: i64,
+}
+
+impl PriceLeaf {
+    pub fn new(symbol: &str, price: i64, confidence: i64, expo: i32, timestamp: i64) -> Self {
+        Self {
+            asset_id: asset_id(symbol),
+            price,
+            confidence,
+            expo,
+            timestamp,
+        }
+    }
+
+    pub fn price(&self) -> Price {
+        Price::new(self.price, self.expo)
+    }
+
+    pub fn to_bytes(&self) -> [u8; LEAF_LEN] {
+        let mut bytes = [0u8; LEAF_LEN];
+        bytes[..32].copy_from_slice(&self.asset_id);
+        bytes[32..40].copy_from_slice(&self.price.to_le_bytes());
+        bytes[40..48].copy_from_slice(&self.confidence.to_le_bytes());
+        bytes[48..52].copy_from_slice(&self.expo.to_le_bytes());
+        bytes[52..60].copy_from_slice(&self.timestamp.to_le_bytes());
+        bytes
+    }
+
+    pub fn hash(&self) -> Hash {
+        hashv(&[&self.to_bytes()]).to_bytes()
+    }
+}
+
+/// Size of the message a validator signs to vote for a batch root
+pub const VOTE_MESSAGE_LEN: usize = 40;
+
+/// Bytes signed by a consensus vote: `batch_number (u64 LE) || root`
+pub fn vote_message(batch_number: u64, root: &Hash) -> [u8; VOTE_MESSAGE_LEN] {
+    let mut message = [0u8; VOTE_MESSAGE_LEN];
+    message[..8].copy_from_slice(&batch_number.to_le_bytes());
+    message[8..].copy_from_slice(root);
+    message
+}
+
+/// Hash two sibling nodes, smaller one first
+pub fn hash_pair(a: &Hash, b: &Hash) -> Hash {
+    if a <= b {
+        hashv(&[a, b]).to_bytes()
+    } else {
+        hashv(&[b, a]).to_bytes()
+    }
+}
+
+/// Climb from a leaf hash to the root using the sibling path
+pub fn compute_root(leaf: Hash, proof: &[Hash]) -> Hash {
+    proof.iter().fold(leaf, |current, sibling| hash_pair(&current, sibling))
+}
+
+/// Check that `leaf` is committed under `root`
+pub fn verify(leaf: Hash, proof: &[Hash], root: &Hash) -> bool {
+    compute_root(leaf, proof) == *root
+}
+
+/// Merkle tree over leaf hashes, kept level by level from the leaves up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    pub fn from_leaves(leaves: Vec<Hash>) -> Self {
+        if leaves.is_empty() {
+            return Self { levels: vec![] };
+        }
+
+        let mut levels = vec![leaves];
+        while levels.last().map_or(0, Vec::len) > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|chunk| match chunk {
+                    [left, right] => hash_pair(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Self { levels }
+    }
+
+    /// Rebuild a tree from the flattened node list produced by [`MerkleTree::nodes`].
+    /// Returns `None` if the list is not a well-formed tree.
+    pub fn from_nodes(nodes: &[Hash]) -> Option<Self> {
+        let leaf_count = (0..=nodes.len()).find(|&n| flattened_len(n) == nodes.len())?;
+        let tree = Self::from_leaves(nodes[..leaf_count].to_vec());
+        (tree.nodes() == nodes).then_some(tree)
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.levels.first().map_or(0, Vec::len)
+    }
+
+    pub fn root(&self) -> Hash {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or(EMPTY_ROOT)
+    }
+
+    /// Sibling path for the leaf at `index`, bottom-up
+    pub fn proof(&self, index: usize) -> Option<Vec<Hash>> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+
+        let mut proof = Vec::new();
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                proof.push(level[sibling]);
+            }
+            index /= 2;
+        }
+
+        Some(proof)
+    }
+
+    /// All nodes flattened level by level, leaves first and root last
+    pub fn nodes(&self) -> Vec<Hash> {
+        self.levels.iter().flatten().copied().collect()
+    }
+}
+
+fn flattened_len(leaf_count: usize) -> usize {
+    let mut total = 0;
+    let mut width = leaf_count;
+    while width > 1 {
+        total += width;
+        width = width.div_ceil(2);
+    }
+    total + width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(n: usize) -> Vec<PriceLeaf> {
+        (0..n)
+            .map(|i| PriceLeaf::new(&format!("ASSET{}/USD", i), 1_000 + i as i64, 10, -8, 1_700_000_000))
+            .collect()
+    }
+
+    #[test]
+    fn test_leaf_layout() {
+        let leaf = PriceLeaf::new("BTC/USD", 42, -7, -8, 1_700_000_000);
+        let bytes = leaf.to_bytes();
+
+        assert_eq!(&bytes[..32], &asset_id("BTC/USD"));
+        assert_eq!(&bytes[32..40], &42i64.to_le_bytes());
+        assert_eq!(&bytes[40..48], &(-7i64).to_le_bytes());
+        assert_eq!(&bytes[48..52], &(-8i32).to_le_bytes());
+        assert_eq!(&bytes[52..60], &1_700_000_000i64.to_le_bytes());
+        assert_eq!(leaf.price(), Price::new(42, -8));
+        assert_eq!(leaf.hash(), hashv(&[&bytes]).to_bytes());
+    }
+
+    #[test]
+    fn test_every_proof_verifies() {
+        for n in 1..=17 {
+            let leaves = leaves(n);
+            let tree = MerkleTree::from_leaves(leaves.iter().map(PriceLeaf::hash).collect());
+
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(i).unwrap();
+                assert!(verify(leaf.hash(), &proof, &tree.root()), "n={} i={}", n, i);
+            }
+            assert!(tree.proof(n).is_none());
+        }
+    }
+
+    #[test]
+    fn test_tampered_leaf_fails() {
+        let leaves = leaves(5);
+        let tree = MerkleTree::from_leaves(leaves.iter().map(PriceLeaf::hash).collect());
+        let proof = tree.proof(2).unwrap();
+
+        let mut forged = leaves[2];
+        forged.price += 1;
+        assert!(!verify(forged.hash(), &proof, &tree.root()));
+
+        // Same mantissa read at another exponent
+        let mut rescaled = leaves[2];
+        rescaled.expo += 1;
+        assert!(!verify(rescaled.hash(), &proof, &tree.root()));
+    }
+
+    #[test]
+    fn test_nodes_round_trip() {
+        for n in 0..=9 {
+            let tree = MerkleTree::from_leaves(leaves(n).iter().map(PriceLeaf::hash).collect());
+            let rebuilt = MerkleTree::from_nodes(&tree.nodes()).unwrap();
+            assert_eq!(rebuilt, tree);
+        }
+
+        let mut nodes = MerkleTree::from_leaves(leaves(4).iter().map(PriceLeaf::hash).collect()).nodes();
+        nodes[5][0] ^= 1;
+        assert!(MerkleTree::from_nodes(&nodes).is_none());
+        assert!(MerkleTree::from_nodes(&nodes[..4]).is_none());
+    }
+
+    #[test]
+    fn test_empty_and_single() {
+        assert_eq!(MerkleTree::from_leaves(vec![]).root(), EMPTY_ROOT);
+
+        let leaf = leaves(1)[0].hash();
+        let tree = MerkleTree::from_leaves(vec![leaf]);
+        assert_eq!(tree.root(), leaf);
+        assert!(tree.proof(0).unwrap().is_empty());
+    }
+}
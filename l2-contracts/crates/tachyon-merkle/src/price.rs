@@ -0,0 +1,445 @@
+//! Fixed-point prices: an integer mantissa scaled by a power of ten.
+//!
+//! `Price { mantissa: 6_512_345, expo: -2 }` is 65123.45. Exchange quotes are
+//! parsed from their decimal text without going through `f64`, and every
+//! change of exponent is checked: a price converts exactly, rounds half away
+//! from zero when digits are dropped, or fails with [`PriceError::Overflow`].
+
+use core::cmp::Ordering;
+use core::fmt;
+use core::str::FromStr;
+
+/// Finest exponent a price may use
+pub const MIN_EXPO: i32 = -18;
+
+/// Coarsest exponent a price may use
+pub const MAX_EXPO: i32 = 18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceError {
+    /// Not a decimal number
+    Invalid,
+    /// The mantissa does not fit an i64 at the requested exponent
+    Overflow,
+    /// Exponent outside `MIN_EXPO..=MAX_EXPO`
+    ExpoOutOfRange(i32),
+    /// Divided by a zero price
+    DivideByZero,
+}
+
+impl fmt::Display for PriceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceError::Invalid => write!(f, "not a decimal number"),
+            PriceError::Overflow => write!(f, "price does not fit a 64-bit mantissa"),
+            PriceError::ExpoOutOfRange(expo) => {
+                write!(f, "exponent {} outside {}..={}", expo, MIN_EXPO, MAX_EXPO)
+            }
+            PriceError::DivideByZero => write!(f, "division by a zero price"),
+        }
+    }
+}
+
+impl std::error::Error for PriceError {}
+
+/// `mantissa × 10^expo`. Equality and ordering compare values, so
+/// 1.50 (150, -2) equals 1.5 (15, -1).
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Price {
+    pub mantissa: i64,
+    pub expo: i32,
+}
+
+impl Price {
+    pub const fn new(mantissa: i64, expo: i32) -> Self {
+        Self { mantissa, expo }
+    }
+
+    /// Exact value of a decimal string such as "65123.45", "-0.0012" or
+    /// "1.5e-7", at the exponent its digits call for
+    pub fn parse(text: &str) -> Result<Self, PriceError> {
+        let text = text.trim();
+        let (negative, unsigned) = match text.as_bytes().first() {
+            Some(b'-') => (true, &text[1..]),
+            Some(b'+') => (false, &text[1..]),
+            _ => (false, text),
+        };
+        let (number, exponent) = match unsigned.find(['e', 'E']) {
+            Some(at) => (
+                &unsigned[..at],
+                unsigned[at + 1..].parse::<i32>().map_err(|_| PriceError::Invalid)?,
+            ),
+            None => (unsigned, 0),
+        };
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+
+        let digits = || integer.bytes().chain(fraction.bytes());
+        if integer.len() + fraction.len() == 0 || !digits().all(|b| b.is_ascii_digit()) {
+            return Err(PriceError::Invalid);
+        }
+
+        let mut expo = i32::try_from(fraction.len())
+            .ok()
+            .and_then(|decimals| exponent.checked_sub(decimals))
+            .ok_or(PriceError::Invalid)?;
+
+        // Leading zeros carry nothing; trailing zeros only while they are
+        // needed to stay within range
+        let significant: Vec<u8> = digits().skip_while(|&b| b == b'0').collect();
+        if significant.is_empty() {
+            return Ok(Self::new(0, expo.clamp(MIN_EXPO, MAX_EXPO)));
+        }
+        let mut len = significant.len();
+        while significant[len - 1] == b'0' && (len > 18 || expo < MIN_EXPO) {
+            len -= 1;
+            expo += 1;
+        }
+        if expo < MIN_EXPO {
+            return Err(PriceError::ExpoOutOfRange(expo));
+        }
+
+        let mut mantissa: i64 = 0;
+        for digit in &significant[..len] {
+            mantissa = mantissa
+                .checked_mul(10)
+                .and_then(|m| m.checked_add(i64::from(digit - b'0')))
+                .ok_or(PriceError::Overflow)?;
+        }
+        let price = Self::new(if negative { -mantissa } else { mantissa }, expo);
+
+        if expo > MAX_EXPO {
+            price.rescale(MAX_EXPO)
+        } else {
+            Ok(price)
+        }
+    }
+
+    /// The same value at `expo`, rounding half away from zero when the new
+    /// exponent is coarser
+    pub fn rescale(self, expo: i32) -> Result<Self, PriceError> {
+        round_to(i128::from(self.mantissa), self.expo, expo)
+    }
+
+    /// `self × other` at `expo`, from the exact product, rounded once
+    pub fn checked_mul(self, other: Self, expo: i32) -> Result<Self, PriceError> {
+        let product = i128::from(self.mantissa) * i128::from(other.mantissa);
+        round_to(product, self.expo + other.expo, expo)
+    }
+
+    /// `self / divisor` at `expo`, rounded once
+    pub fn checked_div(self, divisor: Self, expo: i32) -> Result<Self, PriceError> {
+        check_expo(expo)?;
+        if divisor.mantissa == 0 {
+            return Err(PriceError::DivideByZero);
+        }
+        if self.mantissa == 0 {
+            return Ok(Self::new(0, expo));
+        }
+
+        // The quotient's mantissa at `expo` is a × 10^shift / b
+        let shift = self.expo - divisor.expo - expo;
+        let (mut numerator, mut denominator) = (i128::from(self.mantissa), i128::from(divisor.mantissa));
+        if shift >= 0 {
+            numerator = pow10(shift.unsigned_abs())
+                .and_then(|factor| numerator.checked_mul(factor))
+                .ok_or(PriceError::Overflow)?;
+        } else {
+            match pow10(shift.unsigned_abs()).and_then(|factor| denominator.checked_mul(factor)) {
+                Some(scaled) => denominator = scaled,
+                // Far below one unit at `expo`
+                None => return Ok(Self::new(0, expo)),
+            }
+        }
+        if denominator < 0 {
+            (numerator, denominator) = (-numerator, -denominator);
+        }
+
+        i64::try_from(div_round(numerator, denominator))
+            .map(|mantissa| Self::new(mantissa, expo))
+            .map_err(|_| PriceError::Overflow)
+    }
+
+    /// A computed value (an average, a ratio) at `expo`, rounded to the nearest unit
+    pub fn from_f64(value: f64, expo: i32) -> Result<Self, PriceError> {
+        check_expo(expo)?;
+        if !value.is_finite() {
+            return Err(PriceError::Invalid);
+        }
+
+        let scaled = if expo <= 0 {
+            value * 10f64.powi(-expo)
+        } else {
+            value / 10f64.powi(expo)
+        };
+        let rounded = scaled.round();
+        // i64::MAX as f64 is 2^63, one past the largest mantissa
+        if rounded < i64::MIN as f64 || rounded >= i64::MAX as f64 {
+            return Err(PriceError::Overflow);
+        }
+
+        Ok(Self::new(rounded as i64, expo))
+    }
+
+    /// Nearest f64, for statistics and display
+    pub fn to_f64(self) -> f64 {
+        if self.expo <= 0 {
+            self.mantissa as f64 / 10f64.powi(-self.expo)
+        } else {
+            self.mantissa as f64 * 10f64.powi(self.expo)
+        }
+    }
+
+    pub fn checked_add(self, other: Self) -> Result<Self, PriceError> {
+        let expo = self.expo.min(other.expo);
+        let (a, b) = (self.rescale(expo)?, other.rescale(expo)?);
+        a.mantissa
+            .checked_add(b.mantissa)
+            .map(|mantissa| Self::new(mantissa, expo))
+            .ok_or(PriceError::Overflow)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Result<Self, PriceError> {
+        let negated = other.mantissa.checked_neg().ok_or(PriceError::Overflow)?;
+        self.checked_add(Self::new(negated, other.expo))
+    }
+
+    /// Halfway between two prices: exact, one digit finer if needed, unless
+    /// that digit does not fit, in which case it rounds
+    pub fn midpoint(self, other: Self) -> Result<Self, PriceError> {
+        let expo = self.expo.min(other.expo);
+        let (a, b) = (self.rescale(expo)?, other.rescale(expo)?);
+        let sum = i128::from(a.mantissa) + i128::from(b.mantissa);
+
+        if sum % 2 != 0 && expo > MIN_EXPO {
+            if let Ok(mantissa) = i64::try_from(sum * 5) {
+                return Ok(Self::new(mantissa, expo - 1));
+            }
+        }
+        i64::try_from(div_round(sum, 2))
+            .map(|mantissa| Self::new(mantissa, expo))
+            .map_err(|_| PriceError::Overflow)
+    }
+}
+
+fn check_expo(expo: i32) -> Result<(), PriceError> {
+    if (MIN_EXPO..=MAX_EXPO).contains(&expo) {
+        Ok(())
+    } else {
+        Err(PriceError::ExpoOutOfRange(expo))
+    }
+}
+
+/// `mantissa × 10^from` at `expo`, rounding half away from zero when `expo`
+/// is coarser
+fn round_to(mantissa: i128, from: i32, expo: i32) -> Result<Price, PriceError> {
+    check_expo(expo)?;
+    if mantissa == 0 {
+        return Ok(Price::new(0, expo));
+    }
+
+    let scaled = if expo <= from {
+        pow10(from.abs_diff(expo))
+            .and_then(|factor| mantissa.checked_mul(factor))
+            .ok_or(PriceError::Overflow)?
+    } else {
+        // Every digit dropped rounds to zero
+        pow10(expo.abs_diff(from)).map_or(0, |divisor| div_round(mantissa, divisor))
+    };
+
+    i64::try_from(scaled)
+        .map(|mantissa| Price::new(mantissa, expo))
+        .map_err(|_| PriceError::Overflow)
+}
+
+fn pow10(exponent: u32) -> Option<i128> {
+    10i128.checked_pow(exponent)
+}
+
+/// `n / d` for positive `d`, rounded half away from zero
+fn div_round(n: i128, d: i128) -> i128 {
+    let (quotient, remainder) = (n / d, n % d);
+    if remainder.abs() >= d - remainder.abs() {
+        quotient + n.signum()
+    } else {
+        quotient
+    }
+}
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (sign, other_sign) = (self.mantissa.signum(), other.mantissa.signum());
+        if sign != other_sign || sign == 0 {
+            return sign.cmp(&other_sign);
+        }
+
+        // Same sign: bring the coarser one to the finer exponent
+        let (coarse, fine) = if self.expo >= other.expo { (self, other) } else { (other, self) };
+        let ordering = match pow10(coarse.expo.abs_diff(fine.expo))
+            .and_then(|factor| i128::from(coarse.mantissa).checked_mul(factor))
+        {
+            Some(scaled) => scaled.cmp(&i128::from(fine.mantissa)),
+            // Too large to scale, so its magnitude dominates
+            None => sign.cmp(&0),
+        };
+
+        if self.expo >= other.expo {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    }
+}
+
+impl FromStr for Price {
+    type Err = PriceError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text)
+    }
+}
+
+/// Exact decimal notation
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let digits = self.mantissa.unsigned_abs().to_string();
+
+        if self.expo >= 0 {
+            return write!(f, "{}{}{}", sign, digits, "0".repeat(self.expo as usize));
+        }
+        let decimals = self.expo.unsigned_abs() as usize;
+        let padded = format!("{:0>width$}", digits, width = decimals + 1);
+        let (integer, fraction) = padded.split_at(padded.len() - decimals);
+        write!(f, "{}{}.{}", sign, integer, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(text: &str) -> (i64, i32) {
+        let price = Price::parse(text).unwrap();
+        (price.mantissa, price.expo)
+    }
+
+    #[test]
+    fn test_parse_is_exact() {
+        assert_eq!(parts("65123.45"), (6_512_345, -2));
+        assert_eq!(parts("0.29"), (29, -2));
+        assert_eq!(parts("-0.0012"), (-12, -4));
+        assert_eq!(parts("+7"), (7, 0));
+        assert_eq!(parts(".5"), (5, -1));
+        assert_eq!(parts("3."), (3, 0));
+        assert_eq!(parts("1.5e-7"), (15, -8));
+        assert_eq!(parts("2E3"), (2, 3));
+        assert_eq!(parts("0.000"), (0, -3));
+        // Beyond 18 decimals only if the extra digits are zeros
+        assert_eq!(parts("0.00000000000000000100"), (1, -18));
+        assert_eq!(Price::parse("0.0000000000000000001"), Err(PriceError::ExpoOutOfRange(-19)));
+
+        assert_eq!(Price::parse("9223372036854775807").unwrap().mantissa, i64::MAX);
+        assert_eq!(Price::parse("9223372036854775808"), Err(PriceError::Overflow));
+        assert_eq!(parts("1e30"), (1_000_000_000_000, 18));
+        assert_eq!(Price::parse("1e40"), Err(PriceError::Overflow));
+
+        for bad in ["", "-", ".", "1.2.3", "1e", "NaN", "inf", "1,000", "0x10"] {
+            assert_eq!(Price::parse(bad), Err(PriceError::Invalid), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_rescale_rounds_and_checks() {
+        let btc = Price::parse("65123.456").unwrap();
+        assert_eq!(btc.rescale(-8).unwrap().mantissa, 6_512_345_600_000);
+        assert_eq!(btc.rescale(-2).unwrap().mantissa, 6_512_346);
+        assert_eq!(btc.rescale(0).unwrap().mantissa, 65_123);
+        assert_eq!(Price::new(-25, -1).rescale(0).unwrap().mantissa, -3);
+        assert_eq!(Price::new(24, -1).rescale(0).unwrap().mantissa, 2);
+
+        // Too large for the finer exponent, and out of range
+        assert_eq!(btc.rescale(-18), Err(PriceError::Overflow));
+        assert_eq!(btc.rescale(-19), Err(PriceError::ExpoOutOfRange(-19)));
+        // Very small assets round to zero at a coarse exponent
+        assert_eq!(Price::parse("0.00000001234").unwrap().rescale(-8).unwrap().mantissa, 1);
+        assert_eq!(Price::parse("0.00000001234").unwrap().rescale(-6).unwrap().mantissa, 0);
+    }
+
+    #[test]
+    fn test_from_f64_rounds_instead_of_truncating() {
+        // 0.29 × 1e8 is 28999999.999999996 in f64
+        assert_eq!(Price::from_f64(0.29, -8).unwrap(), Price::new(29_000_000, -8));
+        assert_eq!(Price::from_f64(-1.005, -2).unwrap().expo, -2);
+        assert_eq!(Price::from_f64(1.5e6, 3).unwrap().mantissa, 1_500);
+
+        assert_eq!(Price::from_f64(1e11, -9), Err(PriceError::Overflow));
+        assert_eq!(Price::from_f64(f64::NAN, -8), Err(PriceError::Invalid));
+        assert_eq!(Price::from_f64(1.0, 19), Err(PriceError::ExpoOutOfRange(19)));
+
+        assert_eq!(Price::new(6_512_345, -2).to_f64(), 65123.45);
+    }
+
+    #[test]
+    fn test_value_comparison() {
+        assert_eq!(Price::new(150, -2), Price::new(15, -1));
+        assert!(Price::new(1, 0) > Price::new(999, -3));
+        assert!(Price::new(-1, 0) < Price::new(-999, -3));
+        assert!(Price::new(0, 5) < Price::new(1, -18));
+        assert!(Price::new(i64::MAX, 18) > Price::new(i64::MAX, -18));
+        assert!(Price::new(-1, 18) < Price::new(i64::MIN, -18));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let bid = Price::parse("3000.4").unwrap();
+        let ask = Price::parse("3000.65").unwrap();
+        assert_eq!(bid.midpoint(ask).unwrap(), Price::parse("3000.525").unwrap());
+        assert_eq!(ask.checked_sub(bid).unwrap(), Price::parse("0.25").unwrap());
+        assert_eq!(bid.checked_add(ask).unwrap(), Price::parse("6001.05").unwrap());
+        assert_eq!(Price::new(i64::MAX, 0).checked_add(Price::new(1, 0)), Err(PriceError::Overflow));
+    }
+
+    #[test]
+    fn test_mul_and_div_round_once() {
+        let p = |text: &str| Price::parse(text).unwrap();
+
+        // Exact product, then one rounding at the requested exponent
+        assert_eq!(p("0.00001234").checked_mul(p("0.998"), -12).unwrap(), p("0.00001231532"));
+        assert_eq!(p("1.25").checked_mul(p("1.1"), -2).unwrap().mantissa, 138);
+        assert_eq!(p("-1.25").checked_mul(p("1.1"), -2).unwrap().mantissa, -138);
+        // Mantissas past 2^53 stay exact
+        assert_eq!(Price::new(9_007_199_254_740_993, -8).checked_mul(p("1"), -8).unwrap().mantissa, 9_007_199_254_740_993);
+        assert_eq!(Price::new(i64::MAX, 0).checked_mul(p("10"), 0), Err(PriceError::Overflow));
+
+        assert_eq!(p("1").checked_div(p("0.998"), -12).unwrap(), p("1.002004008016"));
+        assert_eq!(p("2").checked_div(p("3"), -4).unwrap().mantissa, 6_667);
+        assert_eq!(p("1").checked_div(p("-3"), -4).unwrap().mantissa, -3_333);
+        assert_eq!(p("1").checked_div(Price::new(1, 18), -2).unwrap().mantissa, 0);
+        assert_eq!(p("1").checked_div(p("0"), -2), Err(PriceError::DivideByZero));
+        assert_eq!(Price::new(i64::MAX, 0).checked_div(p("0.1"), 0), Err(PriceError::Overflow));
+    }
+
+    #[test]
+    fn test_display() {
+        for text in ["65123.45", "-0.0012", "0.5", "7", "0.000000000000000001"] {
+            assert_eq!(Price::parse(text).unwrap().to_string(), text);
+        }
+        assert_eq!(Price::new(2, 3).to_string(), "2000");
+        assert_eq!(Price::new(i64::MIN, -2).to_string(), "-92233720368547758.08");
+    }
+}
@@ -1,7 +1,14 @@
 use anchor_lang::prelude::*;
+use tachyon_merkle::PriceLeaf;
 
 declare_id!("BRDGK2ASP86oe5wj18XYwRBuhEELpEGFqZGBhxnwwnTW");
 
+// TachyonStateCompression program ID, owner of the committed Merkle root
+const STATE_COMPRESSION_PROGRAM_ID: Pubkey = solana_program::pubkey!("L2TA7eVsDyXx7nxF4p2Xay3iWgdCHuMPx6YV5odwMTx");
+
+// L2State layout: discriminator (8) + authority (32) + current_root (32)
+const L2_STATE_ROOT_OFFSET: usize = 8 + 32;
+
 /// TachyonBridge - Cross-chain oracle data bridge
 /// 
 /// This contract enables cross-chain oracle data transfer,
@@ -31,12 +38,14 @@ pub mod tachyon_bridge {
     }
 
     /// Send price data to another chain
+    #[allow(clippy::too_many_arguments)]
     pub fn send_cross_chain(
         ctx: Context<SendCrossChain>,
         target_chain: u16,
         asset_id: [u8; 32],
         price: i64,
         confidence: i64,
+        expo: i32,
         timestamp: i64,
         merkle_proof: Vec<[u8; 32]>,
     ) -> Result<()> {
@@ -44,6 +53,27 @@ pub mod tachyon_bridge {
         
         require!(bridge_state.is_active, BridgeError::BridgeInactive);
         
+        // Only relay prices committed under the current L2 root
+        let l2_data = ctx.accounts.l2_state.try_borrow_data()?;
+        require!(
+            l2_data.len() >= L2_STATE_ROOT_OFFSET + 32,
+            BridgeError::InvalidL2State
+        );
+        let mut current_root = [0u8; 32];
+        current_root.copy_from_slice(&l2_data[L2_STATE_ROOT_OFFSET..L2_STATE_ROOT_OFFSET + 32]);
+        
+        let leaf = PriceLeaf {
+            asset_id,
+            price,
+            confidence,
+            expo,
+            timestamp,
+        };
+        require!(
+            tachyon_merkle::verify(leaf.hash(), &merkle_proof, &current_root),
+            BridgeError::InvalidProof
+        );
+        
         // Create cross-chain message
         let message = CrossChainMessage {
             source_chain: 1, // X1 chain ID
@@ -51,6 +81,7 @@ pub mod tachyon_bridge {
             asset_id,
             price,
             confidence,
+            expo,
             timestamp,
             merkle_proof: merkle_proof.clone(),
             nonce: bridge_state.total_messages_sent,
@@ -163,6 +194,15 @@ pub struct SendCrossChain<'info> {
     )]
     pub bridge_state: Account<'info, BridgeState>,
     
+    /// CHECK: TachyonStateCompression L2 state holding the current Merkle root
+    #[account(
+        seeds = [b"l2-state"],
+        bump,
+        seeds::program = STATE_COMPRESSION_PROGRAM_ID,
+        owner = STATE_COMPRESSION_PROGRAM_ID,
+    )]
+    pub l2_state: UncheckedAccount<'info>,
+    
     pub sender: Signer<'info>,
 }
 
@@ -216,6 +256,7 @@ pub struct CrossChainMessage {
     pub asset_id: [u8; 32],
     pub price: i64,
     pub confidence: i64,
+    pub expo: i32,
     pub timestamp: i64,
     pub merkle_proof: Vec<[u8; 32]>,
     pub nonce: u64,
@@ -229,5 +270,9 @@ pub enum BridgeError {
     BridgeInactive,
     #[msg("Insufficient signatures for cross-chain message")]
     InsufficientSignatures,
+    #[msg("L2 state account is malformed")]
+    InvalidL2State,
+    #[msg("Price is not committed under the current L2 root")]
+    InvalidProof,
 }
 
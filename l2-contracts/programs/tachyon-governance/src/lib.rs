@@ -1,8 +1,13 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions as sysvar_instructions;
 use anchor_spl::token::{self, Token, TokenAccount, Transfer, Mint};
+use tachyon_merkle::ed25519::load_vote_signatures;
 
 declare_id!("TACHdFYQ4uDuAdo6Hz4V1RaCezEpHkVRZGQ7yh24Ad9");
 
+/// Share of stake slashed for signing two roots for one batch (basis points)
+pub const EQUIVOCATION_SLASH_BPS: u64 = 1_000;
+
 /// TachyonGovernance - Protocol governance with TACH token
 /// 
 /// This contract manages protocol governance, staking, and rewards
@@ -191,50 +196,74 @@ pub mod tachyon_governance {
         Ok(())
     }
 
-    /// Slash a misbehaving validator (authority only)
-    pub fn slash(
-        ctx: Context<Slash>,
-        slash_amount: u64,
-        reason: String,
+    /// Slash a validator that signed two different roots for the same batch.
+    ///
+    /// Anyone can submit the proof. Both votes must be checked by Ed25519
+    /// program instructions earlier in the transaction, and each
+    /// (validator, batch) pair can only be slashed once.
+    pub fn slash_equivocation(
+        ctx: Context<SlashEquivocation>,
+        proof: EquivocationProof,
     ) -> Result<()> {
-        let governance_state = &mut ctx.accounts.governance_state;
-        let staker_info = &mut ctx.accounts.staker_info;
-        
         require!(
-            ctx.accounts.authority.key() == governance_state.authority,
-            GovernanceError::Unauthorized
+            proof.first_root != proof.second_root,
+            GovernanceError::NotEquivocation
         );
         
-        require!(
-            staker_info.staked_amount >= slash_amount,
-            GovernanceError::InsufficientStake
-        );
+        let signatures = load_vote_signatures(&ctx.accounts.instructions)?
+            .ok_or_else(|| error!(GovernanceError::InvalidEd25519Instruction))?;
+        let validator = proof.validator.to_bytes();
+        for (root, signature) in [
+            (&proof.first_root, &proof.first_signature),
+            (&proof.second_root, &proof.second_signature),
+        ] {
+            let message = tachyon_merkle::vote_message(proof.batch_number, root);
+            require!(
+                signatures.iter().any(|sig| sig.matches(&validator, signature, &message)),
+                GovernanceError::MissingVoteSignature
+            );
+        }
         
-        // Transfer slashed tokens from vault to rewards pool
-        let seeds = &[
-            b"governance".as_ref(),
-            &[governance_state.bump],
-        ];
-        let signer = &[&seeds[..]];
+        let governance_state = &mut ctx.accounts.governance_state;
+        let staker_info = &mut ctx.accounts.staker_info;
+        let slash_amount = (staker_info.staked_amount as u128 * EQUIVOCATION_SLASH_BPS as u128 / 10_000) as u64;
         
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.vault.to_account_info(),
-                    to: ctx.accounts.rewards_pool.to_account_info(),
-                    authority: governance_state.to_account_info(),
-                },
-                signer,
-            ),
-            slash_amount,
-        )?;
+        // Transfer slashed tokens from vault to rewards pool
+        if slash_amount > 0 {
+            let seeds = &[
+                b"governance".as_ref(),
+                &[governance_state.bump],
+            ];
+            let signer = &[&seeds[..]];
+            
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.vault.to_account_info(),
+                        to: ctx.accounts.rewards_pool.to_account_info(),
+                        authority: governance_state.to_account_info(),
+                    },
+                    signer,
+                ),
+                slash_amount,
+            )?;
+        }
         
         staker_info.staked_amount -= slash_amount;
         governance_state.total_staked -= slash_amount;
-        
-        msg!("⚠️  SLASHED {} TACH from {}", slash_amount, ctx.accounts.slashed_staker.key());
-        msg!("Reason: {}", reason);
+        governance_state.total_slashed += slash_amount;
+        
+        let record = &mut ctx.accounts.equivocation_record;
+        record.validator = proof.validator;
+        record.batch_number = proof.batch_number;
+        record.slashed_amount = slash_amount;
+        record.reporter = ctx.accounts.reporter.key();
+        record.slashed_at = Clock::get()?.unix_timestamp;
+        record.bump = ctx.bumps.equivocation_record;
+        
+        msg!("⚠️  SLASHED {} TACH from {}", slash_amount, proof.validator);
+        msg!("Equivocation on batch {}", proof.batch_number);
         msg!("Remaining stake: {} TACH", staker_info.staked_amount);
         
         Ok(())
@@ -1148,7 +1177,8 @@ pub struct Unstake<'info> {
 }
 
 #[derive(Accounts)]
-pub struct Slash<'info> {
+#[instruction(proof: EquivocationProof)]
+pub struct SlashEquivocation<'info> {
     #[account(
         mut,
         seeds = [b"governance"],
@@ -1172,17 +1202,31 @@ pub struct Slash<'info> {
     
     #[account(
         mut,
-        seeds = [b"staker-info", slashed_staker.key().as_ref()],
+        seeds = [b"staker-v2", proof.validator.as_ref()],
         bump = staker_info.bump
     )]
     pub staker_info: Account<'info, StakerInfo>,
     
-    /// CHECK: The staker being slashed
-    pub slashed_staker: AccountInfo<'info>,
+    /// One record per (validator, batch) so a proof cannot be replayed
+    #[account(
+        init,
+        payer = reporter,
+        space = 8 + EquivocationRecord::INIT_SPACE,
+        seeds = [b"equivocation", proof.validator.as_ref(), &proof.batch_number.to_le_bytes()],
+        bump
+    )]
+    pub equivocation_record: Account<'info, EquivocationRecord>,
     
-    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub reporter: Signer<'info>,
+    
+    /// CHECK: Instructions sysvar, used to find the Ed25519 vote signatures
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
     
     pub token_program: Program<'info, Token>,
+    
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
@@ -1369,6 +1413,30 @@ pub struct StakerInfo {
     pub vesting_start: i64,             // 8 bytes - Vesting start time
 }
 
+/// Slashing record for one equivocation
+#[account]
+#[derive(InitSpace)]
+pub struct EquivocationRecord {
+    pub validator: Pubkey,              // 32 bytes
+    pub batch_number: u64,              // 8 bytes
+    pub slashed_amount: u64,            // 8 bytes
+    pub reporter: Pubkey,               // 32 bytes
+    pub slashed_at: i64,                // 8 bytes
+    pub bump: u8,                       // 1 byte
+}
+
+/// Two conflicting votes signed by the same validator for one batch,
+/// as produced by the node's equivocation detector
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct EquivocationProof {
+    pub validator: Pubkey,
+    pub batch_number: u64,
+    pub first_root: [u8; 32],
+    pub first_signature: [u8; 64],
+    pub second_root: [u8; 32],
+    pub second_signature: [u8; 64],
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct Proposal {
@@ -1529,4 +1597,10 @@ pub enum GovernanceError {
     InvalidAccountData,
     #[msg("Amount does not match expected value")]
     InvalidAmount,
+    #[msg("Both votes endorse the same root")]
+    NotEquivocation,
+    #[msg("Vote is not signed by an Ed25519 instruction in this transaction")]
+    MissingVoteSignature,
+    #[msg("Malformed Ed25519 instruction")]
+    InvalidEd25519Instruction,
 }
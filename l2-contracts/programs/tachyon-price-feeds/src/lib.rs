@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use tachyon_merkle::{Price, PriceError};
 
 declare_id!("PFEDu3nNzRQQYmX1Xvso2BxtPbUQaZEVoiLbXDy6U3W");
 
@@ -15,6 +16,7 @@ pub mod tachyon_price_feeds {
     ) -> Result<()> {
         require!(symbol.len() <= 32, PriceFeedError::SymbolTooLong);
         require!(description.len() <= 128, PriceFeedError::DescriptionTooLong);
+        require!(decimals <= 18, PriceFeedError::InvalidDecimals);
         
         let feed = &mut ctx.accounts.price_feed;
         feed.authority = ctx.accounts.authority.key();
@@ -23,7 +25,7 @@ pub mod tachyon_price_feeds {
         feed.decimals = decimals;
         feed.price = 0;
         feed.confidence = 0;
-        feed.expo = 0;
+        feed.expo = -i32::from(decimals);
         feed.last_update = 0;
         feed.publisher_count = 0;
         feed.status = FeedStatus::Inactive as u8;
@@ -51,6 +53,12 @@ pub mod tachyon_price_feeds {
             PriceFeedError::Unauthorized
         );
         
+        // Stored at the feed's own exponent, whatever the publisher used
+        let feed_expo = feed.feed_expo();
+        let price = to_feed_expo(price, expo, feed_expo)?;
+        let confidence = confidence_to_feed_expo(confidence, expo, feed_expo)?;
+        let expo = feed_expo;
+        
         // Update price data
         feed.price = price;
         feed.confidence = confidence;
@@ -90,26 +98,31 @@ pub mod tachyon_price_feeds {
         require!(!prices.is_empty(), PriceFeedError::NoPrices);
         require!(prices.len() <= 100, PriceFeedError::TooManyPrices);
         
-        // Calculate median price
-        let mut sorted_prices: Vec<i64> = prices.iter().map(|p| p.price).collect();
+        // Bring every submission to the feed's exponent before comparing
+        let feed_expo = feed.feed_expo();
+        let mut sorted_prices = prices
+            .iter()
+            .map(|p| to_feed_expo(p.price, p.expo, feed_expo))
+            .collect::<Result<Vec<i64>>>()?;
         sorted_prices.sort();
         let median_price = sorted_prices[sorted_prices.len() / 2];
         
-        // Calculate confidence (standard deviation)
-        let mean: i64 = sorted_prices.iter().sum::<i64>() / sorted_prices.len() as i64;
-        let variance: u64 = sorted_prices
+        // Calculate confidence (standard deviation), wide enough not to overflow
+        let count = sorted_prices.len() as i128;
+        let mean = sorted_prices.iter().map(|&p| i128::from(p)).sum::<i128>() / count;
+        let variance = sorted_prices
             .iter()
-            .map(|p| {
-                let diff = (*p - mean).abs() as u64;
-                diff * diff
+            .map(|&p| {
+                let diff = (i128::from(p) - mean).unsigned_abs();
+                diff.saturating_mul(diff)
             })
-            .sum::<u64>() / sorted_prices.len() as u64;
+            .fold(0u128, u128::saturating_add) / count as u128;
         let confidence = (variance as f64).sqrt() as u64;
         
         // Update feed
         feed.price = median_price;
         feed.confidence = confidence;
-        feed.expo = prices[0].expo; // Assume all have same exponent
+        feed.expo = feed_expo;
         feed.last_update = clock.unix_timestamp;
         feed.publisher_count = prices.len() as u32;
         feed.status = FeedStatus::Active as u8;
@@ -119,6 +132,7 @@ pub mod tachyon_price_feeds {
             symbol: feed.symbol.clone(),
             price: median_price,
             confidence,
+            expo: feed_expo,
             publisher_count: prices.len() as u32,
             timestamp: clock.unix_timestamp,
         });
@@ -165,6 +179,24 @@ pub mod tachyon_price_feeds {
     }
 }
 
+/// `mantissa × 10^expo` as a mantissa at the feed's exponent
+fn to_feed_expo(mantissa: i64, expo: i32, feed_expo: i32) -> Result<i64> {
+    Price::new(mantissa, expo)
+        .rescale(feed_expo)
+        .map(|price| price.mantissa)
+        .map_err(|e| match e {
+            PriceError::ExpoOutOfRange(_) => error!(PriceFeedError::InvalidExpo),
+            PriceError::Overflow | PriceError::Invalid | PriceError::DivideByZero => {
+                error!(PriceFeedError::PriceOverflow)
+            }
+        })
+}
+
+fn confidence_to_feed_expo(confidence: u64, expo: i32, feed_expo: i32) -> Result<u64> {
+    let mantissa = i64::try_from(confidence).map_err(|_| error!(PriceFeedError::PriceOverflow))?;
+    Ok(to_feed_expo(mantissa, expo, feed_expo)?.unsigned_abs())
+}
+
 // Accounts
 
 #[derive(Accounts)]
@@ -250,13 +282,20 @@ pub struct PriceFeed {
     pub decimals: u8,                   // 1 byte
     pub price: i64,                     // 8 bytes - Current price
     pub confidence: u64,                // 8 bytes - Confidence interval
-    pub expo: i32,                      // 4 bytes - Price exponent
+    pub expo: i32,                      // 4 bytes - Price exponent, always -decimals
     pub last_update: i64,               // 8 bytes - Last update timestamp
     pub publisher_count: u32,           // 4 bytes - Number of publishers
     pub status: u8,                     // 1 byte - 0=Inactive, 1=Active, 2=Deprecated
     pub bump: u8,                       // 1 byte
 }
 
+impl PriceFeed {
+    /// Exponent prices are stored at, fixed by the feed's decimals
+    pub fn feed_expo(&self) -> i32 {
+        -i32::from(self.decimals)
+    }
+}
+
 // Data structures
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
@@ -296,6 +335,7 @@ pub struct PriceAggregated {
     pub symbol: String,
     pub price: i64,
     pub confidence: u64,
+    pub expo: i32,
     pub publisher_count: u32,
     pub timestamp: i64,
 }
@@ -333,5 +373,31 @@ pub enum PriceFeedError {
     
     #[msg("Feed is inactive")]
     FeedInactive,
+    
+    #[msg("Decimals out of range (max 18)")]
+    InvalidDecimals,
+    
+    #[msg("Exponent out of range")]
+    InvalidExpo,
+    
+    #[msg("Price does not fit the feed's exponent")]
+    PriceOverflow,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_submissions_rescaled_to_feed_expo() {
+        // 97180.5 at 6 and 9 decimals, read by an 8-decimal feed
+        assert_eq!(to_feed_expo(97_180_500_000, -6, -8).unwrap(), 9_718_050_000_000);
+        assert_eq!(to_feed_expo(97_180_500_000_000, -9, -8).unwrap(), 9_718_050_000_000);
+        assert_eq!(confidence_to_feed_expo(125, -9, -8).unwrap(), 13);
+
+        assert!(to_feed_expo(i64::MAX / 2, -8, -9).is_err());
+        assert!(to_feed_expo(1, -8, -19).is_err());
+        assert!(confidence_to_feed_expo(u64::MAX, -8, -8).is_err());
+    }
+}
@@ -24,6 +24,8 @@ pub mod tachyon_sequencer {
         sequencer_state.total_batches_submitted = 0;
         sequencer_state.is_permissioned = true;
         sequencer_state.bump = ctx.bumps.sequencer_state;
+        sequencer_state.last_batch_number = 0;
+        sequencer_state.last_batch_timestamp = 0;
         
         msg!("Tachyon Sequencer initialized");
         msg!("Min stake: {} TACH", min_stake);
@@ -76,17 +78,35 @@ pub mod tachyon_sequencer {
     }
 
     /// Submit a batch (only authorized sequencers)
+    ///
+    /// Batch numbers must strictly increase and timestamps may not go
+    /// backwards, so replayed or reordered batches are rejected.
     pub fn submit_batch(
         ctx: Context<SubmitBatch>,
         batch_number: u64,
         merkle_root: [u8; 32],
         feed_count: u32,
+        timestamp: i64,
     ) -> Result<()> {
         let sequencer_info = &mut ctx.accounts.sequencer_info;
         let sequencer_state = &mut ctx.accounts.sequencer_state;
         
         require!(sequencer_info.is_active, SequencerError::SequencerInactive);
+        require!(
+            batch_number != sequencer_state.last_batch_number,
+            SequencerError::DuplicateBatch
+        );
+        require!(
+            batch_number > sequencer_state.last_batch_number,
+            SequencerError::StaleBatch
+        );
+        require!(
+            timestamp >= sequencer_state.last_batch_timestamp,
+            SequencerError::TimestampRegression
+        );
         
+        sequencer_state.last_batch_number = batch_number;
+        sequencer_state.last_batch_timestamp = timestamp;
         sequencer_info.batches_submitted += 1;
         sequencer_state.total_batches_submitted += 1;
         
@@ -100,6 +120,59 @@ pub mod tachyon_sequencer {
         Ok(())
     }
 
+    /// Grow a sequencer state created before batch sequencing was tracked.
+    /// The new fields start zeroed, so the next batch number is unconstrained.
+    pub fn migrate_state(ctx: Context<MigrateState>) -> Result<()> {
+        let state_info = ctx.accounts.sequencer_state.to_account_info();
+        let new_size = 8 + SequencerState::INIT_SPACE;
+        
+        if state_info.data_len() >= new_size {
+            msg!("✅ Sequencer state already migrated!");
+            return Ok(());
+        }
+        
+        // Authority is the first field after the discriminator
+        {
+            let data = state_info.try_borrow_data()?;
+            require!(data.len() >= 8 + 32, SequencerError::Unauthorized);
+            let authority = Pubkey::try_from(&data[8..40]).unwrap();
+            require!(
+                authority == ctx.accounts.authority.key(),
+                SequencerError::Unauthorized
+            );
+        }
+        
+        let rent = Rent::get()?;
+        let new_rent_minimum = rent.minimum_balance(new_size);
+        let current_lamports = state_info.lamports();
+        
+        if current_lamports < new_rent_minimum {
+            let additional_rent = new_rent_minimum - current_lamports;
+            msg!("Adding {} lamports for rent", additional_rent);
+            
+            let transfer_ix = anchor_lang::solana_program::system_instruction::transfer(
+                &ctx.accounts.authority.key(),
+                &state_info.key(),
+                additional_rent,
+            );
+            
+            anchor_lang::solana_program::program::invoke(
+                &transfer_ix,
+                &[
+                    ctx.accounts.authority.to_account_info(),
+                    state_info.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
+        
+        // New fields are appended, and resizing zero-fills them
+        state_info.resize(new_size)?;
+        
+        msg!("Sequencer state migrated to {} bytes", new_size);
+        Ok(())
+    }
+
     /// Slash a sequencer for misbehavior
     pub fn slash_sequencer(
         ctx: Context<SlashSequencer>,
@@ -231,6 +304,22 @@ pub struct SubmitBatch<'info> {
     pub sequencer: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct MigrateState<'info> {
+    /// CHECK: Old layout cannot be deserialized; authority is checked manually
+    #[account(
+        mut,
+        seeds = [b"sequencer"],
+        bump,
+    )]
+    pub sequencer_state: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct SlashSequencer<'info> {
     #[account(
@@ -270,6 +359,8 @@ pub struct SequencerState {
     pub total_batches_submitted: u64,   // 8 bytes
     pub is_permissioned: bool,          // 1 byte
     pub bump: u8,                       // 1 byte
+    pub last_batch_number: u64,         // 8 bytes
+    pub last_batch_timestamp: i64,      // 8 bytes
 }
 
 #[account]
@@ -291,5 +382,11 @@ pub enum SequencerError {
     InsufficientStake,
     #[msg("Sequencer is not active")]
     SequencerInactive,
+    #[msg("Batch number was already submitted")]
+    DuplicateBatch,
+    #[msg("Batch number is older than the latest submitted batch")]
+    StaleBatch,
+    #[msg("Batch timestamp is earlier than the latest submitted batch")]
+    TimestampRegression,
 }
 
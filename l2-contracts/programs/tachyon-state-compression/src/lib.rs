@@ -1,10 +1,12 @@
 use anchor_lang::prelude::*;
-use solana_program::keccak;
+use anchor_lang::solana_program::sysvar::instructions as sysvar_instructions;
+use tachyon_merkle::ed25519::{load_vote_signatures, SignedVote};
+use tachyon_merkle::PriceLeaf;
 
 declare_id!("L2TA7eVsDyXx7nxF4p2Xay3iWgdCHuMPx6YV5odwMTx");
 
-// TachyonSequencer program ID for cross-program checks
-const SEQUENCER_PROGRAM_ID: Pubkey = solana_program::pubkey!("SEQRXNAYH7s4DceD8K3Bb7oChunLVYqZKRcCJGRoQ1M");
+// TachyonGovernance program ID, owner of the stake accounts votes are weighed by
+const GOVERNANCE_PROGRAM_ID: Pubkey = solana_program::pubkey!("TACHdFYQ4uDuAdo6Hz4V1RaCezEpHkVRZGQ7yh24Ad9");
 
 #[program]
 pub mod tachyon_state_compression {
@@ -34,110 +36,63 @@ pub mod tachyon_state_compression {
         Ok(())
     }
 
-    /// Submit a new Merkle root (with sequencer authorization)
-    pub fn submit_root(
-        ctx: Context<SubmitRoot>,
+    /// Submit root with consensus votes (2/3 stake verification)
+    ///
+    /// The votes are the signatures over `vote_message(batch_number, root)`
+    /// verified by Ed25519 program instructions earlier in the same
+    /// transaction. `remaining_accounts` holds the governance `StakerInfo` of
+    /// each signer, in signature order, and the quorum is measured against
+    /// `GovernanceState.total_staked`.
+    pub fn submit_root_with_consensus<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SubmitRootWithConsensus<'info>>,
+        batch_number: u64,
         root: [u8; 32],
         feed_count: u32,
         timestamp: i64,
     ) -> Result<()> {
-        let l2_state = &mut ctx.accounts.l2_state;
-        
-        // Check if submitter is authorized sequencer
-        // Option 1: Check if it's the authority (for backwards compatibility)
-        // Option 2: Check if registered in TachyonSequencer (for multi-validator)
-        let is_authority = ctx.accounts.authority.key() == l2_state.authority;
-        let is_sequencer = ctx.accounts.sequencer_info.is_some();
+        let total_stake = read_total_staked(&ctx.accounts.governance_state)?;
+        require!(total_stake > 0, L2Error::NoStake);
         
+        // Signatures over anything else, such as another root, are not votes for this one
+        let message = tachyon_merkle::vote_message(batch_number, &root);
+        let votes: Vec<SignedVote> = load_vote_signatures(&ctx.accounts.instructions)?
+            .ok_or_else(|| error!(L2Error::InvalidEd25519Instruction))?
+            .into_iter()
+            .filter(|vote| vote.message == message)
+            .collect();
+        require!(!votes.is_empty(), L2Error::MissingVoteSignature);
         require!(
-            is_authority || is_sequencer,
-            L2Error::Unauthorized
-        );
-        
-        // If sequencer info provided, verify it's active
-        if let Some(sequencer_info) = &ctx.accounts.sequencer_info {
-            require!(
-                sequencer_info.is_active,
-                L2Error::SequencerNotActive
-            );
-            require!(
-                sequencer_info.sequencer == ctx.accounts.authority.key(),
-                L2Error::InvalidSequencer
-            );
-        }
-        
-        // Update state
-        l2_state.current_root = root;
-        l2_state.batch_number += 1;
-        l2_state.feed_count = feed_count;
-        l2_state.last_update = timestamp;
-        
-        msg!(
-            "New root submitted: batch={}, feeds={}, root={:?}, submitter={}",
-            l2_state.batch_number,
-            feed_count,
-            &root[..8],
-            ctx.accounts.authority.key()
+            ctx.remaining_accounts.len() == votes.len(),
+            L2Error::MissingStakerAccount
         );
         
-        Ok(())
-    }
-
-    /// Submit root with consensus votes (2/3 stake verification)
-    /// Submit root with consensus votes (2/3 stake verification)
-    /// Note: In production, this would parse governance_state account data
-    /// For now, simplified to accept total_stake as parameter
-    pub fn submit_root_with_consensus(
-        ctx: Context<SubmitRootWithConsensus>,
-        root: [u8; 32],
-        feed_count: u32,
-        timestamp: i64,
-        total_stake: u64,
-        votes: Vec<ConsensusVote>,
-    ) -> Result<()> {
-        let l2_state = &mut ctx.accounts.l2_state;
-        
         // Verify we have enough votes (2/3 of total stake)
-        let mut root_votes: std::collections::HashMap<[u8; 32], u64> = std::collections::HashMap::new();
+        let mut voters: Vec<Pubkey> = Vec::with_capacity(votes.len());
+        let mut agreeing_stake = 0u64;
         
-        for vote in &votes {
-            // TODO: Verify signature in production
-            // For now, trust the votes
-            *root_votes.entry(vote.root).or_insert(0) += vote.stake;
-        }
-        
-        // Find the root with most stake
-        let mut max_stake = 0u64;
-        let mut consensus_root = [0u8; 32];
-        for (voted_root, stake) in root_votes.iter() {
-            if *stake > max_stake {
-                max_stake = *stake;
-                consensus_root = *voted_root;
-            }
+        for (vote, staker_info) in votes.iter().zip(ctx.remaining_accounts) {
+            let validator = Pubkey::new_from_array(vote.pubkey);
+            require!(!voters.contains(&validator), L2Error::DuplicateVote);
+            voters.push(validator);
+            
+            // Count the stake recorded by governance
+            agreeing_stake = agreeing_stake.saturating_add(read_staked_amount(staker_info, &validator)?);
         }
         
         // Verify 2/3 threshold
         let quorum_threshold = (total_stake * 2) / 3;
         require!(
-            max_stake >= quorum_threshold,
+            agreeing_stake >= quorum_threshold,
             L2Error::InsufficientConsensus
         );
         
-        // Verify the consensus root matches submitted root
-        require!(
-            consensus_root == root,
-            L2Error::RootMismatch
-        );
-        
         // Update state
-        l2_state.current_root = root;
-        l2_state.batch_number += 1;
-        l2_state.feed_count = feed_count;
-        l2_state.last_update = timestamp;
+        let l2_state = &mut ctx.accounts.l2_state;
+        l2_state.advance(batch_number, root, feed_count, timestamp)?;
         
         msg!(
             "✅ Consensus reached: {}/{} stake agrees on root",
-            max_stake,
+            agreeing_stake,
             total_stake
         );
         msg!(
@@ -156,47 +111,40 @@ pub mod tachyon_state_compression {
         asset_id: [u8; 32],
         price: i64,
         confidence: i64,
+        expo: i32,
         timestamp: i64,
         proof: Vec<[u8; 32]>,
     ) -> Result<PriceData> {
         let l2_state = &ctx.accounts.l2_state;
         
-        // Serialize the price feed (same as L2 aggregator)
-        let mut leaf_data = Vec::with_capacity(56);
-        leaf_data.extend_from_slice(&asset_id);
-        leaf_data.extend_from_slice(&price.to_le_bytes());
-        leaf_data.extend_from_slice(&confidence.to_le_bytes());
-        leaf_data.extend_from_slice(&timestamp.to_le_bytes());
-        
-        // Hash the leaf using keccak256
-        let mut current_hash = keccak::hash(&leaf_data).to_bytes();
-        
-        // Verify proof
-        for proof_element in proof.iter() {
-            current_hash = if current_hash < *proof_element {
-                keccak::hash(&[&current_hash[..], &proof_element[..]].concat()).to_bytes()
-            } else {
-                keccak::hash(&[&proof_element[..], &current_hash[..]].concat()).to_bytes()
-            };
-        }
+        // Rebuild the leaf exactly as the node's aggregator committed it
+        let leaf = PriceLeaf {
+            asset_id,
+            price,
+            confidence,
+            expo,
+            timestamp,
+        };
         
         // Check if computed root matches stored root
         require!(
-            current_hash == l2_state.current_root,
+            tachyon_merkle::verify(leaf.hash(), &proof, &l2_state.current_root),
             L2Error::InvalidProof
         );
         
         msg!(
-            "Proof verified for asset {:?}: price={}, conf={}",
+            "Proof verified for asset {:?}: price={}, conf={}, expo={}",
             &asset_id[..8],
             price,
-            confidence
+            confidence,
+            expo
         );
         
         Ok(PriceData {
             asset_id,
             price,
             confidence,
+            expo,
             timestamp,
             batch_number: l2_state.batch_number,
         })
@@ -246,35 +194,26 @@ pub struct TransferAuthority<'info> {
     pub authority: Signer<'info>,
 }
 
-#[derive(Accounts)]
-pub struct SubmitRoot<'info> {
-    #[account(
-        mut,
-        seeds = [b"l2-state"],
-        bump = l2_state.bump
-    )]
-    pub l2_state: Account<'info, L2State>,
-    
-    pub authority: Signer<'info>,
-    
-    /// Optional: Sequencer info account for multi-validator authorization
-    /// If provided, will check if submitter is registered sequencer
-    #[account(
-        seeds = [b"sequencer-info", authority.key().as_ref()],
-        bump,
-        seeds::program = SEQUENCER_PROGRAM_ID,
-    )]
-    pub sequencer_info: Option<Account<'info, SequencerInfo>>,
+/// Leading fields of TachyonGovernance `GovernanceState`
+#[account]
+pub struct GovernanceState {
+    pub authority: Pubkey,
+    pub tach_mint: Pubkey,
+    pub vault: Pubkey,
+    pub rewards_pool: Pubkey,
+    pub min_stake: u64,
+    pub min_proposal_stake: u64,
+    pub voting_period: i64,
+    pub total_proposals: u64,
+    pub total_staked: u64,
 }
 
-/// Sequencer info from TachyonSequencer program (for CPI verification)
+/// Leading fields of TachyonGovernance `StakerInfo`
 #[account]
-pub struct SequencerInfo {
-    pub sequencer: Pubkey,
-    pub stake_amount: u64,
-    pub is_active: bool,
-    pub total_submissions: u64,
-    pub last_submission: i64,
+pub struct StakerInfo {
+    pub staked_amount: u64,
+    pub last_stake_timestamp: i64,
+    pub bump: u8,
 }
 
 #[derive(Accounts)]
@@ -304,13 +243,20 @@ pub struct SubmitRootWithConsensus<'info> {
     )]
     pub l2_state: Account<'info, L2State>,
     
-    /// CHECK: TachyonGovernance state for total stake verification (read-only)
-    pub governance_state: AccountInfo<'info>,
+    /// CHECK: TachyonGovernance state PDA, decoded in `read_total_staked`
+    #[account(
+        seeds = [b"governance"],
+        bump,
+        seeds::program = GOVERNANCE_PROGRAM_ID,
+        owner = GOVERNANCE_PROGRAM_ID,
+    )]
+    pub governance_state: UncheckedAccount<'info>,
     
     pub authority: Signer<'info>,
     
-    /// CHECK: TachyonGovernance program
-    pub governance_program: AccountInfo<'info>,
+    /// CHECK: Instructions sysvar, used to find the Ed25519 vote signatures
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
 }
 
 #[account]
@@ -324,11 +270,27 @@ pub struct L2State {
     pub bump: u8,                   // 1 byte
 }
 
+impl L2State {
+    /// Commit the next batch, rejecting replays, reordering and clock regressions
+    fn advance(&mut self, batch_number: u64, root: [u8; 32], feed_count: u32, timestamp: i64) -> Result<()> {
+        require!(batch_number != self.batch_number, L2Error::DuplicateBatch);
+        require!(batch_number > self.batch_number, L2Error::StaleBatch);
+        require!(timestamp >= self.last_update, L2Error::TimestampRegression);
+        
+        self.current_root = root;
+        self.batch_number = batch_number;
+        self.feed_count = feed_count;
+        self.last_update = timestamp;
+        Ok(())
+    }
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct PriceData {
     pub asset_id: [u8; 32],
     pub price: i64,
     pub confidence: i64,
+    pub expo: i32,
     pub timestamp: i64,
     pub batch_number: u64,
 }
@@ -342,26 +304,53 @@ pub struct L2StateData {
     pub last_update: i64,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct ConsensusVote {
-    pub validator: Pubkey,
-    pub root: [u8; 32],
-    pub stake: u64,
-    pub signature: [u8; 64],
-}
-
 #[error_code]
 pub enum L2Error {
-    #[msg("Unauthorized: Only authority or registered sequencer can submit roots")]
-    Unauthorized,
     #[msg("Invalid Merkle proof")]
     InvalidProof,
-    #[msg("Sequencer is not active")]
-    SequencerNotActive,
-    #[msg("Invalid sequencer account")]
-    InvalidSequencer,
     #[msg("Insufficient consensus: need 2/3 stake agreement")]
     InsufficientConsensus,
     #[msg("Root mismatch: consensus root doesn't match submitted root")]
     RootMismatch,
+    #[msg("No stake recorded in governance")]
+    NoStake,
+    #[msg("Each vote needs its validator's staker account")]
+    MissingStakerAccount,
+    #[msg("Staker account does not belong to the voting validator")]
+    InvalidStakerAccount,
+    #[msg("Validator voted more than once")]
+    DuplicateVote,
+    #[msg("Vote is not signed by an Ed25519 instruction in this transaction")]
+    MissingVoteSignature,
+    #[msg("Malformed Ed25519 instruction")]
+    InvalidEd25519Instruction,
+    #[msg("Batch number was already committed")]
+    DuplicateBatch,
+    #[msg("Batch number is older than the latest committed batch")]
+    StaleBatch,
+    #[msg("Batch timestamp is earlier than the latest committed batch")]
+    TimestampRegression,
+}
+
+fn read_total_staked(governance_state: &AccountInfo) -> Result<u64> {
+    let data = governance_state.try_borrow_data()?;
+    let state = GovernanceState::try_deserialize(&mut &data[..])?;
+    Ok(state.total_staked)
+}
+
+/// Stake of `validator`, read from its `["staker-v2", validator]` account
+fn read_staked_amount(staker_info: &AccountInfo, validator: &Pubkey) -> Result<u64> {
+    require_keys_eq!(*staker_info.owner, GOVERNANCE_PROGRAM_ID, L2Error::InvalidStakerAccount);
+    
+    let data = staker_info.try_borrow_data()?;
+    let info = StakerInfo::try_deserialize(&mut &data[..])?;
+    
+    let expected = Pubkey::create_program_address(
+        &[b"staker-v2", validator.as_ref(), &[info.bump]],
+        &GOVERNANCE_PROGRAM_ID,
+    )
+    .map_err(|_| error!(L2Error::InvalidStakerAccount))?;
+    require_keys_eq!(staker_info.key(), expected, L2Error::InvalidStakerAccount);
+    
+    Ok(info.staked_amount)
 }
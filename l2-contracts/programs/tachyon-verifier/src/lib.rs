@@ -1,5 +1,5 @@
 use anchor_lang::prelude::*;
-use solana_program::keccak;
+use tachyon_merkle::PriceLeaf;
 
 declare_id!("VRFYGHjfBedWbwTBw8DhmoUYa6s3Ga5ybJUPny7buAR");
 
@@ -12,37 +12,28 @@ pub mod tachyon_verifier {
     use super::*;
 
     /// Verify a Merkle proof and return verified price data
+    #[allow(clippy::too_many_arguments)]
     pub fn verify_price(
         ctx: Context<VerifyPrice>,
         asset_id: [u8; 32],
         price: i64,
         confidence: i64,
+        expo: i32,
         timestamp: i64,
         merkle_root: [u8; 32],
         proof: Vec<[u8; 32]>,
     ) -> Result<VerifiedPrice> {
-        // Serialize the price feed (same format as L2 aggregator)
-        let mut leaf_data = Vec::with_capacity(56);
-        leaf_data.extend_from_slice(&asset_id);
-        leaf_data.extend_from_slice(&price.to_le_bytes());
-        leaf_data.extend_from_slice(&confidence.to_le_bytes());
-        leaf_data.extend_from_slice(&timestamp.to_le_bytes());
-        
-        // Hash the leaf
-        let mut current_hash = keccak::hash(&leaf_data).to_bytes();
-        
-        // Verify proof by climbing the tree
-        for proof_element in proof.iter() {
-            current_hash = if current_hash < *proof_element {
-                keccak::hash(&[&current_hash[..], &proof_element[..]].concat()).to_bytes()
-            } else {
-                keccak::hash(&[&proof_element[..], &current_hash[..]].concat()).to_bytes()
-            };
-        }
+        let price_data = PriceData {
+            asset_id,
+            price,
+            confidence,
+            expo,
+            timestamp,
+        };
         
-        // Check if computed root matches provided root
+        // Check if the proof climbs to the provided root
         require!(
-            current_hash == merkle_root,
+            verify_leaf(&price_data, &proof, &merkle_root),
             VerifierError::InvalidProof
         );
         
@@ -54,16 +45,18 @@ pub mod tachyon_verifier {
         );
         
         msg!(
-            "✅ Price verified: asset={:?}, price={}, conf={}",
+            "✅ Price verified: asset={:?}, price={}, conf={}, expo={}",
             &asset_id[..8],
             price,
-            confidence
+            confidence,
+            expo
         );
         
         Ok(VerifiedPrice {
             asset_id,
             price,
             confidence,
+            expo,
             timestamp,
             verified_at: current_time,
             is_valid: true,
@@ -85,27 +78,7 @@ pub mod tachyon_verifier {
         let mut results = Vec::with_capacity(prices.len());
         
         for (price_data, proof) in prices.iter().zip(proofs.iter()) {
-            // Serialize the price feed
-            let mut leaf_data = Vec::with_capacity(56);
-            leaf_data.extend_from_slice(&price_data.asset_id);
-            leaf_data.extend_from_slice(&price_data.price.to_le_bytes());
-            leaf_data.extend_from_slice(&price_data.confidence.to_le_bytes());
-            leaf_data.extend_from_slice(&price_data.timestamp.to_le_bytes());
-            
-            // Hash the leaf
-            let mut current_hash = keccak::hash(&leaf_data).to_bytes();
-            
-            // Verify proof
-            for proof_element in proof.iter() {
-                current_hash = if current_hash < *proof_element {
-                    keccak::hash(&[&current_hash[..], &proof_element[..]].concat()).to_bytes()
-                } else {
-                    keccak::hash(&[&proof_element[..], &current_hash[..]].concat()).to_bytes()
-                };
-            }
-            
-            // Check if valid
-            results.push(current_hash == merkle_root);
+            results.push(verify_leaf(price_data, proof, &merkle_root));
         }
         
         msg!("Batch verified: {}/{} valid", results.iter().filter(|&&v| v).count(), results.len());
@@ -114,6 +87,18 @@ pub mod tachyon_verifier {
     }
 }
 
+/// Check a price leaf against a Merkle root using the canonical leaf encoding
+pub fn verify_leaf(price_data: &PriceData, proof: &[[u8; 32]], merkle_root: &[u8; 32]) -> bool {
+    let leaf = PriceLeaf {
+        asset_id: price_data.asset_id,
+        price: price_data.price,
+        confidence: price_data.confidence,
+        expo: price_data.expo,
+        timestamp: price_data.timestamp,
+    };
+    tachyon_merkle::verify(leaf.hash(), proof, merkle_root)
+}
+
 #[derive(Accounts)]
 pub struct VerifyPrice<'info> {
     pub payer: Signer<'info>,
@@ -127,8 +112,10 @@ pub struct VerifyBatch<'info> {
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct PriceData {
     pub asset_id: [u8; 32],
+    /// `price` and `confidence` are mantissas at `expo`
     pub price: i64,
     pub confidence: i64,
+    pub expo: i32,
     pub timestamp: i64,
 }
 
@@ -137,6 +124,7 @@ pub struct VerifiedPrice {
     pub asset_id: [u8; 32],
     pub price: i64,
     pub confidence: i64,
+    pub expo: i32,
     pub timestamp: i64,
     pub verified_at: i64,
     pub is_valid: bool,
@@ -152,3 +140,51 @@ pub enum VerifierError {
     MismatchedInputs,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tachyon_merkle::MerkleTree;
+
+    // Build the tree the same way the node's aggregator does and check
+    // every proof against the program's verification path.
+    fn node_batch(symbols: &[&str]) -> (Vec<PriceData>, MerkleTree) {
+        let leaves: Vec<PriceLeaf> = symbols
+            .iter()
+            .enumerate()
+            .map(|(i, symbol)| PriceLeaf::new(symbol, 6_500_000_000_000 + i as i64, 99_900_000, -8, 1_700_000_000))
+            .collect();
+        let tree = MerkleTree::from_leaves(leaves.iter().map(PriceLeaf::hash).collect());
+        let prices = leaves
+            .iter()
+            .map(|leaf| PriceData {
+                asset_id: leaf.asset_id,
+                price: leaf.price,
+                confidence: leaf.confidence,
+                expo: leaf.expo,
+                timestamp: leaf.timestamp,
+            })
+            .collect();
+        (prices, tree)
+    }
+
+    #[test]
+    fn test_node_proofs_verify() {
+        let (prices, tree) = node_batch(&["BTC/USD", "ETH/USD", "SOL/USD", "AVAX/USD", "BNB/USD"]);
+        let root = tree.root();
+
+        for (i, price_data) in prices.iter().enumerate() {
+            let proof = tree.proof(i).unwrap();
+            assert!(verify_leaf(price_data, &proof, &root));
+        }
+    }
+
+    #[test]
+    fn test_wrong_price_rejected() {
+        let (mut prices, tree) = node_batch(&["BTC/USD", "ETH/USD", "SOL/USD"]);
+        let proof = tree.proof(1).unwrap();
+
+        prices[1].price -= 1;
+        assert!(!verify_leaf(&prices[1], &proof, &tree.root()));
+    }
+}
@@ -0,0 +1,314 @@
+// Derived Feeds - ratios, products, baskets and conversions of other feeds
+//
+// Computed by the aggregator from the latest fresh value of each input feed,
+// after quorum, and committed like any other feed. Confidence intervals propagate to first
+// order, treating the inputs' errors as independent. The arithmetic stays
+// fixed-point: every step keeps as many digits as a 64-bit mantissa holds,
+// and the result is rounded once more, to the derived feed's exponent.
+
+use std::collections::HashMap;
+use anyhow::Result;
+use tachyon_merkle::price::{Price, PriceError, MAX_EXPO, MIN_EXPO};
+
+use crate::config::{DerivedFeedConfig, DerivedFormula};
+use crate::fetcher::pricing;
+
+/// An aggregated feed; `price` and `confidence` share an exponent
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedValue {
+    pub price: Price,
+    pub confidence: Price,
+    pub publishers: Vec<String>,
+}
+
+/// Price and confidence, for the formulas
+type Value = (Price, Price);
+
+/// Check the formulas once at startup; inputs are only resolved per batch,
+/// since a feed may come from peers without being configured locally
+pub fn validate(feeds: &[DerivedFeedConfig]) -> Result<()> {
+    for (i, feed) in feeds.iter().enumerate() {
+        if feeds[..i].iter().any(|earlier| earlier.symbol == feed.symbol) {
+            return Err(anyhow::anyhow!("Derived feed {} is defined twice", feed.symbol));
+        }
+
+        let inputs = inputs(&feed.formula);
+        if inputs.is_empty() {
+            return Err(anyhow::anyhow!("Derived feed {} has no inputs", feed.symbol));
+        }
+        if inputs.contains(&feed.symbol.as_str()) {
+            return Err(anyhow::anyhow!("Derived feed {} depends on itself", feed.symbol));
+        }
+        if !(MIN_EXPO..=MAX_EXPO).contains(&feed.expo) {
+            return Err(anyhow::anyhow!("Derived feed {} has expo {}, outside {}..={}", feed.symbol, feed.expo, MIN_EXPO, MAX_EXPO));
+        }
+
+        if let DerivedFormula::Convert { feed: from, via } = &feed.formula {
+            conversion(&feed.symbol, from, via)?;
+        }
+    }
+    Ok(())
+}
+
+/// Feeds a formula reads
+pub fn inputs(formula: &DerivedFormula) -> Vec<&str> {
+    match formula {
+        DerivedFormula::Ratio { numerator, denominator } => vec![numerator, denominator],
+        DerivedFormula::Product { factors } => factors.iter().map(String::as_str).collect(),
+        DerivedFormula::Basket { components } => components.keys().map(String::as_str).collect(),
+        DerivedFormula::Convert { feed, via } => vec![feed, via],
+    }
+}
+
+/// Whether converting `from` (X/A) to `symbol` (X/B) divides by `via`:
+/// true for B/A, false for A/B
+fn conversion(symbol: &str, from: &str, via: &str) -> Result<bool> {
+    let pair = |symbol| split(symbol).ok_or_else(|| anyhow::anyhow!("{} is not a BASE/QUOTE pair", symbol));
+    let (base, target_quote) = pair(symbol)?;
+    let (from_base, from_quote) = pair(from)?;
+    let (via_base, via_quote) = pair(via)?;
+
+    if base != from_base {
+        return Err(anyhow::anyhow!("Cannot convert {} to {}: different base", from, symbol));
+    }
+    match ((via_base, via_quote), (from_quote, target_quote)) {
+        ((b, q), (a, t)) if b == a && q == t => Ok(false),
+        ((b, q), (a, t)) if b == t && q == a => Ok(true),
+        _ => Err(anyhow::anyhow!("{} does not link {} to {}", via, from, symbol)),
+    }
+}
+
+fn split(symbol: &str) -> Option<(&str, &str)> {
+    symbol.split_once('/').filter(|(base, quote)| !base.is_empty() && !quote.is_empty())
+}
+
+/// Compute every derived feed whose inputs are in `values`, in config
+/// order, and add it; feeds already present (published directly) are kept
+pub fn compute_all(feeds: &[DerivedFeedConfig], values: &mut HashMap<String, FeedValue>) -> Vec<String> {
+    let mut computed = Vec::new();
+
+    for feed in feeds {
+        if values.contains_key(&feed.symbol) {
+            continue;
+        }
+        if let Some(value) = compute(feed, values) {
+            values.insert(feed.symbol.clone(), value);
+            computed.push(feed.symbol.clone());
+        }
+    }
+    computed
+}
+
+/// None if an input has no fresh value, a price it divides by is zero,
+/// or the result does not fit the feed's exponent
+pub fn compute(feed: &DerivedFeedConfig, values: &HashMap<String, FeedValue>) -> Option<FeedValue> {
+    let input = |symbol: &str| values.get(symbol).map(|value| (value.price, value.confidence));
+
+    let (price, deviations) = match &feed.formula {
+        DerivedFormula::Ratio { numerator, denominator } => quotient(input(numerator)?, input(denominator)?)?,
+        DerivedFormula::Product { factors } => {
+            let factors: Option<Vec<Value>> = factors.iter().map(|factor| input(factor)).collect();
+            product(&factors?)?
+        }
+        DerivedFormula::Basket { components } => {
+            let mut price = Price::new(0, 0);
+            let mut deviations = Vec::with_capacity(components.len());
+            for (symbol, weight) in components {
+                let (component, confidence) = input(symbol)?;
+                price = add(price, mul(*weight, component)?)?;
+                deviations.push(mul(*weight, confidence)?);
+            }
+            (price, deviations)
+        }
+        DerivedFormula::Convert { feed: from, via } => {
+            let (from_value, via_value) = (input(from)?, input(via)?);
+            if conversion(&feed.symbol, from, via).ok()? {
+                quotient(from_value, via_value)?
+            } else {
+                product(&[from_value, via_value])?
+            }
+        }
+    };
+
+    let price = price.rescale(feed.expo).ok()?;
+    let confidence = pricing::quadrature(&deviations, 1, feed.expo)?;
+
+    let mut publishers: Vec<String> = inputs(&feed.formula).iter()
+        .filter_map(|symbol| values.get(*symbol))
+        .flat_map(|value| value.publishers.iter().cloned())
+        .collect();
+    publishers.sort();
+    publishers.dedup();
+
+    Some(FeedValue { price, confidence, publishers })
+}
+
+/// Price and how far each input's confidence moves it: relative errors
+/// add in quadrature for products and quotients
+fn product(factors: &[Value]) -> Option<(Price, Vec<Price>)> {
+    let price = factors.iter().try_fold(Price::new(1, 0), |price, (factor, _)| mul(price, *factor))?;
+    Some((price, deviations(price, factors)?))
+}
+
+fn quotient(numerator: Value, denominator: Value) -> Option<(Price, Vec<Price>)> {
+    let price = finest(|expo| numerator.0.checked_div(denominator.0, expo))?;
+    Some((price, deviations(price, &[numerator, denominator])?))
+}
+
+/// `result × confidence / price` for each input
+fn deviations(result: Price, inputs: &[Value]) -> Option<Vec<Price>> {
+    inputs.iter()
+        .map(|(price, confidence)| {
+            let scaled = mul(result, *confidence)?;
+            finest(|expo| scaled.checked_div(*price, expo))
+        })
+        .collect()
+}
+
+fn mul(a: Price, b: Price) -> Option<Price> {
+    finest(|expo| a.checked_mul(b, expo))
+}
+
+fn add(a: Price, b: Price) -> Option<Price> {
+    finest(|expo| a.rescale(expo)?.checked_add(b.rescale(expo)?))
+}
+
+/// An operation at the finest exponent its result fits, keeping every
+/// digit a 64-bit mantissa can hold
+fn finest(op: impl Fn(i32) -> Result<Price, PriceError>) -> Option<Price> {
+    (MIN_EXPO..=MAX_EXPO).find_map(|expo| op(expo).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::source::px;
+
+    fn derived(symbol: &str, formula: DerivedFormula) -> DerivedFeedConfig {
+        DerivedFeedConfig { symbol: symbol.to_string(), expo: -8, policy: Default::default(), formula }
+    }
+
+    fn convert(symbol: &str, feed: &str, via: &str) -> DerivedFeedConfig {
+        derived(symbol, DerivedFormula::Convert { feed: feed.to_string(), via: via.to_string() })
+    }
+
+    fn values(feeds: &[(&str, &str, &str, &str)]) -> HashMap<String, FeedValue> {
+        feeds.iter()
+            .map(|(symbol, price, confidence, publisher)| {
+                (symbol.to_string(), FeedValue { price: px(price), confidence: px(confidence), publishers: vec![publisher.to_string()] })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ratio_and_product_propagate_relative_error() {
+        let values = values(&[("ETH/USD", "3000", "3", "node1"), ("BTC/USD", "60000", "80", "node2")]);
+
+        let eth_btc = compute(&derived("ETH/BTC", DerivedFormula::Ratio {
+            numerator: "ETH/USD".to_string(),
+            denominator: "BTC/USD".to_string(),
+        }), &values).unwrap();
+        assert_eq!(eth_btc.price, Price::new(5_000_000, -8));
+        // 0.05 × √(0.001² + (1/750)²), to the nearest 1e-8
+        assert_eq!(eth_btc.confidence, Price::new(8_333, -8));
+        assert_eq!(eth_btc.publishers, vec!["node1", "node2"]);
+
+        let squared = compute(&derived("ETH2", DerivedFormula::Product {
+            factors: vec!["ETH/USD".to_string(), "ETH/USD".to_string()],
+        }), &values).unwrap();
+        assert_eq!(squared.price, Price::new(9_000_000, 0));
+        // 9e6 × √2 × 0.001
+        assert_eq!(squared.confidence, px("12727.92206136"));
+
+        // 9e6 has no room for 18 decimals in a 64-bit mantissa
+        let fine = DerivedFeedConfig { expo: -18, ..derived("ETH2", DerivedFormula::Product {
+            factors: vec!["ETH/USD".to_string(), "ETH/USD".to_string()],
+        }) };
+        assert!(compute(&fine, &values).is_none());
+    }
+
+    #[test]
+    fn test_basket_adds_absolute_error() {
+        let values = values(&[("A/USD", "10", "3", "node1"), ("B/USD", "20", "2", "node1")]);
+        let index = compute(&derived("INDEX", DerivedFormula::Basket {
+            components: [("A/USD".to_string(), px("2")), ("B/USD".to_string(), px("0.5"))].into_iter().collect(),
+        }), &values).unwrap();
+
+        assert_eq!(index.price, Price::new(30, 0));
+        // √37
+        assert_eq!(index.confidence, px("6.08276253"));
+        assert_eq!(index.publishers, vec!["node1"]);
+    }
+
+    #[test]
+    fn test_convert_through_leg() {
+        let values = values(&[
+            ("SOL/USDC", "150", "0", "node1"),
+            ("USDC/USD", "0.999", "0", "node1"),
+            ("BTC/USD", "66000", "0", "node1"),
+            ("EUR/USD", "1.1", "0", "node1"),
+        ]);
+
+        // Leg quoted in the target currency: multiply
+        let sol = compute(&convert("SOL/USD", "SOL/USDC", "USDC/USD"), &values).unwrap();
+        assert!(sol.price == Price::new(14_985, -2));
+
+        // Leg quoted in the source currency: divide
+        let btc = compute(&convert("BTC/EUR", "BTC/USD", "EUR/USD"), &values).unwrap();
+        assert!(btc.price == Price::new(60_000, 0));
+    }
+
+    #[test]
+    fn test_keeps_every_digit() {
+        let zero = values(&[("X/USD", "1", "0", "node1"), ("ZERO", "0", "0", "node1")]);
+
+        // 18 significant digits: no f64 holds this price
+        let values = values(&[("X/USD", "98765432.1234567891", "0.0000000002", "node1"), ("USD/EUR", "1", "0", "node1"), ("TWO", "2", "0", "node1")]);
+
+        let converted = compute(&DerivedFeedConfig { expo: -10, ..convert("X/EUR", "X/USD", "USD/EUR") }, &values).unwrap();
+        assert_eq!(converted.price, px("98765432.1234567891"));
+        assert_eq!(converted.confidence, px("0.0000000002"));
+
+        let halved = compute(&DerivedFeedConfig { expo: -11, ..derived("HALF", DerivedFormula::Ratio {
+            numerator: "X/USD".to_string(),
+            denominator: "TWO".to_string(),
+        }) }, &values).unwrap();
+        assert_eq!(halved.price, px("49382716.06172839455"));
+        assert_eq!(halved.confidence, px("0.00000000010"));
+
+        // A zero price to divide by has no ratio
+        assert!(compute(&derived("X", DerivedFormula::Ratio { numerator: "X/USD".to_string(), denominator: "ZERO".to_string() }), &zero).is_none());
+    }
+
+    #[test]
+    fn test_compute_all_in_order() {
+        let mut values = values(&[("ETH/USD", "3000", "0", "node1"), ("BTC/USD", "60000", "0", "node1"), ("EUR/USD", "1.2", "0", "node1")]);
+        let feeds = vec![
+            convert("ETH/EUR", "ETH/USD", "EUR/USD"),
+            convert("BTC/EUR", "BTC/USD", "EUR/USD"),
+            // Uses the two above
+            derived("ETH/BTC", DerivedFormula::Ratio { numerator: "ETH/EUR".to_string(), denominator: "BTC/EUR".to_string() }),
+            // Input not in this batch
+            convert("SOL/EUR", "SOL/USD", "EUR/USD"),
+        ];
+        validate(&feeds).unwrap();
+
+        assert_eq!(compute_all(&feeds, &mut values), vec!["ETH/EUR", "BTC/EUR", "ETH/BTC"]);
+        assert_eq!(values["ETH/BTC"].price, Price::new(5, -2));
+        assert!(!values.contains_key("SOL/EUR"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&[convert("BTC/EUR", "BTC/USD", "EUR/USD")]).is_ok());
+        assert!(validate(&[convert("BTC/EUR", "BTC/USD", "USD/EUR")]).is_ok());
+        assert!(validate(&[convert("BTC/EUR", "ETH/USD", "EUR/USD")]).is_err());
+        assert!(validate(&[convert("BTC/EUR", "BTC/USD", "GBP/USD")]).is_err());
+        assert!(validate(&[convert("BTCEUR", "BTC/USD", "EUR/USD")]).is_err());
+
+        assert!(validate(&[derived("X", DerivedFormula::Product { factors: vec![] })]).is_err());
+        assert!(validate(&[derived("X", DerivedFormula::Product { factors: vec!["X".to_string()] })]).is_err());
+        assert!(validate(&[convert("BTC/EUR", "BTC/USD", "EUR/USD"), convert("BTC/EUR", "BTC/USD", "USD/EUR")]).is_err());
+        assert!(validate(&[DerivedFeedConfig { expo: -19, ..convert("BTC/EUR", "BTC/USD", "EUR/USD") }]).is_err());
+    }
+}
@@ -2,17 +2,36 @@
 use std::sync::Arc;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use sha2::{Sha256, Digest};
-use std::collections::HashMap;
-use tokio::sync::mpsc;
-use tokio::time::{interval, Duration};
-use tracing::{info, debug};
+use tachyon_merkle::{MerkleTree, Price, PriceLeaf};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use tokio::sync::{mpsc, RwLock};
+use tracing::{info, debug, warn};
+use solana_sdk::signature::Signer;
 
-use crate::config::NodeConfig;
+use crate::api::NodeStatus;
+use crate::api::stream::StreamEvent;
+use crate::config::{DerivedFeedConfig, NodeConfig};
+use crate::consensus::root_bytes;
 use crate::fetcher::PriceUpdate;
+use crate::ledger::oracle_ledger::{OracleLedger, PriceRecord};
+use crate::replay::Clock;
+
+// Ratios, products, baskets and conversions of the batch's feeds
+pub mod derived;
+
+// Deviation, heartbeat and staleness: which feeds go into a batch
+pub mod policy;
+
+use derived::FeedValue;
+use policy::{FeedPolicyStats, PublishTracker};
+
+/// Batches kept in memory for the API; older ones are read from the ledger
+const RECENT_BATCHES: usize = 64;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MerkleBatch {
+    /// Network-wide sequence number, see [`batch_number_at`]
+    pub batch_number: u64,
     pub root: String,
     pub timestamp: i64,
     pub feeds: Vec<FeedData>,
@@ -22,59 +41,210 @@ pub struct MerkleBatch {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FeedData {
     pub asset_id: String,
+    /// Mantissa at `expo`
     pub price: i64,
+    /// Confidence interval around `price`, same exponent
     pub confidence: i64,
+    pub expo: i32,
     pub timestamp: i64,
     pub publishers: Vec<String>,
 }
 
+/// Latest finalized prices and batches, shared with the API
+#[derive(Debug, Default)]
+pub struct AggregatorState {
+    /// Most recent committed price per symbol
+    pub latest: HashMap<String, PriceRecord>,
+    /// Newest finalized batch first
+    pub recent_batches: VecDeque<MerkleBatch>,
+    /// Publish decisions and staleness per feed
+    pub publishing: BTreeMap<String, FeedPolicyStats>,
+}
+
+impl AggregatorState {
+    pub fn record_batch(&mut self, batch: &MerkleBatch, submitter: [u8; 32]) -> Result<()> {
+        let merkle_root = root_bytes(&batch.root)?;
+        for feed in &batch.feeds {
+            let record = PriceRecord::from_feed(feed, batch.batch_number, merkle_root, submitter);
+            self.latest.insert(record.symbol.clone(), record);
+        }
+        
+        self.recent_batches.push_front(batch.clone());
+        self.recent_batches.truncate(RECENT_BATCHES);
+        Ok(())
+    }
+    
+    pub fn batch(&self, batch_number: u64) -> Option<&MerkleBatch> {
+        self.recent_batches.iter().find(|b| b.batch_number == batch_number)
+    }
+    
+    /// Whether `symbol` has gone without fresh source data past its policy
+    pub fn is_stale(&self, symbol: &str) -> bool {
+        self.publishing.get(symbol).is_some_and(|stats| stats.stale)
+    }
+}
+
+/// Built batches waiting for consensus. Only a batch finalized on its own
+/// root is committed; the others are dropped once a later batch finalizes.
+#[derive(Debug, Default)]
+struct Proposals {
+    batches: BTreeMap<u64, MerkleBatch>,
+}
+
+impl Proposals {
+    fn propose(&mut self, batch: MerkleBatch) {
+        self.batches.insert(batch.batch_number, batch);
+        while self.batches.len() > RECENT_BATCHES {
+            self.batches.pop_first();
+        }
+    }
+    
+    /// The batch to commit, if consensus closed on the root we built
+    fn finalize(&mut self, batch_number: u64, consensus_root: &str) -> Option<MerkleBatch> {
+        let batch = self.batches.remove(&batch_number);
+        // Consensus finishes batches in order; earlier ones never will now
+        self.batches.retain(|number, _| *number > batch_number);
+        batch.filter(|batch| batch.root == consensus_root)
+    }
+}
+
+/// Everything a built batch is handed to
+#[derive(Clone)]
+pub struct AggregatorOutbound {
+    pub batch_tx: mpsc::Sender<MerkleBatch>,
+    pub ledger: Arc<OracleLedger>,
+    pub state: Arc<RwLock<AggregatorState>>,
+    pub status: Arc<RwLock<NodeStatus>>,
+    pub events: tokio::sync::broadcast::Sender<StreamEvent>,
+}
+
 pub async fn start_aggregator(
     config: Arc<NodeConfig>,
+    clock: Clock,
     mut price_rx: mpsc::Receiver<PriceUpdate>,
     mut gossip_rx: mpsc::Receiver<PriceUpdate>,
-    batch_tx: mpsc::Sender<MerkleBatch>,
+    outbound: AggregatorOutbound,
     mut shutdown: tokio::sync::broadcast::Receiver<()>,
 ) -> Result<()> {
     info!("🌳 Starting local aggregator...");
     
+    // Tick on interval boundaries, so every node closes a batch at the same
+    // moment. Made first: replay waits on the ticker until it is dropped.
+    let mut ticker = clock.interval(config.batch_interval_ms);
+    
+    derived::validate(&config.derived_feeds)?;
+    if !config.derived_feeds.is_empty() {
+        info!("🌳 Derived feeds: {:?}", config.derived_feeds.iter().map(|f| &f.symbol).collect::<Vec<_>>());
+    }
+    
+    let node_pubkey = config.identity.pubkey().to_bytes();
+    
+    // Every publisher's latest update per asset, ours and our peers', kept
+    // while fresh. `updated` holds the assets that got new data since the
+    // last batch; a batch aggregates the whole fresh cache for those assets
+    // and the derived feeds that read them.
     let mut price_cache: HashMap<String, Vec<PriceUpdate>> = HashMap::new();
-    let mut ticker = interval(Duration::from_millis(config.batch_interval_ms));
+    let mut updated: HashSet<String> = HashSet::new();
+    let mut tracker = PublishTracker::new(&config, clock.now());
+    let mut last_batch_number = 0u64;
+    // Finalized batches commit their feeds to the publish policy, the
+    // ledger and the API
+    let mut finalized = outbound.events.subscribe();
+    let mut proposals = Proposals::default();
     
     loop {
         tokio::select! {
+            // Updates that arrived by the tick go into its batch
+            biased;
+            
             // Receive local price updates
             Some(update) = price_rx.recv() => {
-                price_cache.entry(update.asset.clone())
-                    .or_insert_with(Vec::new)
-                    .push(update);
+                outbound.status.write().await.price_updates_sent += 1;
+                tracker.observe(&update.asset, update.timestamp);
+                updated.insert(update.asset.clone());
+                cache_update(&mut price_cache, update);
             }
             
             // Receive gossip price updates from other nodes
             Some(update) = gossip_rx.recv() => {
-                price_cache.entry(update.asset.clone())
-                    .or_insert_with(Vec::new)
-                    .push(update);
+                tracker.observe(&update.asset, update.timestamp);
+                updated.insert(update.asset.clone());
+                if !cache_update(&mut price_cache, update) {
+                    debug!("🌳 Dropped a repeated update from gossip");
+                }
             }
             
+            event = finalized.recv() => match event {
+                Ok(StreamEvent::BatchFinalized { batch_number, consensus_root, .. }) => {
+                    tracker.finalize(batch_number, &consensus_root);
+                    match proposals.finalize(batch_number, &consensus_root) {
+                        Some(batch) => commit_batch(&outbound, batch, node_pubkey).await,
+                        None => debug!("🌳 Batch {} finalized on a root we did not build", batch_number),
+                    }
+                }
+                Ok(_) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("🌳 Missed {} stream events, some finalized feeds stay uncommitted", skipped);
+                }
+                // We hold a sender, so the channel stays open
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => {}
+            },
+            
             // Build Merkle batch every interval
             _ = ticker.tick() => {
-                if price_cache.is_empty() {
+                let now_ms = clock.now_ms();
+                tracker.refresh_staleness(now_ms.div_euclid(1000));
+                if updated.is_empty() {
+                    outbound.state.write().await.publishing = tracker.stats().clone();
+                    continue;
+                }
+                
+                let batch_number = batch_number_at(now_ms, config.batch_interval_ms);
+                if batch_number <= last_batch_number {
+                    // Clock went backwards; keep the cache for the next slot
+                    warn!("🌳 Batch {} is not after batch {}, skipping", batch_number, last_batch_number);
                     continue;
                 }
+                last_batch_number = batch_number;
                 
-                let batch = build_merkle_batch(&price_cache, config.min_publishers);
+                let batch = build_merkle_batch(
+                    batch_number,
+                    now_ms.div_euclid(1000),
+                    &price_cache,
+                    &updated,
+                    config.min_publishers,
+                    &config.derived_feeds,
+                    &mut tracker,
+                );
+                updated.clear();
+                outbound.state.write().await.publishing = tracker.stats().clone();
                 
                 if !batch.feeds.is_empty() {
                     debug!("🌳 Built Merkle batch with {} feeds, root: {}",
                         batch.feeds.len(), &batch.root[..8]);
                     
-                    if let Err(e) = batch_tx.send(batch).await {
+                    proposals.propose(batch.clone());
+                    outbound.status.write().await.batches_created += 1;
+                    
+                    // No stream subscribers is fine
+                    for feed in &batch.feeds {
+                        let _ = outbound.events.send(StreamEvent::Feed {
+                            batch_number: batch.batch_number,
+                            feed: feed.clone(),
+                        });
+                    }
+                    
+                    if let Err(e) = outbound.batch_tx.send(batch).await {
                         tracing::error!("Failed to send batch: {}", e);
                     }
                 }
                 
-                // Clear cache after batching
-                price_cache.clear();
+                // Forget updates too old to be aggregated again
+                let now = now_ms.div_euclid(1000);
+                price_cache.retain(|asset, updates| {
+                    updates.retain(|update| tracker.is_fresh(asset, update.timestamp, now));
+                    !updates.is_empty()
+                });
             }
             
             _ = shutdown.recv() => {
@@ -87,134 +257,442 @@ pub async fn start_aggregator(
     Ok(())
 }
 
+/// Write a finalized batch to the ledger and serve its feeds as the latest
+async fn commit_batch(outbound: &AggregatorOutbound, batch: MerkleBatch, submitter: [u8; 32]) {
+    let ledger = Arc::clone(&outbound.ledger);
+    let stored = {
+        let batch = batch.clone();
+        tokio::task::spawn_blocking(move || ledger.store_batch(&batch, submitter)).await
+    };
+    match stored {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!("Failed to write batch {} to ledger: {}", batch.batch_number, e),
+        Err(e) => warn!("Ledger write for batch {} panicked: {}", batch.batch_number, e),
+    }
+    if let Err(e) = outbound.state.write().await.record_batch(&batch, submitter) {
+        warn!("Failed to record batch {}: {}", batch.batch_number, e);
+    }
+}
+
+/// Keep `update` as its publisher's latest for the asset. An update is one
+/// observation per (publisher, asset, timestamp): one we already have, or one
+/// older than the publisher's latest, is dropped. Returns whether it was kept.
+fn cache_update(price_cache: &mut HashMap<String, Vec<PriceUpdate>>, update: PriceUpdate) -> bool {
+    let updates = price_cache.entry(update.asset.clone()).or_default();
+    match updates.iter_mut().find(|cached| cached.node_pubkey == update.node_pubkey) {
+        Some(cached) if cached.timestamp >= update.timestamp => false,
+        Some(cached) => {
+            *cached = update;
+            true
+        }
+        None => {
+            updates.push(update);
+            true
+        }
+    }
+}
+
+/// Batch number for the batch interval containing `timestamp_ms`.
+/// Every node derives the same number for the same slot, and it only grows.
+pub fn batch_number_at(timestamp_ms: i64, batch_interval_ms: u64) -> u64 {
+    timestamp_ms.max(0) as u64 / batch_interval_ms.max(1)
+}
+
+/// Batch of the assets in `updated` and the derived feeds that read them.
+/// Derived feeds take every input's latest fresh value from `price_cache`,
+/// so their inputs need not arrive within the same batch.
 fn build_merkle_batch(
+    batch_number: u64,
+    timestamp: i64,
     price_cache: &HashMap<String, Vec<PriceUpdate>>,
+    updated: &HashSet<String>,
     min_publishers: u8,
+    derived_feeds: &[DerivedFeedConfig],
+    tracker: &mut PublishTracker,
 ) -> MerkleBatch {
-    let mut feeds = Vec::new();
+    let mut values: HashMap<String, FeedValue> = HashMap::new();
+    let mut symbols = Vec::new();
     
-    for (asset, updates) in price_cache {
-        // Group by publisher
-        let mut publisher_prices: HashMap<String, f64> = HashMap::new();
+    // Leaves go in symbol order so every node, and every replay, builds the same tree
+    let mut assets: Vec<&String> = price_cache.keys().collect();
+    assets.sort();
+    
+    for asset in assets {
+        let updates = &price_cache[asset];
+        // Group by publisher, leaving out data too old for the asset's policy
+        let mut publisher_prices: HashMap<String, (Price, Price)> = HashMap::new();
         
-        for update in updates {
-            publisher_prices.insert(update.node_pubkey.clone(), update.price);
+        for update in updates.iter().filter(|update| tracker.is_fresh(asset, update.timestamp, timestamp)) {
+            publisher_prices.insert(update.node_pubkey.clone(), (update.price, update.confidence));
         }
         
+        // Publishers may run with different precision; compare at the one
+        // most of them use, dropping any that do not fit it
+        let expo = common_expo(publisher_prices.values().map(|(price, _)| price.expo));
+        publisher_prices.retain(|publisher, (price, confidence)| {
+            match (price.rescale(expo), confidence.rescale(expo)) {
+                (Ok(rescaled), Ok(rescaled_confidence)) => {
+                    (*price, *confidence) = (rescaled, rescaled_confidence);
+                    true
+                }
+                _ => {
+                    warn!("🌳 {} from {} does not fit expo {}, dropped", asset, publisher, expo);
+                    false
+                }
+            }
+        });
+        
         // Check if we have enough publishers
-        if publisher_prices.len() < min_publishers as usize {
+        if publisher_prices.is_empty() || publisher_prices.len() < min_publishers as usize {
             continue;
         }
         
-        // Calculate median price
-        let mut prices: Vec<f64> = publisher_prices.values().copied().collect();
-        prices.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        // Calculate median price, on the mantissas so every node gets the same digits
+        let mut prices: Vec<i128> = publisher_prices.values().map(|(price, _)| i128::from(price.mantissa)).collect();
+        prices.sort_unstable();
+        let median = quartile(&prices, 2);
         
-        let median = if prices.len() % 2 == 0 {
-            let mid = prices.len() / 2;
-            (prices[mid - 1] + prices[mid]) / 2.0
-        } else {
-            prices[prices.len() / 2]
-        };
+        // Each publisher votes its price and both ends of its interval; the
+        // confidence reaches the farther quartile of those votes
+        let mut votes: Vec<i128> = publisher_prices.values()
+            .map(|(price, confidence)| (i128::from(price.mantissa), i128::from(confidence.mantissa)))
+            .flat_map(|(price, confidence)| [price - confidence, price, price + confidence])
+            .collect();
+        votes.sort_unstable();
+        let confidence = (median - quartile(&votes, 1)).max(quartile(&votes, 3) - median);
         
-        // Calculate confidence (inverse of std dev)
-        let mean = prices.iter().sum::<f64>() / prices.len() as f64;
-        let variance = prices.iter()
-            .map(|p| (p - mean).powi(2))
-            .sum::<f64>() / prices.len() as f64;
-        let std_dev = variance.sqrt();
-        let confidence = if std_dev > 0.0 {
-            1.0 / (1.0 + std_dev / mean)
-        } else {
-            1.0
+        // The median lies between two prices; only a huge interval can overflow
+        let (Ok(median), Ok(confidence)) = (i64::try_from(median), i64::try_from(confidence)) else {
+            warn!("🌳 {} confidence does not fit expo {}, skipped", asset, expo);
+            continue;
         };
         
-        // Convert to fixed-point integers (9 decimals)
-        let price_i64 = (median * 1_000_000_000.0) as i64;
-        let conf_i64 = (confidence * 1_000_000_000.0) as i64;
+        let mut publishers: Vec<String> = publisher_prices.into_keys().collect();
+        publishers.sort();
         
-        feeds.push(FeedData {
-            asset_id: asset.clone(),
-            price: price_i64,
-            confidence: conf_i64,
-            timestamp: chrono::Utc::now().timestamp(),
-            publishers: publisher_prices.keys().cloned().collect(),
+        if updated.contains(asset) {
+            symbols.push(asset.clone());
+        }
+        values.insert(asset.clone(), FeedValue {
+            price: Price::new(median, expo),
+            confidence: Price::new(confidence, expo),
+            publishers,
         });
     }
     
+    // Derived feeds go after the ones they are computed from, whenever one of
+    // their inputs changed; they use every input, published or not
+    let mut changed: HashSet<&str> = updated.iter().map(String::as_str).collect();
+    let computed = derived::compute_all(derived_feeds, &mut values);
+    for feed in derived_feeds.iter().filter(|feed| computed.contains(&feed.symbol)) {
+        if derived::inputs(&feed.formula).iter().any(|input| changed.contains(input)) {
+            changed.insert(&feed.symbol);
+            symbols.push(feed.symbol.clone());
+        }
+    }
+    
+    // Only feeds that moved enough, or whose heartbeat is due; they count as
+    // committed once consensus finalizes the batch
+    symbols.retain(|symbol| tracker.record(symbol, values[symbol].price, timestamp).publishes());
+    
+    let feeds: Vec<FeedData> = symbols.into_iter()
+        .map(|symbol| {
+            let value = &values[&symbol];
+            FeedData {
+                price: value.price.mantissa,
+                confidence: value.confidence.mantissa,
+                expo: value.price.expo,
+                timestamp,
+                publishers: value.publishers.clone(),
+                asset_id: symbol,
+            }
+        })
+        .collect();
+    
     // Build Merkle tree
     let tree = build_merkle_tree(&feeds);
     let root = tree.last().unwrap_or(&String::new()).clone();
+    tracker.propose(
+        batch_number,
+        &root,
+        timestamp,
+        feeds.iter().map(|feed| (feed.asset_id.clone(), Price::new(feed.price, feed.expo))).collect(),
+    );
     
     MerkleBatch {
+        batch_number,
         root,
-        timestamp: chrono::Utc::now().timestamp(),
+        timestamp,
         feeds,
         tree,
     }
 }
 
+/// Exponent used by the most publishers; the finer one on a tie
+fn common_expo(expos: impl Iterator<Item = i32>) -> i32 {
+    let mut counts: HashMap<i32, usize> = HashMap::new();
+    for expo in expos {
+        *counts.entry(expo).or_default() += 1;
+    }
+    counts.into_iter()
+        .max_by_key(|&(expo, count)| (count, -expo))
+        .map_or(0, |(expo, _)| expo)
+}
+
+/// Linearly interpolated quartile (`quarters` of 4) of sorted, non-empty
+/// mantissas, rounded half up to a whole mantissa
+fn quartile(sorted: &[i128], quarters: usize) -> i128 {
+    let rank = quarters * (sorted.len() - 1);
+    let (low, fraction) = (rank / 4, (rank % 4) as i128);
+    if fraction == 0 {
+        return sorted[low];
+    }
+    let step = (sorted[low + 1] - sorted[low]) * fraction;
+    sorted[low] + (step + 2) / 4
+}
+
 fn build_merkle_tree(feeds: &[FeedData]) -> Vec<String> {
     if feeds.is_empty() {
         return vec![];
     }
     
-    // Create leaf hashes
-    let mut current_level: Vec<String> = feeds.iter()
-        .map(|feed| {
-            let data = format!("{}:{}:{}:{}",
-                feed.asset_id, feed.price, feed.confidence, feed.timestamp);
-            hash_data(data.as_bytes())
-        })
+    // Leaves use the same 60-byte encoding the on-chain verifiers rebuild
+    let leaves = feeds.iter()
+        .map(|feed| feed_leaf(feed).hash())
         .collect();
     
-    let mut tree = current_level.clone();
+    MerkleTree::from_leaves(leaves)
+        .nodes()
+        .iter()
+        .map(hex::encode)
+        .collect()
+}
+
+/// Canonical Merkle leaf for a feed
+pub fn feed_leaf(feed: &FeedData) -> PriceLeaf {
+    PriceLeaf::new(&feed.asset_id, feed.price, feed.confidence, feed.expo, feed.timestamp)
+}
+
+/// Sibling path for `leaf_index`, as hex strings, from a batch's flattened tree
+pub fn get_merkle_proof(tree: &[String], leaf_index: usize) -> Vec<String> {
+    let nodes: Option<Vec<[u8; 32]>> = tree.iter()
+        .map(|node| hex::decode(node).ok()?.try_into().ok())
+        .collect();
+    
+    nodes
+        .and_then(|nodes| MerkleTree::from_nodes(&nodes))
+        .and_then(|tree| tree.proof(leaf_index))
+        .map(|proof| proof.iter().map(hex::encode).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
     
-    // Build tree bottom-up
-    while current_level.len() > 1 {
-        let mut next_level = Vec::new();
+    const NOW: i64 = 1_700_000_000;
+    
+    /// Every asset in `cache`, as if all of them just got new data
+    fn every_asset(cache: &HashMap<String, Vec<PriceUpdate>>) -> HashSet<String> {
+        cache.keys().cloned().collect()
+    }
+    
+    fn update(asset: &str, price: f64, node: &str) -> PriceUpdate {
+        PriceUpdate {
+            asset: asset.to_string(),
+            price: Price::from_f64(price, -8).unwrap(),
+            confidence: Price::new(0, -8),
+            timestamp: NOW,
+            exchange: "aggregated".to_string(),
+            node_pubkey: node.to_string(),
+        }
+    }
+    
+    #[test]
+    fn test_batch_proofs_verify_against_root() {
+        let mut cache: HashMap<String, Vec<PriceUpdate>> = HashMap::new();
+        for (asset, price) in [("BTC/USD", 65_000.0), ("ETH/USD", 3_200.0), ("SOL/USD", 150.0)] {
+            cache.insert(asset.to_string(), vec![update(asset, price, "node1")]);
+        }
         
-        for chunk in current_level.chunks(2) {
-            let combined = if chunk.len() == 2 {
-                format!("{}{}", chunk[0], chunk[1])
-            } else {
-                chunk[0].clone()
-            };
-            
-            let parent_hash = hash_data(combined.as_bytes());
-            next_level.push(parent_hash);
+        let batch = build_merkle_batch(7, NOW, &cache, &every_asset(&cache), 1, &[], &mut PublishTracker::default());
+        assert_eq!(batch.batch_number, 7);
+        let root: [u8; 32] = hex::decode(&batch.root).unwrap().try_into().unwrap();
+        
+        for (i, feed) in batch.feeds.iter().enumerate() {
+            let proof: Vec<[u8; 32]> = get_merkle_proof(&batch.tree, i).iter()
+                .map(|node| hex::decode(node).unwrap().try_into().unwrap())
+                .collect();
+            assert!(tachyon_merkle::verify(feed_leaf(feed).hash(), &proof, &root));
         }
+    }
+    
+    #[test]
+    fn test_confidence_spans_publisher_intervals() {
+        let mut cache: HashMap<String, Vec<PriceUpdate>> = HashMap::new();
+        cache.insert("SOL/USD".to_string(), [(100.0, "node1"), (101.0, "node2"), (102.0, "node3")].iter()
+            .map(|(price, node)| PriceUpdate { confidence: Price::new(1, 0), ..update("SOL/USD", *price, node) })
+            .collect());
+        cache.insert("BTC/USD".to_string(), vec![update("BTC/USD", 65_000.0, "node1")]);
+        
+        let batch = build_merkle_batch(1, NOW, &cache, &every_asset(&cache), 1, &[], &mut PublishTracker::default());
+        let feed = |asset: &str| batch.feeds.iter().find(|feed| feed.asset_id == asset).unwrap().clone();
+        
+        // Votes 99..=103 around a median of 101: quartiles at 100 and 102
+        assert_eq!((feed("SOL/USD").price, feed("SOL/USD").confidence, feed("SOL/USD").expo), (10_100_000_000, 100_000_000, -8));
+        // One exact publisher: no interval
+        assert_eq!(feed("BTC/USD").confidence, 0);
         
-        tree.extend(next_level.clone());
-        current_level = next_level;
+        assert_eq!(quartile(&[100, 200, 300, 400], 2), 250);
+        assert_eq!(quartile(&[100, 200, 300, 400], 1), 175);
+        assert_eq!(quartile(&[1, 2], 2), 2);
     }
     
-    tree
-}
-
-fn hash_data(data: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(data);
-    let result = hasher.finalize();
-    hex::encode(result)
-}
-
-pub fn get_merkle_proof(tree: &[String], leaf_index: usize) -> Vec<String> {
-    let mut proof = Vec::new();
-    let mut index = leaf_index;
-    let mut level_size = (tree.len() + 1) / 2;
+    #[test]
+    fn test_publishers_are_compared_at_common_expo() {
+        let mut cache: HashMap<String, Vec<PriceUpdate>> = HashMap::new();
+        cache.insert("BTC/USD".to_string(), vec![
+            PriceUpdate { price: Price::new(6_500_012, -2), confidence: Price::new(1, -2), ..update("BTC/USD", 0.0, "node1") },
+            PriceUpdate { price: Price::new(6_500_014, -2), confidence: Price::new(3, -2), ..update("BTC/USD", 0.0, "node2") },
+            update("BTC/USD", 65_000.123_456_78, "node3"),
+            // Too large for a mantissa at -2
+            PriceUpdate { price: Price::new(i64::MAX, 0), ..update("BTC/USD", 0.0, "node4") },
+        ]);
+        
+        let batch = build_merkle_batch(1, NOW, &cache, &every_asset(&cache), 3, &[], &mut PublishTracker::default());
+        let btc = &batch.feeds[0];
+        // node3 is rounded to 6_500_012
+        assert_eq!((btc.price, btc.expo), (6_500_012, -2));
+        assert_eq!(btc.publishers.len(), 3);
+        assert_eq!(PriceLeaf::new("BTC/USD", btc.price, btc.confidence, btc.expo, 0).price(), Price::new(6_500_012, -2));
+        
+        assert_eq!(common_expo([-8, -2, -2, -8].into_iter()), -8);
+        assert_eq!(common_expo([-8, -2, -2].into_iter()), -2);
+    }
+    
+    #[test]
+    fn test_publish_policy_filters_feeds() {
+        let mut tracker = PublishTracker::default();
+        let mut cache: HashMap<String, Vec<PriceUpdate>> = HashMap::new();
+        cache.insert("BTC/USD".to_string(), vec![update("BTC/USD", 65_000.0, "node1")]);
+        // Older than the default 30 s staleness: left out
+        let old = PriceUpdate { timestamp: NOW - 31, ..update("ETH/USD", 3_000.0, "node1") };
+        cache.insert("ETH/USD".to_string(), vec![old]);
+        
+        let batch = build_merkle_batch(1, NOW, &cache, &every_asset(&cache), 1, &[], &mut tracker);
+        assert_eq!(batch.feeds.iter().map(|feed| feed.asset_id.as_str()).collect::<Vec<_>>(), vec!["BTC/USD"]);
+        assert_eq!(tracker.stats()["BTC/USD"].last_decision, Some(policy::Decision::First));
+        tracker.finalize(1, &batch.root);
+        
+        build_merkle_batch(2, NOW, &cache, &every_asset(&cache), 1, &[], &mut tracker);
+        assert_eq!(tracker.stats()["BTC/USD"].last_decision, Some(policy::Decision::EveryBatch));
+        assert_eq!(tracker.stats()["BTC/USD"].published, 2);
+    }
     
-    while level_size > 1 {
-        let sibling_index = if index % 2 == 0 { index + 1 } else { index - 1 };
+    #[test]
+    fn test_publishers_are_aggregated_across_batches() {
+        let mut cache: HashMap<String, Vec<PriceUpdate>> = HashMap::new();
+        assert!(cache_update(&mut cache, update("BTC/USD", 65_000.0, "node1")));
+        // The same observation again, e.g. relayed by another peer
+        assert!(!cache_update(&mut cache, update("BTC/USD", 65_000.0, "node1")));
+        assert!(!cache_update(&mut cache, PriceUpdate { timestamp: NOW - 1, ..update("BTC/USD", 64_000.0, "node1") }));
         
-        if sibling_index < level_size {
-            proof.push(tree[sibling_index].clone());
+        // node2's update arrives a batch later; node1's is still in the window
+        assert!(cache_update(&mut cache, PriceUpdate { timestamp: NOW + 1, ..update("BTC/USD", 65_002.0, "node2") }));
+        let batch = build_merkle_batch(1, NOW + 1, &cache, &every_asset(&cache), 2, &[], &mut PublishTracker::default());
+        assert_eq!(batch.feeds[0].publishers, vec!["node1", "node2"]);
+        assert_eq!(batch.feeds[0].price, 6_500_100_000_000);
+        
+        // A newer observation replaces the publisher's last
+        assert!(cache_update(&mut cache, PriceUpdate { timestamp: NOW + 2, ..update("BTC/USD", 65_004.0, "node1") }));
+        assert_eq!(cache["BTC/USD"].len(), 2);
+        let batch = build_merkle_batch(2, NOW + 2, &cache, &every_asset(&cache), 2, &[], &mut PublishTracker::default());
+        assert_eq!(batch.feeds[0].price, 6_500_300_000_000);
+    }
+    
+    #[test]
+    fn test_derived_feeds_are_committed() {
+        let mut cache: HashMap<String, Vec<PriceUpdate>> = HashMap::new();
+        cache.insert("ETH/USD".to_string(), vec![update("ETH/USD", 3_000.0, "node1")]);
+        cache.insert("BTC/USD".to_string(), vec![update("BTC/USD", 60_000.0, "node2")]);
+        let derived_feeds = vec![DerivedFeedConfig {
+            symbol: "ETH/BTC".to_string(),
+            expo: -8,
+            policy: Default::default(),
+            formula: crate::config::DerivedFormula::Ratio {
+                numerator: "ETH/USD".to_string(),
+                denominator: "BTC/USD".to_string(),
+            },
+        }];
+        
+        let batch = build_merkle_batch(1, NOW, &cache, &every_asset(&cache), 1, &derived_feeds, &mut PublishTracker::default());
+        assert_eq!(batch.feeds.len(), 3);
+        let (index, eth_btc) = batch.feeds.iter().enumerate().find(|(_, feed)| feed.asset_id == "ETH/BTC").unwrap();
+        assert_eq!(index, 2);
+        assert_eq!((eth_btc.price, eth_btc.confidence, eth_btc.expo), (5_000_000, 0, -8));
+        assert_eq!(eth_btc.publishers, vec!["node1", "node2"]);
+        
+        let root: [u8; 32] = hex::decode(&batch.root).unwrap().try_into().unwrap();
+        let proof: Vec<[u8; 32]> = get_merkle_proof(&batch.tree, index).iter()
+            .map(|node| hex::decode(node).unwrap().try_into().unwrap())
+            .collect();
+        assert!(tachyon_merkle::verify(feed_leaf(eth_btc).hash(), &proof, &root));
+    }
+    
+    #[test]
+    fn test_derived_feeds_from_inputs_of_different_batches() {
+        let derived_feeds = vec![DerivedFeedConfig {
+            symbol: "ETH/BTC".to_string(),
+            expo: -8,
+            policy: Default::default(),
+            formula: crate::config::DerivedFormula::Ratio {
+                numerator: "ETH/USD".to_string(),
+                denominator: "BTC/USD".to_string(),
+            },
+        }];
+        let mut tracker = PublishTracker::default();
+        let mut cache: HashMap<String, Vec<PriceUpdate>> = HashMap::new();
+        let symbols = |batch: &MerkleBatch| batch.feeds.iter().map(|feed| feed.asset_id.clone()).collect::<Vec<_>>();
+        
+        cache_update(&mut cache, update("ETH/USD", 3_000.0, "node1"));
+        let batch = build_merkle_batch(1, NOW, &cache, &HashSet::from(["ETH/USD".to_string()]), 1, &derived_feeds, &mut tracker);
+        assert_eq!(symbols(&batch), vec!["ETH/USD"]);
+        
+        // BTC/USD lands a batch later; ETH/USD is still fresh in the cache
+        cache_update(&mut cache, update("BTC/USD", 60_000.0, "node2"));
+        let batch = build_merkle_batch(2, NOW, &cache, &HashSet::from(["BTC/USD".to_string()]), 1, &derived_feeds, &mut tracker);
+        assert_eq!(symbols(&batch), vec!["BTC/USD", "ETH/BTC"]);
+        assert_eq!(batch.feeds[1].price, 5_000_000);
+        
+        // Nothing new: nothing derived either
+        let batch = build_merkle_batch(3, NOW, &cache, &HashSet::new(), 1, &derived_feeds, &mut tracker);
+        assert!(batch.feeds.is_empty());
+    }
+    
+    #[test]
+    fn test_only_batches_finalized_on_our_root_commit() {
+        let mut cache: HashMap<String, Vec<PriceUpdate>> = HashMap::new();
+        cache.insert("BTC/USD".to_string(), vec![update("BTC/USD", 65_000.0, "node1")]);
+        let mut proposals = Proposals::default();
+        for batch_number in 1..=3 {
+            let batch = build_merkle_batch(batch_number, NOW, &cache, &every_asset(&cache), 1, &[], &mut PublishTracker::default());
+            proposals.propose(batch);
         }
+        let root_3 = proposals.batches[&3].root.clone();
         
-        index /= 2;
-        level_size = (level_size + 1) / 2;
+        // Consensus settled on another root: nothing to commit
+        assert!(proposals.finalize(1, &hex::encode([7u8; 32])).is_none());
+        
+        // Batch 2 never finalized and is forgotten once batch 3 is
+        assert_eq!(proposals.finalize(3, &root_3).map(|batch| batch.batch_number), Some(3));
+        assert!(proposals.batches.is_empty());
     }
     
-    proof
+    #[test]
+    fn test_batch_number_follows_interval() {
+        assert_eq!(batch_number_at(1_700_000_000_000, 100), 17_000_000_000);
+        assert_eq!(batch_number_at(1_700_000_000_099, 100), 17_000_000_000);
+        assert_eq!(batch_number_at(1_700_000_000_100, 100), 17_000_000_001);
+        assert_eq!(batch_number_at(-5, 100), 0);
+    }
 }
-
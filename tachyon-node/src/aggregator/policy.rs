@@ -0,0 +1,333 @@
+// Publish Policy - per-feed deviation, heartbeat and staleness
+//
+// A feed goes into a batch when its price moved at least `deviation_bps`
+// from the last price committed for it, or when `heartbeat_secs` passed
+// since that commitment; otherwise it is left out and the previous
+// commitment stands on chain. A batch's prices only count as committed once
+// consensus finalizes the batch on the root this node built; until then the
+// feeds are pending and later batches still decide against the older
+// commitment. An asset without fresh source data for `stale_after_secs` is
+// marked stale and not published until data returns.
+
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use tachyon_merkle::Price;
+use tracing::{info, warn};
+
+use crate::config::{NodeConfig, PublishPolicy};
+
+/// Built batches awaiting consensus before their prices count as committed
+const MAX_PENDING_BATCHES: usize = 64;
+
+/// Why a feed was or was not published in a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    /// Nothing committed for the feed yet
+    First,
+    /// No deviation or heartbeat configured
+    EveryBatch,
+    Deviation,
+    Heartbeat,
+    /// Inside the deviation band, heartbeat not due
+    Skip,
+    /// No fresh source data
+    Stale,
+}
+
+impl Decision {
+    pub fn publishes(self) -> bool {
+        !matches!(self, Decision::Skip | Decision::Stale)
+    }
+}
+
+/// Publishing decisions for one feed, for the API and metrics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeedPolicyStats {
+    pub published: u64,
+    /// Publications triggered by a move past `deviation_bps`
+    pub deviation_triggers: u64,
+    /// Publications triggered by the heartbeat
+    pub heartbeat_triggers: u64,
+    pub skipped: u64,
+    pub stale: bool,
+    pub last_decision: Option<Decision>,
+    /// Move from the last committed price at the latest decision (basis points)
+    pub deviation_bps: Option<f64>,
+    pub last_published_at: Option<i64>,
+    /// Newest source data for the asset
+    pub last_seen: Option<i64>,
+}
+
+/// Feeds of a built batch, committed if consensus agrees on `root`
+#[derive(Debug)]
+struct PendingBatch {
+    root: String,
+    timestamp: i64,
+    prices: Vec<(String, Price)>,
+}
+
+/// Last commitment and freshness of every feed, owned by the aggregator
+#[derive(Debug, Default)]
+pub struct PublishTracker {
+    policies: HashMap<String, PublishPolicy>,
+    /// Last committed price and when, by feed
+    committed: HashMap<String, (Price, i64)>,
+    /// Batches built but not yet finalized, by batch number
+    pending: BTreeMap<u64, PendingBatch>,
+    stats: BTreeMap<String, FeedPolicyStats>,
+}
+
+impl PublishTracker {
+    /// Configured assets start fresh at `now`, so one that never reports
+    /// goes stale after its `stale_after_secs`
+    pub fn new(config: &NodeConfig, now: i64) -> Self {
+        let mut tracker = Self::default();
+        for asset in &config.assets {
+            tracker.policies.insert(asset.symbol.clone(), asset.policy);
+            tracker.stats.entry(asset.symbol.clone()).or_default().last_seen = Some(now);
+        }
+        for feed in &config.derived_feeds {
+            tracker.policies.insert(feed.symbol.clone(), feed.policy);
+        }
+        tracker
+    }
+
+    /// Feeds not configured locally (e.g. only heard over gossip) use the default
+    fn policy(&self, symbol: &str) -> PublishPolicy {
+        self.policies.get(symbol).copied().unwrap_or_default()
+    }
+
+    /// Note source data for `asset` from `timestamp`
+    pub fn observe(&mut self, asset: &str, timestamp: i64) {
+        let last_seen = &mut self.stats.entry(asset.to_string()).or_default().last_seen;
+        *last_seen = (*last_seen).max(Some(timestamp));
+    }
+
+    /// Whether data from `timestamp` is recent enough to publish
+    pub fn is_fresh(&self, asset: &str, timestamp: i64, now: i64) -> bool {
+        now.saturating_sub(timestamp) <= self.policy(asset).stale_after_secs as i64
+    }
+
+    /// Re-mark every observed asset stale or fresh as of `now`
+    pub fn refresh_staleness(&mut self, now: i64) {
+        for (asset, stats) in self.stats.iter_mut() {
+            let Some(last_seen) = stats.last_seen else {
+                continue;
+            };
+            let stale_after = self.policies.get(asset).copied().unwrap_or_default().stale_after_secs;
+            let stale = now.saturating_sub(last_seen) > stale_after as i64;
+
+            if stale && !stats.stale {
+                warn!("⏸️  {} is stale: no source data for {}s", asset, now - last_seen);
+            } else if !stale && stats.stale {
+                info!("▶️  {} has fresh data again", asset);
+            }
+            stats.stale = stale;
+        }
+    }
+
+    /// Decide whether `price` is published for `symbol` at `now`, without recording it
+    pub fn decide(&self, symbol: &str, price: Price, now: i64) -> (Decision, Option<f64>) {
+        if self.stats.get(symbol).is_some_and(|stats| stats.stale) {
+            return (Decision::Stale, None);
+        }
+        let Some(&(committed, committed_at)) = self.committed.get(symbol) else {
+            return (Decision::First, None);
+        };
+
+        let deviation_bps = deviation_bps(committed, price);
+        let policy = self.policy(symbol);
+        let decision = if policy.deviation_bps.is_none() && policy.heartbeat_secs.is_none() {
+            Decision::EveryBatch
+        } else if policy.deviation_bps.is_some_and(|threshold| deviation_bps >= threshold) {
+            Decision::Deviation
+        } else if policy.heartbeat_secs.is_some_and(|heartbeat| now - committed_at >= heartbeat as i64) {
+            Decision::Heartbeat
+        } else {
+            Decision::Skip
+        };
+        (decision, Some(deviation_bps))
+    }
+
+    /// Decide for `symbol` and count the decision; a published price is
+    /// only committed once its batch is finalized, see [`Self::finalize`]
+    pub fn record(&mut self, symbol: &str, price: Price, now: i64) -> Decision {
+        let (decision, deviation) = self.decide(symbol, price, now);
+
+        let stats = self.stats.entry(symbol.to_string()).or_default();
+        stats.last_decision = Some(decision);
+        stats.deviation_bps = deviation;
+        match decision {
+            Decision::Skip | Decision::Stale => stats.skipped += 1,
+            Decision::Deviation => stats.deviation_triggers += 1,
+            Decision::Heartbeat => stats.heartbeat_triggers += 1,
+            Decision::First | Decision::EveryBatch => {}
+        }
+        if decision.publishes() {
+            stats.published += 1;
+            stats.last_published_at = Some(now);
+        }
+        decision
+    }
+
+    /// Hold the feeds of batch `batch_number`, built on `root`, until consensus closes on it
+    pub fn propose(&mut self, batch_number: u64, root: &str, timestamp: i64, prices: Vec<(String, Price)>) {
+        if prices.is_empty() {
+            return;
+        }
+        self.pending.insert(batch_number, PendingBatch { root: root.to_string(), timestamp, prices });
+        while self.pending.len() > MAX_PENDING_BATCHES {
+            self.pending.pop_first();
+        }
+    }
+
+    /// Consensus closed on `root` for `batch_number`: commit the batch's
+    /// prices if we built the same root. Consensus closes batches in order,
+    /// so earlier pending batches never will be and are dropped.
+    pub fn finalize(&mut self, batch_number: u64, root: &str) {
+        let later = self.pending.split_off(&(batch_number + 1));
+        let Some(batch) = std::mem::replace(&mut self.pending, later).remove(&batch_number) else {
+            return;
+        };
+        if batch.root != root {
+            warn!("🌳 Batch {} finalized on another root, its feeds are not committed", batch_number);
+            return;
+        }
+
+        for (symbol, price) in batch.prices {
+            match self.committed.get(&symbol) {
+                Some(&(_, committed_at)) if committed_at > batch.timestamp => {}
+                _ => {
+                    self.committed.insert(symbol, (price, batch.timestamp));
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> &BTreeMap<String, FeedPolicyStats> {
+        &self.stats
+    }
+}
+
+/// Relative move from `from` to `to` in basis points; any move from zero is infinite
+fn deviation_bps(from: Price, to: Price) -> f64 {
+    let (from, to) = (from.to_f64(), to.to_f64());
+    if from == 0.0 {
+        return if to == 0.0 { 0.0 } else { f64::INFINITY };
+    }
+    ((to - from) / from).abs() * 10_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(policy: PublishPolicy) -> PublishTracker {
+        PublishTracker {
+            policies: HashMap::from([("BTC/USD".to_string(), policy)]),
+            ..PublishTracker::default()
+        }
+    }
+
+    /// Record a decision and, if it publishes, finalize it in its own batch
+    fn publish(tracker: &mut PublishTracker, symbol: &str, price: Price, now: i64) -> Decision {
+        let decision = tracker.record(symbol, price, now);
+        if decision.publishes() {
+            let batch_number = now as u64;
+            tracker.propose(batch_number, "root", now, vec![(symbol.to_string(), price)]);
+            tracker.finalize(batch_number, "root");
+        }
+        decision
+    }
+
+    #[test]
+    fn test_deviation_and_heartbeat() {
+        let mut tracker = tracker(PublishPolicy {
+            deviation_bps: Some(50.0),
+            heartbeat_secs: Some(60),
+            ..PublishPolicy::default()
+        });
+        let now = 1_700_000_000;
+
+        assert_eq!(publish(&mut tracker, "BTC/USD", Price::new(100_000, 0), now), Decision::First);
+        // 0.3% is inside the band
+        assert_eq!(publish(&mut tracker, "BTC/USD", Price::new(100_300, 0), now + 10), Decision::Skip);
+        // 0.5% from the committed 100_000, not from the skipped 100_300
+        assert_eq!(publish(&mut tracker, "BTC/USD", Price::new(99_500, 0), now + 20), Decision::Deviation);
+        assert_eq!(publish(&mut tracker, "BTC/USD", Price::new(99_500, 0), now + 79), Decision::Skip);
+        assert_eq!(publish(&mut tracker, "BTC/USD", Price::new(99_500, 0), now + 80), Decision::Heartbeat);
+
+        let stats = &tracker.stats()["BTC/USD"];
+        assert_eq!((stats.published, stats.skipped), (3, 2));
+        assert_eq!((stats.deviation_triggers, stats.heartbeat_triggers), (1, 1));
+        assert_eq!(stats.last_published_at, Some(now + 80));
+        assert_eq!(stats.deviation_bps, Some(0.0));
+
+        // Unconfigured feeds publish every batch
+        assert_eq!(publish(&mut tracker, "ETH/USD", Price::new(3_000, 0), now), Decision::First);
+        assert_eq!(publish(&mut tracker, "ETH/USD", Price::new(3_000, 0), now), Decision::EveryBatch);
+    }
+
+    #[test]
+    fn test_commits_only_finalized_batches() {
+        let mut tracker = tracker(PublishPolicy { deviation_bps: Some(50.0), ..PublishPolicy::default() });
+        let now = 1_700_000_000;
+        let btc = |mantissa| vec![("BTC/USD".to_string(), Price::new(mantissa, 0))];
+
+        assert_eq!(tracker.record("BTC/USD", Price::new(100_000, 0), now), Decision::First);
+        tracker.propose(1, "a", now, btc(100_000));
+        // Not finalized yet: the next batch publishes again
+        assert_eq!(tracker.record("BTC/USD", Price::new(100_000, 0), now + 1), Decision::First);
+        tracker.propose(2, "b", now + 1, btc(100_000));
+
+        // Batch 1 never finalizes and batch 2 closes on another root
+        tracker.finalize(2, "other");
+        assert!(tracker.pending.is_empty());
+        assert_eq!(tracker.record("BTC/USD", Price::new(100_000, 0), now + 2), Decision::First);
+        tracker.propose(3, "c", now + 2, btc(100_000));
+
+        tracker.finalize(3, "c");
+        assert_eq!(tracker.record("BTC/USD", Price::new(100_100, 0), now + 3), Decision::Skip);
+        assert_eq!(tracker.record("BTC/USD", Price::new(100_500, 0), now + 4), Decision::Deviation);
+    }
+
+    #[test]
+    fn test_stale_until_fresh_data() {
+        let mut tracker = tracker(PublishPolicy { stale_after_secs: 30, ..PublishPolicy::default() });
+        let now = 1_700_000_000;
+        tracker.observe("BTC/USD", now);
+
+        assert!(tracker.is_fresh("BTC/USD", now - 30, now));
+        assert!(!tracker.is_fresh("BTC/USD", now - 31, now));
+
+        tracker.refresh_staleness(now + 31);
+        assert!(tracker.stats()["BTC/USD"].stale);
+        assert_eq!(tracker.record("BTC/USD", Price::new(100_000, 0), now + 31), Decision::Stale);
+
+        // An older report does not make it fresh
+        tracker.observe("BTC/USD", now - 5);
+        tracker.refresh_staleness(now + 32);
+        assert!(tracker.stats()["BTC/USD"].stale);
+
+        tracker.observe("BTC/USD", now + 32);
+        tracker.refresh_staleness(now + 32);
+        assert!(!tracker.stats()["BTC/USD"].stale);
+        assert_eq!(tracker.record("BTC/USD", Price::new(100_000, 0), now + 32), Decision::First);
+    }
+
+    #[test]
+    fn test_policy_is_read_from_asset_table() {
+        let asset: crate::config::AssetConfig = toml::from_str(
+            "symbol = \"BTC/USD\"\nexchanges = [\"kraken\"]\ndeviation_bps = 25.0\nheartbeat_secs = 300\n",
+        ).unwrap();
+        assert_eq!(asset.policy, PublishPolicy {
+            deviation_bps: Some(25.0),
+            heartbeat_secs: Some(300),
+            stale_after_secs: 30,
+        });
+
+        let round_trip: crate::config::AssetConfig = toml::from_str(&toml::to_string(&asset).unwrap()).unwrap();
+        assert_eq!(round_trip.policy, asset.policy);
+    }
+}
@@ -1,19 +1,41 @@
 use solana_sdk::signer::Signer;
 use anyhow::Result;
 use axum::{
-    extract::State,
+    extract::{Path, Query, State},
     http::StatusCode,
     response::Json,
     routing::get,
     Router,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Instant;
+use tachyon_merkle::MerkleTree;
 use tokio::sync::RwLock;
 use tower_http::cors::CorsLayer;
-use tracing::info;
+use tracing::{info, warn};
 
+pub mod stream;
+
+use stream::StreamEvent;
+use crate::aggregator::{feed_leaf, AggregatorState, MerkleBatch};
+use crate::aggregator::policy::FeedPolicyStats;
 use crate::config::NodeConfig;
+use crate::consensus::oracle_tower::TowerStats;
+use crate::fetcher::robust_fetcher::CircuitState;
+use crate::fetcher::stats::{FetcherStats, SourceStats};
+use crate::consensus::root_bytes;
+use crate::ledger::oracle_ledger::{ConsensusRecord, OracleLedger, PriceRecord, SubmissionRecord};
+
+/// Default window for `/v1/feeds/{symbol}/history` (seconds)
+const DEFAULT_HISTORY_WINDOW: i64 = 3600;
+
+/// Most points a history query returns; the newest are kept
+const MAX_HISTORY_POINTS: usize = 10_000;
+
+/// Longest window a history query reads (seconds); `from` is moved up to fit
+const MAX_HISTORY_SPAN: i64 = 7 * 24 * 3600;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeStatus {
@@ -27,9 +49,113 @@ pub struct NodeStatus {
     pub is_leader: bool,
 }
 
+impl NodeStatus {
+    pub fn new(config: &NodeConfig) -> Self {
+        Self {
+            node_pubkey: config.identity.pubkey().to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_seconds: 0,
+            price_updates_sent: 0,
+            batches_created: 0,
+            batches_submitted: 0,
+            peers_connected: 0,
+            is_leader: false,
+        }
+    }
+}
+
+/// A committed price as served by `/v1/feeds`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceResponse {
+    pub symbol: String,
+    pub price: f64,
+    /// Confidence interval around `price`, in price units
+    pub confidence: f64,
+    pub timestamp: i64,
+    pub batch_number: u64,
+    pub merkle_root: String,
+    pub publishers: Vec<String>,
+    /// No fresh source data within the asset's `stale_after_secs`
+    pub stale: bool,
+}
+
+impl From<&PriceRecord> for PriceResponse {
+    fn from(record: &PriceRecord) -> Self {
+        Self {
+            symbol: record.symbol.clone(),
+            price: record.price().to_f64(),
+            confidence: record.confidence().to_f64(),
+            timestamp: record.timestamp,
+            batch_number: record.batch_number,
+            merkle_root: hex::encode(record.merkle_root),
+            publishers: record.publishers.clone(),
+            stale: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsensusResponse {
+    pub consensus_root: Option<String>,
+    pub agreeing_stake: u64,
+    pub total_stake: u64,
+    pub voters: Vec<String>,
+    pub is_leader: bool,
+}
+
+impl From<&ConsensusRecord> for ConsensusResponse {
+    fn from(record: &ConsensusRecord) -> Self {
+        Self {
+            consensus_root: record.consensus_root.map(hex::encode),
+            agreeing_stake: record.agreeing_stake,
+            total_stake: record.total_stake,
+            voters: record.voters.clone(),
+            is_leader: record.is_leader,
+        }
+    }
+}
+
+/// A batch with its consensus outcome and on-chain submission, if any
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResponse {
+    #[serde(flatten)]
+    pub batch: MerkleBatch,
+    pub consensus: Option<ConsensusResponse>,
+    pub submission: Option<SubmissionRecord>,
+}
+
+/// Arguments for `tachyon_verifier::verify_price`, hashes hex-encoded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofResponse {
+    pub batch_number: u64,
+    pub symbol: String,
+    pub asset_id: String,
+    pub price: i64,
+    pub confidence: i64,
+    /// Decimal exponent of `price` and `confidence`
+    pub expo: i32,
+    pub timestamp: i64,
+    pub merkle_root: String,
+    pub proof: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    /// Bucket size in seconds; the last price in each bucket is returned
+    pub interval: Option<i64>,
+}
+
 pub struct AppState {
     pub config: Arc<NodeConfig>,
     pub status: Arc<RwLock<NodeStatus>>,
+    pub tower: Arc<RwLock<TowerStats>>,
+    pub fetcher: Arc<RwLock<FetcherStats>>,
+    pub feeds: Arc<RwLock<AggregatorState>>,
+    pub ledger: Arc<OracleLedger>,
+    pub events: tokio::sync::broadcast::Sender<StreamEvent>,
+    pub started_at: Instant,
 }
 
 impl Clone for AppState {
@@ -37,38 +163,54 @@ impl Clone for AppState {
         Self {
             config: Arc::clone(&self.config),
             status: Arc::clone(&self.status),
+            tower: Arc::clone(&self.tower),
+            fetcher: Arc::clone(&self.fetcher),
+            feeds: Arc::clone(&self.feeds),
+            ledger: Arc::clone(&self.ledger),
+            events: self.events.clone(),
+            started_at: self.started_at,
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn start_api_server(
     config: Arc<NodeConfig>,
+    status: Arc<RwLock<NodeStatus>>,
+    tower: Arc<RwLock<TowerStats>>,
+    fetcher: Arc<RwLock<FetcherStats>>,
+    feeds: Arc<RwLock<AggregatorState>>,
+    ledger: Arc<OracleLedger>,
+    events: tokio::sync::broadcast::Sender<StreamEvent>,
     mut shutdown: tokio::sync::broadcast::Receiver<()>,
 ) -> Result<()> {
     info!("🔌 Starting API server on port {}...", config.api_port);
     
     let api_port = config.api_port;
-    let status = Arc::new(RwLock::new(NodeStatus {
-        node_pubkey: config.identity.pubkey().to_string(),
-        version: env!("CARGO_PKG_VERSION").to_string(),
-        uptime_seconds: 0,
-        price_updates_sent: 0,
-        batches_created: 0,
-        batches_submitted: 0,
-        peers_connected: 0,
-        is_leader: false,
-    }));
-    
     let state = AppState {
         config,
         status,
+        tower,
+        fetcher,
+        feeds,
+        ledger,
+        events,
+        started_at: Instant::now(),
     };
     
     let app = Router::new()
         .route("/", get(root_handler))
         .route("/status", get(status_handler))
         .route("/health", get(health_handler))
+        .route("/tower", get(tower_handler))
+        .route("/fetcher", get(fetcher_handler))
         .route("/metrics", get(metrics_handler))
+        .route("/v1/feeds", get(feeds_handler))
+        .route("/v1/feeds/:symbol/latest", get(latest_price_handler))
+        .route("/v1/feeds/:symbol/history", get(price_history_handler))
+        .route("/v1/batches/:batch_number", get(batch_handler))
+        .route("/v1/proofs/:batch_number/:symbol", get(proof_handler))
+        .route("/v1/stream", get(stream::stream_handler))
         .layer(CorsLayer::permissive())
         .with_state(state);
     
@@ -94,10 +236,25 @@ async fn root_handler() -> &'static str {
 async fn status_handler(
     State(state): State<AppState>,
 ) -> Result<Json<NodeStatus>, StatusCode> {
-    let status = state.status.read().await;
+    let mut status = state.status.write().await;
+    status.uptime_seconds = state.started_at.elapsed().as_secs();
     Ok(Json(status.clone()))
 }
 
+async fn tower_handler(
+    State(state): State<AppState>,
+) -> Result<Json<TowerStats>, StatusCode> {
+    let tower = state.tower.read().await;
+    Ok(Json(tower.clone()))
+}
+
+async fn fetcher_handler(
+    State(state): State<AppState>,
+) -> Result<Json<FetcherStats>, StatusCode> {
+    let fetcher = state.fetcher.read().await;
+    Ok(Json(fetcher.clone()))
+}
+
 async fn health_handler() -> Json<serde_json::Value> {
     Json(serde_json::json!({
         "status": "healthy",
@@ -108,10 +265,14 @@ async fn health_handler() -> Json<serde_json::Value> {
 async fn metrics_handler(
     State(state): State<AppState>,
 ) -> Result<String, StatusCode> {
-    let status = state.status.read().await;
+    let mut status = state.status.write().await;
+    status.uptime_seconds = state.started_at.elapsed().as_secs();
+    let tower = state.tower.read().await;
+    let fetcher = state.fetcher.read().await;
+    let feeds = state.feeds.read().await;
     
     // Prometheus format
-    let metrics = format!(
+    let mut metrics = format!(
         "# HELP tachyon_price_updates_total Total number of price updates sent\n\
          # TYPE tachyon_price_updates_total counter\n\
          tachyon_price_updates_total {}\n\
@@ -130,14 +291,370 @@ async fn metrics_handler(
          \n\
          # HELP tachyon_uptime_seconds Node uptime in seconds\n\
          # TYPE tachyon_uptime_seconds counter\n\
-         tachyon_uptime_seconds {}\n",
+         tachyon_uptime_seconds {}\n\
+         \n\
+         # HELP tachyon_tower_votes Votes held in the consensus tower\n\
+         # TYPE tachyon_tower_votes gauge\n\
+         tachyon_tower_votes {}\n\
+         \n\
+         # HELP tachyon_tower_lockouts Active tower lockouts\n\
+         # TYPE tachyon_tower_lockouts gauge\n\
+         tachyon_tower_lockouts {}\n\
+         \n\
+         # HELP tachyon_tower_last_voted_batch Last batch this node voted on\n\
+         # TYPE tachyon_tower_last_voted_batch gauge\n\
+         tachyon_tower_last_voted_batch {}\n\
+         \n\
+         # HELP tachyon_tower_root_batch Latest rooted batch\n\
+         # TYPE tachyon_tower_root_batch gauge\n\
+         tachyon_tower_root_batch {}\n",
         status.price_updates_sent,
         status.batches_created,
         status.batches_submitted,
         status.peers_connected,
         status.uptime_seconds,
+        tower.total_votes,
+        tower.active_lockouts,
+        tower.last_voted_batch.unwrap_or(0),
+        tower.root_batch.unwrap_or(0),
     );
     
+    metrics.push_str(&fetcher_metrics(&fetcher));
+    metrics.push_str(&publishing_metrics(&feeds.publishing));
     Ok(metrics)
 }
 
+/// Prometheus lines for the price fetcher, labelled by source
+fn fetcher_metrics(fetcher: &FetcherStats) -> String {
+    let mut metrics = format!(
+        "\n\
+         # HELP tachyon_fetcher_ticks_total Fetch rounds started\n\
+         # TYPE tachyon_fetcher_ticks_total counter\n\
+         tachyon_fetcher_ticks_total {}\n\
+         \n\
+         # HELP tachyon_fetcher_ticks_skipped_total Fetch rounds skipped while the previous one was running\n\
+         # TYPE tachyon_fetcher_ticks_skipped_total counter\n\
+         tachyon_fetcher_ticks_skipped_total {}\n",
+        fetcher.ticks,
+        fetcher.ticks_skipped,
+    );
+    
+    let mut family = |name: &str, kind: &str, help: &str, value: fn(&SourceStats) -> String| {
+        metrics.push_str(&format!("\n# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+        for (source, stats) in &fetcher.sources {
+            metrics.push_str(&format!("{}{{source=\"{}\"}} {}\n", name, source, value(stats)));
+        }
+    };
+    family("tachyon_source_requests_total", "counter", "Requests sent to each price source", |s| s.requests.to_string());
+    family("tachyon_source_failures_total", "counter", "Requests that returned an error", |s| s.failures.to_string());
+    family("tachyon_source_timeouts_total", "counter", "Requests that missed the fetch deadline", |s| s.timeouts.to_string());
+    family("tachyon_source_success_ratio", "gauge", "Share of requests that returned a price", |s| s.success_rate().to_string());
+    family("tachyon_source_latency_ms", "gauge", "Average request latency in milliseconds", |s| s.avg_latency_ms().to_string());
+    family("tachyon_source_depeg_flags_total", "counter", "Quotes converted through a stablecoin past the depeg threshold", |s| s.depeg_flags.to_string());
+    
+    metrics.push_str(
+        "\n# HELP tachyon_source_breaker_open Whether a source is skipped for an asset (1 = open, 0.5 = half-open)\n\
+         # TYPE tachyon_source_breaker_open gauge\n",
+    );
+    for (asset, breakers) in &fetcher.breakers {
+        for (source, breaker) in breakers {
+            let open = match breaker.state {
+                CircuitState::Closed => 0.0,
+                CircuitState::HalfOpen => 0.5,
+                CircuitState::Open => 1.0,
+            };
+            metrics.push_str(&format!("tachyon_source_breaker_open{{asset=\"{}\",source=\"{}\"}} {}\n", asset, source, open));
+        }
+    }
+    
+    metrics.push_str(
+        "\n# HELP tachyon_stablecoin_rate Latest internal stablecoin rate\n\
+         # TYPE tachyon_stablecoin_rate gauge\n",
+    );
+    for (symbol, rate) in &fetcher.stablecoins {
+        metrics.push_str(&format!("tachyon_stablecoin_rate{{symbol=\"{}\"}} {}\n", symbol, rate.rate));
+    }
+    metrics.push_str(
+        "\n# HELP tachyon_stablecoin_depegged Whether a stablecoin is past the depeg threshold\n\
+         # TYPE tachyon_stablecoin_depegged gauge\n",
+    );
+    for (symbol, rate) in &fetcher.stablecoins {
+        metrics.push_str(&format!("tachyon_stablecoin_depegged{{symbol=\"{}\"}} {}\n", symbol, rate.depegged as u8));
+    }
+    
+    metrics
+}
+
+/// Prometheus lines for the publish policy, labelled by feed
+fn publishing_metrics(publishing: &BTreeMap<String, FeedPolicyStats>) -> String {
+    let mut metrics = String::new();
+    let mut family = |name: &str, kind: &str, help: &str, value: fn(&FeedPolicyStats) -> Option<String>| {
+        metrics.push_str(&format!("\n# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+        for (asset, stats) in publishing {
+            if let Some(value) = value(stats) {
+                metrics.push_str(&format!("{}{{asset=\"{}\"}} {}\n", name, asset, value));
+            }
+        }
+    };
+    family("tachyon_feed_published_total", "counter", "Batches the feed was published in", |s| Some(s.published.to_string()));
+    family("tachyon_feed_deviation_triggers_total", "counter", "Publications triggered by the deviation threshold", |s| Some(s.deviation_triggers.to_string()));
+    family("tachyon_feed_heartbeat_triggers_total", "counter", "Publications triggered by the heartbeat", |s| Some(s.heartbeat_triggers.to_string()));
+    family("tachyon_feed_skipped_total", "counter", "Batches the feed was left out of, unmoved or stale", |s| Some(s.skipped.to_string()));
+    family("tachyon_feed_stale", "gauge", "Whether the feed has no fresh source data", |s| Some((s.stale as u8).to_string()));
+    family("tachyon_feed_deviation_bps", "gauge", "Move from the last committed price at the latest batch", |s| s.deviation_bps.map(|bps| bps.to_string()));
+    family("tachyon_feed_last_published_timestamp", "gauge", "When the feed was last published (unix seconds)", |s| s.last_published_at.map(|at| at.to_string()));
+    
+    metrics
+}
+
+async fn feeds_handler(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<PriceResponse>>, StatusCode> {
+    let mut records: Vec<PriceRecord> = state.feeds.read().await.latest.values().cloned().collect();
+    
+    // Nothing aggregated since startup yet: serve what the ledger has
+    if records.is_empty() {
+        records = read_ledger(&state, |ledger| {
+            let mut records = Vec::new();
+            for symbol in ledger.get_symbols()? {
+                records.extend(ledger.get_latest_price(&symbol)?);
+            }
+            Ok(records)
+        }).await?;
+    }
+    
+    let aggregator = state.feeds.read().await;
+    let mut feeds: Vec<PriceResponse> = records.iter()
+        .map(|record| PriceResponse { stale: aggregator.is_stale(&record.symbol), ..PriceResponse::from(record) })
+        .collect();
+    
+    feeds.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    Ok(Json(feeds))
+}
+
+async fn latest_price_handler(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+) -> Result<Json<PriceResponse>, StatusCode> {
+    let symbol = normalize_symbol(&symbol);
+    
+    let (stale, latest) = {
+        let aggregator = state.feeds.read().await;
+        (aggregator.is_stale(&symbol), aggregator.latest.get(&symbol).cloned())
+    };
+    let record = match latest {
+        Some(record) => Some(record),
+        None => read_ledger(&state, move |ledger| ledger.get_latest_price(&symbol)).await?,
+    };
+    
+    record
+        .map(|record| Json(PriceResponse { stale, ..PriceResponse::from(&record) }))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn price_history_handler(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<PriceResponse>>, StatusCode> {
+    let symbol = normalize_symbol(&symbol);
+    let to = query.to.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let from = query.from.unwrap_or(to - DEFAULT_HISTORY_WINDOW);
+    if from > to || query.interval.is_some_and(|interval| interval <= 0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let from = from.max(to.saturating_sub(MAX_HISTORY_SPAN));
+    
+    let history = read_ledger(&state, move |ledger| {
+        ledger.get_price_history(&symbol, from, to, query.interval, MAX_HISTORY_POINTS)
+    }).await?;
+    
+    Ok(Json(history.iter().map(PriceResponse::from).collect()))
+}
+
+async fn batch_handler(
+    State(state): State<AppState>,
+    Path(batch_number): Path<u64>,
+) -> Result<Json<BatchResponse>, StatusCode> {
+    let batch = load_batch(&state, batch_number).await?.ok_or(StatusCode::NOT_FOUND)?;
+    let (consensus, submission) = read_ledger(&state, move |ledger| {
+        Ok((ledger.get_consensus(batch_number)?, ledger.get_submission(batch_number)?))
+    }).await?;
+    
+    Ok(Json(BatchResponse {
+        batch,
+        consensus: consensus.as_ref().map(ConsensusResponse::from),
+        submission,
+    }))
+}
+
+async fn proof_handler(
+    State(state): State<AppState>,
+    Path((batch_number, symbol)): Path<(u64, String)>,
+) -> Result<Json<ProofResponse>, StatusCode> {
+    let batch = load_batch(&state, batch_number).await?.ok_or(StatusCode::NOT_FOUND)?;
+    
+    build_proof(&batch, &normalize_symbol(&symbol))
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+// Recent batches are served from memory, older ones from the ledger
+async fn load_batch(state: &AppState, batch_number: u64) -> Result<Option<MerkleBatch>, StatusCode> {
+    if let Some(batch) = state.feeds.read().await.batch(batch_number) {
+        return Ok(Some(batch.clone()));
+    }
+    
+    let record = read_ledger(state, move |ledger| ledger.get_merkle_root(batch_number)).await?;
+    Ok(record.map(|record| record.to_batch()))
+}
+
+// RocksDB reads block; run them off the runtime
+async fn read_ledger<T, F>(state: &AppState, read: F) -> Result<T, StatusCode>
+where
+    T: Send + 'static,
+    F: FnOnce(&OracleLedger) -> Result<T> + Send + 'static,
+{
+    let ledger = Arc::clone(&state.ledger);
+    tokio::task::spawn_blocking(move || read(&ledger))
+        .await
+        .map_err(|e| internal_error(e.into()))?
+        .map_err(internal_error)
+}
+
+/// Merkle proof for `symbol` in `batch`, checked against the batch root
+pub fn build_proof(batch: &MerkleBatch, symbol: &str) -> Result<Option<ProofResponse>> {
+    let Some(index) = batch.feeds.iter().position(|feed| feed.asset_id == symbol) else {
+        return Ok(None);
+    };
+    
+    let nodes = batch.tree.iter()
+        .map(|node| root_bytes(node))
+        .collect::<Result<Vec<_>>>()?;
+    let tree = MerkleTree::from_nodes(&nodes)
+        .ok_or_else(|| anyhow::anyhow!("Batch {} has a malformed tree", batch.batch_number))?;
+    
+    let feed = &batch.feeds[index];
+    let leaf = feed_leaf(feed);
+    let proof = tree.proof(index)
+        .ok_or_else(|| anyhow::anyhow!("Batch {} has no leaf {}", batch.batch_number, index))?;
+    if !tachyon_merkle::verify(leaf.hash(), &proof, &tree.root()) {
+        return Err(anyhow::anyhow!("Feed {} does not match the tree of batch {}", symbol, batch.batch_number));
+    }
+    
+    Ok(Some(ProofResponse {
+        batch_number: batch.batch_number,
+        symbol: symbol.to_string(),
+        asset_id: hex::encode(leaf.asset_id),
+        price: leaf.price,
+        confidence: leaf.confidence,
+        expo: leaf.expo,
+        timestamp: leaf.timestamp,
+        merkle_root: hex::encode(tree.root()),
+        proof: proof.iter().map(hex::encode).collect(),
+    }))
+}
+
+/// Accept `BTC-USD`, `btc_usd` or a percent-encoded `BTC/USD`
+fn normalize_symbol(symbol: &str) -> String {
+    symbol.replace(['-', '_'], "/").to_uppercase()
+}
+
+fn internal_error(e: anyhow::Error) -> StatusCode {
+    warn!("API request failed: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregator::FeedData;
+
+    fn batch(feeds: Vec<FeedData>) -> MerkleBatch {
+        let leaves = feeds.iter().map(|feed| feed_leaf(feed).hash()).collect();
+        let tree = MerkleTree::from_leaves(leaves);
+        MerkleBatch {
+            batch_number: 42,
+            root: hex::encode(tree.root()),
+            timestamp: 1_700_000_000,
+            feeds,
+            tree: tree.nodes().iter().map(hex::encode).collect(),
+        }
+    }
+
+    fn feed(symbol: &str, price: i64) -> FeedData {
+        FeedData {
+            asset_id: symbol.to_string(),
+            price,
+            confidence: 99_000_000,
+            expo: -8,
+            timestamp: 1_700_000_000,
+            publishers: vec![],
+        }
+    }
+
+    #[test]
+    fn test_proof_matches_verifier_arguments() {
+        let batch = batch(vec![feed("BTC/USD", 50_000), feed("ETH/USD", 3_000), feed("SOL/USD", 100)]);
+        let proof = build_proof(&batch, "ETH/USD").unwrap().unwrap();
+
+        assert_eq!(proof.asset_id, hex::encode(tachyon_merkle::asset_id("ETH/USD")));
+        assert_eq!(proof.price, 3_000);
+        assert_eq!(proof.merkle_root, batch.root);
+
+        let leaf = tachyon_merkle::PriceLeaf::new("ETH/USD", proof.price, proof.confidence, proof.expo, proof.timestamp);
+        let path: Vec<[u8; 32]> = proof.proof.iter().map(|node| root_bytes(node).unwrap()).collect();
+        assert!(tachyon_merkle::verify(leaf.hash(), &path, &root_bytes(&proof.merkle_root).unwrap()));
+
+        assert!(build_proof(&batch, "DOGE/USD").unwrap().is_none());
+
+        // A feed that was altered after the tree was built is refused
+        let mut tampered = batch.clone();
+        tampered.feeds[1].price += 1;
+        assert!(build_proof(&tampered, "ETH/USD").is_err());
+    }
+
+    #[test]
+    fn test_normalize_symbol() {
+        assert_eq!(normalize_symbol("btc-usd"), "BTC/USD");
+        assert_eq!(normalize_symbol("eth_usd"), "ETH/USD");
+    }
+
+    #[test]
+    fn test_fetcher_metrics_are_labelled_by_source() {
+        let mut fetcher = FetcherStats::default();
+        fetcher.record("kraken", std::time::Duration::from_millis(30), &crate::fetcher::stats::FetchOutcome::TimedOut);
+
+        let metrics = fetcher_metrics(&fetcher);
+        assert!(metrics.contains("tachyon_source_requests_total{source=\"kraken\"} 1\n"));
+        assert!(metrics.contains("tachyon_source_timeouts_total{source=\"kraken\"} 1\n"));
+        assert!(metrics.contains("tachyon_source_latency_ms{source=\"kraken\"} 30\n"));
+
+        let mut breaker = crate::fetcher::robust_fetcher::CircuitBreaker::new(1, 60);
+        breaker.record_failure();
+        fetcher.breakers.entry("BTC/USD".to_string()).or_default().insert("kraken".to_string(), breaker);
+        assert!(fetcher_metrics(&fetcher).contains("tachyon_source_breaker_open{asset=\"BTC/USD\",source=\"kraken\"} 1\n"));
+
+        let normalizer = crate::fetcher::stablecoins::QuoteNormalizer::new(50);
+        normalizer.update("USDT/USD", crate::fetcher::source::px("0.97"), crate::fetcher::source::px("0.001"), 1_700_000_000);
+        fetcher.stablecoins = normalizer.rates();
+        fetcher.record_basis("kraken", -0.03, true);
+        let metrics = fetcher_metrics(&fetcher);
+        assert!(metrics.contains("tachyon_stablecoin_rate{symbol=\"USDT/USD\"} 0.97\n"));
+        assert!(metrics.contains("tachyon_stablecoin_depegged{symbol=\"USDT/USD\"} 1\n"));
+        assert!(metrics.contains("tachyon_source_depeg_flags_total{source=\"kraken\"} 1\n"));
+    }
+
+    #[test]
+    fn test_publishing_metrics_are_labelled_by_feed() {
+        let stats = FeedPolicyStats { published: 3, heartbeat_triggers: 1, stale: true, ..FeedPolicyStats::default() };
+        let metrics = publishing_metrics(&BTreeMap::from([("BTC/USD".to_string(), stats)]));
+
+        assert!(metrics.contains("tachyon_feed_published_total{asset=\"BTC/USD\"} 3\n"));
+        assert!(metrics.contains("tachyon_feed_heartbeat_triggers_total{asset=\"BTC/USD\"} 1\n"));
+        assert!(metrics.contains("tachyon_feed_stale{asset=\"BTC/USD\"} 1\n"));
+        // Nothing committed yet: no deviation sample
+        assert!(!metrics.contains("tachyon_feed_deviation_bps{"));
+    }
+}
@@ -0,0 +1,328 @@
+// Price Stream - WebSocket subscriptions on /v1/stream
+//
+// Clients send JSON requests and receive JSON events:
+//
+//   -> {"op": "subscribe", "symbols": ["BTC/USD", "ETH/USD"], "from_batch": 1234}
+//   -> {"op": "unsubscribe", "symbols": ["ETH/USD"]}
+//   <- {"type": "feed", "batch_number": 1235, "feed": {...}}
+//   <- {"type": "batch_finalized", "batch_number": 1235, "consensus_root": "...", "signature": "..."}
+//
+// `from_batch` replays committed batches from the ledger before live events;
+// live events of the replayed batches are not sent again.
+// A client that cannot keep up is told where to resume instead of stalling
+// the node, and dropped if a single send blocks for too long.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Result;
+use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    extract::State,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use super::AppState;
+use crate::aggregator::FeedData;
+use crate::ledger::oracle_ledger::OracleLedger;
+
+/// Symbols a single connection may subscribe to
+pub const MAX_SUBSCRIPTIONS: usize = 64;
+
+/// Most batches replayed for `from_batch`
+pub const MAX_REPLAY_BATCHES: usize = 1_000;
+
+/// Events buffered for slow subscribers before they lag
+pub const STREAM_CHANNEL_CAPACITY: usize = 4_096;
+
+/// A send that takes longer than this drops the connection
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Subscribe to every symbol
+const ALL_SYMBOLS: &str = "*";
+
+/// Event published by the node and forwarded to subscribers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// A newly aggregated price
+    Feed {
+        batch_number: u64,
+        feed: FeedData,
+    },
+    /// Consensus closed on a batch; `signature` is set once it landed on chain
+    BatchFinalized {
+        batch_number: u64,
+        consensus_root: String,
+        agreeing_stake: u64,
+        total_stake: u64,
+        signature: Option<String>,
+    },
+    Subscribed {
+        symbols: Vec<String>,
+    },
+    /// Events were dropped because the client fell behind
+    Lagged {
+        skipped: u64,
+        resume_from: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl StreamEvent {
+    fn batch_number(&self) -> Option<u64> {
+        match self {
+            StreamEvent::Feed { batch_number, .. } | StreamEvent::BatchFinalized { batch_number, .. } => Some(*batch_number),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum StreamRequest {
+    Subscribe {
+        symbols: Vec<String>,
+        from_batch: Option<u64>,
+    },
+    Unsubscribe {
+        symbols: Vec<String>,
+    },
+}
+
+/// Symbols one connection is subscribed to
+#[derive(Debug, Default)]
+pub struct Subscriptions {
+    symbols: HashSet<String>,
+}
+
+impl Subscriptions {
+    /// Add symbols, refusing the whole request if it would exceed the limit
+    pub fn subscribe(&mut self, symbols: &[String]) -> Result<()> {
+        let added = symbols.iter()
+            .map(|s| normalize(s))
+            .filter(|s| !self.symbols.contains(s))
+            .collect::<HashSet<_>>();
+        if self.symbols.len() + added.len() > MAX_SUBSCRIPTIONS {
+            return Err(anyhow::anyhow!("At most {} subscriptions per connection", MAX_SUBSCRIPTIONS));
+        }
+
+        self.symbols.extend(added);
+        Ok(())
+    }
+
+    pub fn unsubscribe(&mut self, symbols: &[String]) {
+        for symbol in symbols {
+            self.symbols.remove(&normalize(symbol));
+        }
+    }
+
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<_> = self.symbols.iter().cloned().collect();
+        symbols.sort();
+        symbols
+    }
+
+    /// Feeds are filtered by symbol; batch events go to every subscriber
+    pub fn wants(&self, event: &StreamEvent) -> bool {
+        match event {
+            StreamEvent::Feed { feed, .. } => {
+                self.symbols.contains(ALL_SYMBOLS) || self.symbols.contains(&feed.asset_id)
+            }
+            StreamEvent::BatchFinalized { .. } => !self.symbols.is_empty(),
+            _ => true,
+        }
+    }
+}
+
+fn normalize(symbol: &str) -> String {
+    if symbol == ALL_SYMBOLS {
+        return symbol.to_string();
+    }
+    super::normalize_symbol(symbol)
+}
+
+pub async fn stream_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+) -> Response {
+    // Subscribe before the upgrade so no event published meanwhile is missed
+    let events = state.events.subscribe();
+    ws.on_upgrade(move |socket| async move {
+        if let Err(e) = handle_stream(socket, state, events).await {
+            debug!("🔌 Stream closed: {}", e);
+        }
+    })
+}
+
+async fn handle_stream(
+    mut socket: WebSocket,
+    state: AppState,
+    mut events: broadcast::Receiver<StreamEvent>,
+) -> Result<()> {
+    let mut subscriptions = Subscriptions::default();
+    // Latest batch with delivered events; lagged events always come after it,
+    // so replaying from here leaves no gap
+    let mut resume_from = 0u64;
+    // Last batch replayed from the ledger; live events up to it were sent
+    let mut replayed_through: Option<u64> = None;
+
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e.into()),
+                };
+
+                let request = match serde_json::from_str::<StreamRequest>(&text) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        send(&mut socket, &StreamEvent::Error { message: format!("Invalid request: {}", e) }).await?;
+                        continue;
+                    }
+                };
+
+                match request {
+                    StreamRequest::Subscribe { symbols, from_batch } => {
+                        if let Err(e) = subscriptions.subscribe(&symbols) {
+                            send(&mut socket, &StreamEvent::Error { message: e.to_string() }).await?;
+                            continue;
+                        }
+                        send(&mut socket, &StreamEvent::Subscribed { symbols: subscriptions.symbols() }).await?;
+
+                        if let Some(from_batch) = from_batch {
+                            // A ledger scan; keep it off the runtime
+                            let ledger = Arc::clone(&state.ledger);
+                            let replayed = tokio::task::spawn_blocking(move || replay(&ledger, from_batch)).await??;
+                            for event in replayed {
+                                replayed_through = replayed_through.max(event.batch_number());
+                                if subscriptions.wants(&event) {
+                                    send(&mut socket, &event).await?;
+                                }
+                            }
+                        }
+                    }
+                    StreamRequest::Unsubscribe { symbols } => {
+                        subscriptions.unsubscribe(&symbols);
+                        send(&mut socket, &StreamEvent::Subscribed { symbols: subscriptions.symbols() }).await?;
+                    }
+                }
+            }
+
+            event = events.recv() => {
+                match event {
+                    Ok(event) => {
+                        if let Some(batch_number) = event.batch_number() {
+                            resume_from = resume_from.max(batch_number);
+                        }
+                        let replayed = event.batch_number().is_some_and(|batch_number| Some(batch_number) <= replayed_through);
+                        if subscriptions.wants(&event) && !replayed {
+                            send(&mut socket, &event).await?;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("🔌 Stream subscriber lagged by {} events", skipped);
+                        send(&mut socket, &StreamEvent::Lagged { skipped, resume_from }).await?;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn send(socket: &mut WebSocket, event: &StreamEvent) -> Result<()> {
+    let text = serde_json::to_string(event)?;
+    tokio::time::timeout(SEND_TIMEOUT, socket.send(Message::Text(text)))
+        .await
+        .map_err(|_| anyhow::anyhow!("Subscriber too slow"))??;
+    Ok(())
+}
+
+/// Committed feeds and finalizations from `from_batch` on, oldest first
+fn replay(ledger: &OracleLedger, from_batch: u64) -> Result<Vec<StreamEvent>> {
+    let mut events = Vec::new();
+
+    for record in ledger.get_merkle_roots_since(from_batch, MAX_REPLAY_BATCHES)? {
+        for feed in &record.feeds {
+            events.push(StreamEvent::Feed {
+                batch_number: record.batch_number,
+                feed: feed.clone(),
+            });
+        }
+
+        let Some(consensus) = ledger.get_consensus(record.batch_number)? else {
+            continue;
+        };
+        let Some(consensus_root) = consensus.consensus_root else {
+            continue;
+        };
+        events.push(StreamEvent::BatchFinalized {
+            batch_number: record.batch_number,
+            consensus_root: hex::encode(consensus_root),
+            agreeing_stake: consensus.agreeing_stake,
+            total_stake: consensus.total_stake,
+            signature: ledger.get_submission(record.batch_number)?.map(|s| s.signature),
+        });
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_event(symbol: &str) -> StreamEvent {
+        StreamEvent::Feed {
+            batch_number: 1,
+            feed: FeedData {
+                asset_id: symbol.to_string(),
+                price: 1,
+                confidence: 1,
+                expo: -8,
+                timestamp: 1,
+                publishers: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn test_subscriptions_filter_and_limit() {
+        let mut subscriptions = Subscriptions::default();
+        assert!(!subscriptions.wants(&feed_event("BTC/USD")));
+
+        subscriptions.subscribe(&["btc-usd".to_string()]).unwrap();
+        assert!(subscriptions.wants(&feed_event("BTC/USD")));
+        assert!(!subscriptions.wants(&feed_event("ETH/USD")));
+
+        subscriptions.subscribe(&[ALL_SYMBOLS.to_string()]).unwrap();
+        assert!(subscriptions.wants(&feed_event("ETH/USD")));
+
+        let too_many: Vec<String> = (0..MAX_SUBSCRIPTIONS).map(|i| format!("A{}/USD", i)).collect();
+        assert!(subscriptions.subscribe(&too_many).is_err());
+        assert_eq!(subscriptions.symbols(), vec![ALL_SYMBOLS.to_string(), "BTC/USD".to_string()]);
+
+        subscriptions.unsubscribe(&["BTC/USD".to_string(), ALL_SYMBOLS.to_string()]);
+        assert!(!subscriptions.wants(&feed_event("BTC/USD")));
+    }
+
+    #[test]
+    fn test_wire_format() {
+        let request: StreamRequest = serde_json::from_str(r#"{"op":"subscribe","symbols":["BTC/USD"],"from_batch":7}"#).unwrap();
+        assert!(matches!(request, StreamRequest::Subscribe { from_batch: Some(7), .. }));
+
+        let json = serde_json::to_value(StreamEvent::Lagged { skipped: 3, resume_from: 9 }).unwrap();
+        assert_eq!(json, serde_json::json!({"type": "lagged", "skipped": 3, "resume_from": 9}));
+    }
+}
@@ -1,8 +1,10 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use solana_sdk::signature::{Keypair, Signer};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::fs;
+use tachyon_merkle::Price;
 use tracing::info;
 
 use crate::crypto;
@@ -11,6 +13,46 @@ fn default_keypair() -> Keypair {
     Keypair::new()
 }
 
+fn default_vote_timeout_ms() -> u64 {
+    500
+}
+
+fn default_validator_refresh_secs() -> u64 {
+    300
+}
+
+fn default_tower_path() -> String {
+    "~/.config/tachyon/tower.json".to_string()
+}
+
+fn default_ledger_path() -> String {
+    "~/.config/tachyon/ledger".to_string()
+}
+
+fn default_ledger_retention_secs() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_target_peers() -> usize {
+    8
+}
+
+fn default_peers_path() -> String {
+    "~/.config/tachyon/peers.json".to_string()
+}
+
+fn default_push_fanout() -> usize {
+    6
+}
+
+fn default_pull_interval_ms() -> u64 {
+    5_000
+}
+
+fn default_crds_timeout_ms() -> u64 {
+    60_000
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NodeConfig {
     /// Node identity keypair
@@ -32,6 +74,35 @@ pub struct NodeConfig {
     /// Gossip network port
     pub gossip_port: u16,
     
+    /// IP peers can reach this node at, advertised in its gossip contact info.
+    /// Without it only peers connected to us learn our address.
+    #[serde(default)]
+    pub public_ip: Option<std::net::IpAddr>,
+    
+    /// Gossip addresses ("host:port") dialed to join the network
+    #[serde(default)]
+    pub entrypoints: Vec<String>,
+    
+    /// Peers the gossip dial loop keeps connected
+    #[serde(default = "default_target_peers")]
+    pub target_peers: usize,
+    
+    /// Where peers learned through gossip are kept between restarts
+    #[serde(default = "default_peers_path")]
+    pub peers_path: String,
+    
+    /// Peers each new gossip value is pushed to, picked by stake
+    #[serde(default = "default_push_fanout")]
+    pub push_fanout: usize,
+    
+    /// How often a peer is asked for the gossip values we lack
+    #[serde(default = "default_pull_interval_ms")]
+    pub pull_interval_ms: u64,
+    
+    /// Gossip values older than this are dropped; owners re-sign theirs sooner
+    #[serde(default = "default_crds_timeout_ms")]
+    pub crds_timeout_ms: u64,
+    
     /// API server port
     pub api_port: u16,
     
@@ -44,17 +115,198 @@ pub struct NodeConfig {
     /// Minimum publishers for quorum
     pub min_publishers: u8,
     
+    /// How long to collect peer votes for a batch (ms)
+    #[serde(default = "default_vote_timeout_ms")]
+    pub vote_timeout_ms: u64,
+    
+    /// Maximum age of the cached validator set within an epoch (seconds)
+    #[serde(default = "default_validator_refresh_secs")]
+    pub validator_refresh_secs: u64,
+    
+    /// Where the consensus tower is persisted between restarts
+    #[serde(default = "default_tower_path")]
+    pub tower_path: String,
+    
+    /// RocksDB directory for price and batch history
+    #[serde(default = "default_ledger_path")]
+    pub ledger_path: String,
+    
+    /// How long prices and batches are kept in the ledger (seconds)
+    #[serde(default = "default_ledger_retention_secs")]
+    pub ledger_retention_secs: u64,
+    
     /// Assets to track
     pub assets: Vec<AssetConfig>,
     
     /// Exchange API keys (optional)
     pub exchanges: ExchangeConfig,
+    
+    /// Extra price sources; a source named like a built-in exchange replaces it
+    #[serde(default)]
+    pub sources: Vec<SourceConfig>,
+    
+    /// Stablecoin rates used to bring every venue quote into the asset's quote currency
+    #[serde(default)]
+    pub stablecoins: StablecoinConfig,
+    
+    /// Feeds computed from other feeds of the same batch, in order; a derived
+    /// feed can use the ones listed before it
+    #[serde(default)]
+    pub derived_feeds: Vec<DerivedFeedConfig>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssetConfig {
     pub symbol: String,
     pub exchanges: Vec<String>,
+    
+    /// Prices below this are discarded as bad data
+    #[serde(default, with = "price_bound")]
+    pub min_price: Option<Price>,
+    
+    /// Prices above this are discarded as bad data
+    #[serde(default, with = "price_bound")]
+    pub max_price: Option<Price>,
+    
+    /// Consecutive failures before an exchange is skipped for this asset
+    #[serde(default = "default_breaker_threshold")]
+    pub breaker_threshold: u32,
+    
+    /// How long a tripped exchange is skipped before it is tried again (seconds)
+    #[serde(default = "default_breaker_timeout_secs")]
+    pub breaker_timeout_secs: u64,
+    
+    /// Weight of each exchange in the average; unlisted exchanges weigh 1.0
+    #[serde(default)]
+    pub weights: HashMap<String, f64>,
+    
+    /// How venue quotes are combined into one price
+    #[serde(default)]
+    pub method: AggregationMethod,
+    
+    /// History used by `volume_weighted_mean` and `twap` (seconds)
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    
+    /// Decimal exponent of the published price: the feed carries integer
+    /// mantissas worth `mantissa × 10^expo`
+    #[serde(default = "default_expo")]
+    pub expo: i32,
+    
+    /// When the feed is published and when it is stale
+    #[serde(flatten)]
+    pub policy: PublishPolicy,
+}
+
+/// When a feed goes into a batch. With neither `deviation_bps` nor
+/// `heartbeat_secs` set it is published every batch.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PublishPolicy {
+    /// Publish when the price moved at least this far from the last
+    /// committed one (basis points)
+    #[serde(default)]
+    pub deviation_bps: Option<f64>,
+    
+    /// Publish at least this often, even if the price did not move (seconds)
+    #[serde(default)]
+    pub heartbeat_secs: Option<u64>,
+    
+    /// Without fresh source data for this long the feed is stale and not
+    /// published (seconds)
+    #[serde(default = "default_stale_after_secs")]
+    pub stale_after_secs: u64,
+}
+
+impl Default for PublishPolicy {
+    fn default() -> Self {
+        Self {
+            deviation_bps: None,
+            heartbeat_secs: None,
+            stale_after_secs: default_stale_after_secs(),
+        }
+    }
+}
+
+/// Price computation for an asset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregationMethod {
+    /// Weighted average of each venue's mid (last trade if no book)
+    #[default]
+    Mid,
+    /// Mean of the venue mids over the window, each weighted by its venue's
+    /// 24h volume. Not a VWAP: venues report no per-trade volume.
+    #[serde(alias = "vwap")]
+    VolumeWeightedMean,
+    /// Time-weighted average of the aggregated mid, over the window
+    Twap,
+    /// Median of the venue mids, each counted by its 24h volume
+    VolumeWeightedMedian,
+}
+
+impl AssetConfig {
+    pub fn new(symbol: &str, exchanges: &[&str]) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            exchanges: exchanges.iter().map(|e| e.to_string()).collect(),
+            min_price: None,
+            max_price: None,
+            breaker_threshold: default_breaker_threshold(),
+            breaker_timeout_secs: default_breaker_timeout_secs(),
+            weights: HashMap::new(),
+            method: AggregationMethod::default(),
+            window_secs: default_window_secs(),
+            expo: default_expo(),
+            policy: PublishPolicy::default(),
+        }
+    }
+    
+    pub fn weight(&self, exchange: &str) -> f64 {
+        self.weights.get(exchange).copied().unwrap_or(1.0)
+    }
+}
+
+/// Asset for the generated config, weighting exchanges by volume
+fn default_asset(symbol: &str, exchanges: &[&str], bounds: Option<(Price, Price)>) -> AssetConfig {
+    AssetConfig {
+        min_price: bounds.map(|(min, _)| min),
+        max_price: bounds.map(|(_, max)| max),
+        weights: [("binance", 1.5), ("coinbase", 1.3), ("kraken", 1.2)].iter()
+            .filter(|(exchange, _)| exchanges.contains(exchange))
+            .map(|(exchange, weight)| (exchange.to_string(), *weight))
+            .collect(),
+        policy: default_publish_policy(),
+        ..AssetConfig::new(symbol, exchanges)
+    }
+}
+
+/// Publish policy for the generated config: on a 0.1% move or every minute
+fn default_publish_policy() -> PublishPolicy {
+    PublishPolicy {
+        deviation_bps: Some(10.0),
+        heartbeat_secs: Some(60),
+        ..PublishPolicy::default()
+    }
+}
+
+fn default_breaker_threshold() -> u32 {
+    5
+}
+
+fn default_breaker_timeout_secs() -> u64 {
+    60
+}
+
+fn default_window_secs() -> u64 {
+    60
+}
+
+pub fn default_expo() -> i32 {
+    -8
+}
+
+fn default_stale_after_secs() -> u64 {
+    30
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -62,6 +314,211 @@ pub struct ExchangeConfig {
     pub binance_api_key: Option<String>,
     pub coinbase_api_key: Option<String>,
     pub kraken_api_key: Option<String>,
+    
+    /// Stream tickers over WebSocket, polling REST only as a fallback
+    #[serde(default = "default_streaming")]
+    pub streaming: bool,
+}
+
+fn default_streaming() -> bool {
+    true
+}
+
+/// Stablecoin rates the fetcher tracks internally (not published). A venue
+/// that lists BTC/USDT instead of BTC/USD is converted through USDT/USD.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StablecoinConfig {
+    /// Fetched like assets, from venues that list them against the target currency
+    #[serde(default = "default_stablecoin_feeds")]
+    pub feeds: Vec<AssetConfig>,
+    
+    /// Distance from par beyond which quotes converted through a stablecoin
+    /// are flagged and left out of aggregation (basis points)
+    #[serde(default = "default_depeg_threshold_bps")]
+    pub depeg_threshold_bps: u32,
+}
+
+impl Default for StablecoinConfig {
+    fn default() -> Self {
+        Self {
+            feeds: default_stablecoin_feeds(),
+            depeg_threshold_bps: default_depeg_threshold_bps(),
+        }
+    }
+}
+
+/// Price bounds read exactly from a TOML number or decimal string, and
+/// written back as strings
+mod price_bound {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use tachyon_merkle::Price;
+
+    pub fn serialize<S: Serializer>(bound: &Option<Price>, serializer: S) -> Result<S::Ok, S::Error> {
+        match bound {
+            Some(price) => serializer.serialize_some(&price.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Price>, D::Error> {
+        Option::<Number>::deserialize(deserializer)?
+            .map(Number::price)
+            .transpose()
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    pub(super) enum Number {
+        Integer(i64),
+        // Shortest decimal that reads back as the same float, e.g. "0.1"
+        Float(f64),
+        Text(String),
+    }
+
+    impl Number {
+        pub(super) fn price<E: de::Error>(self) -> Result<Price, E> {
+            let text = match self {
+                Number::Integer(integer) => integer.to_string(),
+                Number::Float(float) => float.to_string(),
+                Number::Text(text) => text,
+            };
+            Price::parse(&text).map_err(E::custom)
+        }
+    }
+}
+
+/// Basket weights, read exactly like price bounds
+mod basket_weights {
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use tachyon_merkle::Price;
+    use super::price_bound::Number;
+
+    pub fn serialize<S: Serializer>(weights: &BTreeMap<String, Price>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(weights.iter().map(|(symbol, weight)| (symbol, weight.to_string())))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, Price>, D::Error> {
+        BTreeMap::<String, Number>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(symbol, weight)| Ok((symbol, weight.price()?)))
+            .collect()
+    }
+}
+
+fn default_stablecoin_feeds() -> Vec<AssetConfig> {
+    vec![
+        AssetConfig { min_price: Some(Price::new(5, -1)), max_price: Some(Price::new(15, -1)), ..AssetConfig::new("USDT/USD", &["kraken", "coinbase"]) },
+        AssetConfig { min_price: Some(Price::new(5, -1)), max_price: Some(Price::new(15, -1)), ..AssetConfig::new("USDC/USD", &["kraken"]) },
+    ]
+}
+
+fn default_depeg_threshold_bps() -> u32 {
+    50
+}
+
+/// A price source defined in config, referenced by name from `AssetConfig::exchanges`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceConfig {
+    pub name: String,
+    
+    #[serde(flatten)]
+    pub kind: SourceKind,
+    
+    /// Venue symbol built from `{base}`, `{quote}`, `{base_lower}` and `{quote_lower}`
+    #[serde(default = "default_symbol_format")]
+    pub symbol_format: String,
+    
+    /// Per-symbol overrides, e.g. "BTC/USD" = "XBTUSD"
+    #[serde(default)]
+    pub symbols: HashMap<String, String>,
+    
+    /// Quote currencies the source lists markets in instead, e.g. USD = "USDT";
+    /// its prices are converted back through the stablecoin rate
+    #[serde(default)]
+    pub quotes: HashMap<String, String>,
+    
+    /// Maximum requests per second
+    #[serde(default = "default_source_rate_limit")]
+    pub rate_limit_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SourceKind {
+    /// GET `url` (with `{symbol}` substituted) and read the last price at `price_path`,
+    /// e.g. "data.amount", "data[0].last" or "result.*.c[0]"
+    JsonHttp {
+        url: String,
+        price_path: String,
+        /// Best bid and ask, for mid prices and spread-based confidence
+        #[serde(default)]
+        bid_path: Option<String>,
+        #[serde(default)]
+        ask_path: Option<String>,
+        /// 24h volume in base units, for volume-weighted methods
+        #[serde(default)]
+        volume_path: Option<String>,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    /// Long-running process speaking line-delimited JSON on stdin/stdout
+    Subprocess {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+/// A feed the aggregator computes from other feeds instead of fetching it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DerivedFeedConfig {
+    pub symbol: String,
+    
+    /// Decimal exponent of the published price, as for assets
+    #[serde(default = "default_expo")]
+    pub expo: i32,
+    
+    /// Publish policy, as for assets; staleness follows the inputs
+    #[serde(flatten)]
+    pub policy: PublishPolicy,
+    
+    #[serde(flatten)]
+    pub formula: DerivedFormula,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DerivedFormula {
+    /// `numerator / denominator`, e.g. ETH/BTC from ETH/USD and BTC/USD
+    Ratio {
+        numerator: String,
+        denominator: String,
+    },
+    /// Product of all factors
+    Product {
+        factors: Vec<String>,
+    },
+    /// Sum of weight × price over the components, e.g. an index
+    Basket {
+        #[serde(with = "basket_weights")]
+        components: BTreeMap<String, Price>,
+    },
+    /// `feed` re-quoted through `via`, a feed between its quote currency and
+    /// the derived symbol's, e.g. SOL/USD from SOL/USDC via USDC/USD, or
+    /// BTC/EUR from BTC/USD via EUR/USD
+    Convert {
+        feed: String,
+        via: String,
+    },
+}
+
+fn default_symbol_format() -> String {
+    "{base}{quote}".to_string()
+}
+
+fn default_source_rate_limit() -> f64 {
+    10.0
 }
 
 impl NodeConfig {
@@ -132,26 +589,50 @@ pub async fn init_node(
         program_id: "TACH9r2uZzoFM6daofesADjeDn9NqB1pKFWP5mfByb1".to_string(),
         l2_program_id: "L2TA7eVsDyXx7nxF4p2Xay3iWgdCHuMPx6YV5odwMTx".to_string(),
         gossip_port,
+        public_ip: None,
+        entrypoints: vec![],
+        target_peers: default_target_peers(),
+        peers_path: default_peers_path(),
+        push_fanout: default_push_fanout(),
+        pull_interval_ms: default_pull_interval_ms(),
+        crds_timeout_ms: default_crds_timeout_ms(),
         api_port,
         update_interval_ms: 1000, // 1 second
         batch_interval_ms: 100,    // 100ms batches
         min_publishers: 3,
+        vote_timeout_ms: default_vote_timeout_ms(),
+        validator_refresh_secs: default_validator_refresh_secs(),
+        tower_path: default_tower_path(),
+        ledger_path: default_ledger_path(),
+        ledger_retention_secs: default_ledger_retention_secs(),
         assets: vec![
-            AssetConfig { symbol: "BTC/USD".to_string(), exchanges: vec!["binance".to_string(), "coinbase".to_string()] },
-            AssetConfig { symbol: "ETH/USD".to_string(), exchanges: vec!["binance".to_string(), "coinbase".to_string()] },
-            AssetConfig { symbol: "SOL/USD".to_string(), exchanges: vec!["binance".to_string(), "coinbase".to_string()] },
-            AssetConfig { symbol: "AVAX/USD".to_string(), exchanges: vec!["binance".to_string(), "coinbase".to_string()] },
-            AssetConfig { symbol: "MATIC/USD".to_string(), exchanges: vec!["binance".to_string(), "coinbase".to_string()] },
-            AssetConfig { symbol: "BNB/USD".to_string(), exchanges: vec!["binance".to_string()] },
-            AssetConfig { symbol: "XRP/USD".to_string(), exchanges: vec!["binance".to_string(), "coinbase".to_string()] },
-            AssetConfig { symbol: "ADA/USD".to_string(), exchanges: vec!["binance".to_string(), "coinbase".to_string()] },
-            AssetConfig { symbol: "DOT/USD".to_string(), exchanges: vec!["binance".to_string(), "coinbase".to_string()] },
+            default_asset("BTC/USD", &["binance", "coinbase"], Some((Price::new(1_000, 0), Price::new(1_000_000, 0)))),
+            default_asset("ETH/USD", &["binance", "coinbase"], Some((Price::new(10, 0), Price::new(100_000, 0)))),
+            default_asset("SOL/USD", &["binance", "coinbase"], Some((Price::new(1, -1), Price::new(10_000, 0)))),
+            default_asset("AVAX/USD", &["binance", "coinbase"], None),
+            default_asset("MATIC/USD", &["binance", "coinbase"], None),
+            default_asset("BNB/USD", &["binance"], None),
+            default_asset("XRP/USD", &["binance", "coinbase"], None),
+            default_asset("ADA/USD", &["binance", "coinbase"], None),
+            default_asset("DOT/USD", &["binance", "coinbase"], None),
         ],
         exchanges: ExchangeConfig {
             binance_api_key: None,
             coinbase_api_key: None,
             kraken_api_key: None,
+            streaming: default_streaming(),
         },
+        sources: vec![],
+        stablecoins: StablecoinConfig::default(),
+        derived_feeds: vec![DerivedFeedConfig {
+            symbol: "ETH/BTC".to_string(),
+            expo: default_expo(),
+            policy: default_publish_policy(),
+            formula: DerivedFormula::Ratio {
+                numerator: "ETH/USD".to_string(),
+                denominator: "BTC/USD".to_string(),
+            },
+        }],
     };
     
     // Save config
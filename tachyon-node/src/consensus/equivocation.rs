@@ -0,0 +1,254 @@
+// Equivocation Detector - catches validators signing two roots for one batch
+//
+// Every signed vote a member of the validator set sends over gossip is
+// remembered per validator (bounded like `TowerVoteState`). A second,
+// different root for a batch we already hold a vote for yields an
+// `EquivocationProof` that TachyonGovernance can verify and slash on its own.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+use anyhow::Result;
+use borsh::BorshSerialize;
+use serde::{Deserialize, Serialize};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar;
+use tracing::warn;
+
+use super::Vote;
+
+/// Signed votes kept per validator
+const MAX_VOTES_PER_VALIDATOR: usize = 32;
+
+/// Two conflicting signed votes from one validator for the same batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EquivocationProof {
+    pub first: Vote,
+    pub second: Vote,
+}
+
+impl EquivocationProof {
+    pub fn validator(&self) -> &str {
+        &self.first.node_pubkey
+    }
+
+    pub fn batch_number(&self) -> u64 {
+        self.first.batch_number
+    }
+
+    /// Both votes come from the same validator, cover the same batch, disagree
+    /// on the root and carry valid signatures
+    pub fn verify(&self) -> bool {
+        self.first.node_pubkey == self.second.node_pubkey
+            && self.first.batch_number == self.second.batch_number
+            && self.first.root_hash != self.second.root_hash
+            && self.first.verify()
+            && self.second.verify()
+    }
+
+    /// Ed25519 checks for both votes followed by `slash_equivocation`
+    pub fn slash_instructions(&self, governance_program: &Pubkey, reporter: &Pubkey) -> Result<Vec<Instruction>> {
+        #[derive(BorshSerialize)]
+        struct SlashEquivocationParams {
+            validator: [u8; 32],
+            batch_number: u64,
+            first_root: [u8; 32],
+            first_signature: [u8; 64],
+            second_root: [u8; 32],
+            second_signature: [u8; 64],
+        }
+
+        let validator = Pubkey::from_str(self.validator())?;
+        let batch_number = self.batch_number();
+
+        let mut instructions = Vec::with_capacity(3);
+        let mut signed = Vec::with_capacity(2);
+        for vote in [&self.first, &self.second] {
+            let root = super::root_bytes(&vote.root_hash)?;
+            let signature: [u8; 64] = vote.signature.as_slice().try_into()
+                .map_err(|_| anyhow::anyhow!("Invalid signature length"))?;
+            instructions.push(solana_ed25519_program::new_ed25519_instruction_with_signature(
+                &tachyon_merkle::vote_message(batch_number, &root),
+                &signature,
+                &validator.to_bytes(),
+            ));
+            signed.push((root, signature));
+        }
+
+        let params = SlashEquivocationParams {
+            validator: validator.to_bytes(),
+            batch_number,
+            first_root: signed[0].0,
+            first_signature: signed[0].1,
+            second_root: signed[1].0,
+            second_signature: signed[1].1,
+        };
+
+        let mut data = slash_equivocation_discriminator().to_vec();
+        params.serialize(&mut data)?;
+
+        let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, governance_program).0;
+        let token_program = Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")?;
+
+        instructions.push(Instruction {
+            program_id: *governance_program,
+            accounts: vec![
+                AccountMeta::new(pda(&[b"governance"]), false),
+                AccountMeta::new(pda(&[b"vault"]), false),
+                AccountMeta::new(pda(&[b"rewards-pool"]), false),
+                AccountMeta::new(pda(&[b"staker-v2", validator.as_ref()]), false),
+                AccountMeta::new(pda(&[b"equivocation", validator.as_ref(), &batch_number.to_le_bytes()]), false),
+                AccountMeta::new(*reporter, true),
+                AccountMeta::new_readonly(sysvar::instructions::ID, false),
+                AccountMeta::new_readonly(token_program, false),
+                AccountMeta::new_readonly(solana_program::system_program::ID, false),
+            ],
+            data,
+        });
+
+        Ok(instructions)
+    }
+}
+
+// sha256("global:slash_equivocation")[0..8]
+fn slash_equivocation_discriminator() -> [u8; 8] {
+    use sha2::{Digest, Sha256};
+    let hash = Sha256::digest(b"global:slash_equivocation");
+    let mut disc = [0u8; 8];
+    disc.copy_from_slice(&hash[..8]);
+    disc
+}
+
+/// Remembers recent signed votes per validator and reports conflicts
+#[derive(Default)]
+pub struct EquivocationDetector {
+    /// Most recent votes first
+    history: HashMap<String, VecDeque<Vote>>,
+    /// (validator, batch) pairs we already produced a proof for
+    reported: HashSet<(String, u64)>,
+}
+
+impl EquivocationDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a vote and return a proof if it conflicts with an earlier one.
+    /// Only votes from `validators` are kept, so history never outgrows the
+    /// set; votes with bad signatures are ignored so every proof is slashable.
+    pub fn observe(&mut self, vote: &Vote, validators: &[(String, u64)]) -> Option<EquivocationProof> {
+        let is_member = |pubkey: &str| validators.iter().any(|(validator, _)| validator == pubkey);
+        if !is_member(&vote.node_pubkey) || !vote.verify() {
+            return None;
+        }
+
+        // Validators that left the set take their history with them
+        if !self.history.contains_key(&vote.node_pubkey) && self.history.len() >= validators.len() {
+            self.history.retain(|validator, _| is_member(validator));
+            self.reported.retain(|(validator, _)| is_member(validator));
+        }
+
+        let votes = self.history.entry(vote.node_pubkey.clone()).or_default();
+        if let Some(earlier) = votes.iter().find(|v| v.batch_number == vote.batch_number) {
+            if earlier.root_hash == vote.root_hash {
+                return None;
+            }
+
+            let key = (vote.node_pubkey.clone(), vote.batch_number);
+            if !self.reported.insert(key) {
+                return None;
+            }
+
+            warn!("🚨 Validator {} signed two roots for batch {}", vote.node_pubkey, vote.batch_number);
+            return Some(EquivocationProof {
+                first: earlier.clone(),
+                second: vote.clone(),
+            });
+        }
+
+        votes.push_front(vote.clone());
+        votes.truncate(MAX_VOTES_PER_VALIDATOR);
+
+        // Forget reports for batches no longer in any history
+        if let Some(oldest) = votes.back().map(|v| v.batch_number) {
+            let validator = &vote.node_pubkey;
+            self.reported.retain(|(v, batch)| v != validator || *batch >= oldest);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+
+    fn members(keypairs: &[&Keypair]) -> Vec<(String, u64)> {
+        keypairs.iter().map(|keypair| (keypair.pubkey().to_string(), 100)).collect()
+    }
+
+    #[test]
+    fn test_detects_conflicting_roots() {
+        let keypair = Keypair::new();
+        let root_a = hex::encode([1u8; 32]);
+        let root_b = hex::encode([2u8; 32]);
+        let validators = members(&[&keypair]);
+        let mut detector = EquivocationDetector::new();
+
+        assert!(detector.observe(&Vote::new_signed(&keypair, 7, &root_a, 100).unwrap(), &validators).is_none());
+        // Re-broadcast of the same vote is fine
+        assert!(detector.observe(&Vote::new_signed(&keypair, 7, &root_a, 100).unwrap(), &validators).is_none());
+
+        let proof = detector.observe(&Vote::new_signed(&keypair, 7, &root_b, 100).unwrap(), &validators).unwrap();
+        assert!(proof.verify());
+        assert_eq!(proof.validator(), keypair.pubkey().to_string());
+        assert_eq!(proof.batch_number(), 7);
+
+        // Only reported once
+        assert!(detector.observe(&Vote::new_signed(&keypair, 7, &hex::encode([3u8; 32]), 100).unwrap(), &validators).is_none());
+
+        // Survives a JSON round trip and builds the slashing transaction
+        let json = serde_json::to_string(&proof).unwrap();
+        let proof: EquivocationProof = serde_json::from_str(&json).unwrap();
+        assert!(proof.verify());
+        let instructions = proof.slash_instructions(&Pubkey::new_unique(), &Pubkey::new_unique()).unwrap();
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[2].data[..8], slash_equivocation_discriminator());
+    }
+
+    #[test]
+    fn test_forged_votes_are_ignored() {
+        let keypair = Keypair::new();
+        let validators = members(&[&keypair]);
+        let mut detector = EquivocationDetector::new();
+        detector.observe(&Vote::new_signed(&keypair, 7, &hex::encode([1u8; 32]), 100).unwrap(), &validators);
+
+        // Someone else claims the validator signed another root
+        let mut forged = Vote::new_signed(&Keypair::new(), 7, &hex::encode([2u8; 32]), 100).unwrap();
+        forged.node_pubkey = keypair.pubkey().to_string();
+        assert!(detector.observe(&forged, &validators).is_none());
+    }
+
+    #[test]
+    fn test_history_bounded_by_validator_set() {
+        let (alice, bob) = (Keypair::new(), Keypair::new());
+        let mut detector = EquivocationDetector::new();
+
+        // Votes from outside the set are never remembered
+        for _ in 0..100 {
+            let stranger = Keypair::new();
+            detector.observe(&Vote::new_signed(&stranger, 7, &hex::encode([1u8; 32]), 100).unwrap(), &members(&[&alice]));
+        }
+        assert!(detector.history.is_empty());
+
+        detector.observe(&Vote::new_signed(&alice, 7, &hex::encode([1u8; 32]), 100).unwrap(), &members(&[&alice]));
+        assert_eq!(detector.history.len(), 1);
+
+        // Alice left the set and Bob joined
+        detector.observe(&Vote::new_signed(&bob, 7, &hex::encode([1u8; 32]), 100).unwrap(), &members(&[&bob]));
+        assert_eq!(detector.history.len(), 1);
+        assert!(detector.history.contains_key(&bob.pubkey().to_string()));
+    }
+}
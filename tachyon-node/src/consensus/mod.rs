@@ -2,20 +2,34 @@
 use std::sync::Arc;
 use solana_sdk::signer::Signer;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
 use solana_client::rpc_client::RpcClient;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
-use tokio::sync::mpsc;
+use std::path::PathBuf;
+use tokio::sync::{mpsc, watch, RwLock};
+use tokio::time::{Duration, Instant};
 use tracing::{info, debug, warn};
 
 use crate::aggregator::MerkleBatch;
 use crate::config::NodeConfig;
+use crate::crypto;
 
 // Tower BFT for production-grade consensus
 pub mod oracle_tower;
 
+// On-chain validator set discovery
+pub mod validator_set;
+
+// Double-vote detection and slashing proofs
+pub mod equivocation;
+
+use equivocation::{EquivocationDetector, EquivocationProof};
+use oracle_tower::{MerkleRoot, OracleTower, TowerStats};
+use validator_set::{fetch_staker_accounts, staker_info_address, StakerInfo, ValidatorSetCache};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConsensusResult {
     pub batch: MerkleBatch,
@@ -29,100 +43,259 @@ pub struct ConsensusResult {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vote {
     pub node_pubkey: String,
+    pub batch_number: u64,
     pub root_hash: String,
     pub stake: u64,
     pub signature: Vec<u8>,
 }
 
+impl Vote {
+    /// Create a vote for `root_hash` signed with the node identity
+    pub fn new_signed(keypair: &Keypair, batch_number: u64, root_hash: &str, stake: u64) -> Result<Self> {
+        let message = vote_message(batch_number, root_hash)?;
+        
+        Ok(Self {
+            node_pubkey: keypair.pubkey().to_string(),
+            batch_number,
+            root_hash: root_hash.to_string(),
+            stake,
+            signature: crypto::sign_message(keypair, &message),
+        })
+    }
+    
+    /// Check the signature against the voter's identity
+    pub fn verify(&self) -> bool {
+        let Ok(pubkey) = Pubkey::from_str(&self.node_pubkey) else {
+            return false;
+        };
+        let Ok(message) = vote_message(self.batch_number, &self.root_hash) else {
+            return false;
+        };
+        let Ok(signature) = <[u8; 64]>::try_from(self.signature.as_slice()) else {
+            return false;
+        };
+        
+        crypto::verify_signature(&pubkey.to_bytes(), &message, &signature)
+    }
+}
+
+/// Bytes signed by a vote, shared with the state-compression program
+pub fn vote_message(batch_number: u64, root_hash: &str) -> Result<Vec<u8>> {
+    Ok(tachyon_merkle::vote_message(batch_number, &root_bytes(root_hash)?).to_vec())
+}
+
+/// Channels the consensus module publishes on
+#[derive(Clone)]
+pub struct ConsensusOutbound {
+    /// Our signed votes, relayed to peers by gossip
+    pub vote_tx: mpsc::Sender<Vote>,
+    /// Tallied batches for the sequencer
+    pub result_tx: mpsc::Sender<ConsensusResult>,
+    /// Equivocation proofs for the sequencer to submit
+    pub evidence_tx: mpsc::Sender<EquivocationProof>,
+}
+
+/// The chain as consensus sees it: the slot for leader selection and the
+/// governance stakers. Live nodes ask RPC; replay answers from memory.
+pub trait Cluster: Send + Sync {
+    fn slot(&self) -> Result<u64>;
+    
+    fn epoch(&self) -> Result<u64>;
+    
+    /// Every staker account owned by `governance_program`
+    fn staker_accounts(&self, governance_program: &Pubkey) -> Result<Vec<(Pubkey, StakerInfo)>>;
+}
+
+impl Cluster for RpcClient {
+    fn slot(&self) -> Result<u64> {
+        Ok(self.get_slot()?)
+    }
+    
+    fn epoch(&self) -> Result<u64> {
+        Ok(self.get_epoch_info()?.epoch)
+    }
+    
+    fn staker_accounts(&self, governance_program: &Pubkey) -> Result<Vec<(Pubkey, StakerInfo)>> {
+        fetch_staker_accounts(self, governance_program)
+    }
+}
+
+/// Maximum number of future batches we buffer early peer votes for
+const MAX_PENDING_BATCHES: usize = 64;
+
+/// How often slot and epoch are read from the cluster, about once a slot
+const CLUSTER_POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Slot and epoch as last read from the cluster
+#[derive(Debug, Clone, Copy, Default)]
+struct ClusterClock {
+    slot: u64,
+    epoch: u64,
+}
+
+/// A batch we voted on, collecting peer votes until every validator voted
+/// or its deadline passed
+struct Round {
+    batch: MerkleBatch,
+    votes: HashMap<String, Vote>,
+    total_stake: u64,
+    slot: u64,
+    deadline: Instant,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn start_consensus(
     config: Arc<NodeConfig>,
+    cluster: Arc<dyn Cluster>,
+    mut validator_cache: ValidatorSetCache,
     mut batch_rx: mpsc::Receiver<MerkleBatch>,
-    consensus_tx: mpsc::Sender<ConsensusResult>,
+    mut peer_vote_rx: mpsc::Receiver<Vote>,
+    outbound: ConsensusOutbound,
+    tower_stats: Arc<RwLock<TowerStats>>,
     mut shutdown: tokio::sync::broadcast::Receiver<()>,
 ) -> Result<()> {
     info!("🗳️  Starting consensus module with stake-weighted voting...");
     
     let node_pubkey = config.identity.pubkey().to_string();
-    let rpc_client = RpcClient::new(&config.rpc_url);
+    let governance_program = Pubkey::from_str(&config.program_id)?;
+    // Leaders are picked among staker accounts, which every node reads alike
+    let node_staker = staker_info_address(&config.identity.pubkey(), &governance_program);
+    // Every vote we sign goes through the tower, which survives restarts
+    let tower_path = PathBuf::from(shellexpand::tilde(&config.tower_path).to_string());
+    let mut tower = OracleTower::load_or_new(&tower_path, config.identity.pubkey().to_bytes())?;
+    info!("🗼 Loaded tower from {} (last vote: {:?})", tower_path.display(), tower.vote_state.last_voted_batch);
+    *tower_stats.write().await = tower.stats();
+    
+    // Slot and epoch are read off the runtime and cached; the first batch
+    // is voted on with the validator set already loaded
+    let clock = read_cluster_clock(Arc::clone(&cluster)).await.unwrap_or_else(|e| {
+        warn!("Failed to read slot and epoch: {}", e);
+        ClusterClock::default()
+    });
+    match fetch_stakers(Arc::clone(&cluster), governance_program).await {
+        Ok(accounts) => validator_cache.apply(clock.epoch, accounts),
+        Err(e) => warn!("Failed to load validator set: {}", e),
+    }
+    let (clock_tx, mut clock_rx) = watch::channel(clock);
+    tokio::spawn(poll_cluster(Arc::clone(&cluster), clock_tx));
+    
+    // Refreshed staker accounts, fetched off the runtime
+    let (stakers_tx, mut stakers_rx) = mpsc::channel(1);
+    let mut refreshing = false;
+    
+    // Our own stake is matched as soon as the set lists it
+    validator_cache.observe_identity(&node_pubkey);
+    let mut validators = validator_cache.current().sorted();
+    
+    // Batches still collecting votes, finished in batch order
+    let mut rounds: BTreeMap<u64, Round> = BTreeMap::new();
+    let mut last_batch: Option<u64> = None;
+    
+    // Peer votes that arrived before we built the matching batch
+    let mut pending_votes: HashMap<u64, Vec<Vote>> = HashMap::new();
+    
+    // Every peer vote is checked against that validator's earlier votes
+    let mut detector = EquivocationDetector::new();
     
     loop {
+        let next_deadline = rounds.values().next().map(|round| round.deadline);
+        
         tokio::select! {
             Some(batch) = batch_rx.recv() => {
                 debug!("🗳️  Processing batch with root: {}", &batch.root[..8]);
                 
-                // 1. Get current slot
-                let current_slot = match rpc_client.get_slot() {
-                    Ok(slot) => slot,
-                    Err(e) => {
-                        warn!("Failed to get current slot: {}", e);
-                        0
-                    }
-                };
-                
-                // 2. Query all stakers from governance
-                let (validators, total_stake) = match query_validators(&config, &rpc_client).await {
-                    Ok(result) => result,
-                    Err(e) => {
-                        warn!("Failed to query validators: {}", e);
-                        // Fallback: assume we're the only validator
-                        (vec![(node_pubkey.clone(), 100_000_000_000_000u64)], 100_000_000_000_000u64)
-                    }
-                };
+                // 1. Slot for leader selection, as last read from the cluster
+                let current_slot = clock_rx.borrow().slot;
+                let total_stake = validator_cache.current().total_stake;
                 
                 debug!("🗳️  Found {} validators with total stake: {}", validators.len(), total_stake);
                 
-                // 3. Broadcast our root to peers (via gossip)
-                // TODO: Implement actual gossip broadcasting
-                // For now, we simulate by assuming all nodes agree
-                
-                // 4. Collect votes (in production, from gossip)
+                // 2. Count peer votes that arrived before this batch was built
+                let batch_number = batch.batch_number;
                 let mut votes = HashMap::new();
+                let mut incoming = pending_votes.remove(&batch_number).unwrap_or_default();
+                for proof in admit_votes(&mut votes, &mut incoming, &mut validators, &mut validator_cache, &mut detector) {
+                    if let Err(e) = outbound.evidence_tx.send(proof).await {
+                        warn!("Failed to report equivocation: {}", e);
+                    }
+                }
+                pending_votes.retain(|number, _| *number > batch_number);
                 
-                // Add our own vote
+                // 3. Choose a root through the tower, persist it, then sign and broadcast
                 let our_stake = validators.iter()
                     .find(|(pubkey, _)| pubkey == &node_pubkey)
                     .map(|(_, stake)| *stake)
                     .unwrap_or(0);
                 
-                votes.insert(node_pubkey.clone(), Vote {
-                    node_pubkey: node_pubkey.clone(),
-                    root_hash: batch.root.clone(),
-                    stake: our_stake,
-                    signature: vec![], // TODO: Sign the root
-                });
-                
-                // 5. Tally votes and check for 2/3 consensus
-                let (consensus_root, agreeing_stake) = tally_votes(&votes, total_stake);
-                
-                // 6. Determine if we're the leader for this slot
-                let is_leader = match select_leader(&validators, current_slot) {
-                    Some(leader_pubkey) => leader_pubkey == node_pubkey,
-                    None => false,
+                let voted = match tower_vote(&mut tower, &batch, &votes, total_stake) {
+                    Ok(Some(root)) => persist_tower(&tower, &tower_path).await.map(|_| Some(root)),
+                    other => other,
                 };
-                
-                if consensus_root.is_some() {
-                    info!("✅ Consensus reached: {}/{} stake agrees", agreeing_stake, total_stake);
-                } else {
-                    warn!("❌ No consensus: need 2/3 stake agreement");
-                }
-                
-                if is_leader {
-                    info!("👑 We are the leader for slot {}", current_slot);
-                } else {
-                    debug!("   Not the leader for this slot");
+                match voted {
+                    Ok(Some(root)) => match Vote::new_signed(&config.identity, batch_number, &root, our_stake) {
+                        Ok(our_vote) => {
+                            if let Err(e) = outbound.vote_tx.send(our_vote.clone()).await {
+                                warn!("Failed to broadcast vote: {}", e);
+                            }
+                            votes.insert(node_pubkey.clone(), our_vote);
+                        }
+                        Err(e) => warn!("Failed to sign vote for batch {}: {}", batch_number, e),
+                    },
+                    Ok(None) => warn!("🗼 Tower refused a vote for batch {}", batch_number),
+                    Err(e) => warn!("🗼 Not voting on batch {}: {}", batch_number, e),
                 }
+                *tower_stats.write().await = tower.stats();
                 
-                let result = ConsensusResult {
+                // 4. Collect peer votes until every validator voted or the timeout
+                //    passes, while later batches are voted on
+                last_batch = Some(batch_number);
+                rounds.insert(batch_number, Round {
                     batch,
                     votes,
-                    consensus_root,
-                    agreeing_stake,
                     total_stake,
-                    is_leader,
-                };
+                    slot: current_slot,
+                    deadline: Instant::now() + Duration::from_millis(config.vote_timeout_ms),
+                });
+            }
+            Some(vote) = peer_vote_rx.recv() => {
+                resolve_validator(&vote.node_pubkey, &mut validators, &mut validator_cache);
+                if let Some(proof) = detector.observe(&vote, &validators) {
+                    if let Err(e) = outbound.evidence_tx.send(proof).await {
+                        warn!("Failed to report equivocation: {}", e);
+                    }
+                }
                 
-                if let Err(e) = consensus_tx.send(result).await {
-                    tracing::error!("Failed to send consensus result: {}", e);
+                if let Some(round) = rounds.get_mut(&vote.batch_number) {
+                    accept_vote(&mut round.votes, vote, &validators);
+                } else if last_batch.is_none_or(|last| vote.batch_number > last) {
+                    if pending_votes.len() < MAX_PENDING_BATCHES || pending_votes.contains_key(&vote.batch_number) {
+                        pending_votes.entry(vote.batch_number).or_default().push(vote);
+                    }
+                } else {
+                    debug!("🗳️  Dropping stale vote from {} for batch {}", vote.node_pubkey, vote.batch_number);
+                }
+            }
+            _ = tokio::time::sleep_until(next_deadline.unwrap_or_else(Instant::now)), if next_deadline.is_some() => {}
+            Ok(()) = clock_rx.changed() => {
+                // Refresh the validator set from governance when the epoch changes
+                let epoch = clock_rx.borrow_and_update().epoch;
+                if !refreshing && validator_cache.needs_refresh(epoch) {
+                    refreshing = true;
+                    let (cluster, stakers_tx) = (Arc::clone(&cluster), stakers_tx.clone());
+                    tokio::spawn(async move {
+                        let _ = stakers_tx.send((epoch, fetch_stakers(cluster, governance_program).await)).await;
+                    });
+                }
+            }
+            Some((epoch, fetched)) = stakers_rx.recv() => {
+                refreshing = false;
+                match fetched {
+                    Ok(accounts) => {
+                        validator_cache.apply(epoch, accounts);
+                        validator_cache.observe_identity(&node_pubkey);
+                        validators = validator_cache.current().sorted();
+                    }
+                    Err(e) => warn!("Failed to refresh validator set: {}", e),
                 }
             }
             _ = shutdown.recv() => {
@@ -130,64 +303,198 @@ pub async fn start_consensus(
                 break;
             }
         }
+        
+        // 5. Finish rounds in batch order once every staker, matched or not,
+        //    voted or the round timed out
+        let now = Instant::now();
+        let stakers = validator_cache.current().stakers.len();
+        while let Some(entry) = rounds.first_entry() {
+            let round = entry.get();
+            if round.votes.len() < stakers && round.deadline > now {
+                break;
+            }
+            let round = entry.remove();
+            let result = finish_round(round, &validator_cache, node_staker);
+            if let Err(e) = outbound.result_tx.send(result).await {
+                tracing::error!("Failed to send consensus result: {}", e);
+            }
+        }
     }
     
     Ok(())
 }
 
-// Query all validators and their stakes from TachyonGovernance
-async fn query_validators(config: &NodeConfig, rpc_client: &RpcClient) -> Result<(Vec<(String, u64)>, u64)> {
-    let governance_program = Pubkey::from_str(&config.program_id)?;
+// Tally a round's votes into the result for the sequencer
+fn finish_round(round: Round, validator_cache: &ValidatorSetCache, node_staker: Pubkey) -> ConsensusResult {
+    let Round { batch, votes, total_stake, slot, .. } = round;
+    debug!("🗳️  Collected {} votes for batch {}", votes.len(), batch.batch_number);
+    
+    // Tally votes and check for 2/3 consensus
+    let (consensus_root, agreeing_stake) = tally_votes(&votes, total_stake);
+    
+    // Determine if we're the leader for this slot
+    let is_leader = select_leader(&validator_cache.current().stakers, slot) == Some(node_staker);
+    
+    if consensus_root.is_some() {
+        info!("✅ Consensus reached: {}/{} stake agrees", agreeing_stake, total_stake);
+    } else {
+        warn!("❌ No consensus: need 2/3 stake agreement");
+    }
     
-    // In production, we would query all staker accounts
-    // For now, simplified: just check if we're staked
-    // Use "staker-v2" seed for the new account structure
-    let (staker_info_pda, _) = Pubkey::find_program_address(
-        &[b"staker-v2", config.identity.pubkey().as_ref()],
-        &governance_program,
-    );
-    
-    let mut validators = Vec::new();
-    let mut total_stake = 0u64;
-    
-    // Check our stake
-    match rpc_client.get_account(&staker_info_pda) {
-        Ok(account) => {
-            // Parse stake amount from the account data
-            // StakerInfo structure: discriminator (8) + staked_amount (8) + ...
-            if account.data.len() >= 16 {
-                let stake_bytes: [u8; 8] = account.data[8..16].try_into().unwrap();
-                let stake = u64::from_le_bytes(stake_bytes);
-                info!("✅ Found our stake: {} TACH", stake as f64 / 1e9);
-                validators.push((config.identity.pubkey().to_string(), stake));
-                total_stake += stake;
-            } else {
-                warn!("Staker account too small, cannot read stake");
+    if is_leader {
+        info!("👑 We are the leader for slot {}", slot);
+    } else {
+        debug!("   Not the leader for this slot");
+    }
+    
+    ConsensusResult {
+        batch,
+        votes,
+        consensus_root,
+        agreeing_stake,
+        total_stake,
+        is_leader,
+    }
+}
+
+// Read slot and epoch off the runtime; RPC calls block
+async fn read_cluster_clock(cluster: Arc<dyn Cluster>) -> Result<ClusterClock> {
+    tokio::task::spawn_blocking(move || {
+        Ok(ClusterClock { slot: cluster.slot()?, epoch: cluster.epoch()? })
+    }).await?
+}
+
+// Fetch every staker account off the runtime
+async fn fetch_stakers(cluster: Arc<dyn Cluster>, governance_program: Pubkey) -> Result<Vec<(Pubkey, StakerInfo)>> {
+    tokio::task::spawn_blocking(move || cluster.staker_accounts(&governance_program)).await?
+}
+
+// Keep the cached slot and epoch current until consensus stops
+async fn poll_cluster(cluster: Arc<dyn Cluster>, clock_tx: watch::Sender<ClusterClock>) {
+    let mut poll_interval = tokio::time::interval(CLUSTER_POLL_INTERVAL);
+    poll_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        poll_interval.tick().await;
+        if clock_tx.is_closed() {
+            break;
+        }
+        match read_cluster_clock(Arc::clone(&cluster)).await {
+            Ok(clock) => {
+                clock_tx.send_replace(clock);
             }
+            Err(e) => warn!("Failed to get current slot: {}", e),
         }
-        Err(_) => {
-            warn!("Node not staked, cannot participate in consensus");
+    }
+}
+
+// Admit buffered peer votes, matching first-time voters against unresolved stake.
+// Each is checked for double votes again: its voter may only be in the set now.
+fn admit_votes(
+    votes: &mut HashMap<String, Vote>,
+    incoming: &mut Vec<Vote>,
+    validators: &mut Vec<(String, u64)>,
+    validator_cache: &mut ValidatorSetCache,
+    detector: &mut EquivocationDetector,
+) -> Vec<EquivocationProof> {
+    let mut proofs = Vec::new();
+    for vote in incoming.drain(..) {
+        resolve_validator(&vote.node_pubkey, validators, validator_cache);
+        proofs.extend(detector.observe(&vote, validators));
+        accept_vote(votes, vote, validators);
+    }
+    proofs
+}
+
+// First vote from a staker we haven't matched yet adds it to the set
+fn resolve_validator(pubkey: &str, validators: &mut Vec<(String, u64)>, validator_cache: &mut ValidatorSetCache) {
+    if validators.iter().any(|(validator, _)| validator == pubkey) {
+        return;
+    }
+    if let Some(stake) = validator_cache.observe_identity(pubkey) {
+        validators.push((pubkey.to_string(), stake));
+        validators.sort();
+    }
+}
+
+// Run fork choice for the batch and record the chosen root in the tower.
+// The caller persists the tower before signing, so a restart cannot vote differently.
+fn tower_vote(
+    tower: &mut OracleTower,
+    batch: &MerkleBatch,
+    votes: &HashMap<String, Vote>,
+    total_stake: u64,
+) -> Result<Option<String>> {
+    let own_root = root_bytes(&batch.root)?;
+    
+    let mut root_stakes: HashMap<MerkleRoot, u64> = HashMap::new();
+    for vote in votes.values() {
+        if let Ok(root) = root_bytes(&vote.root_hash) {
+            *root_stakes.entry(root).or_insert(0) += vote.stake;
         }
     }
     
-    // TODO: Query other validators from on-chain data
-    // This would involve:
-    // 1. Getting all staker-info accounts
-    // 2. Parsing their stake amounts
-    // 3. Building the validator list
+    let root = tower.choose_root(batch.batch_number, own_root, &root_stakes, total_stake);
+    if root != own_root {
+        info!("🗼 Switching to heavier root {} for batch {}", &hex::encode(root)[..8], batch.batch_number);
+    }
+    
+    if !tower.can_vote(batch.batch_number, &root) {
+        return Ok(None);
+    }
+    tower.record_vote(batch.batch_number, root, batch.timestamp)?;
+    
+    Ok(Some(hex::encode(root)))
+}
+
+// Write the tower to disk off the async runtime; the fsync would otherwise
+// stall every other task once per batch
+async fn persist_tower(tower: &OracleTower, tower_path: &std::path::Path) -> Result<()> {
+    let tower = tower.clone();
+    let tower_path = tower_path.to_path_buf();
+    tokio::task::spawn_blocking(move || tower.save(&tower_path)).await?
+}
+
+pub(crate) fn root_bytes(root_hash: &str) -> Result<MerkleRoot> {
+    hex::decode(root_hash)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Invalid root hash length"))
+}
+
+// Add a peer vote if it comes from a known validator and its signature checks out.
+// The counted stake always comes from the validator set, never from the vote itself.
+fn accept_vote(votes: &mut HashMap<String, Vote>, mut vote: Vote, validators: &[(String, u64)]) -> bool {
+    let Some(stake) = validators.iter()
+        .find(|(pubkey, _)| pubkey == &vote.node_pubkey)
+        .map(|(_, stake)| *stake)
+    else {
+        debug!("🗳️  Ignoring vote from unknown validator {}", vote.node_pubkey);
+        return false;
+    };
+    
+    if !vote.verify() {
+        warn!("🗳️  Rejecting vote with invalid signature from {}", vote.node_pubkey);
+        return false;
+    }
     
-    Ok((validators, total_stake))
+    vote.stake = stake;
+    votes.entry(vote.node_pubkey.clone()).or_insert(vote);
+    true
 }
 
 // Tally votes and return consensus root if 2/3 agreement reached
 fn tally_votes(votes: &HashMap<String, Vote>, total_stake: u64) -> (Option<String>, u64) {
     let mut root_stakes: HashMap<String, u64> = HashMap::new();
     
-    // Group votes by root hash
-    for vote in votes.values() {
+    // Group votes by root hash, counting only correctly signed votes
+    for vote in votes.values().filter(|vote| vote.verify()) {
         *root_stakes.entry(vote.root_hash.clone()).or_insert(0) += vote.stake;
     }
     
+    // Without a known validator set nothing can reach quorum
+    if total_stake == 0 {
+        return (None, 0);
+    }
+    
     // Find root with most stake
     let quorum_threshold = (total_stake * 2) / 3;
     
@@ -201,13 +508,15 @@ fn tally_votes(votes: &HashMap<String, Vote>, total_stake: u64) -> (Option<Strin
     (None, 0)
 }
 
-// Stake-weighted leader selection (deterministic based on slot)
-fn select_leader(validators: &[(String, u64)], slot: u64) -> Option<String> {
-    if validators.is_empty() {
+// Stake-weighted leader selection (deterministic based on slot). Runs over
+// every staker account in address order, matched to an identity or not, so
+// nodes that matched different identities still pick the same leader.
+fn select_leader(stakers: &BTreeMap<Pubkey, u64>, slot: u64) -> Option<Pubkey> {
+    if stakers.is_empty() {
         return None;
     }
     
-    let total_stake: u64 = validators.iter().map(|(_, stake)| stake).sum();
+    let total_stake: u64 = stakers.values().sum();
     if total_stake == 0 {
         return None;
     }
@@ -217,27 +526,19 @@ fn select_leader(validators: &[(String, u64)], slot: u64) -> Option<String> {
     let target = (slot * 12345) % total_stake;
     
     let mut cumulative = 0u64;
-    for (pubkey, stake) in validators {
+    for (staker, stake) in stakers {
         cumulative += stake;
         if cumulative > target {
-            return Some(pubkey.clone());
+            return Some(*staker);
         }
     }
     
-    validators.first().map(|(pubkey, _)| pubkey.clone())
+    stakers.keys().next().copied()
 }
 
-// Verify that 2/3 of stake voted for the same root
+// Verify that 2/3 of stake signed a vote for the same root
 pub fn verify_quorum(votes: &HashMap<String, Vote>, total_stake: u64) -> bool {
-    let mut root_stakes: HashMap<String, u64> = HashMap::new();
-    
-    for vote in votes.values() {
-        *root_stakes.entry(vote.root_hash.clone()).or_insert(0) += vote.stake;
-    }
-    
-    // Check if any root has 2/3+ stake
-    let quorum_threshold = (total_stake * 2) / 3;
-    root_stakes.values().any(|&stake| stake >= quorum_threshold)
+    tally_votes(votes, total_stake).0.is_some()
 }
 
 #[cfg(test)]
@@ -246,46 +547,230 @@ mod tests {
     
     #[test]
     fn test_leader_selection() {
-        let validators = vec![
-            ("validator1".to_string(), 100),
-            ("validator2".to_string(), 200),
-            ("validator3".to_string(), 300),
-        ];
+        let program = Pubkey::new_unique();
+        let identities: Vec<_> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let accounts: Vec<_> = identities.iter().zip([100, 200, 300])
+            .map(|(identity, stake)| {
+                (staker_info_address(identity, &program), StakerInfo { staked_amount: stake, ..StakerInfo::default() })
+            })
+            .collect();
+        let mut cache = ValidatorSetCache::new(program, Duration::from_secs(60));
+        cache.apply(1, accounts);
+        let stakers = &cache.current().stakers;
         
         // Same slot should always select same leader
-        let leader1 = select_leader(&validators, 100);
-        let leader2 = select_leader(&validators, 100);
+        let leader1 = select_leader(stakers, 100);
+        let leader2 = select_leader(stakers, 100);
         assert_eq!(leader1, leader2);
         
         // Different slots may select different leaders
-        let leader_slot_1 = select_leader(&validators, 1);
-        let leader_slot_2 = select_leader(&validators, 2);
+        let leader_slot_1 = select_leader(stakers, 1);
+        let leader_slot_2 = select_leader(stakers, 2);
         assert!(leader_slot_1.is_some());
         assert!(leader_slot_2.is_some());
+        
+        // A node that matched an identity picks the same leaders as one that did not
+        let mut matched = ValidatorSetCache::new(program, Duration::from_secs(60));
+        matched.apply(1, cache.current().stakers.iter()
+            .map(|(address, stake)| (*address, StakerInfo { staked_amount: *stake, ..StakerInfo::default() }))
+            .collect());
+        matched.observe_identity(&identities[0].to_string());
+        for slot in 0..50 {
+            assert_eq!(select_leader(&matched.current().stakers, slot), select_leader(&cache.current().stakers, slot));
+        }
     }
     
     #[test]
     fn test_quorum_verification() {
+        let root = hex::encode([1u8; 32]);
         let mut votes = HashMap::new();
         
-        votes.insert("v1".to_string(), Vote {
-            node_pubkey: "v1".to_string(),
-            root_hash: "root1".to_string(),
+        for (validator, stake) in [(Keypair::new(), 200), (Keypair::new(), 100)] {
+            let vote = Vote::new_signed(&validator, 1, &root, stake).unwrap();
+            votes.insert(vote.node_pubkey.clone(), vote);
+        }
+        
+        // 300/400 = 75% > 66.67%, should reach quorum
+        assert!(verify_quorum(&votes, 400));
+        
+        // 300/500 = 60% < 66.67%, should not reach quorum
+        assert!(!verify_quorum(&votes, 500));
+        
+        // An unsigned vote adds no stake
+        votes.insert("v3".to_string(), Vote {
+            node_pubkey: "v3".to_string(),
+            batch_number: 1,
+            root_hash: root,
             stake: 200,
             signature: vec![],
         });
+        assert!(!verify_quorum(&votes, 500));
+    }
+    
+    #[test]
+    fn test_tally_counts_only_signed_votes() {
+        let root = hex::encode([7u8; 32]);
+        let honest = Keypair::new();
+        let other = Keypair::new();
         
-        votes.insert("v2".to_string(), Vote {
-            node_pubkey: "v2".to_string(),
-            root_hash: "root1".to_string(),
-            stake: 100,
-        signature: vec![],
+        let mut votes = HashMap::new();
+        let vote = Vote::new_signed(&honest, 5, &root, 300).unwrap();
+        votes.insert(vote.node_pubkey.clone(), vote);
+        
+        // Unsigned vote claiming a large stake must not count
+        votes.insert(other.pubkey().to_string(), Vote {
+            node_pubkey: other.pubkey().to_string(),
+            batch_number: 5,
+            root_hash: root.clone(),
+            stake: 1_000,
+            signature: vec![0; 64],
         });
         
-        // 300/400 = 75% > 66.67%, should reach quorum
-        assert!(verify_quorum(&votes, 400));
+        let (consensus_root, agreeing_stake) = tally_votes(&votes, 400);
+        assert_eq!(consensus_root, Some(root));
+        assert_eq!(agreeing_stake, 300);
         
-        // 300/500 = 60% < 66.67%, should not reach quorum
-        assert!(!verify_quorum(&votes, 500));
+        // Signature over a different batch number does not verify
+        let mut replayed = Vote::new_signed(&honest, 5, &hex::encode([7u8; 32]), 300).unwrap();
+        replayed.batch_number = 6;
+        assert!(!replayed.verify());
+    }
+    
+    #[test]
+    fn test_buffered_votes_are_checked_for_double_votes() {
+        let program = Pubkey::new_unique();
+        let peer = Keypair::new();
+        let mut cache = ValidatorSetCache::new(program, Duration::from_secs(60));
+        let mut validators = Vec::new();
+        let mut detector = EquivocationDetector::new();
+        
+        // Buffered before the set listed the peer, so nothing to check it against yet
+        let first = Vote::new_signed(&peer, 5, &hex::encode([1u8; 32]), 0).unwrap();
+        resolve_validator(&first.node_pubkey, &mut validators, &mut cache);
+        assert!(detector.observe(&first, &validators).is_none());
+        
+        // The set now lists the peer, which signs another root for the same batch
+        let staker = StakerInfo { staked_amount: 100, ..StakerInfo::default() };
+        cache.apply(1, vec![(staker_info_address(&peer.pubkey(), &program), staker)]);
+        let second = Vote::new_signed(&peer, 5, &hex::encode([2u8; 32]), 0).unwrap();
+        resolve_validator(&second.node_pubkey, &mut validators, &mut cache);
+        assert!(detector.observe(&second, &validators).is_none());
+        
+        // Admitting the buffered vote catches it
+        let mut votes = HashMap::new();
+        let proofs = admit_votes(&mut votes, &mut vec![first], &mut validators, &mut cache, &mut detector);
+        assert_eq!(proofs.len(), 1);
+        assert_eq!(votes.len(), 1);
+    }
+    
+    #[test]
+    fn test_accept_vote_uses_validator_stake() {
+        let root = hex::encode([9u8; 32]);
+        let peer = Keypair::new();
+        let validators = vec![(peer.pubkey().to_string(), 250)];
+        
+        let mut votes = HashMap::new();
+        let vote = Vote::new_signed(&peer, 1, &root, 1_000_000).unwrap();
+        assert!(accept_vote(&mut votes, vote, &validators));
+        assert_eq!(votes[&peer.pubkey().to_string()].stake, 250);
+        
+        let stranger = Keypair::new();
+        let vote = Vote::new_signed(&stranger, 1, &root, 10).unwrap();
+        assert!(!accept_vote(&mut votes, vote, &validators));
+    }
+    
+    /// Cluster at slot 0 of epoch 0 in which the listed identities hold 100 each
+    struct TestCluster {
+        stakers: Vec<Pubkey>,
+    }
+    
+    impl Cluster for TestCluster {
+        fn slot(&self) -> Result<u64> {
+            Ok(0)
+        }
+        
+        fn epoch(&self) -> Result<u64> {
+            Ok(0)
+        }
+        
+        fn staker_accounts(&self, governance_program: &Pubkey) -> Result<Vec<(Pubkey, StakerInfo)>> {
+            Ok(self.stakers.iter()
+                .map(|identity| {
+                    let staker = StakerInfo { staked_amount: 100, ..StakerInfo::default() };
+                    (staker_info_address(identity, governance_program), staker)
+                })
+                .collect())
+        }
+    }
+    
+    fn batch(batch_number: u64) -> MerkleBatch {
+        MerkleBatch {
+            batch_number,
+            root: hex::encode([batch_number as u8; 32]),
+            timestamp: batch_number as i64,
+            feeds: Vec::new(),
+            tree: Vec::new(),
+        }
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_batches_collect_votes_concurrently() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config: NodeConfig = toml::from_str(r#"
+            keypair_path = "unused"
+            rpc_url = "http://127.0.0.1:1"
+            program_id = "TACH9r2uZzoFM6daofesADjeDn9NqB1pKFWP5mfByb1"
+            l2_program_id = "L2TA7eVsDyXx7nxF4p2Xay3iWgdCHuMPx6YV5odwMTx"
+            gossip_port = 0
+            api_port = 0
+            update_interval_ms = 1000
+            batch_interval_ms = 100
+            vote_timeout_ms = 500
+            min_publishers = 1
+            assets = []
+            exchanges = {}
+        "#).unwrap();
+        config.tower_path = dir.path().join("tower.bin").to_string_lossy().into_owned();
+        let config = Arc::new(config);
+        
+        // We and a peer that only votes on batch 2
+        let peer = Keypair::new();
+        let cluster = Arc::new(TestCluster { stakers: vec![config.identity.pubkey(), peer.pubkey()] });
+        let validator_cache = ValidatorSetCache::new(Pubkey::from_str(&config.program_id).unwrap(), Duration::from_secs(60));
+        let (batch_tx, batch_rx) = mpsc::channel(10);
+        let (peer_vote_tx, peer_vote_rx) = mpsc::channel(10);
+        let (vote_tx, _votes) = mpsc::channel(10);
+        let (result_tx, mut results) = mpsc::channel(10);
+        let (evidence_tx, _evidence) = mpsc::channel(10);
+        let (_shutdown, shutdown_rx) = tokio::sync::broadcast::channel(1);
+        tokio::spawn(start_consensus(
+            Arc::clone(&config),
+            cluster,
+            validator_cache,
+            batch_rx,
+            peer_vote_rx,
+            ConsensusOutbound { vote_tx, result_tx, evidence_tx },
+            Arc::new(RwLock::new(TowerStats::default())),
+            shutdown_rx,
+        ));
+        
+        // Batches come faster than the vote timeout
+        let start = Instant::now();
+        for batch_number in 1..=3 {
+            batch_tx.send(batch(batch_number)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        peer_vote_tx.send(Vote::new_signed(&peer, 2, &batch(2).root, 0).unwrap()).await.unwrap();
+        
+        let mut finished = Vec::new();
+        for _ in 1..=3 {
+            let result = results.recv().await.unwrap();
+            finished.push((result.batch.batch_number, result.votes.len(), result.consensus_root.is_some()));
+        }
+        
+        // In batch order, batch 2 with the peer's vote, and all within one
+        // timeout of the last batch rather than one timeout each
+        assert_eq!(finished, vec![(1, 1, false), (2, 2, true), (3, 1, false)]);
+        assert!(start.elapsed() <= Duration::from_millis(200 + 500));
     }
 }
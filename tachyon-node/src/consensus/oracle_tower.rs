@@ -1,10 +1,24 @@
 #![allow(dead_code)]
 // Oracle Tower - Simplified Tower BFT for Tachyon Oracle Network
 // Adapted from Solana Tower BFT for Merkle root consensus
+//
+// Every vote this node signs goes through the tower first. A batch can only
+// ever get one root from us, we never vote for an older batch than our last
+// vote, and lockouts decide whether we may follow a competing root instead of
+// our own. The tower is persisted before each vote leaves the node.
 
 use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
 use serde::{Serialize, Deserialize};
-use anyhow::Result;
+use anyhow::{Context, Result};
+
+/// Maximum depth of the lockout stack; a vote this deep becomes the root
+pub const MAX_LOCKOUT_HISTORY: usize = 32;
+
+/// Share of total stake another root needs before we follow it instead of our own
+pub const SWITCH_FORK_THRESHOLD: f64 = 0.38;
 
 /// Batch number (equivalent to Solana's Slot)
 pub type BatchNumber = u64;
@@ -77,7 +91,7 @@ impl Default for TowerVoteState {
 }
 
 /// Lockout period for a vote (exponential backoff)
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Lockout {
     pub batch_number: BatchNumber,
     pub confirmation_count: u32,
@@ -114,15 +128,10 @@ pub struct OracleTower {
     /// Threshold depth for switching forks
     pub threshold_depth: usize,
     
-    /// Threshold size (stake percentage)
-    pub threshold_size: f64,
-    
     /// Vote history for replay protection
-    #[serde(skip)]
     pub vote_history: HashMap<BatchNumber, MerkleRoot>,
     
-    /// Lockouts for safety
-    #[serde(skip)]
+    /// Lockouts for safety, oldest first
     pub lockouts: Vec<Lockout>,
 }
 
@@ -132,7 +141,6 @@ impl OracleTower {
             node_pubkey,
             vote_state: TowerVoteState::new(),
             threshold_depth: 8,
-            threshold_size: 0.67, // 2/3
             vote_history: HashMap::new(),
             lockouts: Vec::new(),
         }
@@ -146,14 +154,11 @@ impl OracleTower {
             return existing_root == root;
         }
 
-        // Check lockouts
-        for lockout in &self.lockouts {
-            if lockout.is_locked_out_at(batch_number) {
-                return false;
-            }
+        // Never go back to a batch older than our last vote
+        match self.vote_state.last_voted_batch {
+            Some(last) => batch_number > last,
+            None => true,
         }
-
-        true
     }
 
     /// Record a vote
@@ -173,8 +178,11 @@ impl OracleTower {
         // Update vote state
         self.vote_state.push_vote(vote);
 
-        // Record in history
+        // Record in history, keeping only batches still in the vote state
         self.vote_history.insert(batch_number, root);
+        if let Some(oldest) = self.vote_state.votes.back().map(|v| v.batch_number) {
+            self.vote_history.retain(|&b, _| b >= oldest);
+        }
 
         // Update lockouts
         self.update_lockouts(batch_number);
@@ -184,19 +192,24 @@ impl OracleTower {
 
     /// Update lockouts after a vote
     fn update_lockouts(&mut self, batch_number: BatchNumber) {
+        // Drop lockouts that expired before this batch
+        self.lockouts.retain(|l| l.is_locked_out_at(batch_number));
+
         // Add new lockout
         self.lockouts.push(Lockout::new(batch_number));
 
-        // Increment confirmation counts for previous lockouts
-        for lockout in &mut self.lockouts {
-            if lockout.batch_number < batch_number {
+        // Double the lockout of every vote with enough confirmations stacked on it
+        let depth = self.lockouts.len();
+        for (i, lockout) in self.lockouts.iter_mut().enumerate() {
+            if depth > i + lockout.confirmation_count as usize {
                 lockout.confirmation_count += 1;
             }
         }
 
-        // Remove expired lockouts (keep last 32)
-        if self.lockouts.len() > 32 {
-            self.lockouts.drain(0..self.lockouts.len() - 32);
+        // A full stack roots its oldest vote
+        if self.lockouts.len() > MAX_LOCKOUT_HISTORY {
+            let rooted = self.lockouts.remove(0);
+            self.update_root(rooted.batch_number);
         }
     }
 
@@ -221,27 +234,103 @@ impl OracleTower {
         self.vote_history.retain(|&b, _| b >= batch_number);
     }
 
-    /// Check if we should switch to a different fork
+    /// Check if a vote for `root` at `batch_number` conflicts with a vote we
+    /// are still locked out by. Batches do not build on one another, so only
+    /// our own vote on the same batch for another root can conflict.
+    pub fn is_locked_out_of(&self, batch_number: BatchNumber, root: &MerkleRoot) -> bool {
+        self.lockouts.iter()
+            .filter(|l| l.batch_number == batch_number && l.is_locked_out_at(batch_number))
+            .any(|l| self.vote_history.get(&l.batch_number).is_some_and(|voted| voted != root))
+    }
+
+    /// Check if we should switch to a different fork: nothing we voted for
+    /// locks us out of it, and it holds more than `SWITCH_FORK_THRESHOLD` of
+    /// the stake and more than our own fork
     pub fn should_switch_fork(
         &self,
-        _current_batch: BatchNumber,
-        current_stake: u64,
         new_batch: BatchNumber,
+        new_root: &MerkleRoot,
+        current_stake: u64,
         new_stake: u64,
         total_stake: u64,
     ) -> bool {
-        // Can't switch if locked out
-        if self.is_locked_out(new_batch) {
+        if total_stake == 0 || self.is_locked_out_of(new_batch, new_root) {
             return false;
         }
 
-        // Calculate stake percentages
-        let current_pct = (current_stake as f64) / (total_stake as f64);
         let new_pct = (new_stake as f64) / (total_stake as f64);
+        new_pct > SWITCH_FORK_THRESHOLD && new_stake > current_stake
+    }
 
-        // Switch if new fork has significantly more stake
-        let threshold = self.threshold_size;
-        new_pct > current_pct + threshold
+    /// Fork choice for `batch_number`: the root we will vote for, given our own
+    /// root and the stake already seen behind each competing root
+    pub fn choose_root(
+        &self,
+        batch_number: BatchNumber,
+        own_root: MerkleRoot,
+        root_stakes: &HashMap<MerkleRoot, u64>,
+        total_stake: u64,
+    ) -> MerkleRoot {
+        if let Some(voted) = self.vote_history.get(&batch_number) {
+            return *voted;
+        }
+        if total_stake == 0 {
+            return own_root;
+        }
+
+        let own_stake = root_stakes.get(&own_root).copied().unwrap_or(0);
+        let heaviest = root_stakes.iter()
+            .filter(|(root, _)| **root != own_root)
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)));
+
+        match heaviest {
+            Some((root, stake)) if self.should_switch_fork(batch_number, root, own_stake, *stake, total_stake) => *root,
+            _ => own_root,
+        }
+    }
+
+    /// Load the tower saved at `path`, or start a new one if none exists
+    pub fn load_or_new(path: &Path, node_pubkey: [u8; 32]) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::new(node_pubkey));
+        }
+
+        let content = fs::read(path)
+            .with_context(|| format!("Failed to read tower file: {}", path.display()))?;
+        let tower: Self = serde_json::from_slice(&content)
+            .with_context(|| format!("Failed to parse tower file: {}", path.display()))?;
+
+        // Voting with another identity's tower would defeat the lockouts
+        if tower.node_pubkey != node_pubkey {
+            return Err(anyhow::anyhow!("Tower file {} belongs to a different identity", path.display()));
+        }
+
+        Ok(tower)
+    }
+
+    /// Persist the tower atomically: write a temp file, fsync, rename over
+    /// `path`, then fsync the directory so the rename survives a crash
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fs::create_dir_all(dir)?;
+
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut file = fs::File::create(&tmp_path)
+                .with_context(|| format!("Failed to create tower file: {}", tmp_path.display()))?;
+            file.write_all(&serde_json::to_vec(self)?)?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace tower file: {}", path.display()))?;
+        fs::File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .with_context(|| format!("Failed to sync tower directory: {}", dir.display()))?;
+
+        Ok(())
     }
 
     /// Get vote statistics
@@ -256,7 +345,7 @@ impl OracleTower {
 }
 
 /// Tower statistics
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct TowerStats {
     pub total_votes: usize,
     pub active_lockouts: usize,
@@ -345,5 +434,75 @@ mod tests {
         // Old votes should be cleaned up
         assert!(!tower.vote_history.contains_key(&9));
     }
+
+    #[test]
+    fn test_no_votes_for_older_batches() {
+        let mut tower = OracleTower::new([1u8; 32]);
+        tower.record_vote(10, [42u8; 32], 1000).unwrap();
+        tower.record_vote(12, [42u8; 32], 1001).unwrap();
+
+        assert!(!tower.can_vote(11, &[42u8; 32]));
+        assert!(tower.record_vote(11, [42u8; 32], 1002).is_err());
+        assert!(tower.can_vote(13, &[43u8; 32]));
+    }
+
+    #[test]
+    fn test_lockouts_double_and_root() {
+        let mut tower = OracleTower::new([1u8; 32]);
+        for batch in 1..=(MAX_LOCKOUT_HISTORY as u64 + 1) {
+            tower.record_vote(batch, [batch as u8; 32], batch as i64).unwrap();
+        }
+
+        assert_eq!(tower.lockouts.len(), MAX_LOCKOUT_HISTORY);
+        assert_eq!(tower.root_batch(), Some(1));
+        // Deepest remaining vote has been confirmed by everything above it
+        assert_eq!(tower.lockouts[0].confirmation_count, MAX_LOCKOUT_HISTORY as u32);
+        assert_eq!(tower.lockouts.last().unwrap().confirmation_count, 1);
+    }
+
+    #[test]
+    fn test_fork_choice() {
+        let own = [1u8; 32];
+        let other = [2u8; 32];
+        let stakes = HashMap::from([(other, 90u64)]);
+
+        // A fresh tower follows a root that has more than the switch threshold
+        let tower = OracleTower::new([9u8; 32]);
+        assert_eq!(tower.choose_root(5, own, &stakes, 100), other);
+        assert_eq!(tower.choose_root(5, own, &HashMap::from([(other, 30u64)]), 100), own);
+        assert_eq!(tower.choose_root(5, own, &HashMap::from([(own, 50u64), (other, 45u64)]), 100), own);
+
+        // Votes on earlier batches do not lock us out of a heavier root
+        let mut tower = OracleTower::new([9u8; 32]);
+        for batch in 1..=4 {
+            tower.record_vote(batch, own, 1000 + batch as i64).unwrap();
+        }
+        assert!(tower.is_locked_out(5));
+        assert_eq!(tower.choose_root(5, own, &stakes, 100), other);
+
+        // Once we voted on a batch, that is the only root we can choose
+        tower.record_vote(5, own, 1005).unwrap();
+        assert!(tower.is_locked_out_of(5, &other));
+        assert!(!tower.should_switch_fork(5, &other, 0, 90, 100));
+        assert_eq!(tower.choose_root(5, other, &stakes, 100), own);
+    }
+
+    #[test]
+    fn test_persistence_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tower.json");
+
+        let mut tower = OracleTower::load_or_new(&path, [1u8; 32]).unwrap();
+        tower.record_vote(7, [42u8; 32], 1000).unwrap();
+        tower.save(&path).unwrap();
+
+        // A restarted node still refuses to vote a different root for batch 7
+        let restored = OracleTower::load_or_new(&path, [1u8; 32]).unwrap();
+        assert!(!restored.can_vote(7, &[43u8; 32]));
+        assert!(restored.is_locked_out(8));
+        assert!(!path.with_extension("tmp").exists());
+
+        assert!(OracleTower::load_or_new(&path, [2u8; 32]).is_err());
+    }
 }
 
@@ -0,0 +1,376 @@
+// Validator Set - stake-weighted view of every TachyonGovernance staker
+//
+// Staker accounts are PDAs of ["staker-v2", identity] and do not store the
+// identity itself, so each account is matched to a validator once we learn
+// its identity (our own key, gossip votes). Stake of accounts we cannot match
+// yet still counts towards the network total. Only identities of current
+// stakers are remembered, and those that leave the set are forgotten on the
+// next refresh. Every change to a staker is sent to subscribers, keyed by its
+// staker account so they can follow stakers that are not matched yet.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use borsh::BorshDeserialize;
+use sha2::{Digest, Sha256};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcProgramAccountsConfig;
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use anyhow::Result;
+use tokio::sync::broadcast;
+use tracing::{debug, info};
+
+/// Borsh layout of `tachyon_governance::StakerInfo` (after the 8-byte discriminator)
+#[derive(Debug, Clone, Default, BorshDeserialize)]
+pub struct StakerInfo {
+    pub staked_amount: u64,
+    pub last_stake_timestamp: i64,
+    pub bump: u8,
+    pub total_rewards_claimed: u64,
+    pub last_claim_timestamp: i64,
+    pub pending_rewards: u64,
+    pub compounded_rewards: u64,
+    pub uptime_score: u64,
+    pub submissions_count: u64,
+    pub accurate_submissions: u64,
+    pub first_stake_timestamp: i64,
+    pub loyalty_tier: u8,
+    pub referrer: [u8; 32],
+    pub referral_count: u64,
+    pub referral_rewards: u64,
+    pub vested_rewards: u64,
+    pub vesting_start: i64,
+}
+
+/// Account size of a `StakerInfo` created by `init_staker` (8 + INIT_SPACE).
+/// Accounts created by `recover_old_stake` are `8 + size_of::<StakerInfo>()` and
+/// so larger; only this prefix is decoded.
+pub const STAKER_INFO_LEN: usize = 8 + 146;
+
+impl StakerInfo {
+    /// Decode a full account, checking the Anchor discriminator
+    pub fn try_from_account_data(data: &[u8]) -> Result<Self> {
+        if data.len() < STAKER_INFO_LEN {
+            return Err(anyhow::anyhow!("StakerInfo account too small: {} bytes", data.len()));
+        }
+        if data[..8] != staker_info_discriminator() {
+            return Err(anyhow::anyhow!("Not a StakerInfo account"));
+        }
+
+        let mut body = &data[8..];
+        Ok(Self::deserialize(&mut body)?)
+    }
+}
+
+/// Anchor account discriminator: sha256("account:StakerInfo")[..8]
+pub fn staker_info_discriminator() -> [u8; 8] {
+    let hash = Sha256::digest(b"account:StakerInfo");
+    let mut disc = [0u8; 8];
+    disc.copy_from_slice(&hash[..8]);
+    disc
+}
+
+/// Staker PDA for a validator identity
+pub fn staker_info_address(identity: &Pubkey, governance_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"staker-v2", identity.as_ref()], governance_program).0
+}
+
+/// Fetch and decode every staker account owned by the governance program.
+/// Filtered by discriminator only: staker accounts come in more than one size.
+pub fn fetch_staker_accounts(rpc_client: &RpcClient, governance_program: &Pubkey) -> Result<Vec<(Pubkey, StakerInfo)>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &staker_info_discriminator())),
+        ]),
+        ..Default::default()
+    };
+
+    let accounts = rpc_client.get_program_accounts_with_config(governance_program, config)?;
+
+    let mut stakers = Vec::with_capacity(accounts.len());
+    for (address, account) in accounts {
+        match StakerInfo::try_from_account_data(&account.data) {
+            Ok(info) => stakers.push((address, info)),
+            Err(e) => debug!("Skipping staker account {}: {}", address, e),
+        }
+    }
+
+    Ok(stakers)
+}
+
+/// Change to one staker, sent to subscribers when the set is refreshed or a
+/// staker is matched to its identity. `identity` is None until it is matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidatorSetChange {
+    Added { staker: Pubkey, identity: Option<String>, stake: u64 },
+    Removed { staker: Pubkey, identity: Option<String> },
+    StakeChanged { staker: Pubkey, identity: Option<String>, old_stake: u64, new_stake: u64 },
+}
+
+/// Snapshot of staked validators for one epoch
+#[derive(Debug, Clone, Default)]
+pub struct ValidatorSet {
+    pub epoch: u64,
+    /// Stake by staker PDA for every staker, matched or not
+    pub stakers: BTreeMap<Pubkey, u64>,
+    /// Stake by validator identity for stakers we have matched
+    pub validators: HashMap<String, u64>,
+    /// Stake by staker PDA for stakers whose identity we have not seen yet
+    pub unresolved: HashMap<Pubkey, u64>,
+    /// Stake of every staker, matched or not
+    pub total_stake: u64,
+}
+
+impl ValidatorSet {
+    pub fn stake_of(&self, identity: &str) -> Option<u64> {
+        self.validators.get(identity).copied()
+    }
+
+    /// Validators sorted by identity so every node iterates them in the same order
+    pub fn sorted(&self) -> Vec<(String, u64)> {
+        let mut validators: Vec<_> = self.validators.iter()
+            .map(|(identity, stake)| (identity.clone(), *stake))
+            .collect();
+        validators.sort();
+        validators
+    }
+}
+
+/// Epoch-cached validator set with change notifications
+pub struct ValidatorSetCache {
+    governance_program: Pubkey,
+    max_age: Duration,
+    /// Staker PDA -> identity for the stakers we have matched
+    identities: HashMap<Pubkey, String>,
+    current: ValidatorSet,
+    refreshed_at: Option<Instant>,
+    changes: broadcast::Sender<ValidatorSetChange>,
+}
+
+impl ValidatorSetCache {
+    pub fn new(governance_program: Pubkey, max_age: Duration) -> Self {
+        let (changes, _) = broadcast::channel(256);
+        Self {
+            governance_program,
+            max_age,
+            identities: HashMap::new(),
+            current: ValidatorSet::default(),
+            refreshed_at: None,
+            changes,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ValidatorSetChange> {
+        self.changes.subscribe()
+    }
+
+    pub fn current(&self) -> &ValidatorSet {
+        &self.current
+    }
+
+    /// Refresh when the epoch advanced or the cached set is older than `max_age`
+    pub fn needs_refresh(&self, epoch: u64) -> bool {
+        match self.refreshed_at {
+            None => true,
+            Some(at) => epoch != self.current.epoch || at.elapsed() >= self.max_age,
+        }
+    }
+
+    /// Replace the set with freshly fetched staker accounts, forgetting the
+    /// identities of stakers no longer in it, and notify subscribers
+    pub fn apply(&mut self, epoch: u64, accounts: Vec<(Pubkey, StakerInfo)>) {
+        let mut next = ValidatorSet {
+            epoch,
+            ..Default::default()
+        };
+
+        for (address, info) in accounts {
+            if info.staked_amount == 0 {
+                continue;
+            }
+            next.total_stake = next.total_stake.saturating_add(info.staked_amount);
+            next.stakers.insert(address, info.staked_amount);
+            match self.identities.get(&address) {
+                Some(identity) => {
+                    next.validators.insert(identity.clone(), info.staked_amount);
+                }
+                None => {
+                    next.unresolved.insert(address, info.staked_amount);
+                }
+            }
+        }
+
+        for (staker, stake) in &next.stakers {
+            let identity = self.identities.get(staker).cloned();
+            match self.current.stakers.get(staker) {
+                None => self.notify(ValidatorSetChange::Added { staker: *staker, identity, stake: *stake }),
+                Some(old) if old != stake => self.notify(ValidatorSetChange::StakeChanged {
+                    staker: *staker,
+                    identity,
+                    old_stake: *old,
+                    new_stake: *stake,
+                }),
+                Some(_) => {}
+            }
+        }
+        for staker in self.current.stakers.keys() {
+            if !next.stakers.contains_key(staker) {
+                let identity = self.identities.get(staker).cloned();
+                self.notify(ValidatorSetChange::Removed { staker: *staker, identity });
+            }
+        }
+
+        self.identities.retain(|_, identity| next.validators.contains_key(identity));
+
+        info!("🗳️  Validator set for epoch {}: {} matched, {} unmatched, total stake {}",
+            epoch, next.validators.len(), next.unresolved.len(), next.total_stake);
+
+        self.current = next;
+        self.refreshed_at = Some(Instant::now());
+    }
+
+    /// Match a validator identity to its staker account and return its stake,
+    /// if it has one in the current set. Anyone else is not remembered.
+    pub fn observe_identity(&mut self, identity: &str) -> Option<u64> {
+        if let Some(stake) = self.current.stake_of(identity) {
+            return Some(stake);
+        }
+
+        let pubkey = Pubkey::from_str(identity).ok()?;
+        let address = staker_info_address(&pubkey, &self.governance_program);
+        let stake = self.current.unresolved.remove(&address)?;
+        self.identities.insert(address, identity.to_string());
+        self.current.validators.insert(identity.to_string(), stake);
+        self.notify(ValidatorSetChange::Added { staker: address, identity: Some(identity.to_string()), stake });
+        Some(stake)
+    }
+
+    fn notify(&self, change: ValidatorSetChange) {
+        debug!("🗳️  Validator set change: {:?}", change);
+        // No subscribers is fine
+        let _ = self.changes.send(change);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn staker(amount: u64) -> StakerInfo {
+        StakerInfo {
+            staked_amount: amount,
+            last_stake_timestamp: 0,
+            bump: 255,
+            total_rewards_claimed: 0,
+            last_claim_timestamp: 0,
+            pending_rewards: 0,
+            compounded_rewards: 0,
+            uptime_score: 10000,
+            submissions_count: 0,
+            accurate_submissions: 0,
+            first_stake_timestamp: 0,
+            loyalty_tier: 0,
+            referrer: [0; 32],
+            referral_count: 0,
+            referral_rewards: 0,
+            vested_rewards: 0,
+            vesting_start: 0,
+        }
+    }
+
+    #[test]
+    fn test_decode_real_layout() {
+        let mut data = staker_info_discriminator().to_vec();
+        data.extend_from_slice(&1_000u64.to_le_bytes());   // staked_amount
+        data.extend_from_slice(&7i64.to_le_bytes());       // last_stake_timestamp
+        data.push(254);                                    // bump
+        data.extend_from_slice(&[0u8; 8 * 8]);             // rewards + performance + first_stake
+        data.push(2);                                      // loyalty_tier
+        data.extend_from_slice(&[9u8; 32]);                // referrer
+        data.extend_from_slice(&[0u8; 8 * 4]);             // referral + vesting
+        assert_eq!(data.len(), STAKER_INFO_LEN);
+
+        let info = StakerInfo::try_from_account_data(&data).unwrap();
+        assert_eq!(info.staked_amount, 1_000);
+        assert_eq!(info.bump, 254);
+        assert_eq!(info.loyalty_tier, 2);
+        assert_eq!(info.referrer, [9u8; 32]);
+
+        data[0] ^= 1;
+        assert!(StakerInfo::try_from_account_data(&data).is_err());
+    }
+
+    #[test]
+    fn test_decode_migrated_account() {
+        // `recover_old_stake` allocates 8 + size_of::<StakerInfo>(), padding included
+        let mut data = staker_info_discriminator().to_vec();
+        data.extend_from_slice(&5_000u64.to_le_bytes());
+        data.resize(8 + std::mem::size_of::<StakerInfo>(), 0);
+        assert!(data.len() > STAKER_INFO_LEN);
+
+        let info = StakerInfo::try_from_account_data(&data).unwrap();
+        assert_eq!(info.staked_amount, 5_000);
+
+        assert!(StakerInfo::try_from_account_data(&data[..STAKER_INFO_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn test_identities_resolve_and_prune() {
+        let program = Pubkey::new_unique();
+        let alice = Pubkey::new_unique();
+        let bob = Pubkey::new_unique();
+        let mut cache = ValidatorSetCache::new(program, Duration::from_secs(60));
+        let mut changes = cache.subscribe();
+        let (alice_staker, bob_staker) = (staker_info_address(&alice, &program), staker_info_address(&bob, &program));
+
+        // Not a staker yet: nothing to match, nothing remembered
+        assert_eq!(cache.observe_identity(&alice.to_string()), None);
+        assert!(cache.identities.is_empty());
+
+        cache.apply(1, vec![(alice_staker, staker(300)), (bob_staker, staker(100))]);
+        assert_eq!(cache.current().total_stake, 400);
+        assert_eq!(cache.current().unresolved.len(), 2);
+        let mut added = vec![changes.try_recv().unwrap(), changes.try_recv().unwrap()];
+        added.sort_by_key(|change| match change {
+            ValidatorSetChange::Added { stake, .. } => *stake,
+            _ => 0,
+        });
+        assert_eq!(added, vec![
+            ValidatorSetChange::Added { staker: bob_staker, identity: None, stake: 100 },
+            ValidatorSetChange::Added { staker: alice_staker, identity: None, stake: 300 },
+        ]);
+
+        // Both show up on gossip; identities without stake are ignored
+        assert_eq!(cache.observe_identity(&alice.to_string()), Some(300));
+        assert_eq!(cache.observe_identity(&bob.to_string()), Some(100));
+        assert_eq!(cache.observe_identity(&Pubkey::new_unique().to_string()), None);
+        assert!(cache.current().unresolved.is_empty());
+        assert_eq!(cache.identities.len(), 2);
+        assert_eq!(changes.try_recv().unwrap(), ValidatorSetChange::Added {
+            staker: alice_staker,
+            identity: Some(alice.to_string()),
+            stake: 300,
+        });
+        assert!(matches!(changes.try_recv().unwrap(), ValidatorSetChange::Added { staker, .. } if staker == bob_staker));
+        assert!(changes.try_recv().is_err());
+
+        // Next epoch: Alice unstaked and is forgotten, Bob stays matched
+        assert!(cache.needs_refresh(2));
+        cache.apply(2, vec![(bob_staker, staker(150))]);
+        assert_eq!(changes.try_recv().unwrap(), ValidatorSetChange::StakeChanged {
+            staker: bob_staker,
+            identity: Some(bob.to_string()),
+            old_stake: 100,
+            new_stake: 150,
+        });
+        assert_eq!(changes.try_recv().unwrap(), ValidatorSetChange::Removed {
+            staker: alice_staker,
+            identity: Some(alice.to_string()),
+        });
+        assert_eq!(cache.current().stake_of(&bob.to_string()), Some(150));
+        assert_eq!(cache.current().stake_of(&alice.to_string()), None);
+        assert_eq!(cache.identities.len(), 1);
+        assert!(!cache.needs_refresh(2));
+    }
+}
@@ -0,0 +1,306 @@
+// Exchange Streams - WebSocket ticker protocols of the built-in exchanges
+//
+// Each venue has its own URL, subscribe request, keepalive and message
+// format. Parsing turns a text frame into quote updates keyed by the venue's
+// symbol, plus the sequence number when the venue sends one.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use super::exchanges::Venue;
+use tachyon_merkle::Price;
+
+use super::source::{json_price, Ticker};
+
+/// Latest top of book and/or trade for one venue symbol
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickerUpdate {
+    pub venue_symbol: String,
+    /// Only the fields this message carries are set
+    pub ticker: Ticker,
+    pub sequence: Option<Sequence>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sequence {
+    /// Increases with every update; gaps are normal
+    Monotonic(u64),
+    /// Names the previous update, so a missed one can be detected
+    Chained { prev: u64, current: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamMessage {
+    Tickers(Vec<TickerUpdate>),
+    /// Keepalive from the venue, or the answer to our ping
+    Heartbeat,
+    /// Subscription acks and channels we don't read
+    Ignored,
+}
+
+impl Venue {
+    /// Symbol format on the stream, which can differ from the REST ticker
+    pub fn stream_symbol_format(&self) -> &'static str {
+        match self {
+            Venue::Kraken => "{base}/{quote}",
+            Venue::Coinbase | Venue::Okx => "{base}-{quote}",
+            Venue::Binance | Venue::Bybit => "{base}{quote}",
+        }
+    }
+
+    pub fn stream_url(&self, venue_symbols: &[String]) -> String {
+        match self {
+            // Binance subscribes through the URL
+            Venue::Binance => {
+                let streams: Vec<String> = venue_symbols.iter()
+                    .map(|symbol| format!("{}@bookTicker", symbol.to_lowercase()))
+                    .collect();
+                format!("wss://stream.binance.com:9443/stream?streams={}", streams.join("/"))
+            }
+            Venue::Coinbase => "wss://ws-feed.exchange.coinbase.com".to_string(),
+            Venue::Kraken => "wss://ws.kraken.com/v2".to_string(),
+            Venue::Okx => "wss://ws.okx.com:8443/ws/v5/public".to_string(),
+            Venue::Bybit => "wss://stream.bybit.com/v5/public/spot".to_string(),
+        }
+    }
+
+    /// Frames to send after connecting
+    pub fn subscribe_messages(&self, venue_symbols: &[String]) -> Vec<String> {
+        let message = match self {
+            Venue::Binance => return vec![],
+            Venue::Coinbase => json!({
+                "type": "subscribe",
+                "product_ids": venue_symbols,
+                "channels": ["ticker", "heartbeat"],
+            }),
+            Venue::Kraken => json!({
+                "method": "subscribe",
+                "params": {"channel": "ticker", "symbol": venue_symbols},
+            }),
+            Venue::Okx => json!({
+                "op": "subscribe",
+                "args": venue_symbols.iter()
+                    .map(|symbol| json!({"channel": "bbo-tbt", "instId": symbol}))
+                    .collect::<Vec<_>>(),
+            }),
+            Venue::Bybit => json!({
+                "op": "subscribe",
+                "args": venue_symbols.iter().map(|symbol| format!("tickers.{}", symbol)).collect::<Vec<_>>(),
+            }),
+        };
+        vec![message.to_string()]
+    }
+
+    /// Application-level ping; venues without one get a WebSocket ping frame
+    pub fn ping_message(&self) -> Option<String> {
+        match self {
+            Venue::Okx => Some("ping".to_string()),
+            Venue::Bybit => Some(json!({"op": "ping"}).to_string()),
+            Venue::Binance | Venue::Coinbase | Venue::Kraken => None,
+        }
+    }
+
+    pub fn parse_stream_message(&self, text: &str) -> Result<StreamMessage> {
+        // OKX answers its text ping with a bare "pong"
+        if text == "pong" {
+            return Ok(StreamMessage::Heartbeat);
+        }
+
+        let message: Value = serde_json::from_str(text)?;
+        match self {
+            Venue::Binance => parse_binance(&message),
+            Venue::Coinbase => parse_coinbase(&message),
+            Venue::Kraken => parse_kraken(&message),
+            Venue::Okx => parse_okx(&message),
+            Venue::Bybit => parse_bybit(&message),
+        }
+    }
+}
+
+/// Exact price from a number or numeric string
+fn price(value: &Value) -> Option<Price> {
+    json_price(value).ok()
+}
+
+/// Number or numeric string
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+fn integer(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(number) => number.as_u64(),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+fn parse_binance(message: &Value) -> Result<StreamMessage> {
+    // {"stream": "btcusdt@bookTicker", "data": {"u": 400900217, "s": "BTCUSDT", "b": "...", "a": "..."}}
+    let Some(data) = message.get("data") else {
+        return Ok(StreamMessage::Ignored);
+    };
+    let Some(venue_symbol) = text(&data["s"]) else {
+        return Ok(StreamMessage::Ignored);
+    };
+
+    Ok(StreamMessage::Tickers(vec![TickerUpdate {
+        venue_symbol,
+        ticker: Ticker { bid: price(&data["b"]), ask: price(&data["a"]), ..Ticker::default() },
+        sequence: integer(&data["u"]).map(Sequence::Monotonic),
+    }]))
+}
+
+fn parse_coinbase(message: &Value) -> Result<StreamMessage> {
+    match message["type"].as_str() {
+        Some("ticker") => Ok(StreamMessage::Tickers(vec![TickerUpdate {
+            venue_symbol: text(&message["product_id"]).unwrap_or_default(),
+            ticker: Ticker {
+                bid: price(&message["best_bid"]),
+                ask: price(&message["best_ask"]),
+                last: price(&message["price"]),
+                volume_24h: number(&message["volume_24h"]),
+            },
+            sequence: integer(&message["sequence"]).map(Sequence::Monotonic),
+        }])),
+        Some("heartbeat") => Ok(StreamMessage::Heartbeat),
+        Some("error") => Err(anyhow::anyhow!("Coinbase stream error: {}", message["message"])),
+        _ => Ok(StreamMessage::Ignored),
+    }
+}
+
+fn parse_kraken(message: &Value) -> Result<StreamMessage> {
+    if message["success"] == Value::Bool(false) {
+        return Err(anyhow::anyhow!("Kraken stream error: {}", message["error"]));
+    }
+
+    match message["channel"].as_str() {
+        Some("ticker") => {
+            let tickers = message["data"].as_array().map(Vec::as_slice).unwrap_or_default();
+            Ok(StreamMessage::Tickers(tickers.iter()
+                .filter_map(|ticker| Some(TickerUpdate {
+                    venue_symbol: text(&ticker["symbol"])?,
+                    ticker: Ticker {
+                        bid: price(&ticker["bid"]),
+                        ask: price(&ticker["ask"]),
+                        last: price(&ticker["last"]),
+                        volume_24h: number(&ticker["volume"]),
+                    },
+                    sequence: None,
+                }))
+                .collect()))
+        }
+        Some("heartbeat") => Ok(StreamMessage::Heartbeat),
+        _ => Ok(StreamMessage::Ignored),
+    }
+}
+
+fn parse_okx(message: &Value) -> Result<StreamMessage> {
+    if message["event"] == "error" {
+        return Err(anyhow::anyhow!("OKX stream error {}: {}", message["code"], message["msg"]));
+    }
+    let Some(venue_symbol) = text(&message["arg"]["instId"]) else {
+        return Ok(StreamMessage::Ignored);
+    };
+    let Some(books) = message["data"].as_array() else {
+        return Ok(StreamMessage::Ignored);
+    };
+
+    // Levels are [price, size, _, orders]
+    Ok(StreamMessage::Tickers(books.iter()
+        .map(|book| TickerUpdate {
+            venue_symbol: venue_symbol.clone(),
+            ticker: Ticker {
+                bid: price(&book["bids"][0][0]),
+                ask: price(&book["asks"][0][0]),
+                ..Ticker::default()
+            },
+            sequence: match (integer(&book["prevSeqId"]), integer(&book["seqId"])) {
+                (Some(prev), Some(current)) => Some(Sequence::Chained { prev, current }),
+                (None, Some(current)) => Some(Sequence::Monotonic(current)),
+                _ => None,
+            },
+        })
+        .collect()))
+}
+
+fn parse_bybit(message: &Value) -> Result<StreamMessage> {
+    match message["op"].as_str() {
+        Some("ping") | Some("pong") => return Ok(StreamMessage::Heartbeat),
+        Some(_) if message["success"] == Value::Bool(false) => {
+            return Err(anyhow::anyhow!("Bybit stream error: {}", message["ret_msg"]));
+        }
+        Some(_) => return Ok(StreamMessage::Ignored),
+        None => {}
+    }
+
+    let data = &message["data"];
+    let Some(venue_symbol) = text(&data["symbol"]) else {
+        return Ok(StreamMessage::Ignored);
+    };
+
+    Ok(StreamMessage::Tickers(vec![TickerUpdate {
+        venue_symbol,
+        // Deltas after the snapshot only carry the fields that changed
+        ticker: Ticker {
+            bid: price(&data["bid1Price"]),
+            ask: price(&data["ask1Price"]),
+            last: price(&data["lastPrice"]),
+            volume_24h: number(&data["volume24h"]),
+        },
+        sequence: integer(&message["cs"]).map(Sequence::Monotonic),
+    }]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::source::px;
+
+    fn tickers(venue: Venue, text: &str) -> Vec<TickerUpdate> {
+        match venue.parse_stream_message(text).unwrap() {
+            StreamMessage::Tickers(tickers) => tickers,
+            other => panic!("expected tickers, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_stream_messages() {
+        let binance = tickers(Venue::Binance, r#"{"stream":"btcusdt@bookTicker","data":{"u":400900217,"s":"BTCUSDT","b":"50000.10","B":"1.2","a":"50000.30","A":"0.4"}}"#);
+        assert_eq!((binance[0].ticker.bid, binance[0].ticker.ask), (Some(px("50000.10")), Some(px("50000.30"))));
+        assert_eq!(binance[0].sequence, Some(Sequence::Monotonic(400900217)));
+
+        let coinbase = tickers(Venue::Coinbase, r#"{"type":"ticker","sequence":37475248783,"product_id":"ETH-USD","price":"3000.5","best_bid":"3000.4","best_ask":"3000.6","volume_24h":"12000.5"}"#);
+        assert_eq!((coinbase[0].venue_symbol.as_str(), coinbase[0].ticker.last), ("ETH-USD", Some(px("3000.5"))));
+        assert_eq!(coinbase[0].ticker.volume_24h, Some(12000.5));
+
+        let kraken = tickers(Venue::Kraken, r#"{"channel":"ticker","type":"update","data":[{"symbol":"BTC/USD","bid":49999.9,"ask":50000.1,"last":50000.0,"volume":250.5}]}"#);
+        assert_eq!((kraken[0].ticker.last, kraken[0].ticker.volume_24h), (Some(px("50000")), Some(250.5)));
+
+        let okx = tickers(Venue::Okx, r#"{"arg":{"channel":"bbo-tbt","instId":"BTC-USDT"},"data":[{"asks":[["8476.98","415","0","13"]],"bids":[["8476.97","256","0","12"]],"ts":"1597026383085","seqId":124,"prevSeqId":123}]}"#);
+        assert_eq!(okx[0].sequence, Some(Sequence::Chained { prev: 123, current: 124 }));
+
+        let bybit = tickers(Venue::Bybit, r#"{"topic":"tickers.BTCUSDT","ts":1673853746003,"type":"snapshot","cs":2588407389,"data":{"symbol":"BTCUSDT","lastPrice":"21109.77","volume24h":"6780.8"}}"#);
+        assert_eq!((bybit[0].ticker.last, bybit[0].ticker.bid), (Some(px("21109.77")), None));
+        assert_eq!(bybit[0].ticker.volume_24h, Some(6780.8));
+    }
+
+    #[test]
+    fn test_keepalive_and_errors() {
+        assert_eq!(Venue::Okx.parse_stream_message("pong").unwrap(), StreamMessage::Heartbeat);
+        assert_eq!(Venue::Coinbase.parse_stream_message(r#"{"type":"heartbeat","sequence":90}"#).unwrap(), StreamMessage::Heartbeat);
+        assert_eq!(Venue::Bybit.parse_stream_message(r#"{"success":true,"ret_msg":"pong","op":"ping"}"#).unwrap(), StreamMessage::Heartbeat);
+        assert_eq!(Venue::Coinbase.parse_stream_message(r#"{"type":"subscriptions","channels":[]}"#).unwrap(), StreamMessage::Ignored);
+
+        assert!(Venue::Okx.parse_stream_message(r#"{"event":"error","code":"60012","msg":"Invalid request"}"#).is_err());
+        assert!(Venue::Bybit.parse_stream_message(r#"{"success":false,"ret_msg":"invalid topic","op":"subscribe"}"#).is_err());
+    }
+}
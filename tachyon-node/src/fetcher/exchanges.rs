@@ -0,0 +1,359 @@
+// Built-in exchange sources
+//
+// Each venue keeps its own URL, symbol format, rate limit, API key header and
+// response format. Any of them can be replaced from config by defining a
+// source with the same name.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::config::ExchangeConfig;
+use super::source::{PriceSource, RateLimiter, SourceAnswer, SymbolMap, Ticker};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Venue {
+    Binance,
+    Coinbase,
+    Kraken,
+    Okx,
+    Bybit,
+}
+
+impl Venue {
+    pub const ALL: [Venue; 5] = [Venue::Binance, Venue::Coinbase, Venue::Kraken, Venue::Okx, Venue::Bybit];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Venue::Binance => "binance",
+            Venue::Coinbase => "coinbase",
+            Venue::Kraken => "kraken",
+            Venue::Okx => "okx",
+            Venue::Bybit => "bybit",
+        }
+    }
+
+    fn url(&self, venue_symbol: &str) -> String {
+        match self {
+            Venue::Binance => format!("https://api.binance.com/api/v3/ticker/24hr?symbol={}", venue_symbol),
+            Venue::Coinbase => format!("https://api.exchange.coinbase.com/products/{}/ticker", venue_symbol),
+            Venue::Kraken => format!("https://api.kraken.com/0/public/Ticker?pair={}", venue_symbol),
+            Venue::Okx => format!("https://www.okx.com/api/v5/market/ticker?instId={}", venue_symbol),
+            Venue::Bybit => format!("https://api.bybit.com/v5/market/tickers?category=spot&symbol={}", venue_symbol),
+        }
+    }
+
+    fn symbol_format(&self) -> &'static str {
+        match self {
+            Venue::Coinbase | Venue::Okx => "{base}-{quote}",
+            Venue::Binance | Venue::Kraken | Venue::Bybit => "{base}{quote}",
+        }
+    }
+
+    /// Quote currencies listed under another name: the offshore venues quote
+    /// dollar markets in USDT
+    pub fn listed_quotes(&self) -> HashMap<String, String> {
+        match self {
+            Venue::Binance | Venue::Okx | Venue::Bybit => HashMap::from([("USD".to_string(), "USDT".to_string())]),
+            Venue::Coinbase | Venue::Kraken => HashMap::new(),
+        }
+    }
+
+    /// Public rate limits, with headroom
+    fn rate_limit_per_sec(&self) -> f64 {
+        match self {
+            Venue::Binance => 20.0,
+            Venue::Coinbase => 10.0,
+            Venue::Kraken => 1.0,
+            Venue::Okx => 10.0,
+            Venue::Bybit => 10.0,
+        }
+    }
+
+    /// Header carrying the API key, for venues we hold a key for
+    fn api_key(&self, keys: &ExchangeConfig) -> Option<(&'static str, String)> {
+        match self {
+            Venue::Binance => keys.binance_api_key.clone().map(|key| ("X-MBX-APIKEY", key)),
+            Venue::Coinbase => keys.coinbase_api_key.clone().map(|key| ("CB-ACCESS-KEY", key)),
+            Venue::Kraken => keys.kraken_api_key.clone().map(|key| ("API-Key", key)),
+            Venue::Okx | Venue::Bybit => None,
+        }
+    }
+
+    fn parse_ticker(&self, body: &[u8]) -> Result<Ticker> {
+        match self {
+            Venue::Binance => parse_binance(body),
+            Venue::Coinbase => parse_coinbase(body),
+            Venue::Kraken => parse_kraken(body),
+            Venue::Okx => parse_okx(body),
+            Venue::Bybit => parse_bybit(body),
+        }
+    }
+}
+
+/// REST ticker of a built-in exchange
+pub struct ExchangeSource {
+    venue: Venue,
+    client: reqwest::Client,
+    symbols: SymbolMap,
+    limiter: RateLimiter,
+    api_key: Option<(&'static str, String)>,
+}
+
+impl ExchangeSource {
+    pub fn new(venue: Venue, client: reqwest::Client, keys: &ExchangeConfig) -> Self {
+        Self {
+            venue,
+            client,
+            symbols: SymbolMap::new(venue.symbol_format()).with_quotes(venue.listed_quotes()),
+            limiter: RateLimiter::new(venue.rate_limit_per_sec()),
+            api_key: venue.api_key(keys),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceSource for ExchangeSource {
+    fn name(&self) -> &str {
+        self.venue.name()
+    }
+
+    async fn fetch_ticker(&self, symbol: &str) -> Result<Ticker> {
+        self.fetch_answer(symbol).await.ticker
+    }
+
+    async fn fetch_answer(&self, symbol: &str) -> SourceAnswer {
+        self.limiter.acquire().await;
+
+        let mut request = self.client.get(self.venue.url(&self.symbols.map(symbol)));
+        if let Some((header, key)) = &self.api_key {
+            request = request.header(*header, key);
+        }
+
+        let body = match request.send().await {
+            Ok(response) => response.bytes().await,
+            Err(e) => Err(e),
+        };
+        match body {
+            Ok(body) => SourceAnswer {
+                ticker: self.venue.parse_ticker(&body),
+                body: Some(String::from_utf8_lossy(&body).into_owned()),
+            },
+            Err(e) => SourceAnswer::failed(e.into()),
+        }
+    }
+
+    fn listed_quote(&self, quote: &str) -> String {
+        self.symbols.listed_quote(quote)
+    }
+}
+
+/// One source per built-in exchange
+pub fn builtin_sources(client: &reqwest::Client, keys: &ExchangeConfig) -> Vec<Arc<dyn PriceSource>> {
+    Venue::ALL.iter()
+        .map(|venue| Arc::new(ExchangeSource::new(*venue, client.clone(), keys)) as Arc<dyn PriceSource>)
+        .collect()
+}
+
+/// Optional numeric string field: an exact price, or a volume
+fn field<T: FromStr>(value: &Option<String>) -> Option<T> {
+    value.as_deref().and_then(|v| v.parse().ok())
+}
+
+fn parse_binance(body: &[u8]) -> Result<Ticker> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct BinanceResponse {
+        last_price: Option<String>,
+        bid_price: Option<String>,
+        ask_price: Option<String>,
+        volume: Option<String>,
+        code: Option<i32>,
+        msg: Option<String>,
+    }
+
+    let data: BinanceResponse = serde_json::from_slice(body)?;
+
+    // Check for error response (geo-blocking, etc.)
+    if let Some(code) = data.code {
+        return Err(anyhow::anyhow!("Binance API error {}: {}", code, data.msg.unwrap_or_default()));
+    }
+
+    let price_str = data.last_price.as_deref().ok_or_else(|| anyhow::anyhow!("Missing price field"))?;
+    Ok(Ticker {
+        last: Some(price_str.parse()?),
+        bid: field(&data.bid_price),
+        ask: field(&data.ask_price),
+        volume_24h: field(&data.volume),
+    })
+}
+
+fn parse_coinbase(body: &[u8]) -> Result<Ticker> {
+    #[derive(Deserialize)]
+    struct CoinbaseResponse {
+        price: Option<String>,
+        bid: Option<String>,
+        ask: Option<String>,
+        volume: Option<String>,
+        message: Option<String>,
+    }
+
+    let data: CoinbaseResponse = serde_json::from_slice(body)?;
+
+    let Some(price_str) = data.price.as_deref() else {
+        return Err(anyhow::anyhow!("Coinbase API error: {}", data.message.unwrap_or_default()));
+    };
+    Ok(Ticker {
+        last: Some(price_str.parse()?),
+        bid: field(&data.bid),
+        ask: field(&data.ask),
+        volume_24h: field(&data.volume),
+    })
+}
+
+fn parse_kraken(body: &[u8]) -> Result<Ticker> {
+    #[derive(Deserialize)]
+    struct KrakenResult {
+        a: Vec<String>, // Ask [price, whole lot volume, lot volume]
+        b: Vec<String>, // Bid [price, whole lot volume, lot volume]
+        c: Vec<String>, // Last trade closed array [price, lot volume]
+        v: Vec<String>, // Volume [today, last 24 hours]
+    }
+
+    #[derive(Deserialize)]
+    struct KrakenResponse {
+        result: HashMap<String, KrakenResult>,
+    }
+
+    let data: KrakenResponse = serde_json::from_slice(body)?;
+
+    if let Some((_, result)) = data.result.iter().next() {
+        if let Some(price_str) = result.c.first() {
+            return Ok(Ticker {
+                last: Some(price_str.parse()?),
+                bid: field(&result.b.first().cloned()),
+                ask: field(&result.a.first().cloned()),
+                volume_24h: field(&result.v.get(1).cloned()),
+            });
+        }
+    }
+
+    Err(anyhow::anyhow!("Failed to parse Kraken response"))
+}
+
+fn parse_okx(body: &[u8]) -> Result<Ticker> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct OkxData {
+        last: String,
+        bid_px: Option<String>,
+        ask_px: Option<String>,
+        vol24h: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct OkxResponse {
+        code: String,
+        data: Vec<OkxData>,
+    }
+
+    let data: OkxResponse = serde_json::from_slice(body)?;
+
+    if data.code != "0" {
+        return Err(anyhow::anyhow!("OKX API error: code {}", data.code));
+    }
+
+    if let Some(ticker) = data.data.first() {
+        return Ok(Ticker {
+            last: Some(ticker.last.parse()?),
+            bid: field(&ticker.bid_px),
+            ask: field(&ticker.ask_px),
+            volume_24h: field(&ticker.vol24h),
+        });
+    }
+
+    Err(anyhow::anyhow!("Failed to parse OKX response"))
+}
+
+fn parse_bybit(body: &[u8]) -> Result<Ticker> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct BybitTicker {
+        last_price: String,
+        bid1_price: Option<String>,
+        ask1_price: Option<String>,
+        volume24h: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct BybitResult {
+        list: Vec<BybitTicker>,
+    }
+
+    #[derive(Deserialize)]
+    struct BybitResponse {
+        #[serde(rename = "retCode")]
+        ret_code: i32,
+        result: BybitResult,
+    }
+
+    let data: BybitResponse = serde_json::from_slice(body)?;
+
+    if data.ret_code != 0 {
+        return Err(anyhow::anyhow!("Bybit API error: code {}", data.ret_code));
+    }
+
+    if let Some(ticker) = data.result.list.first() {
+        return Ok(Ticker {
+            last: Some(ticker.last_price.parse()?),
+            bid: field(&ticker.bid1_price),
+            ask: field(&ticker.ask1_price),
+            volume_24h: field(&ticker.volume24h),
+        });
+    }
+
+    Err(anyhow::anyhow!("Failed to parse Bybit response"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::source::px;
+
+    #[test]
+    fn test_parse_venue_responses() {
+        let binance = Venue::Binance.parse_ticker(br#"{"symbol":"BTCUSDT","lastPrice":"50000.10","bidPrice":"50000.00","askPrice":"50000.20","volume":"1234.5"}"#).unwrap();
+        assert_eq!((binance.last, binance.mid(), binance.volume_24h), (Some(px("50000.1")), Some(px("50000.1")), Some(1234.5)));
+        assert_eq!(binance.last.unwrap().expo, -2);
+        assert!(Venue::Binance.parse_ticker(br#"{"code":0,"msg":"restricted location"}"#).is_err());
+
+        let coinbase = Venue::Coinbase.parse_ticker(br#"{"ask":"3000.6","bid":"3000.4","volume":"5000","trade_id":1,"price":"3000.5","size":"0.1"}"#).unwrap();
+        assert_eq!((coinbase.last, coinbase.half_spread()), (Some(px("3000.5")), Some(px("0.1"))));
+        assert!(Venue::Coinbase.parse_ticker(br#"{"message":"NotFound"}"#).is_err());
+
+        let kraken = Venue::Kraken.parse_ticker(br#"{"error":[],"result":{"XXBTZUSD":{"a":["50000.1","1","1.0"],"b":["49999.9","2","2.0"],"c":["49999.9","0.1"],"v":["100.0","250.5"]}}}"#).unwrap();
+        assert_eq!((kraken.last, kraken.mid(), kraken.volume_24h), (Some(px("49999.9")), Some(px("50000")), Some(250.5)));
+
+        assert!(Venue::Okx.parse_ticker(br#"{"code":"51001","data":[]}"#).is_err());
+        let okx = Venue::Okx.parse_ticker(br#"{"code":"0","data":[{"last":"150.2","bidPx":"150.1","askPx":"150.3","vol24h":"9000"}]}"#).unwrap();
+        assert_eq!(okx.volume_24h, Some(9000.0));
+
+        let bybit = Venue::Bybit.parse_ticker(br#"{"retCode":0,"result":{"list":[{"lastPrice":"150.25","bid1Price":"150.2","ask1Price":"150.3","volume24h":"700"}]}}"#).unwrap();
+        assert_eq!((bybit.last, bybit.bid, bybit.volume_24h), (Some(px("150.25")), Some(px("150.2")), Some(700.0)));
+        assert!(Venue::Bybit.parse_ticker(br#"{"retCode":0,"result":{"list":[{"lastPrice":"1.5e","volume24h":"700"}]}}"#).is_err());
+    }
+
+    #[test]
+    fn test_venue_symbols() {
+        assert_eq!(SymbolMap::new(Venue::Coinbase.symbol_format()).map("BTC/USD"), "BTC-USD");
+        assert_eq!(SymbolMap::new(Venue::Binance.symbol_format()).map("BTC/USDT"), "BTCUSDT");
+
+        let keys = ExchangeConfig { binance_api_key: None, coinbase_api_key: None, kraken_api_key: None, streaming: false };
+        let binance = ExchangeSource::new(Venue::Binance, reqwest::Client::new(), &keys);
+        let kraken = ExchangeSource::new(Venue::Kraken, reqwest::Client::new(), &keys);
+        assert_eq!((binance.listed_quote("USD"), kraken.listed_quote("USD")), ("USDT".to_string(), "USD".to_string()));
+    }
+}
@@ -0,0 +1,231 @@
+// JSON-over-HTTP price source
+//
+// Covers most REST tickers without code: GET a URL with the venue symbol
+// substituted for `{symbol}` and read the price at a path such as
+// "data.amount", "data[0].last" or "result.*.c[0]" ("*" takes the first
+// member of an object or array). Bid, ask and 24h volume are read the same
+// way when configured. Values may be JSON numbers or strings.
+
+use std::collections::HashMap;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use tachyon_merkle::Price;
+
+use super::source::{json_price, PriceSource, RateLimiter, SourceAnswer, SymbolMap, Ticker};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+    /// First member of an object or array
+    Any,
+}
+
+/// Parse a price path like "result.*.c[0]"; a leading "$." is ignored
+pub fn parse_path(path: &str) -> Result<Vec<PathSegment>> {
+    let path = path.strip_prefix("$.").unwrap_or(path);
+    let mut segments = Vec::new();
+
+    for part in path.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(at) => part.split_at(at),
+            None => (part, ""),
+        };
+
+        match key {
+            "" if rest.is_empty() => return Err(anyhow::anyhow!("Empty segment in price path: {}", path)),
+            "" => {}
+            "*" => segments.push(PathSegment::Any),
+            key => segments.push(PathSegment::Key(key.to_string())),
+        }
+
+        while !rest.is_empty() {
+            let close = rest.find(']')
+                .ok_or_else(|| anyhow::anyhow!("Unclosed '[' in price path: {}", path))?;
+            let index = &rest[1..close];
+            segments.push(match index {
+                "*" => PathSegment::Any,
+                index => PathSegment::Index(index.parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid index '{}' in price path: {}", index, path))?),
+            });
+            rest = &rest[close + 1..];
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Follow `path` into `value` and read a number or numeric string exactly
+pub fn extract_price(value: &Value, path: &[PathSegment]) -> Result<Price> {
+    let mut current = value;
+    for segment in path {
+        let next = match (segment, current) {
+            (PathSegment::Key(key), Value::Object(map)) => map.get(key),
+            (PathSegment::Index(index), Value::Array(items)) => items.get(*index),
+            (PathSegment::Any, Value::Object(map)) => map.values().next(),
+            (PathSegment::Any, Value::Array(items)) => items.first(),
+            _ => None,
+        };
+        current = next.ok_or_else(|| anyhow::anyhow!("Price path {:?} not found in response", segment))?;
+    }
+
+    json_price(current)
+}
+
+/// Where each ticker field is found in a response
+#[derive(Debug, Clone)]
+pub struct TickerPaths {
+    pub price: Vec<PathSegment>,
+    pub bid: Option<Vec<PathSegment>>,
+    pub ask: Option<Vec<PathSegment>>,
+    pub volume: Option<Vec<PathSegment>>,
+}
+
+impl TickerPaths {
+    pub fn parse(price: &str, bid: Option<&str>, ask: Option<&str>, volume: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            price: parse_path(price)?,
+            bid: bid.map(parse_path).transpose()?,
+            ask: ask.map(parse_path).transpose()?,
+            volume: volume.map(parse_path).transpose()?,
+        })
+    }
+
+    /// The price is required; other fields are read when present
+    pub fn extract(&self, value: &Value) -> Result<Ticker> {
+        let optional = |path: &Option<Vec<PathSegment>>| path.as_ref()
+            .and_then(|path| extract_price(value, path).ok());
+
+        Ok(Ticker {
+            last: Some(extract_price(value, &self.price)?),
+            bid: optional(&self.bid),
+            ask: optional(&self.ask),
+            volume_24h: optional(&self.volume).map(Price::to_f64),
+        })
+    }
+}
+
+pub struct JsonHttpSource {
+    name: String,
+    client: reqwest::Client,
+    url: String,
+    paths: TickerPaths,
+    headers: HashMap<String, String>,
+    symbols: SymbolMap,
+    limiter: RateLimiter,
+}
+
+impl JsonHttpSource {
+    pub fn new(
+        name: &str,
+        client: reqwest::Client,
+        url: &str,
+        paths: TickerPaths,
+        headers: HashMap<String, String>,
+        symbols: SymbolMap,
+        limiter: RateLimiter,
+    ) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            client,
+            url: url.to_string(),
+            paths,
+            headers,
+            symbols,
+            limiter,
+        })
+    }
+}
+
+#[async_trait]
+impl PriceSource for JsonHttpSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch_ticker(&self, symbol: &str) -> Result<Ticker> {
+        self.fetch_answer(symbol).await.ticker
+    }
+
+    async fn fetch_answer(&self, symbol: &str) -> SourceAnswer {
+        self.limiter.acquire().await;
+
+        let url = self.url.replace("{symbol}", &self.symbols.map(symbol));
+        let mut request = self.client.get(&url);
+        for (header, value) in &self.headers {
+            request = request.header(header, value);
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => return SourceAnswer::failed(e.into()),
+        };
+        // An error status still has a body worth keeping
+        let status = response.status();
+        let body = match response.text().await {
+            Ok(body) => body,
+            Err(e) => return SourceAnswer::failed(e.into()),
+        };
+        let ticker = if status.is_success() {
+            serde_json::from_str::<Value>(&body)
+                .map_err(anyhow::Error::from)
+                .and_then(|value| self.paths.extract(&value))
+        } else {
+            Err(anyhow::anyhow!("HTTP status {} for url ({})", status, url))
+        };
+
+        SourceAnswer { body: Some(body), ticker }
+    }
+
+    fn listed_quote(&self, quote: &str) -> String {
+        self.symbols.listed_quote(quote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::fetcher::source::px;
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(parse_path("$.data[0].last").unwrap(), vec![
+            PathSegment::Key("data".to_string()),
+            PathSegment::Index(0),
+            PathSegment::Key("last".to_string()),
+        ]);
+        assert_eq!(parse_path("result.*.c[0]").unwrap()[1], PathSegment::Any);
+        assert!(parse_path("data..last").is_err());
+        assert!(parse_path("data[x]").is_err());
+        assert!(parse_path("data[0").is_err());
+    }
+
+    #[test]
+    fn test_extract_price() {
+        let kraken = json!({"error": [], "result": {"XXBTZUSD": {"c": ["49999.9", "0.1"]}}});
+        assert_eq!(extract_price(&kraken, &parse_path("result.*.c[0]").unwrap()).unwrap(), px("49999.9"));
+
+        let coingecko = json!({"bitcoin": {"usd": 50123.5}});
+        assert_eq!(extract_price(&coingecko, &parse_path("*.usd").unwrap()).unwrap(), px("50123.5"));
+
+        assert!(extract_price(&coingecko, &parse_path("bitcoin.eur").unwrap()).is_err());
+        assert!(extract_price(&json!({"price": true}), &parse_path("price").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_ticker_paths() {
+        let okx = json!({"code": "0", "data": [{"last": "3000.5", "bidPx": "3000.4", "askPx": "3000.6", "vol24h": "1200"}]});
+        let paths = TickerPaths::parse("data[0].last", Some("data[0].bidPx"), Some("data[0].askPx"), Some("data[0].vol24h")).unwrap();
+        let ticker = paths.extract(&okx).unwrap();
+        assert_eq!(ticker.last, Some(px("3000.5")));
+        assert_eq!(ticker.mid(), Some(px("3000.5")));
+        assert_eq!(ticker.volume_24h, Some(1200.0));
+
+        // A missing optional field is left out; a missing price is an error
+        let no_book = TickerPaths::parse("data[0].last", Some("data[0].bid"), None, None).unwrap();
+        assert_eq!(no_book.extract(&okx).unwrap().bid, None);
+        assert!(TickerPaths::parse("data[0].price", None, None, None).unwrap().extract(&okx).is_err());
+    }
+}
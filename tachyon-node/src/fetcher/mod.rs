@@ -2,20 +2,50 @@ use std::sync::Arc;
 use solana_sdk::signer::Signer;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc;
-use tokio::time::{interval, Duration};
-use tracing::{info, warn, error};
+use futures::future::join_all;
+use tachyon_merkle::price::{Price, MAX_EXPO, MIN_EXPO};
+use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use tracing::{debug, info, warn, error};
 
-use crate::config::NodeConfig;
+use crate::config::{AssetConfig, NodeConfig};
+use crate::replay::recording::Recorder;
 
 // Robust fetcher with outlier detection, circuit breaker, retry logic
 pub mod robust_fetcher;
 
+// Mid, VWAP, TWAP and volume-weighted median, spread-based confidence
+pub mod pricing;
+
+// Price sources: trait + registry, built-in exchanges, config-defined sources
+pub mod source;
+pub mod exchanges;
+pub mod json_http;
+pub mod subprocess;
+
+// Exchange WebSocket streams in front of the REST tickers
+pub mod exchange_streams;
+pub mod streaming;
+
+// Per-source latency and success counters
+pub mod stats;
+
+// Stablecoin rates and conversion of venue quotes into the asset's currency
+pub mod stablecoins;
+
+use robust_fetcher::{PriceData, RobustFetcher};
+use source::{split_symbol, SourceRegistry};
+use stablecoins::QuoteNormalizer;
+use stats::{FetchOutcome, FetcherStats};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PriceUpdate {
     pub asset: String,
-    pub price: f64,
-    pub confidence: f64,
+    /// At the asset's configured exponent
+    pub price: Price,
+    /// Confidence interval around `price`, at the same exponent
+    pub confidence: Price,
     pub timestamp: i64,
     pub exchange: String,
     pub node_pubkey: String,
@@ -24,52 +54,82 @@ pub struct PriceUpdate {
 pub async fn start_price_fetcher(
     config: Arc<NodeConfig>,
     price_tx: mpsc::Sender<PriceUpdate>,
+    gossip_tx: mpsc::Sender<PriceUpdate>,
+    stats: Arc<RwLock<FetcherStats>>,
+    recorder: Option<Arc<Recorder>>,
     mut shutdown: tokio::sync::broadcast::Receiver<()>,
 ) -> Result<()> {
     info!("📊 Starting price fetcher...");
     info!("📊 Configured assets: {:?}", config.assets.iter().map(|a| &a.symbol).collect::<Vec<_>>());
     info!("📊 Update interval: {}ms", config.update_interval_ms);
     
+    let mut sources = SourceRegistry::from_config(&config)?;
+    info!("📊 Price sources: {:?}", sources.names());
+    
+    // A stablecoin rate must come from markets listed in the target currency
+    for feed in &config.stablecoins.feeds {
+        for exchange in &feed.exchanges {
+            let listed = sources.listed_symbol(exchange, &feed.symbol);
+            if listed != feed.symbol {
+                return Err(anyhow::anyhow!("{} cannot be fetched from {}: it lists {}", feed.symbol, exchange, listed));
+            }
+        }
+    }
+    info!("📊 Stablecoin feeds: {:?}", config.stablecoins.feeds.iter().map(|f| &f.symbol).collect::<Vec<_>>());
+    
+    for asset in config.stablecoins.feeds.iter().chain(&config.assets) {
+        if !(MIN_EXPO..=MAX_EXPO).contains(&asset.expo) {
+            return Err(anyhow::anyhow!("{} has expo {}, outside {}..={}", asset.symbol, asset.expo, MIN_EXPO, MAX_EXPO));
+        }
+    }
+    
+    let streams = if config.exchanges.streaming {
+        streaming::start_streams(&config, &mut sources)
+    } else {
+        Vec::new()
+    };
+    info!("📡 Exchange streams: {}", streams.len());
+    
+    let all_assets: Vec<AssetConfig> = config.stablecoins.feeds.iter().chain(&config.assets).cloned().collect();
+    let fetcher = PriceFetcher {
+        assets: Arc::new(config.assets.clone()),
+        stablecoins: Arc::new(config.stablecoins.feeds.clone()),
+        robust: Arc::new(RobustFetcher::new(Arc::new(sources), &all_assets)),
+        normalizer: Arc::new(QuoteNormalizer::new(config.stablecoins.depeg_threshold_bps)),
+        stats: Arc::clone(&stats),
+        price_tx,
+        gossip_tx: Some(gossip_tx),
+        node_pubkey: config.identity.pubkey().to_string(),
+        deadline: fetch_deadline(config.update_interval_ms),
+        recorder,
+    };
+    info!("📊 Per-source deadline: {}ms", fetcher.deadline.as_millis());
+    
     let mut ticker = interval(Duration::from_millis(config.update_interval_ms));
-    let node_pubkey = config.identity.pubkey().to_string();
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut in_flight: Option<JoinHandle<()>> = None;
     
     loop {
         tokio::select! {
             _ = ticker.tick() => {
-                info!("📊 Tick! Fetching prices for {} assets...", config.assets.len());
-                // Fetch prices for all configured assets
-                for asset in &config.assets {
-                    info!("📊 Fetching {} from {:?}...", asset.symbol, asset.exchanges);
-                    let prices = fetch_asset_prices(&asset.symbol, &asset.exchanges).await;
-                    info!("📊 Got {} prices for {}: {:?}", prices.len(), asset.symbol, prices);
-                    
-                    if prices.is_empty() {
-                        warn!("⚠️  No prices fetched for {}", asset.symbol);
-                        continue;
-                    }
-                    
-                    // Calculate median price and confidence
-                    let (median, confidence) = calculate_median_and_confidence(&prices);
-                    info!("📊 {} median price: ${:.2} (confidence: {:.2}%)", asset.symbol, median, confidence * 100.0);
-                    
-                    let update = PriceUpdate {
-                        asset: asset.symbol.clone(),
-                        price: median,
-                        confidence,
-                        timestamp: chrono::Utc::now().timestamp(),
-                        exchange: "aggregated".to_string(),
-                        node_pubkey: node_pubkey.clone(),
-                    };
-                    
-                    if let Err(e) = price_tx.send(update).await {
-                        error!("Failed to send price update: {}", e);
-                    } else {
-                        info!("✅ Sent price update for {}", asset.symbol);
-                    }
+                // Never stack ticks behind a slow one
+                if in_flight.as_ref().is_some_and(|tick| !tick.is_finished()) {
+                    warn!("⚠️  Previous fetch still running, skipping tick");
+                    stats.write().await.ticks_skipped += 1;
+                    continue;
                 }
+                
+                let fetcher = fetcher.clone();
+                in_flight = Some(tokio::spawn(async move { fetcher.tick().await }));
             }
             _ = shutdown.recv() => {
                 info!("📊 Price fetcher shutting down...");
+                if let Some(tick) = in_flight {
+                    tick.abort();
+                }
+                for stream in &streams {
+                    stream.abort();
+                }
                 break;
             }
         }
@@ -78,213 +138,337 @@ pub async fn start_price_fetcher(
     Ok(())
 }
 
-async fn fetch_asset_prices(symbol: &str, exchanges: &[String]) -> Vec<f64> {
-    let mut prices = Vec::new();
-    
-    for exchange in exchanges {
-        match fetch_from_exchange(symbol, exchange).await {
-            Ok(price) => prices.push(price),
-            Err(e) => warn!("Failed to fetch {} from {}: {}", symbol, exchange, e),
-        }
-    }
-    
-    prices
-}
-
-async fn fetch_from_exchange(symbol: &str, exchange: &str) -> Result<f64> {
-    match exchange {
-        "binance" => fetch_binance(symbol).await,
-        "coinbase" => fetch_coinbase(symbol).await,
-        "kraken" => fetch_kraken(symbol).await,
-        "okx" => fetch_okx(symbol).await,
-        "bybit" => fetch_bybit(symbol).await,
-        _ => Err(anyhow::anyhow!("Unknown exchange: {}", exchange)),
-    }
+/// Each source gets 80% of the update interval, leaving room to aggregate
+/// and send before the next tick
+pub fn fetch_deadline(update_interval_ms: u64) -> Duration {
+    Duration::from_millis(update_interval_ms.saturating_mul(4) / 5)
 }
 
-async fn fetch_binance(symbol: &str) -> Result<f64> {
-    let binance_symbol = symbol.replace("/", "");
-    let url = format!("https://api.binance.com/api/v3/ticker/price?symbol={}", binance_symbol);
-    
-    let client = reqwest::Client::new();
-    let response = client.get(&url).send().await?;
-    
-    #[derive(Deserialize)]
-    struct BinanceResponse {
-        price: Option<String>,
-        code: Option<i32>,
-        msg: Option<String>,
-    }
-    
-    let data: BinanceResponse = response.json().await?;
-    
-    // Check for error response (geo-blocking, etc.)
-    if let Some(code) = data.code {
-        return Err(anyhow::anyhow!("Binance API error {}: {}", code, data.msg.unwrap_or_default()));
-    }
-    
-    // Parse price
-    let price_str = data.price.ok_or_else(|| anyhow::anyhow!("Missing price field"))?;
-    let price: f64 = price_str.parse()?;
-    
-    Ok(price)
+/// One source's answer for one asset
+#[derive(Debug, Clone)]
+pub struct SourceFetch {
+    pub source: String,
+    /// Currency of the ticker's prices, e.g. USDT for BTC/USD on Binance
+    pub quote: String,
+    pub latency: Duration,
+    /// When the answer arrived (unix seconds); the quote's age counts from here
+    pub received_at: i64,
+    /// Last response as the source sent it, if it keeps one
+    pub body: Option<String>,
+    pub outcome: FetchOutcome,
 }
 
-async fn fetch_coinbase(symbol: &str) -> Result<f64> {
-    let coinbase_symbol = symbol.replace("/", "-");
-    let url = format!("https://api.coinbase.com/v2/prices/{}/spot", coinbase_symbol);
-    
-    let client = reqwest::Client::new();
-    let response = client.get(&url).send().await?;
-    
-    #[derive(Deserialize)]
-    struct CoinbaseData {
-        amount: String,
-    }
-    
-    #[derive(Deserialize)]
-    struct CoinbaseResponse {
-        data: CoinbaseData,
-    }
-    
-    let data: CoinbaseResponse = response.json().await?;
-    let price: f64 = data.data.amount.parse()?;
-    
-    Ok(price)
+/// Fetches every configured asset from every source in parallel
+#[derive(Clone)]
+pub struct PriceFetcher {
+    pub assets: Arc<Vec<AssetConfig>>,
+    /// Internal feeds, e.g. USDT/USD; not sent to the aggregator
+    pub stablecoins: Arc<Vec<AssetConfig>>,
+    pub robust: Arc<RobustFetcher>,
+    pub normalizer: Arc<QuoteNormalizer>,
+    pub stats: Arc<RwLock<FetcherStats>>,
+    pub price_tx: mpsc::Sender<PriceUpdate>,
+    /// Our updates, for gossip to publish to peers
+    pub gossip_tx: Option<mpsc::Sender<PriceUpdate>>,
+    pub node_pubkey: String,
+    pub deadline: Duration,
+    /// Source answers and updates are written here when recording
+    pub recorder: Option<Arc<Recorder>>,
 }
 
-async fn fetch_kraken(symbol: &str) -> Result<f64> {
-    let kraken_symbol = symbol.replace("/", "");
-    let url = format!("https://api.kraken.com/0/public/Ticker?pair={}", kraken_symbol);
-    
-    let client = reqwest::Client::new();
-    let response = client.get(&url).send().await?;
-    
-    #[derive(Deserialize)]
-    struct KrakenResult {
-        c: Vec<String>, // Last trade closed array [price, lot volume]
+impl PriceFetcher {
+    /// Fetch all assets at once; each is sent as soon as its sources answered
+    pub async fn tick(&self) {
+        debug!("📊 Tick! Fetching prices for {} assets...", self.assets.len());
+        self.stats.write().await.ticks += 1;
+        
+        // Stablecoins convert with the latest rate, possibly the last tick's
+        let stablecoins = self.stablecoins.iter().map(|feed| self.update_asset(feed, true));
+        let assets = self.assets.iter().map(|asset| self.update_asset(asset, false));
+        join_all(stablecoins.chain(assets)).await;
     }
     
-    #[derive(Deserialize)]
-    struct KrakenResponse {
-        result: std::collections::HashMap<String, KrakenResult>,
+    async fn update_asset(&self, asset: &AssetConfig, internal: bool) {
+        let fetches = self.robust.fetch_from_exchanges(asset, self.deadline).await;
+        
+        {
+            let mut stats = self.stats.write().await;
+            for fetch in &fetches {
+                stats.record(&fetch.source, fetch.latency, &fetch.outcome);
+            }
+            stats.breakers = self.robust.breakers();
+        }
+        self.record(|recorder| recorder.sources(&asset.symbol, &fetches));
+        
+        let timestamp = chrono::Utc::now().timestamp();
+        let (_, quote) = split_symbol(&asset.symbol);
+        let mut prices: Vec<PriceData> = Vec::new();
+        
+        for fetch in fetches {
+            let ticker = match fetch.outcome {
+                FetchOutcome::Ticker(ticker) => ticker,
+                FetchOutcome::Failed(e) => {
+                    warn!("Failed to fetch {} from {}: {}", asset.symbol, fetch.source, e);
+                    continue;
+                }
+                FetchOutcome::TimedOut => {
+                    warn!("Timed out fetching {} from {} after {}ms", asset.symbol, fetch.source, fetch.latency.as_millis());
+                    continue;
+                }
+                FetchOutcome::BreakerOpen => {
+                    debug!("Skipping {} for {}: circuit breaker open", fetch.source, asset.symbol);
+                    continue;
+                }
+            };
+            
+            // Into the asset's quote currency, e.g. USDT -> USD
+            let normalized = match self.normalizer.normalize(ticker, &fetch.quote, quote, timestamp) {
+                Ok(normalized) => normalized,
+                Err(e) => {
+                    warn!("Dropping {} from {}: {}", asset.symbol, fetch.source, e);
+                    continue;
+                }
+            };
+            if let Some(basis) = normalized.basis {
+                self.stats.write().await.record_basis(&fetch.source, basis, normalized.depegged);
+                if normalized.depegged {
+                    warn!("⚠️  Dropping {} from {}: quoted in depegged {} ({:+.1} bps)",
+                        asset.symbol, fetch.source, fetch.quote, basis * 10_000.0);
+                    continue;
+                }
+            }
+            
+            match PriceData::from_ticker(normalized.ticker, &fetch.source, fetch.received_at) {
+                Some(price) => prices.push(price),
+                None => warn!("Ticker for {} from {} has no price", asset.symbol, fetch.source),
+            }
+        }
+        
+        if prices.is_empty() {
+            warn!("⚠️  No prices fetched for {}", asset.symbol);
+            return;
+        }
+        
+        // Bounds, outliers, staleness, then the weighted average and its
+        // confidence interval, at the asset's exponent
+        let (price, confidence) = match self.robust.aggregate_price(&asset.symbol, prices) {
+            Ok(aggregated) => aggregated,
+            Err(e) => {
+                warn!("⚠️  {}", e);
+                return;
+            }
+        };
+        
+        if internal {
+            let rate = self.normalizer.update(&asset.symbol, price, confidence, timestamp);
+            if rate.depegged {
+                warn!("⚠️  {} at {} is off its peg", asset.symbol, price);
+            }
+            self.stats.write().await.stablecoins = self.normalizer.rates();
+            return;
+        }
+        
+        let update = PriceUpdate {
+            asset: asset.symbol.clone(),
+            price,
+            confidence,
+            timestamp: chrono::Utc::now().timestamp(),
+            exchange: "aggregated".to_string(),
+            node_pubkey: self.node_pubkey.clone(),
+        };
+        
+        self.record(|recorder| recorder.update(&update));
+        // Gossip must never hold up the aggregator
+        if let Some(gossip_tx) = &self.gossip_tx {
+            if let Err(e) = gossip_tx.try_send(update.clone()) {
+                warn!("📡 Not publishing {} update: {}", update.asset, e);
+            }
+        }
+        if let Err(e) = self.price_tx.send(update).await {
+            error!("Failed to send price update: {}", e);
+        }
     }
     
-    let data: KrakenResponse = response.json().await?;
-    
-    if let Some((_, result)) = data.result.iter().next() {
-        if let Some(price_str) = result.c.first() {
-            let price: f64 = price_str.parse()?;
-            return Ok(price);
+    /// A failed write is logged; the node keeps running without it
+    fn record(&self, write: impl FnOnce(&Recorder) -> Result<()>) {
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = write(recorder) {
+                warn!("⏺️  Failed to record: {}", e);
+            }
         }
     }
-    
-    Err(anyhow::anyhow!("Failed to parse Kraken response"))
 }
 
-async fn fetch_okx(symbol: &str) -> Result<f64> {
-    // OKX uses format like "BTC-USDT"
-    let okx_symbol = symbol.replace("/", "-");
-    let url = format!("https://www.okx.com/api/v5/market/ticker?instId={}", okx_symbol);
-    
-    let client = reqwest::Client::new();
-    let response = client.get(&url).send().await?;
-    
-    #[derive(Deserialize)]
-    struct OkxData {
-        #[serde(rename = "last")]
-        last: String,
-    }
-    
-    #[derive(Deserialize)]
-    struct OkxResponse {
-        code: String,
-        data: Vec<OkxData>,
-    }
-    
-    let data: OkxResponse = response.json().await?;
-    
-    if data.code != "0" {
-        return Err(anyhow::anyhow!("OKX API error: code {}", data.code));
-    }
-    
-    if let Some(ticker) = data.data.first() {
-        let price: f64 = ticker.last.parse()?;
-        return Ok(price);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use axum::{extract::Path, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+    use source::{px, RateLimiter, SymbolMap, Ticker};
+    use json_http::{JsonHttpSource, TickerPaths};
+    use crate::replay::recording::Record;
+
+    /// Serve `router` on a local port and return its base URL
+    async fn mock_server(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}", addr)
     }
-    
-    Err(anyhow::anyhow!("Failed to parse OKX response"))
-}
 
-async fn fetch_bybit(symbol: &str) -> Result<f64> {
-    // Bybit uses format like "BTCUSDT"
-    let bybit_symbol = symbol.replace("/", "");
-    let url = format!("https://api.bybit.com/v5/market/tickers?category=spot&symbol={}", bybit_symbol);
-    
-    let client = reqwest::Client::new();
-    let response = client.get(&url).send().await?;
-    
-    #[derive(Deserialize)]
-    struct BybitTicker {
-        #[serde(rename = "lastPrice")]
-        last_price: String,
+    fn mock_source(name: &str, url: String, symbols: SymbolMap) -> Arc<JsonHttpSource> {
+        Arc::new(JsonHttpSource::new(
+            name,
+            reqwest::Client::new(),
+            &url,
+            TickerPaths::parse("price", None, None, None).unwrap(),
+            HashMap::new(),
+            symbols,
+            RateLimiter::new(0.0),
+        ).unwrap())
     }
-    
-    #[derive(Deserialize)]
-    struct BybitResult {
-        list: Vec<BybitTicker>,
+
+    async fn venues(assets: &[AssetConfig]) -> Arc<RobustFetcher> {
+        let base = mock_server(Router::new()
+            .route("/fast/:symbol", get(|| async { Json(serde_json::json!({"price": "100.0"})) }))
+            .route("/also-fast/:symbol", get(|| async { Json(serde_json::json!({"price": 102.0})) }))
+            .route("/slow/:symbol", get(|| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Json(serde_json::json!({"price": 1.0}))
+            }))
+            .route("/broken/:symbol", get(|| async { StatusCode::INTERNAL_SERVER_ERROR }))
+            .route("/offshore/:symbol", get(|Path(symbol): Path<String>| async move {
+                // Only lists USDT markets
+                match symbol.as_str() {
+                    "BTCUSDT" => Json(serde_json::json!({"price": 101.0})).into_response(),
+                    _ => StatusCode::NOT_FOUND.into_response(),
+                }
+            }))
+            .route("/tether/:symbol", get(|| async { Json(serde_json::json!({"price": 0.99})) })))
+            .await;
+
+        let mut registry = SourceRegistry::default();
+        for name in ["fast", "also-fast", "slow", "broken", "tether"] {
+            registry.register(mock_source(name, format!("{}/{}/{{symbol}}", base, name), SymbolMap::new("{base}{quote}")));
+        }
+        let usdt = SymbolMap::new("{base}{quote}").with_quotes(HashMap::from([("USD".to_string(), "USDT".to_string())]));
+        registry.register(mock_source("offshore", format!("{}/offshore/{{symbol}}", base), usdt));
+        Arc::new(RobustFetcher::new(Arc::new(registry), assets).with_retries(0, 0))
     }
-    
-    #[derive(Deserialize)]
-    struct BybitResponse {
-        #[serde(rename = "retCode")]
-        ret_code: i32,
-        result: BybitResult,
+
+    #[tokio::test]
+    async fn test_slow_source_does_not_delay_the_rest() {
+        let btc = AssetConfig::new("BTC/USD", &["fast", "slow", "broken", "also-fast"]);
+        let robust = venues(std::slice::from_ref(&btc)).await;
+        let started = tokio::time::Instant::now();
+
+        let fetches = robust.fetch_from_exchanges(&btc, Duration::from_millis(300)).await;
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let outcomes: Vec<_> = fetches.iter().map(|fetch| (fetch.source.as_str(), &fetch.outcome)).collect();
+        assert_eq!(outcomes[0], ("fast", &FetchOutcome::Ticker(Ticker::from_last(px("100")))));
+        assert_eq!(outcomes[1], ("slow", &FetchOutcome::TimedOut));
+        assert!(matches!(outcomes[2], ("broken", FetchOutcome::Failed(_))));
+        assert_eq!(outcomes[3], ("also-fast", &FetchOutcome::Ticker(Ticker::from_last(px("102")))));
     }
-    
-    let data: BybitResponse = response.json().await?;
-    
-    if data.ret_code != 0 {
-        return Err(anyhow::anyhow!("Bybit API error: code {}", data.ret_code));
+
+    #[tokio::test]
+    async fn test_tick_sends_updates_and_records_stats() {
+        let (price_tx, mut price_rx) = mpsc::channel(10);
+        let mut eth = AssetConfig::new("ETH/USD", &["broken"]);
+        eth.breaker_threshold = 1;
+        let assets = vec![AssetConfig::new("BTC/USD", &["fast", "slow", "also-fast"]), eth];
+        let recording = tempfile::NamedTempFile::new().unwrap();
+        let fetcher = PriceFetcher {
+            robust: venues(&assets).await,
+            assets: Arc::new(assets),
+            stablecoins: Arc::new(vec![]),
+            normalizer: Arc::new(QuoteNormalizer::new(50)),
+            stats: Arc::new(RwLock::new(FetcherStats::default())),
+            price_tx,
+            gossip_tx: None,
+            node_pubkey: "node".to_string(),
+            deadline: Duration::from_millis(300),
+            recorder: Some(Arc::new(Recorder::create(recording.path()).unwrap())),
+        };
+
+        fetcher.tick().await;
+
+        // ETH/USD had no working source, so only BTC/USD is sent
+        let update = price_rx.try_recv().unwrap();
+        assert_eq!((update.asset.as_str(), update.price), ("BTC/USD", px("101")));
+        assert!(price_rx.try_recv().is_err());
+
+        let stats = fetcher.stats.read().await;
+        assert_eq!(stats.ticks, 1);
+        assert_eq!(stats.sources["fast"].successes, 1);
+        assert_eq!(stats.sources["slow"].timeouts, 1);
+        assert_eq!(stats.sources["broken"].failures, 1);
+        assert!(stats.sources["slow"].last_latency_ms >= 300);
+        assert_eq!(stats.breakers["ETH/USD"]["broken"].state, robust_fetcher::CircuitState::Open);
+        assert_eq!(stats.breakers["BTC/USD"]["fast"].state, robust_fetcher::CircuitState::Closed);
+        
+        // Every answer, failed or not, with the response the source sent, and the update that was sent
+        let records = crate::replay::recording::read_recording(recording.path()).unwrap();
+        let sources: Vec<_> = records.iter()
+            .filter_map(|record| match record {
+                Record::Source { asset, source, body, .. } => Some((asset.as_str(), source.as_str(), body.as_deref())),
+                Record::Update { .. } => None,
+            })
+            .collect();
+        assert_eq!(sources.len(), 4);
+        assert!(sources.contains(&("BTC/USD", "fast", Some(r#"{"price":"100.0"}"#))));
+        assert!(sources.contains(&("BTC/USD", "slow", None)));
+        // The error page of a failed request is kept too
+        assert!(sources.contains(&("ETH/USD", "broken", Some(""))));
+        assert!(matches!(records.last(), Some(Record::Update { update, .. }) if update.price == px("101")));
     }
-    
-    if let Some(ticker) = data.result.list.first() {
-        let price: f64 = ticker.last_price.parse()?;
-        return Ok(price);
+
+    #[tokio::test]
+    async fn test_usdt_quotes_are_converted_to_usd() {
+        let (price_tx, mut price_rx) = mpsc::channel(10);
+        let usdt = AssetConfig::new("USDT/USD", &["tether"]);
+        let btc = AssetConfig::new("BTC/USD", &["fast", "offshore"]);
+        let fetcher = PriceFetcher {
+            robust: venues(&[usdt.clone(), btc.clone()]).await,
+            assets: Arc::new(vec![btc]),
+            stablecoins: Arc::new(vec![usdt]),
+            normalizer: Arc::new(QuoteNormalizer::new(50)),
+            stats: Arc::new(RwLock::new(FetcherStats::default())),
+            price_tx,
+            gossip_tx: None,
+            node_pubkey: "node".to_string(),
+            deadline: Duration::from_millis(300),
+            recorder: None,
+        };
+
+        // The first tick may finish BTC/USD before USDT/USD has a rate
+        fetcher.tick().await;
+        while price_rx.try_recv().is_ok() {}
+        fetcher.tick().await;
+
+        // USDT at 0.99 is past the 50 bps threshold: the USDT venue is left out
+        let update = price_rx.try_recv().unwrap();
+        assert_eq!((update.asset.as_str(), update.price), ("BTC/USD", px("100")));
+        assert!(price_rx.try_recv().is_err());
+
+        let stats = fetcher.stats.read().await;
+        assert!(stats.stablecoins["USDT/USD"].depegged);
+        assert!(stats.sources["offshore"].depeg_flags >= 1);
+        assert!((stats.sources["offshore"].last_basis_bps.unwrap() + 100.0).abs() < 1e-6);
+        assert_eq!(stats.sources["fast"].last_basis_bps, None);
+        drop(stats);
+
+        // Back within the threshold, 101 USDT at 0.998 is 100.798 USD
+        fetcher.normalizer.update("USDT/USD", px("0.998"), px("0"), chrono::Utc::now().timestamp());
+        fetcher.update_asset(&fetcher.assets[0], false).await;
+        let update = price_rx.try_recv().unwrap();
+        assert!((update.price.to_f64() - (100.0 + 100.798) / 2.0).abs() < 1e-8);
     }
-    
-    Err(anyhow::anyhow!("Failed to parse Bybit response"))
-}
 
-fn calculate_median_and_confidence(prices: &[f64]) -> (f64, f64) {
-    if prices.is_empty() {
-        return (0.0, 0.0);
+    #[test]
+    fn test_fetch_deadline() {
+        assert_eq!(fetch_deadline(1000), Duration::from_millis(800));
+        assert_eq!(fetch_deadline(u64::MAX), Duration::from_millis(u64::MAX / 5));
     }
-    
-    let mut sorted = prices.to_vec();
-    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    
-    let median = if sorted.len() % 2 == 0 {
-        let mid = sorted.len() / 2;
-        (sorted[mid - 1] + sorted[mid]) / 2.0
-    } else {
-        sorted[sorted.len() / 2]
-    };
-    
-    // Calculate confidence as inverse of standard deviation
-    let mean = sorted.iter().sum::<f64>() / sorted.len() as f64;
-    let variance = sorted.iter()
-        .map(|p| (p - mean).powi(2))
-        .sum::<f64>() / sorted.len() as f64;
-    let std_dev = variance.sqrt();
-    
-    // Confidence is higher when std_dev is lower
-    let confidence = if std_dev > 0.0 {
-        1.0 / (1.0 + std_dev / mean)
-    } else {
-        1.0
-    };
-    
-    (median, confidence)
 }
-
@@ -0,0 +1,277 @@
+// Pricing - per-asset price computation over venue tickers
+//
+// The robust fetcher validates quotes and drops outliers; what is left is
+// combined here according to the asset's `AggregationMethod`. The volume
+// weighted mean and TWAP read a short history kept in a `PriceWindow` per asset. The confidence
+// interval comes from the venues' bid-ask spreads and how far their prices
+// sit from the aggregate, at the aggregate's exponent.
+//
+// Prices stay fixed-point throughout. Means are taken over each price's
+// distance from the first one, so f64 only ever holds the spread between
+// venues and the result is rounded once, at the exponent asked for.
+
+use std::collections::VecDeque;
+use tachyon_merkle::price::{Price, MAX_EXPO, MIN_EXPO};
+
+/// One price observation kept for windowed methods
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub price: Price,
+    /// 24h volume of the venue it came from; 0 when unknown
+    pub volume: f64,
+    pub at_ms: i64,
+}
+
+/// Recent samples of one asset, oldest first
+#[derive(Debug, Clone)]
+pub struct PriceWindow {
+    samples: VecDeque<Sample>,
+    window_ms: i64,
+}
+
+impl PriceWindow {
+    pub fn new(window_secs: u64) -> Self {
+        Self {
+            samples: VecDeque::new(),
+            window_ms: window_secs.saturating_mul(1000) as i64,
+        }
+    }
+
+    pub fn push(&mut self, sample: Sample) {
+        self.samples.push_back(sample);
+    }
+
+    /// Drop samples that fell out of the window
+    pub fn prune(&mut self, now_ms: i64) {
+        while self.samples.front().is_some_and(|sample| sample.at_ms < now_ms - self.window_ms) {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Prices weighted by their venue's 24h volume, at `expo`; None if no
+    /// sample carries volume
+    pub fn volume_weighted_mean(&self, expo: i32) -> Option<Price> {
+        let weighted: Vec<(Price, f64)> = self.samples.iter()
+            .map(|sample| (sample.price, sample.volume))
+            .collect();
+
+        weighted_mean(&weighted, expo)
+    }
+
+    /// Each sample holds until the next one, the newest until `now_ms`; at `expo`
+    pub fn twap(&self, now_ms: i64, expo: i32) -> Option<Price> {
+        let held: Vec<(Price, f64)> = self.samples.iter()
+            .enumerate()
+            .map(|(i, sample)| {
+                let until = self.samples.get(i + 1).map_or(now_ms, |next| next.at_ms);
+                (sample.price, (until - sample.at_ms).max(0) as f64)
+            })
+            .collect();
+
+        // All samples taken at `now_ms`: the newest
+        weighted_mean(&held, expo)
+            .or_else(|| self.samples.back().and_then(|sample| sample.price.rescale(expo).ok()))
+    }
+}
+
+/// Mean of `(price, weight)` pairs, rounded to `expo`; None if the weights
+/// sum to zero or the mean does not fit
+pub fn weighted_mean(values: &[(Price, f64)], expo: i32) -> Option<Price> {
+    let (reference, _) = *values.first()?;
+
+    let mut offset = 0.0;
+    let mut weight_sum = 0.0;
+    for (price, weight) in values {
+        offset += price.checked_sub(reference).ok()?.to_f64() * weight;
+        weight_sum += weight;
+    }
+    if weight_sum <= 0.0 {
+        return None;
+    }
+
+    // Add up at the finer exponent, then round once
+    let fine = expo.min(reference.expo);
+    let offset = Price::from_f64(offset / weight_sum, fine).ok()?;
+    reference.checked_add(offset).and_then(|mean| mean.rescale(expo)).ok()
+}
+
+/// Price at half of the total weight; None if the weights sum to zero.
+/// With equal weights this is the ordinary median.
+pub fn weighted_median(values: &[(Price, f64)]) -> Option<Price> {
+    let mut values: Vec<(Price, f64)> = values.iter()
+        .copied()
+        .filter(|(_, weight)| *weight > 0.0)
+        .collect();
+    values.sort_by_key(|(price, _)| *price);
+
+    let half = values.iter().map(|(_, weight)| weight).sum::<f64>() / 2.0;
+    let mut cumulative = 0.0;
+
+    for (i, (value, weight)) in values.iter().enumerate() {
+        cumulative += weight;
+        if cumulative > half {
+            return Some(*value);
+        }
+        if cumulative == half {
+            // Exactly on the boundary: halfway to the next price
+            return values.get(i + 1).map_or(Some(*value), |(next, _)| value.midpoint(*next).ok());
+        }
+    }
+    None
+}
+
+/// Confidence interval around `aggregate`, at its exponent: the RMS of
+/// each venue's half spread combined with the RMS distance of its price
+/// from the aggregate. Venues without a book contribute no spread. None if
+/// the squares overflow.
+pub fn confidence_interval(quotes: &[(Price, Option<Price>)], aggregate: Price) -> Option<Price> {
+    let mut deviations = Vec::with_capacity(2 * quotes.len());
+    for (price, half_spread) in quotes {
+        deviations.push(price.checked_sub(aggregate).ok()?);
+        deviations.extend(*half_spread);
+    }
+    quadrature(&deviations, quotes.len(), aggregate.expo)
+}
+
+/// Digits finer than the result at which `quadrature` squares, where they fit
+const GUARD_DIGITS: u32 = 6;
+
+/// `√(Σ deviation² / count)` at `expo`, rounded to the nearest unit: the
+/// root sum of squares for a count of one. None if the squares overflow.
+pub fn quadrature(deviations: &[Price], count: usize, expo: i32) -> Option<Price> {
+    if !(MIN_EXPO..=MAX_EXPO).contains(&expo) {
+        return None;
+    }
+    let count = u128::try_from(count.max(1)).ok()?;
+
+    // Squared a few digits finer than `expo`, so the root is rounded once
+    let root = (0..=GUARD_DIGITS).rev().find_map(|guard| {
+        let fine = expo - guard as i32;
+        let mut sum: u128 = 0;
+        for deviation in deviations {
+            let mantissa = mantissa_at(*deviation, fine)?.unsigned_abs();
+            sum = sum.checked_add(mantissa.checked_mul(mantissa)?)?;
+        }
+        Some(rounded_sqrt(sum, 10u128.pow(2 * guard).checked_mul(count)?))
+    })?;
+    i64::try_from(root).ok().map(|mantissa| Price::new(mantissa, expo))
+}
+
+/// `price`'s mantissa at `expo`, exact when `expo` is finer
+fn mantissa_at(price: Price, expo: i32) -> Option<i128> {
+    if price.expo >= expo {
+        10i128.checked_pow(price.expo.abs_diff(expo))?.checked_mul(i128::from(price.mantissa))
+    } else {
+        price.rescale(expo).ok().map(|price| i128::from(price.mantissa))
+    }
+}
+
+/// `√(numerator / denominator)` rounded half up, for a nonzero denominator
+fn rounded_sqrt(numerator: u128, denominator: u128) -> u128 {
+    let root = (numerator / denominator).isqrt();
+    // Past r + ½ once the fraction reaches r² + r + ¼
+    let rest = (root * (root + 1))
+        .checked_mul(denominator)
+        .and_then(|floor| numerator.checked_sub(floor));
+    match rest {
+        Some(rest) if rest.saturating_mul(4) >= denominator => root + 1,
+        _ => root,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::source::px;
+
+    fn sample(price: &str, volume: f64, at_ms: i64) -> Sample {
+        Sample { price: px(price), volume, at_ms }
+    }
+
+    #[test]
+    fn test_window_means() {
+        let mut window = PriceWindow::new(10);
+        window.push(sample("100", 1.0, 0));
+        window.push(sample("110", 3.0, 2_000));
+        window.push(sample("120", 0.0, 8_000));
+
+        // Volume-less samples don't move the volume weighted mean
+        assert_eq!(window.volume_weighted_mean(-8), Some(px("107.5")));
+        // 100 for 2s, 110 for 6s, 120 for 2s
+        assert_eq!(window.twap(10_000, -8), Some(px("110")));
+
+        window.prune(11_000);
+        assert_eq!(window.len(), 2);
+        assert_eq!(window.volume_weighted_mean(-8), Some(px("110")));
+
+        window.prune(30_000);
+        assert!(window.is_empty());
+        assert_eq!((window.volume_weighted_mean(-8), window.twap(30_000, -8)), (None, None));
+    }
+
+    #[test]
+    fn test_weighted_mean_keeps_every_digit() {
+        // 18 significant digits: no f64 holds these prices
+        let low = px("98765432.1234567891");
+        let high = px("98765432.1234567893");
+        assert_eq!(weighted_mean(&[(low, 1.0), (high, 1.0)], -10), Some(px("98765432.1234567892")));
+        assert_eq!(weighted_mean(&[(low, 1.0), (low, 5.0)], -10), Some(low));
+
+        // Rounded once, at the exponent asked for
+        assert_eq!(weighted_mean(&[(low, 1.0), (high, 4.0)], -10), Some(px("98765432.1234567893")));
+        assert_eq!(weighted_mean(&[(low, 1.0)], -8), Some(px("98765432.12345679")));
+        assert_eq!(weighted_mean(&[(low, 0.0)], -8), None);
+    }
+
+    #[test]
+    fn test_weighted_median() {
+        // Equal weights: the ordinary median
+        assert_eq!(weighted_median(&[(px("3"), 1.0), (px("1"), 1.0), (px("2"), 1.0)]), Some(px("2")));
+        assert_eq!(weighted_median(&[(px("1"), 1.0), (px("2"), 1.0), (px("3"), 1.0), (px("4"), 1.0)]), Some(px("2.5")));
+
+        // One deep venue outweighs two thin ones
+        assert_eq!(weighted_median(&[(px("100"), 1.0), (px("101"), 1.0), (px("105"), 10.0)]), Some(px("105")));
+
+        assert_eq!(weighted_median(&[(px("100"), 0.0)]), None);
+    }
+
+    #[test]
+    fn test_confidence_interval() {
+        // Agreeing venues: the interval is the spread, √0.125
+        assert_eq!(confidence_interval(&[(px("100"), Some(px("0.3"))), (px("100"), Some(px("0.4")))], px("100.00000000")), Some(px("0.35355339")));
+
+        // No book: only the disagreement counts
+        assert_eq!(confidence_interval(&[(px("99"), None), (px("101"), None)], px("100")), Some(px("1")));
+
+        // Both together
+        assert_eq!(confidence_interval(&[(px("97"), Some(px("4")))], px("100")), Some(px("5")));
+        assert_eq!(confidence_interval(&[], px("100")), Some(px("0")));
+
+        // At the aggregate's exponent, rounded to the nearest unit
+        assert_eq!(confidence_interval(&[(px("100"), Some(px("0.3"))), (px("100"), Some(px("0.4")))], px("100.0")), Some(px("0.4")));
+        assert_eq!(confidence_interval(&[(px("1"), None)], px("-9223372036854775807")), None);
+    }
+
+    #[test]
+    fn test_quadrature() {
+        assert_eq!(quadrature(&[px("3"), px("-4")], 1, -2), Some(px("5")));
+        assert_eq!(quadrature(&[px("1")], 2, -8), Some(px("0.70710678")));
+        // Squared finer than the result: 0.4 and 0.4 are 0.57, not 0
+        assert_eq!(quadrature(&[px("0.4"), px("0.4")], 1, 0), Some(px("1")));
+        // Rounded half up: √0.2025 is 0.45
+        assert_eq!(quadrature(&[px("0.45")], 1, -1), Some(px("0.5")));
+        assert_eq!(quadrature(&[px("1e-10"), px("1e-10")], 1, -18), Some(px("0.000000000141421356")));
+        assert_eq!(quadrature(&[], 0, -8), Some(px("0")));
+
+        assert_eq!(quadrature(&[px("9000000000000000000"), px("9000000000000000000")], 1, 0), None);
+        assert_eq!(quadrature(&[px("1")], 1, -19), None);
+    }
+}
@@ -1,17 +1,48 @@
 // Robust Price Fetcher - Production-grade price aggregation
 // Includes: Outlier detection, circuit breaker, retry logic, weighted averaging
-// Infrastructure code for future use
-#![allow(dead_code)]
-
-use std::collections::HashMap;
+//
+// Breakers, exchange weights, sanity bounds and the aggregation method come
+// from each asset's `AssetConfig`. Breakers are kept per (asset, exchange),
+// since a venue can fail for one symbol (e.g. a delisting) and still serve
+// the others.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use std::time::Duration;
 use anyhow::Result;
+use futures::future::join_all;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
-use tokio::time::sleep;
-use tracing::{warn, info};
+use tachyon_merkle::price::{Price, MIN_EXPO};
+use tokio::time::{sleep, timeout, Instant};
+use tracing::{debug, warn, info};
+
+use crate::config::{default_expo, AggregationMethod, AssetConfig};
+use super::pricing::{self, PriceWindow, Sample};
+use super::source::{split_symbol, SourceRegistry, Ticker};
+use super::stats::FetchOutcome;
+use super::SourceFetch;
+
+/// Quotes older than this are dropped before aggregation (seconds)
+const MAX_PRICE_AGE_SECS: i64 = 60;
+
+/// Quotes further from the median than this many scaled MADs are outliers
+const OUTLIER_MADS: f64 = 3.0;
+
+/// Scales the median absolute deviation to a standard deviation
+const MAD_SCALE: f64 = 1.4826;
+
+/// Narrowest outlier band as a fraction of the median, so that near-identical
+/// quotes (MAD close to zero) don't turn every small difference into an outlier
+const MIN_OUTLIER_BAND: f64 = 0.001;
+
+/// Decimals the outlier band keeps beyond the quotes', enough for
+/// `MIN_OUTLIER_BAND` of the median
+const BAND_DECIMALS: i32 = 3;
 
 /// Circuit breaker state
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CircuitState {
     Closed,   // Normal operation
     Open,     // Stop fetching (too many failures)
@@ -19,7 +50,7 @@ pub enum CircuitState {
 }
 
 /// Circuit breaker for exchange API calls
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CircuitBreaker {
     pub state: CircuitState,
     pub failure_count: u32,
@@ -100,242 +131,296 @@ impl CircuitBreaker {
     }
 }
 
-/// Exchange weight for weighted averaging
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct ExchangeWeight {
-    pub exchange: String,
-    pub weight: f64, // Based on volume/reliability
-}
-
-impl Default for ExchangeWeight {
-    fn default() -> Self {
-        Self {
-            exchange: String::new(),
-            weight: 1.0,
-        }
-    }
-}
-
 /// Price with metadata
 #[derive(Clone, Debug)]
 pub struct PriceData {
-    pub price: f64,
+    /// The ticker's mid, or its last trade, exactly as quoted
+    pub price: Price,
+    pub ticker: Ticker,
     pub exchange: String,
     pub timestamp: i64,
 }
 
-/// Robust price fetcher
+impl PriceData {
+    /// None if the ticker has no usable price
+    pub fn from_ticker(ticker: Ticker, exchange: &str, timestamp: i64) -> Option<Self> {
+        Some(Self {
+            price: ticker.price()?,
+            ticker,
+            exchange: exchange.to_string(),
+            timestamp,
+        })
+    }
+}
+
+/// Robust price fetcher, shared by all fetch tasks
 pub struct RobustFetcher {
-    circuit_breakers: HashMap<String, CircuitBreaker>,
-    exchange_weights: HashMap<String, f64>,
+    sources: Arc<SourceRegistry>,
+    assets: HashMap<String, AssetConfig>,
+    /// Keyed by (asset, exchange)
+    circuit_breakers: Mutex<HashMap<(String, String), CircuitBreaker>>,
+    /// History for windowed methods, by asset
+    windows: Mutex<HashMap<String, PriceWindow>>,
     max_retries: u32,
     retry_delay_ms: u64,
 }
 
 impl RobustFetcher {
-    pub fn new() -> Self {
-        // Default exchange weights (based on volume/reliability)
-        let mut weights = HashMap::new();
-        weights.insert("binance".to_string(), 1.5);   // Highest volume
-        weights.insert("coinbase".to_string(), 1.3);  // High reliability
-        weights.insert("kraken".to_string(), 1.2);    // Good reliability
-        weights.insert("okx".to_string(), 1.0);       // Standard
-        weights.insert("bybit".to_string(), 1.0);     // Standard
-
+    pub fn new(sources: Arc<SourceRegistry>, assets: &[AssetConfig]) -> Self {
         Self {
-            circuit_breakers: HashMap::new(),
-            exchange_weights: weights,
+            sources,
+            assets: assets.iter().map(|asset| (asset.symbol.clone(), asset.clone())).collect(),
+            circuit_breakers: Mutex::new(HashMap::new()),
+            windows: Mutex::new(HashMap::new()),
             max_retries: 3,
             retry_delay_ms: 100,
         }
     }
 
-    /// Get or create circuit breaker for an exchange
-    fn get_circuit_breaker(&mut self, exchange: &str) -> &mut CircuitBreaker {
-        self.circuit_breakers
-            .entry(exchange.to_string())
-            .or_insert_with(|| CircuitBreaker::new(5, 60))
+    pub fn with_retries(mut self, max_retries: u32, retry_delay_ms: u64) -> Self {
+        self.max_retries = max_retries;
+        self.retry_delay_ms = retry_delay_ms;
+        self
+    }
+
+    /// Check the breaker for `exchange` on `asset`, creating it on first use
+    fn can_call(&self, asset: &AssetConfig, exchange: &str) -> bool {
+        self.circuit_breakers.lock()
+            .entry((asset.symbol.clone(), exchange.to_string()))
+            .or_insert_with(|| CircuitBreaker::new(asset.breaker_threshold, asset.breaker_timeout_secs))
+            .can_call()
+    }
+
+    fn record_outcome(&self, asset: &AssetConfig, exchange: &str, success: bool) {
+        let mut breakers = self.circuit_breakers.lock();
+        let Some(breaker) = breakers.get_mut(&(asset.symbol.clone(), exchange.to_string())) else {
+            return;
+        };
+
+        if success {
+            breaker.record_success();
+        } else {
+            breaker.record_failure();
+            if breaker.state == CircuitState::Open {
+                warn!("🔒 {} disabled for {} for {}s", exchange, asset.symbol, breaker.timeout_secs);
+            }
+        }
+    }
+
+    /// Exponent `symbol` is published at; prices are rounded to it once
+    fn expo(&self, symbol: &str) -> i32 {
+        self.assets.get(symbol).map_or_else(default_expo, |asset| asset.expo)
+    }
+
+    /// Breaker state by asset, then exchange
+    pub fn breakers(&self) -> BTreeMap<String, BTreeMap<String, CircuitBreaker>> {
+        let mut snapshot: BTreeMap<String, BTreeMap<String, CircuitBreaker>> = BTreeMap::new();
+        for ((symbol, exchange), breaker) in self.circuit_breakers.lock().iter() {
+            snapshot.entry(symbol.clone()).or_default().insert(exchange.clone(), breaker.clone());
+        }
+        snapshot
     }
 
-    /// Fetch price with retry logic and circuit breaker
+    /// Fetch price with retry logic and circuit breaker. Retries stop at
+    /// `deadline`; the breaker sees one success or failure per call. The
+    /// ticker is in the quote currency the venue lists the asset in, and
+    /// comes with the last response the source sent, if it keeps one.
     pub async fn fetch_price_robust(
-        &mut self,
-        symbol: &str,
+        &self,
+        asset: &AssetConfig,
         exchange: &str,
-    ) -> Result<f64> {
-        let breaker = self.get_circuit_breaker(exchange);
-        
+        deadline: Duration,
+    ) -> (FetchOutcome, Option<String>) {
         // Check circuit breaker
-        if !breaker.can_call() {
-            return Err(anyhow::anyhow!("Circuit breaker OPEN for {}", exchange));
+        if !self.can_call(asset, exchange) {
+            return (FetchOutcome::BreakerOpen, None);
         }
 
+        // Ask for the market the venue lists, e.g. BTC/USDT for BTC/USD
+        let symbol = self.sources.listed_symbol(exchange, &asset.symbol);
+        // Filled by each attempt, so a timeout keeps what came before it
+        let mut body = None;
+        let outcome = match timeout(deadline, self.fetch_with_retries(&symbol, exchange, &mut body)).await {
+            Ok(Ok(ticker)) => FetchOutcome::Ticker(ticker),
+            Ok(Err(e)) => FetchOutcome::Failed(e.to_string()),
+            Err(_) => FetchOutcome::TimedOut,
+        };
+
+        self.record_outcome(asset, exchange, matches!(outcome, FetchOutcome::Ticker(_)));
+        (outcome, body)
+    }
+
+    async fn fetch_with_retries(&self, symbol: &str, exchange: &str, body: &mut Option<String>) -> Result<Ticker> {
         // Retry logic with exponential backoff
         let mut retries = 0;
         let mut delay = Duration::from_millis(self.retry_delay_ms);
 
         loop {
-            match self.fetch_price_once(symbol, exchange).await {
-                Ok(price) => {
-                    // Success!
-                    let breaker = self.get_circuit_breaker(exchange);
-                    breaker.record_success();
-                    return Ok(price);
-                }
+            let answer = self.sources.fetch_answer(exchange, symbol).await;
+            *body = answer.body;
+            match answer.ticker {
+                Ok(ticker) => return Ok(ticker),
                 Err(e) if retries < self.max_retries => {
                     retries += 1;
-                    warn!("⚠️  Retry {}/{} for {} on {}: {}", retries, self.max_retries, symbol, exchange, e);
+                    debug!("⚠️  Retry {}/{} for {} on {}: {}", retries, self.max_retries, symbol, exchange, e);
                     sleep(delay).await;
                     delay *= 2; // Exponential backoff
                 }
-                Err(e) => {
-                    // All retries failed
-                    let breaker = self.get_circuit_breaker(exchange);
-                    breaker.record_failure();
-                    return Err(e);
-                }
+                Err(e) => return Err(e),
             }
         }
     }
 
-    /// Single fetch attempt (implement actual API calls here)
-    async fn fetch_price_once(&self, symbol: &str, exchange: &str) -> Result<f64> {
-        // This would call the actual exchange API
-        // For now, placeholder that calls existing fetch functions
-        match exchange {
-            "binance" => super::fetch_binance(symbol).await,
-            "coinbase" => super::fetch_coinbase(symbol).await,
-            "kraken" => super::fetch_kraken(symbol).await,
-            "okx" => super::fetch_okx(symbol).await,
-            "bybit" => super::fetch_bybit(symbol).await,
-            _ => Err(anyhow::anyhow!("Unknown exchange: {}", exchange)),
-        }
-    }
-
-    /// Fetch from multiple exchanges
-    pub async fn fetch_from_exchanges(
-        &mut self,
-        symbol: &str,
-        exchanges: &[String],
-    ) -> Vec<PriceData> {
-        let mut prices = Vec::new();
-        let timestamp = chrono::Utc::now().timestamp();
-
-        for exchange in exchanges {
-            match self.fetch_price_robust(symbol, exchange).await {
-                Ok(price) => {
-                    prices.push(PriceData {
-                        price,
-                        exchange: exchange.clone(),
-                        timestamp,
-                    });
-                }
-                Err(e) => {
-                    warn!("Failed to fetch {} from {}: {}", symbol, exchange, e);
-                }
+    /// Fetch `asset` from all of its exchanges concurrently
+    pub async fn fetch_from_exchanges(&self, asset: &AssetConfig, deadline: Duration) -> Vec<SourceFetch> {
+        join_all(asset.exchanges.iter().map(|exchange| async move {
+            let started = Instant::now();
+            let (outcome, body) = self.fetch_price_robust(asset, exchange, deadline).await;
+
+            SourceFetch {
+                source: exchange.clone(),
+                quote: split_symbol(&self.sources.listed_symbol(exchange, &asset.symbol)).1.to_string(),
+                latency: started.elapsed(),
+                received_at: chrono::Utc::now().timestamp(),
+                body,
+                outcome,
             }
-        }
-
-        prices
+        })).await
     }
 
-    /// Remove outliers using standard deviation
+    /// Remove quotes far from the median, measured in median absolute
+    /// deviations so a single bad quote cannot widen the band around itself
     pub fn remove_outliers(&self, prices: &[PriceData]) -> Vec<PriceData> {
         if prices.len() < 3 {
             return prices.to_vec();
         }
 
-        let values: Vec<f64> = prices.iter().map(|p| p.price).collect();
-        let mean = values.iter().sum::<f64>() / values.len() as f64;
-        
-        // Calculate standard deviation
-        let variance = values.iter()
-            .map(|v| (v - mean).powi(2))
-            .sum::<f64>() / values.len() as f64;
-        let std_dev = variance.sqrt();
-
-        // Keep prices within 3 standard deviations
+        let Some(median) = self.median(prices) else {
+            return prices.to_vec();
+        };
+        let deviations: Option<Vec<Price>> = prices.iter().map(|p| distance(p.price, median)).collect();
+        let Some(mad) = deviations.and_then(median_of) else {
+            return prices.to_vec();
+        };
+
+        // Only the band's width is f64: it scales the exact MAD by constants,
+        // then quotes are compared with it as a price. A band too wide to
+        // fit even at the quotes' precision leaves every quote in.
+        let width = (OUTLIER_MADS * MAD_SCALE * mad.to_f64()).max(MIN_OUTLIER_BAND * median.to_f64().abs());
+        let expo = median.expo.min(mad.expo);
+        let Ok(band) = Price::from_f64(width, (expo - BAND_DECIMALS).max(MIN_EXPO))
+            .or_else(|_| Price::from_f64(width, expo))
+        else {
+            return prices.to_vec();
+        };
         prices
             .iter()
-            .filter(|p| (p.price - mean).abs() <= 3.0 * std_dev)
+            .filter(|p| distance(p.price, median).is_some_and(|deviation| deviation <= band))
             .cloned()
             .collect()
     }
 
-    /// Calculate weighted average
-    pub fn weighted_average(&self, prices: &[PriceData]) -> Option<f64> {
-        if prices.is_empty() {
-            return None;
-        }
+    /// Calculate weighted average, using the asset's exchange weights, at
+    /// the asset's exponent
+    pub fn weighted_average(&self, symbol: &str, prices: &[PriceData]) -> Option<Price> {
+        let weighted: Vec<(Price, f64)> = prices.iter()
+            .map(|price_data| {
+                let weight = self.assets.get(symbol)
+                    .map(|asset| asset.weight(&price_data.exchange))
+                    .unwrap_or(1.0);
+                (price_data.price, weight)
+            })
+            .collect();
+
+        pricing::weighted_mean(&weighted, self.expo(symbol))
+    }
 
-        let mut total = 0.0;
-        let mut weight_sum = 0.0;
-
-        for price_data in prices {
-            let weight = self.exchange_weights
-                .get(&price_data.exchange)
-                .copied()
-                .unwrap_or(1.0);
-            
-            total += price_data.price * weight;
-            weight_sum += weight;
-        }
+    /// Calculate median
+    pub fn median(&self, prices: &[PriceData]) -> Option<Price> {
+        median_of(prices.iter().map(|p| p.price).collect())
+    }
 
-        if weight_sum > 0.0 {
-            Some(total / weight_sum)
-        } else {
-            None
-        }
+    /// Volume-weighted median of the quotes, or the plain median if no
+    /// venue reports volume
+    pub fn volume_weighted_median(&self, prices: &[PriceData]) -> Option<Price> {
+        let weighted: Vec<(Price, f64)> = prices.iter()
+            .map(|p| (p.price, p.ticker.volume_24h.unwrap_or(0.0)))
+            .collect();
+
+        pricing::weighted_median(&weighted).or_else(|| self.median(prices))
     }
 
-    /// Calculate median
-    pub fn median(&self, prices: &[PriceData]) -> Option<f64> {
-        if prices.is_empty() {
-            return None;
+    /// Record this tick's quotes in the asset's window and read the volume
+    /// weighted mean or TWAP back; None for methods without a window
+    fn windowed_price(&self, asset: &AssetConfig, prices: &[PriceData], now_ms: i64) -> Option<Price> {
+        let mut windows = self.windows.lock();
+        let window = windows.entry(asset.symbol.clone())
+            .or_insert_with(|| PriceWindow::new(asset.window_secs));
+
+        match asset.method {
+            AggregationMethod::VolumeWeightedMean => {
+                for p in prices {
+                    window.push(Sample { price: p.price, volume: p.ticker.volume_24h.unwrap_or(0.0), at_ms: now_ms });
+                }
+                window.prune(now_ms);
+                window.volume_weighted_mean(asset.expo)
+            }
+            AggregationMethod::Twap => {
+                window.push(Sample { price: self.weighted_average(&asset.symbol, prices)?, volume: 0.0, at_ms: now_ms });
+                window.prune(now_ms);
+                window.twap(now_ms, asset.expo)
+            }
+            AggregationMethod::Mid | AggregationMethod::VolumeWeightedMedian => None,
         }
+    }
 
-        let mut values: Vec<f64> = prices.iter().map(|p| p.price).collect();
-        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-        let mid = values.len() / 2;
-        if values.len() % 2 == 0 {
-            Some((values[mid - 1] + values[mid]) / 2.0)
-        } else {
-            Some(values[mid])
+    /// Price of `symbol` by its configured method. Medians are one of the
+    /// quotes (or halfway between two) as quoted; means are at the asset's exponent.
+    pub fn compute_price(&self, symbol: &str, prices: &[PriceData], now_ms: i64) -> Option<Price> {
+        let Some(asset) = self.assets.get(symbol) else {
+            return self.weighted_average(symbol, prices);
+        };
+
+        match asset.method {
+            AggregationMethod::Mid => self.weighted_average(symbol, prices),
+            AggregationMethod::VolumeWeightedMedian => self.volume_weighted_median(prices),
+            // Venues without volume leave the mean empty; the median needs none
+            AggregationMethod::VolumeWeightedMean => self.windowed_price(asset, prices, now_ms)
+                .or_else(|| self.median(prices)),
+            AggregationMethod::Twap => self.windowed_price(asset, prices, now_ms)
+                .or_else(|| self.weighted_average(symbol, prices)),
         }
     }
 
-    /// Calculate confidence (based on spread)
-    pub fn confidence(&self, prices: &[PriceData]) -> f64 {
-        if prices.len() < 2 {
-            return 0.0;
+    /// Confidence interval around `price` from the venues' spreads and
+    /// dispersion, at the price's exponent. A single venue has no dispersion
+    /// to measure: it is trusted as fully as before, up to its own spread.
+    pub fn confidence(&self, prices: &[PriceData], price: Price) -> Option<Price> {
+        if let [single] = prices {
+            return single.ticker.half_spread()
+                .map_or(Ok(Price::new(0, price.expo)), |half_spread| half_spread.rescale(price.expo))
+                .ok();
         }
 
-        let values: Vec<f64> = prices.iter().map(|p| p.price).collect();
-        let mean = values.iter().sum::<f64>() / values.len() as f64;
-        
-        let max_deviation = values.iter()
-            .map(|v| (v - mean).abs() / mean)
-            .max_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap_or(0.0);
+        let quotes: Vec<(Price, Option<Price>)> = prices.iter()
+            .map(|p| (p.price, p.ticker.half_spread()))
+            .collect();
 
-        // Confidence decreases with spread
-        (1.0 - max_deviation).max(0.0)
+        pricing::confidence_interval(&quotes, price)
     }
 
-    /// Validate price is in reasonable range
-    pub fn validate_price(&self, symbol: &str, price: f64) -> bool {
-        if price <= 0.0 {
+    /// Validate price is within the asset's configured bounds
+    pub fn validate_price(&self, symbol: &str, price: Price) -> bool {
+        if price.mantissa <= 0 {
             return false;
         }
 
-        // Symbol-specific validation
-        match symbol {
-            s if s.contains("BTC") => price > 1000.0 && price < 1_000_000.0,
-            s if s.contains("ETH") => price > 10.0 && price < 100_000.0,
-            s if s.contains("SOL") => price > 0.1 && price < 10_000.0,
-            s if s.contains("XNT") => price > 0.0001 && price < 1000.0,
-            _ => price > 0.0 && price < 1_000_000.0,
+        match self.assets.get(symbol) {
+            Some(asset) => {
+                asset.min_price.is_none_or(|min| price >= min)
+                    && asset.max_price.is_none_or(|max| price <= max)
+            }
+            None => true,
         }
     }
 
@@ -345,66 +430,123 @@ impl RobustFetcher {
         (now - timestamp) > max_age_secs
     }
 
-    /// Full robust aggregation pipeline
-    pub async fn aggregate_price(
-        &mut self,
+    /// Full robust aggregation pipeline over fetched quotes. The price and
+    /// its confidence interval are at the asset's exponent.
+    pub fn aggregate_price(
+        &self,
         symbol: &str,
-        exchanges: &[String],
-    ) -> Result<(f64, f64)> {
-        // 1. Fetch from all exchanges
-        let mut prices = self.fetch_from_exchanges(symbol, exchanges).await;
-
+        mut prices: Vec<PriceData>,
+    ) -> Result<(Price, Price)> {
         if prices.is_empty() {
             return Err(anyhow::anyhow!("No prices fetched for {}", symbol));
         }
 
-        info!("📊 Fetched {} prices for {}", prices.len(), symbol);
-
-        // 2. Remove outliers
-        prices = self.remove_outliers(&prices);
-        info!("📊 After outlier removal: {} prices", prices.len());
-
-        if prices.is_empty() {
-            return Err(anyhow::anyhow!("All prices were outliers for {}", symbol));
-        }
-
-        // 3. Validate prices
-        prices.retain(|p| self.validate_price(symbol, p.price));
-        info!("📊 After validation: {} prices", prices.len());
+        // 1. Validate prices, before they can skew the outlier band
+        prices.retain(|p| {
+            let valid = self.validate_price(symbol, p.price);
+            if !valid {
+                warn!("⚠️  Rejected {} price {} from {}: out of bounds", symbol, p.price, p.exchange);
+            }
+            valid
+        });
+        debug!("📊 After validation: {} prices", prices.len());
 
         if prices.is_empty() {
             return Err(anyhow::anyhow!("No valid prices for {}", symbol));
         }
 
-        // 4. Check staleness
-        prices.retain(|p| !self.is_stale(p.timestamp, 60));
+        // 2. Remove outliers
+        prices = self.remove_outliers(&prices);
+        debug!("📊 After outlier removal: {} prices", prices.len());
+
+        // 3. Check staleness
+        prices.retain(|p| !self.is_stale(p.timestamp, MAX_PRICE_AGE_SECS));
 
         if prices.is_empty() {
             return Err(anyhow::anyhow!("All prices are stale for {}", symbol));
         }
 
-        // 5. Calculate weighted average
-        let price = self.weighted_average(&prices)
-            .ok_or_else(|| anyhow::anyhow!("Failed to calculate weighted average"))?;
+        // 4. Calculate price by the asset's method
+        let price = self.compute_price(symbol, &prices, chrono::Utc::now().timestamp_millis())
+            .and_then(|price| price.rescale(self.expo(symbol)).ok())
+            .ok_or_else(|| anyhow::anyhow!("Failed to calculate price for {}", symbol))?;
 
-        // 6. Calculate confidence
-        let confidence = self.confidence(&prices);
+        // 5. Calculate confidence interval
+        let confidence = self.confidence(&prices, price)
+            .ok_or_else(|| anyhow::anyhow!("Confidence for {} does not fit expo {}", symbol, price.expo))?;
 
-        info!("✅ Aggregated price for {}: ${:.2} (confidence: {:.2}%)", symbol, price, confidence * 100.0);
+        info!("✅ Aggregated price for {}: ${} (± ${})", symbol, price, confidence);
 
         Ok((price, confidence))
     }
 }
 
-impl Default for RobustFetcher {
-    fn default() -> Self {
-        Self::new()
+/// Median, halfway between the middle two of an even count; None if empty
+fn median_of(mut values: Vec<Price>) -> Option<Price> {
+    if values.is_empty() {
+        return None;
     }
+    values.sort();
+
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        values[mid - 1].midpoint(values[mid]).ok()
+    } else {
+        Some(values[mid])
+    }
+}
+
+/// `|a - b|`, exactly; None if it does not fit
+fn distance(a: Price, b: Price) -> Option<Price> {
+    let (high, low) = if a >= b { (a, b) } else { (b, a) };
+    high.checked_sub(low).ok()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use async_trait::async_trait;
+    use super::super::source::{px, PriceSource};
+
+    fn fetcher(assets: &[AssetConfig]) -> RobustFetcher {
+        RobustFetcher::new(Arc::new(SourceRegistry::default()), assets)
+    }
+
+    fn quote(exchange: &str, price: f64) -> PriceData {
+        PriceData::from_ticker(Ticker::from_last(px(&price.to_string())), exchange, chrono::Utc::now().timestamp()).unwrap()
+    }
+
+    fn book(exchange: &str, bid: f64, ask: f64, volume: f64) -> PriceData {
+        let ticker = Ticker {
+            bid: Some(px(&bid.to_string())),
+            ask: Some(px(&ask.to_string())),
+            last: None,
+            volume_24h: Some(volume),
+        };
+        PriceData::from_ticker(ticker, exchange, chrono::Utc::now().timestamp()).unwrap()
+    }
+
+    /// Fails for every symbol in `broken`, quotes 100.0 otherwise
+    struct FlakySource {
+        broken: Vec<String>,
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl PriceSource for FlakySource {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn fetch_ticker(&self, symbol: &str) -> Result<Ticker> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            if self.broken.iter().any(|broken| broken == symbol) {
+                return Err(anyhow::anyhow!("symbol delisted"));
+            }
+            Ok(Ticker::from_last(px("100")))
+        }
+    }
 
     #[test]
     fn test_circuit_breaker() {
@@ -422,13 +564,13 @@ mod tests {
 
     #[test]
     fn test_remove_outliers() {
-        let fetcher = RobustFetcher::new();
+        let fetcher = fetcher(&[]);
         
         let prices = vec![
-            PriceData { price: 100.0, exchange: "a".to_string(), timestamp: 1000 },
-            PriceData { price: 101.0, exchange: "b".to_string(), timestamp: 1000 },
-            PriceData { price: 102.0, exchange: "c".to_string(), timestamp: 1000 },
-            PriceData { price: 200.0, exchange: "d".to_string(), timestamp: 1000 }, // Outlier
+            quote("a", 100.0),
+            quote("b", 101.0),
+            quote("c", 102.0),
+            quote("d", 200.0), // Outlier
         ];
 
         let filtered = fetcher.remove_outliers(&prices);
@@ -437,63 +579,186 @@ mod tests {
 
     #[test]
     fn test_weighted_average() {
-        let mut fetcher = RobustFetcher::new();
-        fetcher.exchange_weights.insert("high".to_string(), 2.0);
-        fetcher.exchange_weights.insert("low".to_string(), 1.0);
+        let mut asset = AssetConfig::new("BTC/USD", &["high", "low"]);
+        asset.weights.insert("high".to_string(), 2.0);
+        let fetcher = fetcher(&[asset]);
 
         let prices = vec![
-            PriceData { price: 100.0, exchange: "high".to_string(), timestamp: 1000 },
-            PriceData { price: 110.0, exchange: "low".to_string(), timestamp: 1000 },
+            quote("high", 100.0),
+            quote("low", 110.0),
         ];
 
-        let avg = fetcher.weighted_average(&prices).unwrap();
-        // (100*2 + 110*1) / (2+1) = 310/3 = 103.33
-        assert!((avg - 103.33).abs() < 0.01);
+        let avg = fetcher.weighted_average("BTC/USD", &prices).unwrap();
+        // (100*2 + 110*1) / (2+1) = 310/3, at the default -8 exponent
+        assert_eq!(avg, px("103.33333333"));
     }
 
     #[test]
     fn test_median() {
-        let fetcher = RobustFetcher::new();
+        let fetcher = fetcher(&[]);
         
         let prices = vec![
-            PriceData { price: 100.0, exchange: "a".to_string(), timestamp: 1000 },
-            PriceData { price: 102.0, exchange: "b".to_string(), timestamp: 1000 },
-            PriceData { price: 101.0, exchange: "c".to_string(), timestamp: 1000 },
+            quote("a", 100.0),
+            quote("b", 102.0),
+            quote("c", 101.0),
         ];
 
         let median = fetcher.median(&prices).unwrap();
-        assert_eq!(median, 101.0);
+        assert_eq!(median, px("101"));
+
+        // Even count: halfway between the middle two, exactly
+        assert_eq!(fetcher.median(&prices[..2]).unwrap(), px("101"));
     }
 
     #[test]
     fn test_validate_price() {
-        let fetcher = RobustFetcher::new();
+        let mut btc = AssetConfig::new("BTC/USD", &["binance"]);
+        btc.min_price = Some(px("1000"));
+        btc.max_price = Some(px("1000000"));
+        let fetcher = fetcher(&[btc]);
         
-        assert!(fetcher.validate_price("BTC/USD", 50000.0));
-        assert!(!fetcher.validate_price("BTC/USD", 100.0)); // Too low
-        assert!(!fetcher.validate_price("BTC/USD", 2_000_000.0)); // Too high
-        assert!(!fetcher.validate_price("BTC/USD", -100.0)); // Negative
+        assert!(fetcher.validate_price("BTC/USD", px("50000")));
+        assert!(!fetcher.validate_price("BTC/USD", px("100"))); // Too low
+        assert!(!fetcher.validate_price("BTC/USD", px("2000000"))); // Too high
+        assert!(!fetcher.validate_price("BTC/USD", px("-100"))); // Negative
+        assert!(!fetcher.validate_price("BTC/USD", px("0")));
+
+        // The bounds themselves are in, anything past them by a digit is out
+        assert!(fetcher.validate_price("BTC/USD", px("1000.000000")));
+        assert!(!fetcher.validate_price("BTC/USD", px("999.999999999999")));
+        assert!(!fetcher.validate_price("BTC/USD", px("1000000.000001")));
+
+        // Assets without bounds only need a positive price
+        assert!(fetcher.validate_price("PEPE/USD", px("0.000001")));
     }
 
     #[test]
     fn test_confidence() {
-        let fetcher = RobustFetcher::new();
+        let fetcher = fetcher(&[]);
+        let hundred = px("100.00000000");
         
-        // Tight spread = high confidence
-        let prices1 = vec![
-            PriceData { price: 100.0, exchange: "a".to_string(), timestamp: 1000 },
-            PriceData { price: 101.0, exchange: "b".to_string(), timestamp: 1000 },
-        ];
-        let conf1 = fetcher.confidence(&prices1);
-        assert!(conf1 > 0.99);
+        // Tight spread = narrow interval
+        let prices1 = vec![book("a", 99.95, 100.05, 0.0), book("b", 99.95, 100.05, 0.0)];
+        assert_eq!(fetcher.confidence(&prices1, hundred), Some(px("0.05")));
+
+        // Wide spread = wide interval
+        let prices2 = vec![book("a", 99.0, 101.0, 0.0), book("b", 99.0, 101.0, 0.0)];
+        assert_eq!(fetcher.confidence(&prices2, hundred), Some(px("1")));
+
+        // Venues disagreeing widen it too
+        let prices3 = vec![quote("a", 100.0), quote("b", 150.0)];
+        assert_eq!(fetcher.confidence(&prices3, px("125")), Some(px("25")));
+
+        // A single venue is only as uncertain as its book, at the price's exponent
+        assert_eq!(fetcher.confidence(&[book("a", 99.0, 101.0, 0.0)], hundred), Some(px("1")));
+        assert_eq!(fetcher.confidence(&[book("a", 99.95, 100.05, 0.0)], px("100")), Some(px("0")));
+        assert_eq!(fetcher.confidence(&[quote("a", 100.0)], hundred), Some(px("0")));
+    }
 
-        // Wide spread = low confidence
-        let prices2 = vec![
-            PriceData { price: 100.0, exchange: "a".to_string(), timestamp: 1000 },
-            PriceData { price: 150.0, exchange: "b".to_string(), timestamp: 1000 },
-        ];
-        let conf2 = fetcher.confidence(&prices2);
-        assert!(conf2 < 0.8);
+    #[test]
+    fn test_compute_price_by_method() {
+        let prices = vec![book("a", 99.0, 101.0, 10.0), book("b", 101.0, 103.0, 10.0), book("c", 109.0, 111.0, 80.0)];
+        let now_ms = 1_700_000_000_000;
+        let with_method = |method| {
+            let mut asset = AssetConfig::new("BTC/USD", &["a", "b", "c"]);
+            asset.method = method;
+            fetcher(&[asset])
+        };
+
+        assert_eq!(with_method(AggregationMethod::Mid).compute_price("BTC/USD", &prices, now_ms), Some(px("104")));
+        assert_eq!(with_method(AggregationMethod::VolumeWeightedMedian).compute_price("BTC/USD", &prices, now_ms), Some(px("110")));
+        assert_eq!(with_method(AggregationMethod::VolumeWeightedMean).compute_price("BTC/USD", &prices, now_ms), Some(px("108.2")));
+
+        // TWAP holds the previous tick's mid until this one
+        let twap = with_method(AggregationMethod::Twap);
+        assert_eq!(twap.compute_price("BTC/USD", &[quote("a", 90.0)], now_ms), Some(px("90")));
+        assert_eq!(twap.compute_price("BTC/USD", &[quote("a", 120.0)], now_ms + 1_000), Some(px("90")));
+        assert_eq!(twap.compute_price("BTC/USD", &[quote("a", 120.0)], now_ms + 4_000), Some(px("112.5")));
+
+        // No volume reported: the volume weighted mean falls back to the median
+        let volume_weighted = with_method(AggregationMethod::VolumeWeightedMean);
+        let unweighted = [quote("a", 100.0), quote("b", 102.0), quote("c", 110.0)];
+        assert_eq!(volume_weighted.compute_price("BTC/USD", &unweighted, now_ms), Some(px("102")));
     }
-}
 
+    #[tokio::test]
+    async fn test_breakers_are_per_asset() {
+        let source = Arc::new(FlakySource { broken: vec!["LUNA/USD".to_string()], calls: AtomicU32::new(0) });
+        let mut registry = SourceRegistry::default();
+        registry.register(source.clone());
+
+        let mut luna = AssetConfig::new("LUNA/USD", &["flaky"]);
+        luna.breaker_threshold = 2;
+        let btc = AssetConfig::new("BTC/USD", &["flaky"]);
+        let fetcher = RobustFetcher::new(Arc::new(registry), &[luna.clone(), btc.clone()]).with_retries(1, 0);
+        let deadline = Duration::from_secs(1);
+
+        assert!(matches!(fetcher.fetch_price_robust(&luna, "flaky", deadline).await.0, FetchOutcome::Failed(_)));
+        assert!(matches!(fetcher.fetch_price_robust(&luna, "flaky", deadline).await.0, FetchOutcome::Failed(_)));
+        let calls = source.calls.load(Ordering::Relaxed);
+
+        // Tripped for LUNA/USD: no more requests until the timeout passes
+        assert_eq!(fetcher.fetch_price_robust(&luna, "flaky", deadline).await.0, FetchOutcome::BreakerOpen);
+        assert_eq!(source.calls.load(Ordering::Relaxed), calls);
+
+        // BTC/USD on the same source is unaffected
+        assert_eq!(fetcher.fetch_price_robust(&btc, "flaky", deadline).await.0, FetchOutcome::Ticker(Ticker::from_last(px("100"))));
+
+        let breakers = fetcher.breakers();
+        assert_eq!(breakers["LUNA/USD"]["flaky"].state, CircuitState::Open);
+        assert_eq!(breakers["BTC/USD"]["flaky"].state, CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_aggregate_price() {
+        let mut btc = AssetConfig::new("BTC/USD", &["a", "b", "c", "d", "e"]);
+        btc.min_price = Some(px("1000"));
+        btc.weights.insert("a".to_string(), 3.0);
+        let fetcher = fetcher(&[btc]);
+
+        let (price, confidence) = fetcher.aggregate_price("BTC/USD", vec![
+            quote("a", 50_000.0),
+            quote("b", 50_100.0),
+            quote("c", 49_900.0),
+            quote("d", 65_000.0), // Outlier
+            quote("e", 0.5),      // Out of bounds
+        ]).unwrap();
+
+        // (50_000*3 + 50_100 + 49_900) / 5
+        assert_eq!(price, px("50000"));
+        // RMS distance of the three remaining quotes from the price
+        assert_eq!(confidence, px("81.64965809"));
+
+        assert!(fetcher.aggregate_price("BTC/USD", vec![quote("e", 0.5)]).is_err());
+
+        // Quotes count from when they were received
+        let old = PriceData { timestamp: chrono::Utc::now().timestamp() - MAX_PRICE_AGE_SECS - 1, ..quote("b", 50_100.0) };
+        let (price, _) = fetcher.aggregate_price("BTC/USD", vec![quote("a", 50_000.0), old.clone()]).unwrap();
+        assert_eq!(price, px("50000"));
+        assert!(fetcher.aggregate_price("BTC/USD", vec![old]).is_err());
+    }
+
+    #[test]
+    fn test_aggregate_keeps_digits_f64_cannot_hold() {
+        let mut asset = AssetConfig::new("BIG/USD", &["a", "b", "c"]);
+        asset.expo = -10;
+        let fetcher = fetcher(&[asset]);
+        let at = |exchange: &str, price: &str| {
+            PriceData::from_ticker(Ticker::from_last(px(price)), exchange, chrono::Utc::now().timestamp()).unwrap()
+        };
+
+        // 987654321234567891 is past 2^53: as f64 it would come back ...7936
+        let (price, _) = fetcher.aggregate_price("BIG/USD", vec![
+            at("a", "98765432.1234567891"),
+            at("b", "98765432.1234567891"),
+            at("c", "98765432.1234567891"),
+        ]).unwrap();
+        assert_eq!((price.mantissa, price.expo), (987_654_321_234_567_891, -10));
+
+        let (price, _) = fetcher.aggregate_price("BIG/USD", vec![
+            at("a", "98765432.1234567891"),
+            at("b", "98765432.1234567893"),
+        ]).unwrap();
+        assert_eq!(price, px("98765432.1234567892"));
+    }
+}
@@ -0,0 +1,394 @@
+// Price Sources - pluggable venues behind one trait
+//
+// Every venue the fetcher can query is a `PriceSource` registered by name.
+// Built-in exchanges are always available; `[[sources]]` entries in the node
+// config add JSON-over-HTTP or subprocess sources, or replace a built-in with
+// the same name.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tachyon_merkle::price::{Price, PriceError, MIN_EXPO};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::info;
+
+use crate::config::{NodeConfig, SourceConfig, SourceKind};
+use super::exchanges;
+use super::json_http::{JsonHttpSource, TickerPaths};
+use super::subprocess::SubprocessSource;
+
+/// Decimals added when a price is converted at a rate, so the result isn't
+/// rounded to the venue's tick
+const SCALE_DECIMALS: i32 = 4;
+
+/// A venue's view of one market; venues fill in what they publish. Prices
+/// are exact, at the decimals the venue quoted them with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ticker {
+    pub bid: Option<Price>,
+    pub ask: Option<Price>,
+    pub last: Option<Price>,
+    /// Traded over the last 24 hours, in base units
+    pub volume_24h: Option<f64>,
+}
+
+impl Ticker {
+    pub fn from_last(price: Price) -> Self {
+        Self {
+            last: Some(price),
+            ..Self::default()
+        }
+    }
+
+    /// Middle of the book, if both sides are known and not crossed
+    pub fn mid(&self) -> Option<Price> {
+        match (self.bid, self.ask) {
+            (Some(bid), Some(ask)) if bid.mantissa > 0 && ask >= bid => bid.midpoint(ask).ok(),
+            _ => None,
+        }
+    }
+
+    /// Mid when the book is known, else the last trade
+    pub fn price(&self) -> Option<Price> {
+        self.mid().or(self.last)
+    }
+
+    /// Half the bid-ask spread
+    pub fn half_spread(&self) -> Option<Price> {
+        self.mid().zip(self.ask).and_then(|(mid, ask)| ask.checked_sub(mid).ok())
+    }
+
+    /// Prices multiplied by `rate`, e.g. to convert USDT prices to USD, each
+    /// rounded once; fails if a price no longer fits. Volume is in base units
+    /// and stays as is.
+    pub fn scale(&self, rate: Price) -> Result<Ticker, PriceError> {
+        let scale = |price: Option<Price>| price
+            .map(|price| price.checked_mul(rate, (price.expo - SCALE_DECIMALS).max(MIN_EXPO)))
+            .transpose();
+
+        Ok(Ticker {
+            bid: scale(self.bid)?,
+            ask: scale(self.ask)?,
+            last: scale(self.last)?,
+            volume_24h: self.volume_24h,
+        })
+    }
+
+    /// `newer` on top of `self`: fields it doesn't carry keep their value
+    pub fn merge(&self, newer: &Ticker) -> Ticker {
+        Ticker {
+            bid: newer.bid.or(self.bid),
+            ask: newer.ask.or(self.ask),
+            last: newer.last.or(self.last),
+            volume_24h: newer.volume_24h.or(self.volume_24h),
+        }
+    }
+}
+
+/// A source's answer to one request: the response as the source sent it,
+/// when there was one, and the ticker read from it
+#[derive(Debug)]
+pub struct SourceAnswer {
+    pub body: Option<String>,
+    pub ticker: Result<Ticker>,
+}
+
+impl SourceAnswer {
+    /// No response to keep, e.g. the request never got through
+    pub fn failed(error: anyhow::Error) -> Self {
+        Self { body: None, ticker: Err(error) }
+    }
+}
+
+/// A venue that can quote a symbol such as "BTC/USD"
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Latest ticker for `symbol`; the source maps it to its own symbol format
+    async fn fetch_ticker(&self, symbol: &str) -> Result<Ticker>;
+
+    /// Latest ticker for `symbol` with the raw response it was read from.
+    /// Sources that read a response override this to keep it for recordings.
+    async fn fetch_answer(&self, symbol: &str) -> SourceAnswer {
+        SourceAnswer { body: None, ticker: self.fetch_ticker(symbol).await }
+    }
+
+    /// Quote currency this source lists `quote` markets in, e.g. USDT for USD
+    fn listed_quote(&self, quote: &str) -> String {
+        quote.to_string()
+    }
+}
+
+/// Exact price from a JSON number or numeric string. Numbers are read back
+/// from their shortest decimal form, which is the text the venue sent unless
+/// it carried more than 17 significant digits.
+pub fn json_price(value: &Value) -> Result<Price> {
+    match value {
+        Value::Number(number) => Ok(number.to_string().parse()?),
+        Value::String(text) => Ok(text.parse()?),
+        other => Err(anyhow::anyhow!("Price is not a number: {}", other)),
+    }
+}
+
+/// Price literal for tests
+#[cfg(test)]
+pub fn px(text: &str) -> Price {
+    text.parse().unwrap()
+}
+
+/// "BTC/USD" as ("BTC", "USD"); a symbol without a slash has no quote
+pub fn split_symbol(symbol: &str) -> (&str, &str) {
+    symbol.split_once('/').unwrap_or((symbol, ""))
+}
+
+/// Maps "BASE/QUOTE" symbols to a venue's format
+#[derive(Debug, Clone)]
+pub struct SymbolMap {
+    format: String,
+    overrides: HashMap<String, String>,
+    quotes: HashMap<String, String>,
+}
+
+impl SymbolMap {
+    pub fn new(format: &str) -> Self {
+        Self {
+            format: format.to_string(),
+            overrides: HashMap::new(),
+            quotes: HashMap::new(),
+        }
+    }
+
+    pub fn with_overrides(mut self, overrides: HashMap<String, String>) -> Self {
+        self.overrides.extend(overrides);
+        self
+    }
+
+    /// Quote currencies the venue lists instead of ours, e.g. USD -> USDT
+    pub fn with_quotes(mut self, quotes: HashMap<String, String>) -> Self {
+        self.quotes.extend(quotes);
+        self
+    }
+
+    pub fn listed_quote(&self, quote: &str) -> String {
+        self.quotes.get(quote).cloned().unwrap_or_else(|| quote.to_string())
+    }
+
+    pub fn map(&self, symbol: &str) -> String {
+        if let Some(mapped) = self.overrides.get(symbol) {
+            return mapped.clone();
+        }
+
+        let (base, quote) = split_symbol(symbol);
+        self.format
+            .replace("{base_lower}", &base.to_lowercase())
+            .replace("{quote_lower}", &quote.to_lowercase())
+            .replace("{base}", base)
+            .replace("{quote}", quote)
+    }
+}
+
+/// Spaces requests to a source at least `1 / per_sec` apart
+#[derive(Debug)]
+pub struct RateLimiter {
+    min_interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(per_sec: f64) -> Self {
+        let min_interval = if per_sec > 0.0 {
+            Duration::from_secs_f64(1.0 / per_sec)
+        } else {
+            Duration::ZERO
+        };
+
+        Self {
+            min_interval,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for this caller's slot
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.min_interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Price sources by name
+#[derive(Default, Clone)]
+pub struct SourceRegistry {
+    sources: HashMap<String, Arc<dyn PriceSource>>,
+}
+
+impl SourceRegistry {
+    /// Built-in exchanges plus every source defined in config
+    pub fn from_config(config: &NodeConfig) -> Result<Self> {
+        let client = reqwest::Client::new();
+        let mut registry = Self::default();
+
+        for source in exchanges::builtin_sources(&client, &config.exchanges) {
+            registry.register(source);
+        }
+        for source in &config.sources {
+            info!("📊 Registering price source {}", source.name);
+            registry.register(build_source(&client, source)?);
+        }
+
+        Ok(registry)
+    }
+
+    /// Add a source, replacing any source with the same name
+    pub fn register(&mut self, source: Arc<dyn PriceSource>) {
+        self.sources.insert(source.name().to_string(), source);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn PriceSource>> {
+        self.sources.get(name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.sources.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// `symbol` with the quote currency the source `name` lists it in
+    pub fn listed_symbol(&self, name: &str, symbol: &str) -> String {
+        let (base, quote) = split_symbol(symbol);
+        match self.get(name) {
+            Some(source) => format!("{}/{}", base, source.listed_quote(quote)),
+            None => symbol.to_string(),
+        }
+    }
+
+    /// Fetch `symbol` from the source called `name`
+    pub async fn fetch_answer(&self, name: &str, symbol: &str) -> SourceAnswer {
+        let Some(source) = self.get(name) else {
+            return SourceAnswer::failed(anyhow::anyhow!("Unknown price source: {}", name));
+        };
+        let mut answer = source.fetch_answer(symbol).await;
+        if answer.ticker.as_ref().is_ok_and(|ticker| ticker.price().is_none()) {
+            answer.ticker = Err(anyhow::anyhow!("{} returned no price for {}", name, symbol));
+        }
+        answer
+    }
+}
+
+fn build_source(client: &reqwest::Client, config: &SourceConfig) -> Result<Arc<dyn PriceSource>> {
+    let symbols = SymbolMap::new(&config.symbol_format)
+        .with_overrides(config.symbols.clone())
+        .with_quotes(config.quotes.clone());
+    let limiter = RateLimiter::new(config.rate_limit_per_sec);
+
+    Ok(match &config.kind {
+        SourceKind::JsonHttp { url, price_path, bid_path, ask_path, volume_path, headers } => Arc::new(JsonHttpSource::new(
+            &config.name,
+            client.clone(),
+            url,
+            TickerPaths::parse(price_path, bid_path.as_deref(), ask_path.as_deref(), volume_path.as_deref())?,
+            headers.clone(),
+            symbols,
+            limiter,
+        )?),
+        SourceKind::Subprocess { command, args } => Arc::new(SubprocessSource::new(
+            &config.name,
+            command,
+            args.clone(),
+            symbols,
+            limiter,
+        )),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_map() {
+        assert_eq!(SymbolMap::new("{base}{quote}").map("BTC/USD"), "BTCUSD");
+        assert_eq!(SymbolMap::new("{base}-{quote}").map("ETH/USDT"), "ETH-USDT");
+        assert_eq!(SymbolMap::new("{base_lower}_{quote_lower}").map("SOL/USD"), "sol_usd");
+
+        let kraken = SymbolMap::new("{base}{quote}")
+            .with_overrides(HashMap::from([("BTC/USD".to_string(), "XBTUSD".to_string())]));
+        assert_eq!(kraken.map("BTC/USD"), "XBTUSD");
+        assert_eq!(kraken.map("ETH/USD"), "ETHUSD");
+
+        let binance = SymbolMap::new("{base}{quote}")
+            .with_quotes(HashMap::from([("USD".to_string(), "USDT".to_string())]));
+        assert_eq!((binance.listed_quote("USD"), binance.listed_quote("EUR")), ("USDT".to_string(), "EUR".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_spaces_requests() {
+        let limiter = RateLimiter::new(20.0);
+        let start = Instant::now();
+
+        // First call is immediate, the next two wait 50ms each
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_ticker_price() {
+        let book = Ticker { bid: Some(px("99")), ask: Some(px("101")), last: Some(px("100.5")), volume_24h: None };
+        assert_eq!(book.price(), Some(px("100")));
+        assert_eq!(book.half_spread(), Some(px("1")));
+
+        // Exact to the last digit, however the sides are quoted
+        let odd = Ticker { bid: Some(px("0.1")), ask: Some(px("0.25")), ..Ticker::default() };
+        assert_eq!((odd.mid(), odd.half_spread()), (Some(px("0.175")), Some(px("0.075"))));
+
+        // Crossed or one-sided books fall back to the last trade
+        let crossed = Ticker { bid: Some(px("101")), ask: Some(px("99")), ..Ticker::from_last(px("100.5")) };
+        assert_eq!(crossed.price(), Some(px("100.5")));
+        assert_eq!(crossed.half_spread(), None);
+
+        let merged = book.merge(&Ticker { bid: Some(px("99.5")), volume_24h: Some(10.0), ..Ticker::default() });
+        assert_eq!((merged.bid, merged.ask, merged.volume_24h), (Some(px("99.5")), Some(px("101")), Some(10.0)));
+
+        // Converted at a rate with extra decimals
+        assert_eq!(Ticker::from_last(px("0.00001234")).scale(px("0.998")).unwrap().last, Some(px("0.000012315320")));
+        assert_eq!(json_price(&serde_json::json!(49999.9)).unwrap(), px("49999.9"));
+        assert_eq!(json_price(&serde_json::json!("1e-7")).unwrap(), px("0.0000001"));
+        assert!(json_price(&serde_json::json!(true)).is_err());
+    }
+
+    #[test]
+    fn test_sources_from_config() {
+        let toml = r#"
+            name = "coingecko"
+            type = "json_http"
+            url = "https://api.coingecko.com/api/v3/simple/price?ids={symbol}&vs_currencies=usd"
+            price_path = "*.usd"
+            symbol_format = "{base_lower}"
+            symbols = { "BTC/USD" = "bitcoin" }
+            quotes = { USD = "USDC" }
+            rate_limit_per_sec = 0.5
+        "#;
+        let source: SourceConfig = toml::from_str(toml).unwrap();
+        assert!(matches!(source.kind, SourceKind::JsonHttp { .. }));
+        assert_eq!(source.symbols["BTC/USD"], "bitcoin");
+
+        let mut registry = SourceRegistry::default();
+        registry.register(build_source(&reqwest::Client::new(), &source).unwrap());
+        assert_eq!(registry.names(), vec!["coingecko".to_string()]);
+        assert_eq!(registry.listed_symbol("coingecko", "BTC/USD"), "BTC/USDC");
+        assert_eq!(registry.listed_symbol("coingecko", "BTC/EUR"), "BTC/EUR");
+    }
+}
@@ -0,0 +1,177 @@
+// Stablecoins - depeg-aware quote normalization
+//
+// Venues list the same market against different dollars: Coinbase and Kraken
+// quote BTC/USD, Binance and Bybit BTC/USDT. The fetcher tracks USDT/USD,
+// USDC/USD and the like as internal feeds, and every venue ticker is
+// converted into the asset's quote currency through the latest rate before
+// aggregation. A venue quoted in a stablecoin that is off par by more than
+// the configured threshold is flagged and left out of aggregation until the
+// rate is back within it.
+
+use std::collections::{BTreeMap, HashMap};
+use anyhow::{Context, Result};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tachyon_merkle::price::{Price, PriceError, MIN_EXPO};
+
+use super::source::Ticker;
+
+/// Rates older than this are not used for conversion (seconds)
+const MAX_RATE_AGE_SECS: i64 = 60;
+
+/// Decimals of a rate taken the other way round, e.g. USD/USDT from USDT/USD
+const INVERSE_RATE_EXPO: i32 = -12;
+
+/// Latest aggregated rate of one stablecoin feed, e.g. USDT/USD
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StableRate {
+    pub rate: Price,
+    pub confidence: Price,
+    pub updated_at: i64,
+    /// Further from par than the depeg threshold
+    pub depegged: bool,
+}
+
+/// A venue ticker in the asset's quote currency
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalized {
+    pub ticker: Ticker,
+    /// Rate minus par of the stablecoin it was converted through, if any
+    pub basis: Option<f64>,
+    pub depegged: bool,
+}
+
+/// Stablecoin rates by feed symbol, shared by all fetch tasks
+#[derive(Debug)]
+pub struct QuoteNormalizer {
+    rates: RwLock<HashMap<String, StableRate>>,
+    /// Rates within the depeg threshold of par, inclusive
+    peg: (Price, Price),
+}
+
+impl QuoteNormalizer {
+    pub fn new(depeg_threshold_bps: u32) -> Self {
+        let bps = i64::from(depeg_threshold_bps);
+        Self {
+            rates: RwLock::new(HashMap::new()),
+            peg: (Price::new(10_000 - bps, -4), Price::new(10_000 + bps, -4)),
+        }
+    }
+
+    /// Record the latest aggregate of a stablecoin feed
+    pub fn update(&self, symbol: &str, rate: Price, confidence: Price, updated_at: i64) -> StableRate {
+        let (low, high) = self.peg;
+        let stable = StableRate {
+            rate,
+            confidence,
+            updated_at,
+            depegged: rate < low || rate > high,
+        };
+        self.rates.write().insert(symbol.to_string(), stable);
+        stable
+    }
+
+    /// Price of one `from` in `to`, from the FROM/TO feed or the inverse of
+    /// TO/FROM; an error if neither is tracked, the rate is stale or its
+    /// inverse doesn't fit
+    pub fn rate(&self, from: &str, to: &str, now: i64) -> Result<StableRate> {
+        let rates = self.rates.read();
+        let fresh = |rate: &&StableRate| now - rate.updated_at <= MAX_RATE_AGE_SECS && rate.rate.mantissa > 0;
+
+        if let Some(direct) = rates.get(&format!("{}/{}", from, to)).filter(fresh) {
+            return Ok(*direct);
+        }
+        let inverse = rates.get(&format!("{}/{}", to, from)).filter(fresh)
+            .ok_or_else(|| anyhow::anyhow!("No fresh {}/{} rate to convert with", from, to))?;
+
+        // d(1/r) = dr / r², with r² exact unless finer than MIN_EXPO
+        let invert = || -> Result<StableRate, PriceError> {
+            let squared = inverse.rate.checked_mul(inverse.rate, (2 * inverse.rate.expo).max(MIN_EXPO))?;
+            Ok(StableRate {
+                rate: Price::new(1, 0).checked_div(inverse.rate, INVERSE_RATE_EXPO)?,
+                confidence: inverse.confidence.checked_div(squared, inverse.confidence.expo)?,
+                ..*inverse
+            })
+        };
+        invert().with_context(|| format!("Cannot invert {}/{} rate {}", to, from, inverse.rate))
+    }
+
+    /// Convert a ticker quoted in `listed` into `quote`
+    pub fn normalize(&self, ticker: Ticker, listed: &str, quote: &str, now: i64) -> Result<Normalized> {
+        if listed == quote {
+            return Ok(Normalized { ticker, basis: None, depegged: false });
+        }
+
+        let rate = self.rate(listed, quote, now)?;
+
+        Ok(Normalized {
+            ticker: ticker.scale(rate.rate)
+                .with_context(|| format!("Cannot convert {} prices to {} at {}", listed, quote, rate.rate))?,
+            basis: Some(rate.rate.to_f64() - 1.0),
+            depegged: rate.depegged,
+        })
+    }
+
+    /// Latest rates for stats
+    pub fn rates(&self) -> BTreeMap<String, StableRate> {
+        self.rates.read().iter().map(|(symbol, rate)| (symbol.clone(), *rate)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::source::px;
+
+    #[test]
+    fn test_normalize_through_stablecoin() {
+        let normalizer = QuoteNormalizer::new(50);
+        let now = 1_700_000_000;
+        let btc_usdt = Ticker { bid: Some(px("49990")), ask: Some(px("50010")), last: Some(px("50000")), volume_24h: Some(12.0) };
+
+        // Native quote: untouched
+        let native = normalizer.normalize(btc_usdt, "USD", "USD", now).unwrap();
+        assert_eq!((native.ticker, native.basis), (btc_usdt, None));
+
+        // No rate yet
+        assert!(normalizer.normalize(btc_usdt, "USDT", "USD", now).is_err());
+
+        normalizer.update("USDT/USD", px("0.998"), px("0.0005"), now);
+        let normalized = normalizer.normalize(btc_usdt, "USDT", "USD", now).unwrap();
+        assert_eq!(normalized.ticker.last, Some(px("49900")));
+        assert_eq!(normalized.ticker.volume_24h, Some(12.0));
+        assert!((normalized.basis.unwrap() + 0.002).abs() < 1e-12);
+        assert!(!normalized.depegged);
+
+        // Rate quoted the other way round
+        let usd = normalizer.normalize(Ticker::from_last(px("0.998")), "USD", "USDT", now).unwrap();
+        assert_eq!(usd.ticker.last, Some(px("1")));
+        let inverse = normalizer.rate("USD", "USDT", now).unwrap();
+        assert_eq!((inverse.rate, inverse.confidence), (px("1.002004008016"), px("0.0005")));
+
+        // A price that no longer fits fails the conversion instead of dropping it
+        let huge = Ticker::from_last(Price::new(i64::MAX / 2, 0));
+        assert!(normalizer.normalize(huge, "USDT", "USD", now).is_err());
+
+        // Stale rate
+        assert!(normalizer.normalize(btc_usdt, "USDT", "USD", now + 61).is_err());
+    }
+
+    #[test]
+    fn test_depeg_flag() {
+        let normalizer = QuoteNormalizer::new(50);
+        let now = 1_700_000_000;
+
+        assert!(!normalizer.update("USDC/USD", px("0.996"), px("0.001"), now).depegged);
+        // The threshold is exact: 50 bps off par is still pegged, a hair past it is not
+        assert!(!normalizer.update("USDC/USD", px("0.995"), px("0.001"), now).depegged);
+        assert!(!normalizer.update("USDC/USD", px("1.005000000"), px("0.001"), now).depegged);
+        assert!(normalizer.update("USDC/USD", px("0.99499999"), px("0.001"), now).depegged);
+        assert!(normalizer.update("USDC/USD", px("0.87"), px("0.01"), now).depegged);
+
+        let normalized = normalizer.normalize(Ticker::from_last(px("100")), "USDC", "USD", now).unwrap();
+        assert!(normalized.depegged);
+        assert_eq!(normalized.ticker.last, Some(px("87")));
+        assert!(normalizer.rates()["USDC/USD"].depegged);
+    }
+}
@@ -0,0 +1,133 @@
+// Fetcher Stats - per-source latency and success counters
+//
+// Updated by the price fetcher after every request and served on `/fetcher`
+// and `/metrics`.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+use super::robust_fetcher::CircuitBreaker;
+use super::source::Ticker;
+use super::stablecoins::StableRate;
+
+/// How a single request to a source ended
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchOutcome {
+    Ticker(Ticker),
+    Failed(String),
+    /// No answer within the per-request deadline
+    TimedOut,
+    /// Not sent: the source's circuit breaker is open for this asset
+    BreakerOpen,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SourceStats {
+    pub requests: u64,
+    pub successes: u64,
+    pub failures: u64,
+    pub timeouts: u64,
+    /// Requests not sent because the breaker was open
+    pub skipped: u64,
+    /// Sum of all request latencies, for the average
+    pub total_latency_ms: u64,
+    pub last_latency_ms: u64,
+    pub last_error: Option<String>,
+    /// Quotes converted through a stablecoin past the depeg threshold
+    pub depeg_flags: u64,
+    /// Basis of the last stablecoin conversion (basis points)
+    pub last_basis_bps: Option<f64>,
+}
+
+impl SourceStats {
+    pub fn success_rate(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.successes as f64 / self.requests as f64
+    }
+
+    pub fn avg_latency_ms(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.total_latency_ms as f64 / self.requests as f64
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FetcherStats {
+    /// Ticks that fetched prices
+    pub ticks: u64,
+    /// Ticks skipped because the previous one was still running
+    pub ticks_skipped: u64,
+    pub sources: BTreeMap<String, SourceStats>,
+    /// Circuit breakers by asset, then source
+    pub breakers: BTreeMap<String, BTreeMap<String, CircuitBreaker>>,
+    /// Latest internal stablecoin rates, e.g. USDT/USD
+    pub stablecoins: BTreeMap<String, StableRate>,
+}
+
+impl FetcherStats {
+    pub fn record(&mut self, source: &str, latency: Duration, outcome: &FetchOutcome) {
+        let stats = self.sources.entry(source.to_string()).or_default();
+        if *outcome == FetchOutcome::BreakerOpen {
+            stats.skipped += 1;
+            return;
+        }
+
+        let latency_ms = latency.as_millis() as u64;
+
+        stats.requests += 1;
+        stats.total_latency_ms += latency_ms;
+        stats.last_latency_ms = latency_ms;
+
+        match outcome {
+            FetchOutcome::Ticker(_) => stats.successes += 1,
+            FetchOutcome::Failed(error) => {
+                stats.failures += 1;
+                stats.last_error = Some(error.clone());
+            }
+            FetchOutcome::TimedOut => {
+                stats.timeouts += 1;
+                stats.last_error = Some("timed out".to_string());
+            }
+            FetchOutcome::BreakerOpen => {}
+        }
+    }
+
+    /// Note the stablecoin basis of a source's converted quote
+    pub fn record_basis(&mut self, source: &str, basis: f64, depegged: bool) {
+        let stats = self.sources.entry(source.to_string()).or_default();
+        stats.last_basis_bps = Some(basis * 10_000.0);
+        if depegged {
+            stats.depeg_flags += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::source::px;
+
+    #[test]
+    fn test_record_outcomes() {
+        let mut stats = FetcherStats::default();
+        stats.record("binance", Duration::from_millis(40), &FetchOutcome::Ticker(Ticker::from_last(px("1"))));
+        stats.record("binance", Duration::from_millis(20), &FetchOutcome::Failed("HTTP 500".to_string()));
+        stats.record("binance", Duration::from_millis(90), &FetchOutcome::TimedOut);
+        stats.record("binance", Duration::from_millis(10), &FetchOutcome::Ticker(Ticker::from_last(px("1"))));
+        stats.record("binance", Duration::ZERO, &FetchOutcome::BreakerOpen);
+
+        let binance = &stats.sources["binance"];
+        assert_eq!((binance.requests, binance.successes, binance.failures, binance.timeouts), (4, 2, 1, 1));
+        assert_eq!(binance.success_rate(), 0.5);
+        assert_eq!(binance.avg_latency_ms(), 40.0);
+        assert_eq!(binance.last_latency_ms, 10);
+        assert_eq!(binance.skipped, 1);
+        assert_eq!(binance.last_error.as_deref(), Some("timed out"));
+    }
+}
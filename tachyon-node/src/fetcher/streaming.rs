@@ -0,0 +1,443 @@
+// Streaming Sources - exchange WebSocket feeds with REST fallback
+//
+// One long-lived connection per exchange keeps the latest bid/ask/last of
+// every subscribed symbol in memory, and the fetch tick reads that cache. A
+// symbol without a live quote (not received yet, connection down, sequence
+// gap) is fetched from the exchange's REST ticker instead.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use parking_lot::RwLock;
+use tachyon_merkle::Price;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep, sleep_until, Instant, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+use crate::config::NodeConfig;
+use super::exchange_streams::{Sequence, StreamMessage, TickerUpdate};
+use super::exchanges::Venue;
+use super::source::{PriceSource, SourceAnswer, SourceRegistry, SymbolMap, Ticker};
+
+/// How often we ping each venue
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// A connection silent for this long is dead, even if the socket is open
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Quotes older than this are not served, even on a live connection
+const MAX_QUOTE_AGE: Duration = Duration::from_secs(60);
+
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// Latest quote for one symbol
+#[derive(Debug, Clone, Copy)]
+pub struct Quote {
+    pub ticker: Ticker,
+    pub received_at: Instant,
+}
+
+impl Quote {
+    /// Mid when both sides are known, else the last trade
+    pub fn price(&self) -> Option<Price> {
+        self.ticker.price()
+    }
+}
+
+/// Live quotes of one connection, keyed by our symbol ("BTC/USD")
+#[derive(Debug, Default)]
+pub struct QuoteCache {
+    quotes: RwLock<HashMap<String, Quote>>,
+}
+
+impl QuoteCache {
+    /// Merge an update; fields it doesn't carry keep their previous value
+    pub fn update(&self, symbol: &str, update: &TickerUpdate) {
+        let mut quotes = self.quotes.write();
+        let previous = quotes.get(symbol).map(|quote| quote.ticker).unwrap_or_default();
+        quotes.insert(symbol.to_string(), Quote {
+            ticker: previous.merge(&update.ticker),
+            received_at: Instant::now(),
+        });
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<Quote> {
+        self.quotes.read().get(symbol)
+            .filter(|quote| quote.received_at.elapsed() <= MAX_QUOTE_AGE)
+            .copied()
+    }
+
+    pub fn remove(&self, symbol: &str) {
+        self.quotes.write().remove(symbol);
+    }
+
+    pub fn clear(&self) {
+        self.quotes.write().clear();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceCheck {
+    InOrder,
+    /// Duplicate or out of order; drop it
+    Stale,
+    /// Updates were missed; the cached quote can't be trusted
+    Gap { expected: u64, received: u64 },
+}
+
+/// Last sequence number seen per symbol
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    last: HashMap<String, u64>,
+}
+
+impl SequenceTracker {
+    pub fn check(&mut self, symbol: &str, sequence: Sequence) -> SequenceCheck {
+        let last = self.last.get(symbol).copied();
+        let (check, current) = match sequence {
+            Sequence::Monotonic(current) => match last {
+                Some(last) if current <= last => (SequenceCheck::Stale, last),
+                _ => (SequenceCheck::InOrder, current),
+            },
+            Sequence::Chained { prev, current } => match last {
+                Some(last) if current <= last => (SequenceCheck::Stale, last),
+                Some(last) if prev != last => (SequenceCheck::Gap { expected: last, received: prev }, current),
+                _ => (SequenceCheck::InOrder, current),
+            },
+        };
+
+        self.last.insert(symbol.to_string(), current);
+        check
+    }
+}
+
+/// WebSocket subscription to one venue, reconnecting until aborted
+pub struct StreamConnection {
+    venue: Venue,
+    url: String,
+    /// Venue symbol -> our symbol
+    symbols: HashMap<String, String>,
+    cache: Arc<QuoteCache>,
+}
+
+impl StreamConnection {
+    pub fn new(venue: Venue, symbols: &[String], cache: Arc<QuoteCache>) -> Self {
+        let symbol_map = SymbolMap::new(venue.stream_symbol_format());
+        let symbols: HashMap<String, String> = symbols.iter()
+            .map(|symbol| (symbol_map.map(symbol), symbol.clone()))
+            .collect();
+        let venue_symbols: Vec<String> = symbols.keys().cloned().collect();
+
+        Self {
+            venue,
+            url: venue.stream_url(&venue_symbols),
+            symbols,
+            cache,
+        }
+    }
+
+    /// Connect to `url` instead of the venue's public endpoint
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+
+    pub async fn run(self) {
+        let mut delay = MIN_RECONNECT_DELAY;
+
+        loop {
+            let mut received = false;
+            match self.connect_once(&mut received).await {
+                Ok(()) => info!("📡 {} stream closed, reconnecting", self.venue.name()),
+                Err(e) => warn!("📡 {} stream failed: {}", self.venue.name(), e),
+            }
+
+            // Nothing cached survives a disconnect; REST covers the gap
+            self.cache.clear();
+
+            if received {
+                delay = MIN_RECONNECT_DELAY;
+            }
+            sleep(delay).await;
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+
+    /// One connection, until it closes, goes silent or loses sync.
+    /// `received` is set once a quote arrived.
+    async fn connect_once(&self, received: &mut bool) -> Result<()> {
+        let (socket, _) = tokio_tungstenite::connect_async(self.url.as_str()).await?;
+        let (mut write, mut read) = socket.split();
+        info!("📡 {} stream connected ({} symbols)", self.venue.name(), self.symbols.len());
+
+        let venue_symbols: Vec<String> = self.symbols.keys().cloned().collect();
+        for message in self.venue.subscribe_messages(&venue_symbols) {
+            write.send(Message::Text(message)).await?;
+        }
+
+        let mut ping = interval(PING_INTERVAL);
+        ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ping.tick().await;
+        let mut last_message = Instant::now();
+        let mut sequences = SequenceTracker::default();
+
+        loop {
+            tokio::select! {
+                _ = ping.tick() => {
+                    let ping = match self.venue.ping_message() {
+                        Some(text) => Message::Text(text),
+                        None => Message::Ping(vec![]),
+                    };
+                    write.send(ping).await?;
+                }
+                _ = sleep_until(last_message + HEARTBEAT_TIMEOUT) => {
+                    return Err(anyhow::anyhow!("No message for {}s", HEARTBEAT_TIMEOUT.as_secs()));
+                }
+                message = read.next() => {
+                    let Some(message) = message else {
+                        return Ok(());
+                    };
+                    last_message = Instant::now();
+
+                    match message? {
+                        Message::Text(text) => self.handle_text(&text, &mut sequences, received)?,
+                        Message::Ping(payload) => write.send(Message::Pong(payload)).await?,
+                        Message::Close(_) => return Ok(()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_text(&self, text: &str, sequences: &mut SequenceTracker, received: &mut bool) -> Result<()> {
+        let tickers = match self.venue.parse_stream_message(text)? {
+            StreamMessage::Tickers(tickers) => tickers,
+            StreamMessage::Heartbeat | StreamMessage::Ignored => return Ok(()),
+        };
+
+        for ticker in tickers {
+            let Some(symbol) = self.symbols.get(&ticker.venue_symbol) else {
+                continue;
+            };
+
+            if let Some(sequence) = ticker.sequence {
+                match sequences.check(symbol, sequence) {
+                    SequenceCheck::InOrder => {}
+                    SequenceCheck::Stale => {
+                        debug!("📡 Dropping out-of-order {} update for {}", self.venue.name(), symbol);
+                        continue;
+                    }
+                    SequenceCheck::Gap { expected, received } => {
+                        // Resubscribing brings a fresh snapshot
+                        self.cache.remove(symbol);
+                        return Err(anyhow::anyhow!(
+                            "Sequence gap on {}: expected {}, got {}", symbol, expected, received
+                        ));
+                    }
+                }
+            }
+
+            self.cache.update(symbol, &ticker);
+            *received = true;
+        }
+
+        Ok(())
+    }
+}
+
+/// Serves streamed quotes, falling back to another source (the REST ticker)
+pub struct StreamingSource {
+    cache: Arc<QuoteCache>,
+    fallback: Arc<dyn PriceSource>,
+}
+
+impl StreamingSource {
+    pub fn new(cache: Arc<QuoteCache>, fallback: Arc<dyn PriceSource>) -> Self {
+        Self { cache, fallback }
+    }
+}
+
+#[async_trait]
+impl PriceSource for StreamingSource {
+    fn name(&self) -> &str {
+        self.fallback.name()
+    }
+
+    async fn fetch_ticker(&self, symbol: &str) -> Result<Ticker> {
+        self.fetch_answer(symbol).await.ticker
+    }
+
+    /// A streamed quote has no single response to keep; the REST fallback does
+    async fn fetch_answer(&self, symbol: &str) -> SourceAnswer {
+        if let Some(quote) = self.cache.get(symbol).filter(|quote| quote.price().is_some()) {
+            return SourceAnswer { body: None, ticker: Ok(quote.ticker) };
+        }
+        self.fallback.fetch_answer(symbol).await
+    }
+
+    fn listed_quote(&self, quote: &str) -> String {
+        self.fallback.listed_quote(quote)
+    }
+}
+
+/// Open a stream to every built-in exchange an asset uses and put it in
+/// front of that exchange's REST source. Exchanges replaced from config
+/// keep their configured source.
+pub fn start_streams(config: &NodeConfig, sources: &mut SourceRegistry) -> Vec<JoinHandle<()>> {
+    let mut handles = Vec::new();
+
+    for venue in Venue::ALL {
+        if config.sources.iter().any(|source| source.name == venue.name()) {
+            continue;
+        }
+        // Subscribe to the markets the venue lists, e.g. BTC/USDT for BTC/USD
+        let mut symbols: Vec<String> = config.assets.iter()
+            .chain(&config.stablecoins.feeds)
+            .filter(|asset| asset.exchanges.iter().any(|exchange| exchange == venue.name()))
+            .map(|asset| sources.listed_symbol(venue.name(), &asset.symbol))
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        if symbols.is_empty() {
+            continue;
+        }
+        let Some(rest) = sources.get(venue.name()) else {
+            continue;
+        };
+
+        let cache = Arc::new(QuoteCache::default());
+        sources.register(Arc::new(StreamingSource::new(Arc::clone(&cache), rest)));
+        handles.push(tokio::spawn(StreamConnection::new(venue, &symbols, cache).run()));
+    }
+
+    handles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::source::px;
+    use axum::{
+        extract::ws::{Message as ServerMessage, WebSocketUpgrade},
+        routing::get,
+        Router,
+    };
+
+    /// Coinbase-style server: sends `frames` on every connection, then hangs
+    /// up; each connection is announced on the returned channel
+    async fn mock_venue(frames: Vec<String>) -> (String, tokio::sync::mpsc::UnboundedReceiver<()>) {
+        let (connections, connected) = tokio::sync::mpsc::unbounded_channel();
+        let app = Router::new().route("/", get(move |ws: WebSocketUpgrade| {
+            let frames = frames.clone();
+            let _ = connections.send(());
+            async move {
+                ws.on_upgrade(|mut socket| async move {
+                    // Wait for the subscribe request
+                    let _ = socket.recv().await;
+                    for frame in frames {
+                        let _ = socket.send(ServerMessage::Text(frame)).await;
+                    }
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    let _ = socket.send(ServerMessage::Close(None)).await;
+                })
+            }
+        }));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("ws://{}/", addr), connected)
+    }
+
+    fn ticker(sequence: u64, bid: &str, ask: &str) -> String {
+        format!(
+            r#"{{"type":"ticker","sequence":{},"product_id":"BTC-USD","price":"1","best_bid":"{}","best_ask":"{}"}}"#,
+            sequence, bid, ask
+        )
+    }
+
+    struct FixedSource;
+
+    #[async_trait]
+    impl PriceSource for FixedSource {
+        fn name(&self) -> &str {
+            "coinbase"
+        }
+
+        async fn fetch_ticker(&self, _symbol: &str) -> Result<Ticker> {
+            Ok(Ticker::from_last(px("42")))
+        }
+    }
+
+    #[test]
+    fn test_sequence_tracker() {
+        let mut tracker = SequenceTracker::default();
+        assert_eq!(tracker.check("BTC/USD", Sequence::Monotonic(10)), SequenceCheck::InOrder);
+        assert_eq!(tracker.check("BTC/USD", Sequence::Monotonic(15)), SequenceCheck::InOrder);
+        assert_eq!(tracker.check("BTC/USD", Sequence::Monotonic(12)), SequenceCheck::Stale);
+        assert_eq!(tracker.check("ETH/USD", Sequence::Monotonic(1)), SequenceCheck::InOrder);
+
+        assert_eq!(tracker.check("SOL/USD", Sequence::Chained { prev: 0, current: 5 }), SequenceCheck::InOrder);
+        assert_eq!(tracker.check("SOL/USD", Sequence::Chained { prev: 5, current: 6 }), SequenceCheck::InOrder);
+        assert_eq!(tracker.check("SOL/USD", Sequence::Chained { prev: 5, current: 6 }), SequenceCheck::Stale);
+        assert_eq!(
+            tracker.check("SOL/USD", Sequence::Chained { prev: 8, current: 9 }),
+            SequenceCheck::Gap { expected: 6, received: 8 }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_fills_cache_and_reconnects() {
+        let frames = vec![
+            r#"{"type":"subscriptions","channels":[]}"#.to_string(),
+            ticker(1, "99.0", "101.0"),
+            ticker(3, "100.0", "102.0"),
+            ticker(2, "1.0", "1.0"), // Out of order, dropped
+        ];
+        let (url, mut connected) = mock_venue(frames).await;
+
+        let cache = Arc::new(QuoteCache::default());
+        let connection = StreamConnection::new(Venue::Coinbase, &["BTC/USD".to_string()], Arc::clone(&cache))
+            .with_url(&url);
+        let mut received = false;
+        connection.connect_once(&mut received).await.unwrap();
+
+        assert!(received);
+        connected.recv().await.unwrap();
+        assert_eq!(cache.get("BTC/USD").unwrap().price(), Some(px("101")));
+
+        // The server hangs up after each batch; run() reconnects, and again
+        // after the next hang-up
+        let handle = tokio::spawn(connection.run());
+        for _ in 0..2 {
+            tokio::time::timeout(Duration::from_secs(30), connected.recv()).await
+                .expect("run() did not reconnect")
+                .unwrap();
+        }
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_streaming_source_falls_back_to_rest() {
+        let cache = Arc::new(QuoteCache::default());
+        let source = StreamingSource::new(Arc::clone(&cache), Arc::new(FixedSource));
+        assert_eq!(source.name(), "coinbase");
+        assert_eq!(source.fetch_ticker("BTC/USD").await.unwrap().price(), Some(px("42")));
+
+        let update = |ticker| TickerUpdate { venue_symbol: "BTC-USD".to_string(), ticker, sequence: None };
+        cache.update("BTC/USD", &update(Ticker { last: Some(px("50000")), volume_24h: Some(900.0), ..Ticker::default() }));
+        cache.update("BTC/USD", &update(Ticker { bid: Some(px("49999")), ask: Some(px("50001")), ..Ticker::default() }));
+        let ticker = source.fetch_ticker("BTC/USD").await.unwrap();
+        assert_eq!((ticker.price(), ticker.half_spread(), ticker.volume_24h), (Some(px("50000")), Some(px("1")), Some(900.0)));
+
+        // Lost sync: back to REST
+        cache.clear();
+        assert_eq!(source.fetch_ticker("BTC/USD").await.unwrap().price(), Some(px("42")));
+    }
+}
@@ -0,0 +1,222 @@
+// Subprocess price source - external adapters speaking line-delimited JSON
+//
+// The process is started on first use and kept running. Each request is one
+// line on its stdin and each answer one line on its stdout:
+//
+//   -> {"id": 7, "symbol": "BTCUSD"}
+//   <- {"id": 7, "price": 50123.5}
+//   <- {"id": 7, "price": 50123.5, "bid": 50123.0, "ask": 50124.0, "volume_24h": 812.4}
+//   <- {"id": 7, "error": "unknown symbol"}
+//
+// Answers to other ids are skipped. A process that exits, writes garbage or
+// misses the deadline is killed and restarted on the next request.
+
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use super::source::{json_price, PriceSource, RateLimiter, SourceAnswer, SymbolMap, Ticker};
+
+/// How long a process may take to answer one request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+struct SubprocessRequest<'a> {
+    id: u64,
+    symbol: &'a str,
+}
+
+/// Prices are kept as JSON values and read exactly
+#[derive(Debug, Deserialize)]
+struct SubprocessResponse {
+    id: Option<u64>,
+    price: Option<Value>,
+    bid: Option<Value>,
+    ask: Option<Value>,
+    volume_24h: Option<f64>,
+    error: Option<String>,
+}
+
+struct Process {
+    // Held so the process is killed when dropped
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+pub struct SubprocessSource {
+    name: String,
+    command: String,
+    args: Vec<String>,
+    symbols: SymbolMap,
+    limiter: RateLimiter,
+    process: Mutex<Option<Process>>,
+    next_id: AtomicU64,
+}
+
+impl SubprocessSource {
+    pub fn new(name: &str, command: &str, args: Vec<String>, symbols: SymbolMap, limiter: RateLimiter) -> Self {
+        Self {
+            name: name.to_string(),
+            command: command.to_string(),
+            args,
+            symbols,
+            limiter,
+            process: Mutex::new(None),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    fn spawn(&self) -> Result<Process> {
+        info!("📊 Starting price source {}: {} {:?}", self.name, self.command, self.args);
+
+        let mut child = Command::new(&self.command)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start price source {}", self.name))?;
+
+        let stdin = child.stdin.take().context("Subprocess has no stdin")?;
+        let stdout = child.stdout.take().context("Subprocess has no stdout")?;
+
+        Ok(Process {
+            _child: child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+        })
+    }
+
+    /// The answer line and what it says. Outer error: the process is
+    /// unusable. Inner error: it answered with an error.
+    async fn request(process: &mut Process, id: u64, symbol: &str) -> Result<(String, std::result::Result<Ticker, String>)> {
+        let mut line = serde_json::to_string(&SubprocessRequest { id, symbol })?;
+        line.push('\n');
+        process.stdin.write_all(line.as_bytes()).await?;
+        process.stdin.flush().await?;
+
+        loop {
+            let line = process.stdout.next_line().await?
+                .ok_or_else(|| anyhow::anyhow!("Price source exited"))?;
+            let response: SubprocessResponse = serde_json::from_str(&line)
+                .with_context(|| format!("Invalid response from price source: {}", line))?;
+
+            if response.id.is_some_and(|answered| answered != id) {
+                continue;
+            }
+            if let Some(error) = response.error {
+                return Ok((line, Err(error)));
+            }
+            let price = |value: &Option<Value>| value.as_ref().and_then(|value| json_price(value).ok());
+            let ticker = Ticker {
+                bid: price(&response.bid),
+                ask: price(&response.ask),
+                last: price(&response.price),
+                volume_24h: response.volume_24h,
+            };
+            if ticker.price().is_none() {
+                return Ok((line, Err("response has no price".to_string())));
+            }
+            return Ok((line, Ok(ticker)));
+        }
+    }
+}
+
+#[async_trait]
+impl PriceSource for SubprocessSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch_ticker(&self, symbol: &str) -> Result<Ticker> {
+        self.fetch_answer(symbol).await.ticker
+    }
+
+    async fn fetch_answer(&self, symbol: &str) -> SourceAnswer {
+        self.limiter.acquire().await;
+
+        let mut process = self.process.lock().await;
+        if process.is_none() {
+            match self.spawn() {
+                Ok(spawned) => *process = Some(spawned),
+                Err(e) => return SourceAnswer::failed(e),
+            }
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let venue_symbol = self.symbols.map(symbol);
+        let running = process.as_mut().expect("process was just started");
+
+        let failure = match tokio::time::timeout(REQUEST_TIMEOUT, Self::request(running, id, &venue_symbol)).await {
+            Ok(Ok((line, answer))) => return SourceAnswer {
+                body: Some(line),
+                ticker: answer.map_err(|error| anyhow::anyhow!("Price source {} error: {}", self.name, error)),
+            },
+            Ok(Err(e)) => e,
+            Err(_) => anyhow::anyhow!("Price source {} timed out", self.name),
+        };
+
+        // Broken pipes, garbage and timeouts restart the process
+        warn!("📊 Restarting price source {}: {}", self.name, failure);
+        *process = None;
+        SourceAnswer::failed(failure)
+    }
+
+    fn listed_quote(&self, quote: &str) -> String {
+        self.symbols.listed_quote(quote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::source::px;
+
+    fn shell_source(script: &str) -> SubprocessSource {
+        SubprocessSource::new(
+            "script",
+            "sh",
+            vec!["-c".to_string(), script.to_string()],
+            SymbolMap::new("{base}{quote}"),
+            RateLimiter::new(0.0),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_line_protocol() {
+        // Only knows BTCUSD
+        let source = shell_source(r#"
+            while read line; do
+                case "$line" in
+                    *BTCUSD*) echo '{"price": 50123.5, "bid": 50123.0, "ask": 50124.0}' ;;
+                    *) echo '{"error": "unknown symbol"}' ;;
+                esac
+            done
+        "#);
+
+        let ticker = source.fetch_ticker("BTC/USD").await.unwrap();
+        assert_eq!((ticker.last, ticker.mid()), (Some(px("50123.5")), Some(px("50123.5"))));
+        assert!(source.fetch_ticker("DOGE/USD").await.is_err());
+        // Still the same process after an application error
+        assert_eq!(source.fetch_ticker("BTC/USD").await.unwrap(), ticker);
+    }
+
+    #[tokio::test]
+    async fn test_restarts_after_exit() {
+        // Answers once, then exits
+        let source = shell_source(r#"read line; echo '{"price": 1.5}'"#);
+
+        assert_eq!(source.fetch_ticker("BTC/USD").await.unwrap().last, Some(px("1.5")));
+        assert!(source.fetch_ticker("BTC/USD").await.is_err());
+        assert_eq!(source.fetch_ticker("BTC/USD").await.unwrap().last, Some(px("1.5")));
+    }
+}
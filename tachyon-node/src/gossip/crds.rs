@@ -3,19 +3,60 @@
 /// 
 /// Inspired by Solana's gossip CRDS implementation.
 /// Stores versioned oracle data with conflict resolution.
+/// Every value is signed by the node it is about, and only values whose
+/// signature checks out get in.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use tachyon_merkle::Price;
+
+use crate::consensus;
+use crate::crypto;
+use crate::fetcher::PriceUpdate;
 
 /// Versioned CRDS value with timestamp and signature
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VersionedCrdsValue {
     pub value: CrdsValue,
+    /// Unix time in milliseconds when the owner wrote this version
     pub wallclock: u64,
+    /// Owner's signature over `value` and `wallclock`
     pub signature: Vec<u8>,
 }
 
+impl VersionedCrdsValue {
+    /// `value` as of `wallclock`, signed by `keypair`, which must be its owner
+    pub fn new_signed(value: CrdsValue, wallclock: u64, keypair: &Keypair) -> anyhow::Result<Self> {
+        let signature = crypto::sign_message(keypair, &signable(&value, wallclock)?);
+        Ok(Self { value, wallclock, signature })
+    }
+
+    /// Check the signature against the owner's pubkey
+    pub fn verify(&self) -> bool {
+        let Ok(message) = signable(&self.value, self.wallclock) else {
+            return false;
+        };
+        let Ok(signature) = <[u8; 64]>::try_from(self.signature.as_slice()) else {
+            return false;
+        };
+        crypto::verify_signature(&self.value.pubkey().to_bytes(), &message, &signature)
+    }
+
+    /// Identifies this version of this value; what pull filters hold
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256::digest(&self.signature).into()
+    }
+}
+
+/// Bytes an owner signs
+fn signable(value: &CrdsValue, wallclock: u64) -> bincode::Result<Vec<u8>> {
+    bincode::serialize(&(value, wallclock))
+}
+
 /// CRDS value types for oracle network
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CrdsValue {
@@ -41,16 +82,66 @@ pub struct ContactInfo {
 pub struct PriceData {
     pub pubkey: Pubkey,
     pub asset: String,
-    pub price: f64,
-    pub confidence: f64,
+    pub price: Price,
+    /// Same exponent as `price`
+    pub confidence: Price,
     pub timestamp: i64,
 }
 
+/// A consensus vote, with the voter's signature over the batch root
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vote {
     pub pubkey: Pubkey,
+    pub batch_number: u64,
     pub root: [u8; 32],
     pub stake: u64,
+    pub signature: Vec<u8>,
+}
+
+impl PriceData {
+    pub fn from_update(pubkey: Pubkey, update: &PriceUpdate) -> Self {
+        Self {
+            pubkey,
+            asset: update.asset.clone(),
+            price: update.price,
+            confidence: update.confidence,
+            timestamp: update.timestamp,
+        }
+    }
+
+    /// The publisher's aggregated price, as the aggregator takes it
+    pub fn to_update(&self) -> PriceUpdate {
+        PriceUpdate {
+            asset: self.asset.clone(),
+            price: self.price,
+            confidence: self.confidence,
+            timestamp: self.timestamp,
+            exchange: "aggregated".to_string(),
+            node_pubkey: self.pubkey.to_string(),
+        }
+    }
+}
+
+impl Vote {
+    pub fn from_consensus(vote: &consensus::Vote) -> anyhow::Result<Self> {
+        Ok(Self {
+            pubkey: Pubkey::from_str(&vote.node_pubkey)?,
+            batch_number: vote.batch_number,
+            root: consensus::root_bytes(&vote.root_hash)?,
+            stake: vote.stake,
+            signature: vote.signature.clone(),
+        })
+    }
+
+    pub fn to_consensus(&self) -> consensus::Vote {
+        consensus::Vote {
+            node_pubkey: self.pubkey.to_string(),
+            batch_number: self.batch_number,
+            root_hash: hex::encode(self.root),
+            stake: self.stake,
+            signature: self.signature.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,7 +156,7 @@ pub struct StakeInfo {
 pub enum CrdsLabel {
     ContactInfo(Pubkey),
     PriceData(Pubkey, String), // pubkey + asset
-    Vote(Pubkey, u64),          // pubkey + slot
+    Vote(Pubkey, u64),          // pubkey + batch number
     StakeInfo(Pubkey),
 }
 
@@ -74,7 +165,7 @@ impl CrdsValue {
         match self {
             CrdsValue::ContactInfo(info) => CrdsLabel::ContactInfo(info.pubkey),
             CrdsValue::PriceData(data) => CrdsLabel::PriceData(data.pubkey, data.asset.clone()),
-            CrdsValue::Vote(vote) => CrdsLabel::Vote(vote.pubkey, 0), // slot would go here
+            CrdsValue::Vote(vote) => CrdsLabel::Vote(vote.pubkey, vote.batch_number),
             CrdsValue::StakeInfo(info) => CrdsLabel::StakeInfo(info.pubkey),
         }
     }
@@ -89,23 +180,34 @@ impl CrdsValue {
     }
 }
 
-/// CRDS store with conflict resolution
+/// CRDS store with conflict resolution. Values from unstaked owners live in
+/// their own budget and are always evicted before any staked owner's value.
 pub struct Crds {
     table: HashMap<CrdsLabel, VersionedCrdsValue>,
+    /// Labels whose owner had no stake when the value came in
+    unstaked: HashSet<CrdsLabel>,
     /// Maximum entries before pruning
     max_entries: usize,
+    /// Maximum entries from unstaked owners
+    max_unstaked: usize,
 }
 
 impl Crds {
-    pub fn new(max_entries: usize) -> Self {
+    pub fn new(max_entries: usize, max_unstaked: usize) -> Self {
         Self {
             table: HashMap::new(),
+            unstaked: HashSet::new(),
             max_entries,
+            max_unstaked: max_unstaked.min(max_entries),
         }
     }
 
-    /// Insert or update a value with conflict resolution
-    pub fn insert(&mut self, value: VersionedCrdsValue) -> Result<(), CrdsError> {
+    /// Insert or update a value with conflict resolution; `staked` is whether
+    /// its owner has stake. Returns the value it replaced.
+    pub fn insert(&mut self, value: VersionedCrdsValue, staked: bool) -> Result<Option<VersionedCrdsValue>, CrdsError> {
+        if !value.verify() {
+            return Err(CrdsError::InvalidSignature);
+        }
         let label = value.value.label();
         
         // Check if we should update
@@ -115,14 +217,19 @@ impl Crds {
             }
         }
         
-        self.table.insert(label, value);
+        let replaced = self.table.insert(label.clone(), value);
+        if staked {
+            self.unstaked.remove(&label);
+        } else {
+            self.unstaked.insert(label);
+        }
         
         // Prune if needed
-        if self.table.len() > self.max_entries {
+        if self.unstaked.len() > self.max_unstaked || self.table.len() > self.max_entries {
             self.prune();
         }
         
-        Ok(())
+        Ok(replaced)
     }
 
     /// Get a value by label
@@ -148,21 +255,36 @@ impl Crds {
         new.wallclock > existing.wallclock
     }
 
-    /// Prune old entries (keep most recent 80%)
+    /// Prune old entries down to 80% of each budget: unstaked owners' values
+    /// first, oldest first, and staked ones only if that is not enough
     fn prune(&mut self) {
-        let target_size = (self.max_entries * 80) / 100;
+        let mut entries: Vec<_> = self.table.iter()
+            .map(|(label, value)| (!self.unstaked.contains(label), value.wallclock, label.clone()))
+            .collect();
+        entries.sort_by_key(|(staked, wallclock, _)| (*staked, *wallclock));
         
-        // Sort by wallclock and keep newest
-        let mut entries: Vec<_> = self.table.iter().map(|(k, v)| (k.clone(), v.wallclock)).collect();
-        entries.sort_by_key(|(_, wallclock)| *wallclock);
-        
-        // Remove oldest entries
-        let to_remove = entries.len().saturating_sub(target_size);
-        for (label, _) in entries.iter().take(to_remove) {
+        let unstaked_target = (self.max_unstaked * 80) / 100;
+        let unstaked_excess = self.unstaked.len().saturating_sub(unstaked_target);
+        let to_remove = if self.table.len() > self.max_entries {
+            unstaked_excess.max(self.table.len() - (self.max_entries * 80) / 100)
+        } else {
+            unstaked_excess
+        };
+        for (_, _, label) in entries.iter().take(to_remove) {
             self.table.remove(label);
+            self.unstaked.remove(label);
         }
     }
 
+    /// Drop every value written before `cutoff`; returns how many went
+    pub fn purge_older_than(&mut self, cutoff: u64) -> usize {
+        let before = self.table.len();
+        self.table.retain(|_, value| value.wallclock >= cutoff);
+        let table = &self.table;
+        self.unstaked.retain(|label| table.contains_key(label));
+        before - self.table.len()
+    }
+
     /// Get number of entries
     pub fn len(&self) -> usize {
         self.table.len()
@@ -173,34 +295,36 @@ impl Crds {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum CrdsError {
+    /// We already hold this version or a newer one
     InsertFailed,
+    InvalidSignature,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use solana_sdk::signer::Signer;
 
-    #[test]
-    fn test_crds_insert_and_get() {
-        let mut crds = Crds::new(1000);
-        let pubkey = Pubkey::new_unique();
-        
-        let contact = ContactInfo {
+    fn contact(pubkey: Pubkey) -> CrdsValue {
+        CrdsValue::ContactInfo(ContactInfo {
             pubkey,
             gossip_addr: "127.0.0.1:7777".parse().unwrap(),
             api_addr: "127.0.0.1:8080".parse().unwrap(),
             version: 1,
-        };
+        })
+    }
+
+    #[test]
+    fn test_crds_insert_and_get() {
+        let mut crds = Crds::new(1000, 100);
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
         
-        let value = VersionedCrdsValue {
-            value: CrdsValue::ContactInfo(contact),
-            wallclock: 100,
-            signature: vec![0; 64],
-        };
+        let value = VersionedCrdsValue::new_signed(contact(pubkey), 100, &keypair).unwrap();
         
-        crds.insert(value.clone()).unwrap();
+        crds.insert(value.clone(), true).unwrap();
         
         let label = CrdsLabel::ContactInfo(pubkey);
         assert!(crds.get(&label).is_some());
@@ -208,34 +332,69 @@ mod tests {
 
     #[test]
     fn test_crds_conflict_resolution() {
-        let mut crds = Crds::new(1000);
-        let pubkey = Pubkey::new_unique();
+        let mut crds = Crds::new(1000, 100);
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
         
-        let contact1 = ContactInfo {
-            pubkey,
-            gossip_addr: "127.0.0.1:7777".parse().unwrap(),
-            api_addr: "127.0.0.1:8080".parse().unwrap(),
-            version: 1,
-        };
-        
-        let value1 = VersionedCrdsValue {
-            value: CrdsValue::ContactInfo(contact1.clone()),
-            wallclock: 100,
-            signature: [0; 64],
-        };
+        let value1 = VersionedCrdsValue::new_signed(contact(pubkey), 100, &keypair).unwrap();
+        let value2 = VersionedCrdsValue::new_signed(contact(pubkey), 200, &keypair).unwrap(); // Newer
         
-        let value2 = VersionedCrdsValue {
-            value: CrdsValue::ContactInfo(contact1),
-            wallclock: 200, // Newer
-            signature: [0; 64],
-        };
-        
-        crds.insert(value1).unwrap();
-        crds.insert(value2.clone()).unwrap();
+        crds.insert(value1.clone(), true).unwrap();
+        crds.insert(value2.clone(), true).unwrap();
+        assert_eq!(crds.insert(value1, true).unwrap_err(), CrdsError::InsertFailed);
         
         let label = CrdsLabel::ContactInfo(pubkey);
         let stored = crds.get(&label).unwrap();
         assert_eq!(stored.wallclock, 200);
     }
+
+    #[test]
+    fn test_crds_rejects_bad_signatures_and_purges_by_wallclock() {
+        let mut crds = Crds::new(1000, 100);
+        let (owner, other) = (Keypair::new(), Keypair::new());
+        
+        // Signed by someone other than the node it is about
+        let forged = VersionedCrdsValue::new_signed(contact(owner.pubkey()), 100, &other).unwrap();
+        assert_eq!(crds.insert(forged, true).unwrap_err(), CrdsError::InvalidSignature);
+        
+        // Signed, then tampered with
+        let mut bumped = VersionedCrdsValue::new_signed(contact(owner.pubkey()), 100, &owner).unwrap();
+        bumped.wallclock = 300;
+        assert_eq!(crds.insert(bumped, true).unwrap_err(), CrdsError::InvalidSignature);
+        assert!(crds.is_empty());
+        
+        crds.insert(VersionedCrdsValue::new_signed(contact(owner.pubkey()), 100, &owner).unwrap(), true).unwrap();
+        crds.insert(VersionedCrdsValue::new_signed(contact(other.pubkey()), 200, &other).unwrap(), true).unwrap();
+        assert_eq!(crds.purge_older_than(150), 1);
+        assert!(crds.get(&CrdsLabel::ContactInfo(owner.pubkey())).is_none());
+        assert!(crds.get(&CrdsLabel::ContactInfo(other.pubkey())).is_some());
+    }
+
+    #[test]
+    fn test_unstaked_values_never_evict_staked_ones() {
+        let mut crds = Crds::new(10, 4);
+        let staked: Vec<_> = (0..5).map(|_| Keypair::new()).collect();
+        for (i, keypair) in staked.iter().enumerate() {
+            crds.insert(VersionedCrdsValue::new_signed(contact(keypair.pubkey()), i as u64, keypair).unwrap(), true).unwrap();
+        }
+
+        // A flood of fresh identities, each newer than every staked value
+        for i in 0..100 {
+            let keypair = Keypair::new();
+            crds.insert(VersionedCrdsValue::new_signed(contact(keypair.pubkey()), 1_000 + i, &keypair).unwrap(), false).unwrap();
+        }
+        assert!(crds.len() <= 9);
+        for keypair in &staked {
+            assert!(crds.get(&CrdsLabel::ContactInfo(keypair.pubkey())).is_some());
+        }
+
+        // Staked values beyond capacity push out the oldest staked ones
+        for i in 0..10 {
+            let keypair = Keypair::new();
+            crds.insert(VersionedCrdsValue::new_signed(contact(keypair.pubkey()), 2_000 + i, &keypair).unwrap(), true).unwrap();
+        }
+        assert!(crds.len() <= 10);
+        assert!(crds.get(&CrdsLabel::ContactInfo(staked[0].pubkey())).is_none());
+    }
 }
 
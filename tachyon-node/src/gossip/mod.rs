@@ -1,85 +1,201 @@
 #![allow(dead_code)]
+use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signer::Signer;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{mpsc, RwLock};
-use tokio::time::{interval, Duration};
+use tokio::sync::{broadcast, mpsc, Notify, RwLock};
+use tokio::time::{interval, timeout, Duration};
 use tracing::{debug, error, info, warn};
 
+use crate::api::NodeStatus;
 use crate::config::NodeConfig;
+use crate::consensus::validator_set::{staker_info_address, ValidatorSetChange};
+use crate::consensus::{Cluster, Vote};
 use crate::fetcher::PriceUpdate;
 
 // Solana-style gossip modules
 pub mod crds;
 pub mod push_pull;
 
+// Dialable peer addresses, persisted between restarts
+pub mod peer_table;
+
+// Framing, encoding and the version handshake
+pub mod wire;
+
+use crds::{ContactInfo, Crds, CrdsLabel, CrdsValue, PriceData, StakeInfo, VersionedCrdsValue};
+use peer_table::PeerTable;
+use push_pull::{BloomFilter, PushGossip, MAX_VALUES_PER_MESSAGE};
+use wire::GossipFramed;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GossipMessage {
-    /// Announce this node to the network
+    /// Announce where this node listens. Only the port of `addr` is used;
+    /// the IP is the one the connection came from.
     Announce {
-        node_id: String,
         addr: SocketAddr,
     },
-    /// Price update to gossip
-    PriceUpdate(PriceUpdate),
+    /// CRDS values new to the sender, pushed on as they arrive
+    Push(Vec<VersionedCrdsValue>),
+    /// Bloom filter of the CRDS values the sender holds
+    PullRequest(BloomFilter),
+    /// CRDS values the pull requester's filter lacked
+    PullResponse(Vec<VersionedCrdsValue>),
     /// Heartbeat to keep connection alive
     Heartbeat,
     /// Request peer list
     GetPeers,
-    /// Response with peer list
+    /// Response with peer list: listen addresses we have been connected to
     Peers(Vec<SocketAddr>),
 }
 
+type PeerWriter = SplitSink<GossipFramed<TcpStream>, GossipMessage>;
+type PeerReader = SplitStream<GossipFramed<TcpStream>>;
+
+/// A connected peer's outbound queue and what it told us about itself
+struct Peer {
+    /// Drained by the peer's writer task, so a slow peer never blocks the rest
+    outbound: mpsc::Sender<GossipMessage>,
+    /// Address it accepts connections on: the one we dialed, or from its announce
+    listen_addr: Option<SocketAddr>,
+    /// Identity it proved in the handshake
+    identity: Pubkey,
+}
+
+/// Connected peers by connection address; each read half is owned by its `handle_peer` task
+type PeerMap = Arc<RwLock<HashMap<SocketAddr, Peer>>>;
+
+/// Channels carrying inbound gossip into the rest of the node
+#[derive(Clone)]
+pub struct GossipInbound {
+    pub price_tx: mpsc::Sender<PriceUpdate>,
+    pub vote_tx: mpsc::Sender<Vote>,
+}
+
+/// Our own prices and votes, for gossip to publish
+pub struct GossipOutbound {
+    pub price_rx: mpsc::Receiver<PriceUpdate>,
+    pub vote_rx: mpsc::Receiver<Vote>,
+}
+
+/// How often the connected peer count is published to the node status
+const PEER_COUNT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often we ask peers for theirs and dial up to `target_peers`
+const DIAL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a dial may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Messages queued for one peer before further ones to it are dropped
+const PEER_QUEUE_LEN: usize = 256;
+
+/// How long one write to a peer may take before it is disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most addresses sent in, or taken from, one `Peers` message
+const MAX_PEERS_SHARED: usize = 64;
+
+/// Most CRDS values held; the oldest go first beyond it
+const CRDS_CAPACITY: usize = 16_384;
+
+/// Most CRDS values held from unstaked nodes, which never push out staked ones
+const CRDS_UNSTAKED_CAPACITY: usize = 2_048;
+
+/// How far ahead of our clock a value's wallclock may be
+const MAX_CLOCK_DRIFT_MS: u64 = 10_000;
+
+#[derive(Clone)]
 pub struct GossipNetwork {
     config: Arc<NodeConfig>,
-    peers: Arc<RwLock<HashMap<SocketAddr, TcpStream>>>,
-    known_peers: Arc<RwLock<Vec<SocketAddr>>>,
+    cluster: Arc<dyn Cluster>,
+    governance_program: Pubkey,
+    /// Stake by staker account, following the validator set consensus keeps
+    stakes: Arc<RwLock<HashMap<Pubkey, u64>>>,
+    /// Signed contact infos, prices, votes and stake, ours and everyone's
+    crds: Arc<RwLock<Crds>>,
+    push: PushGossip,
+    peers: PeerMap,
+    table: Arc<RwLock<PeerTable>>,
+    table_path: PathBuf,
+    /// Wakes the dial loop when new addresses are learned
+    dial_now: Arc<Notify>,
+    status: Arc<RwLock<NodeStatus>>,
 }
 
 impl GossipNetwork {
-    pub fn new(config: Arc<NodeConfig>) -> Self {
-        Self {
+    pub fn new(config: Arc<NodeConfig>, cluster: Arc<dyn Cluster>, status: Arc<RwLock<NodeStatus>>) -> Result<Self> {
+        let governance_program = Pubkey::from_str(&config.program_id)?;
+        let table_path = PathBuf::from(shellexpand::tilde(&config.peers_path).to_string());
+        let table = PeerTable::load(&table_path).unwrap_or_else(|e| {
+            warn!("📡 Starting with an empty peer table: {}", e);
+            PeerTable::default()
+        });
+        info!("📡 Loaded {} known peers from {}", table.len(), table_path.display());
+        
+        Ok(Self {
+            push: PushGossip::new(config.push_fanout),
             config,
+            cluster,
+            governance_program,
+            stakes: Arc::new(RwLock::new(HashMap::new())),
+            crds: Arc::new(RwLock::new(Crds::new(CRDS_CAPACITY, CRDS_UNSTAKED_CAPACITY))),
             peers: Arc::new(RwLock::new(HashMap::new())),
-            known_peers: Arc::new(RwLock::new(Vec::new())),
-        }
+            table: Arc::new(RwLock::new(table)),
+            table_path,
+            dial_now: Arc::new(Notify::new()),
+            status,
+        })
     }
 
     pub async fn start(
         &self,
-        gossip_tx: mpsc::Sender<PriceUpdate>,
+        listener: TcpListener,
+        mut stake_changes: broadcast::Receiver<ValidatorSetChange>,
+        inbound: GossipInbound,
+        outbound: GossipOutbound,
         mut shutdown: tokio::sync::broadcast::Receiver<()>,
     ) -> Result<()> {
-        let bind_addr = format!("0.0.0.0:{}", self.config.gossip_port);
-        let listener = TcpListener::bind(&bind_addr).await?;
-        
-        info!("📡 Starting TCP Gossip network on {}", bind_addr);
+        let GossipOutbound { mut price_rx, mut vote_rx } = outbound;
+        info!("📡 Starting TCP Gossip network on {}", listener.local_addr()?);
         info!("📡 Node ID: {}", self.config.identity.pubkey());
         
-        // Start accepting connections
-        let peers = self.peers.clone();
-        let known_peers = self.known_peers.clone();
-        let gossip_tx_clone = gossip_tx.clone();
+        // Only staked validators' prices and votes are taken from gossip
+        let network = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match stake_changes.recv().await {
+                    Ok(change) => network.apply_stake_change(change).await,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("📡 Missed {} validator set changes, reloading stakes", missed);
+                        network.refresh_stakes().await;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
         
+        // Start accepting connections
+        let network = self.clone();
+        let inbound_clone = inbound.clone();
         tokio::spawn(async move {
             loop {
                 match listener.accept().await {
                     Ok((stream, addr)) => {
                         info!("📡 New peer connected: {}", addr);
-                        peers.write().await.insert(addr, stream);
-                        known_peers.write().await.push(addr);
-                        
-                        // Handle peer messages
-                        let peers_clone = peers.clone();
-                        let gossip_tx_clone2 = gossip_tx_clone.clone();
+                        // The handshake waits on the peer; keep accepting meanwhile
+                        let network = network.clone();
+                        let inbound = inbound_clone.clone();
                         tokio::spawn(async move {
-                            if let Err(e) = Self::handle_peer(addr, peers_clone, gossip_tx_clone2).await {
+                            if let Err(e) = network.register(addr, stream, false, inbound).await {
                                 warn!("📡 Error handling peer {}: {}", addr, e);
                             }
                         });
@@ -91,13 +207,56 @@ impl GossipNetwork {
             }
         });
         
+        // Keep up to `target_peers` connections from entrypoints and learned peers
+        let network = self.clone();
+        let inbound_clone = inbound.clone();
+        tokio::spawn(async move { network.dial_loop(inbound_clone).await });
+        
+        // Keep our own values fresh, drop expired ones and pull what we lack
+        let network = self.clone();
+        tokio::spawn(async move { network.crds_loop().await });
+        
         // Start heartbeat
         let peers_heartbeat = self.peers.clone();
         tokio::spawn(async move {
             let mut heartbeat_interval = interval(Duration::from_secs(30));
             loop {
                 heartbeat_interval.tick().await;
-                Self::send_heartbeat(&peers_heartbeat).await;
+                Self::broadcast(&peers_heartbeat, &GossipMessage::Heartbeat).await;
+            }
+        });
+        
+        // Publish the peer count
+        let peers_status = self.peers.clone();
+        let status = self.status.clone();
+        tokio::spawn(async move {
+            let mut status_interval = interval(PEER_COUNT_INTERVAL);
+            loop {
+                status_interval.tick().await;
+                let count = peers_status.read().await.len() as u32;
+                status.write().await.peers_connected = count;
+            }
+        });
+        
+        // Push our consensus votes into gossip
+        let network = self.clone();
+        tokio::spawn(async move {
+            while let Some(vote) = vote_rx.recv().await {
+                debug!("📡 Publishing vote for batch {}", vote.batch_number);
+                match crds::Vote::from_consensus(&vote) {
+                    Ok(vote) => network.publish(CrdsValue::Vote(vote)).await,
+                    Err(e) => warn!("📡 Cannot gossip vote for batch {}: {}", vote.batch_number, e),
+                }
+            }
+        });
+        
+        // Publish what our fetcher observes, so peers aggregate it too
+        let network = self.clone();
+        tokio::spawn(async move {
+            while let Some(update) = price_rx.recv().await {
+                if let Err(e) = network.broadcast_price_update(&update).await {
+                    warn!("📡 Cannot gossip {} update: {}", update.asset, e);
+                }
             }
         });
         
@@ -106,124 +265,492 @@ impl GossipNetwork {
         // Wait for shutdown
         shutdown.recv().await.ok();
         info!("📡 Gossip network shutting down...");
+        self.save_table().await;
         
         Ok(())
     }
 
+    /// Handshake on a new connection, track it, introduce ourselves and ask
+    /// for its peers
+    async fn register(&self, addr: SocketAddr, stream: TcpStream, dialed: bool, inbound: GossipInbound) -> Result<()> {
+        let (framed, identity) = wire::handshake(stream, &self.config.identity, dialed).await?;
+        if identity == self.config.identity.pubkey() {
+            // Dialed one of our own addresses; remember it so we stop
+            if dialed {
+                self.table.write().await.seen(addr, &identity.to_string(), chrono::Utc::now().timestamp());
+            }
+            info!("📡 {} is this node, disconnecting", addr);
+            return Ok(());
+        }
+        if dialed {
+            self.table.write().await.seen(addr, &identity.to_string(), chrono::Utc::now().timestamp());
+        }
+        
+        let (writer, reader) = framed.split();
+        let (outbound, queue) = mpsc::channel(PEER_QUEUE_LEN);
+        self.peers.write().await.insert(addr, Peer {
+            outbound,
+            listen_addr: dialed.then_some(addr),
+            identity,
+        });
+        info!("📡 Peer {} authenticated as {}", addr, identity);
+        
+        let network = self.clone();
+        let reader_task = tokio::spawn(async move {
+            if let Err(e) = network.handle_peer(addr, reader, inbound).await {
+                warn!("📡 Error handling peer {}: {}", addr, e);
+            }
+            network.peers.write().await.remove(&addr);
+        });
+        // The queue closes once the peer is removed; a failed write removes it
+        let (peers, reader_task) = (self.peers.clone(), reader_task.abort_handle());
+        tokio::spawn(async move {
+            if let Err(e) = write_queued(writer, queue).await {
+                warn!("📡 Failed to send to {}: {}", addr, e);
+                reader_task.abort();
+                peers.write().await.remove(&addr);
+                info!("📡 Removed dead peer: {}", addr);
+            }
+        });
+        
+        let announce = GossipMessage::Announce {
+            addr: SocketAddr::from(([0, 0, 0, 0], self.config.gossip_port)),
+        };
+        self.send(addr, &announce).await?;
+        self.send(addr, &GossipMessage::GetPeers).await
+    }
+
     async fn handle_peer(
+        &self,
         addr: SocketAddr,
-        peers: Arc<RwLock<HashMap<SocketAddr, TcpStream>>>,
-        gossip_tx: mpsc::Sender<PriceUpdate>,
+        mut stream: PeerReader,
+        inbound: GossipInbound,
     ) -> Result<()> {
-        let mut stream = peers.write().await.remove(&addr).ok_or_else(|| {
-            anyhow::anyhow!("Peer not found")
-        })?;
-        
-        let mut buf = vec![0u8; 4096];
+        // A frame that does not decode ends the connection: the stream
+        // cannot be trusted to be in sync after it
+        let Some(identity) = self.peers.read().await.get(&addr).map(|peer| peer.identity) else {
+            return Ok(());
+        };
+        let node_id = identity.to_string();
         
-        loop {
-            match stream.read(&mut buf).await {
-                Ok(0) => {
-                    info!("📡 Peer {} disconnected", addr);
-                    break;
+        while let Some(frame) = stream.next().await {
+            let msg = frame?;
+            
+            match msg {
+                GossipMessage::Push(values) => {
+                    let new = self.accept(values, addr, &identity, &inbound).await;
+                    if !new.is_empty() {
+                        debug!("📡 {} new values pushed by {}", new.len(), addr);
+                        self.push_values(new, Some(addr)).await;
+                    }
                 }
-                Ok(n) => {
-                    // Try to deserialize message
-                    if let Ok(msg) = serde_json::from_slice::<GossipMessage>(&buf[..n]) {
-                        match msg {
-                            GossipMessage::PriceUpdate(update) => {
-                                debug!("📡 Received price update from {}: {}", addr, update.asset);
-                                gossip_tx.send(update).await.ok();
-                            }
-                            GossipMessage::Heartbeat => {
-                                debug!("📡 Heartbeat from {}", addr);
-                            }
-                            GossipMessage::Announce { node_id, addr: peer_addr } => {
-                                info!("📡 Peer announced: {} at {}", node_id, peer_addr);
-                            }
-                            _ => {}
+                GossipMessage::PullRequest(filter) => {
+                    let missing = push_pull::missing_values(&*self.crds.read().await, &filter);
+                    for chunk in missing.chunks(MAX_VALUES_PER_MESSAGE) {
+                        // A peer too far behind gets the rest on its next pull
+                        if let Err(e) = self.send(addr, &GossipMessage::PullResponse(chunk.to_vec())).await {
+                            debug!("📡 Pull response to {} cut short: {}", addr, e);
+                            break;
                         }
                     }
                 }
-                Err(e) => {
-                    warn!("📡 Error reading from peer {}: {}", addr, e);
-                    break;
+                GossipMessage::PullResponse(values) => {
+                    let new = self.accept(values, addr, &identity, &inbound).await;
+                    if !new.is_empty() {
+                        debug!("📡 Pulled {} new values from {}", new.len(), addr);
+                    }
+                }
+                GossipMessage::Heartbeat => {
+                    debug!("📡 Heartbeat from {}", addr);
+                }
+                GossipMessage::Announce { addr: announced } => {
+                    let mut peers = self.peers.write().await;
+                    let Some(peer) = peers.get_mut(&addr) else {
+                        break;
+                    };
+                    
+                    let listen_addr = SocketAddr::new(addr.ip(), announced.port());
+                    info!("📡 Peer announced: {} at {}", node_id, listen_addr);
+                    self.table.write().await.seen(listen_addr, &node_id, chrono::Utc::now().timestamp());
+                    peer.listen_addr = Some(listen_addr);
+                }
+                GossipMessage::GetPeers => {
+                    let requester = self.peers.read().await.get(&addr).and_then(|peer| peer.listen_addr);
+                    let mut shared = self.table.read().await.shareable(MAX_PEERS_SHARED + 1);
+                    shared.retain(|shared_addr| Some(*shared_addr) != requester);
+                    shared.truncate(MAX_PEERS_SHARED);
+                    if let Err(e) = self.send(addr, &GossipMessage::Peers(shared)).await {
+                        debug!("📡 Peers answer to {} dropped: {}", addr, e);
+                    }
+                }
+                GossipMessage::Peers(addrs) => {
+                    let mut table = self.table.write().await;
+                    let learned = addrs.into_iter()
+                        .take(MAX_PEERS_SHARED)
+                        .filter(|learned_addr| table.add(*learned_addr, addr.ip()))
+                        .count();
+                    if learned > 0 {
+                        debug!("📡 Learned {} peers from {}", learned, addr);
+                        self.dial_now.notify_one();
+                    }
                 }
             }
         }
         
+        info!("📡 Peer {} disconnected", addr);
         Ok(())
     }
 
-    async fn send_heartbeat(peers: &Arc<RwLock<HashMap<SocketAddr, TcpStream>>>) {
-        let msg = GossipMessage::Heartbeat;
-        if let Ok(data) = serde_json::to_vec(&msg) {
-            let mut peers_write = peers.write().await;
-            let mut to_remove = Vec::new();
-            
-            for (addr, stream) in peers_write.iter_mut() {
-                if let Err(e) = stream.write_all(&data).await {
-                    warn!("📡 Failed to send heartbeat to {}: {}", addr, e);
-                    to_remove.push(*addr);
+    /// Dial candidates whenever the interval passes or new addresses arrive
+    async fn dial_loop(&self, inbound: GossipInbound) {
+        let mut dial_interval = interval(DIAL_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = dial_interval.tick() => {
+                    // Ask connected peers for theirs on every round
+                    Self::broadcast(&self.peers, &GossipMessage::GetPeers).await;
                 }
+                _ = self.dial_now.notified() => {}
             }
             
-            for addr in to_remove {
-                peers_write.remove(&addr);
-                info!("📡 Removed dead peer: {}", addr);
-            }
+            self.dial_peers(&inbound).await;
+            self.save_table().await;
         }
     }
 
-    pub async fn broadcast_price_update(&self, update: &PriceUpdate) -> Result<()> {
-        let msg = GossipMessage::PriceUpdate(update.clone());
-        let data = serde_json::to_vec(&msg)?;
+    /// Connect to entrypoints, then known peers, until `target_peers` are connected
+    async fn dial_peers(&self, inbound: &GossipInbound) {
+        let (connected, mut skip_ids, count) = {
+            let peers = self.peers.read().await;
+            let connected: HashSet<SocketAddr> = peers.keys().copied()
+                .chain(peers.values().filter_map(|peer| peer.listen_addr))
+                .collect();
+            let connected_ids: HashSet<String> = peers.values().map(|peer| peer.identity.to_string()).collect();
+            (connected, connected_ids, peers.len())
+        };
+        skip_ids.insert(self.config.identity.pubkey().to_string());
         
-        let mut peers = self.peers.write().await;
-        let mut to_remove = Vec::new();
+        let wanted = self.config.target_peers.saturating_sub(count);
+        if wanted == 0 {
+            return;
+        }
         
-        for (addr, stream) in peers.iter_mut() {
-            if let Err(e) = stream.write_all(&data).await {
-                warn!("📡 Failed to broadcast to {}: {}", addr, e);
-                to_remove.push(*addr);
+        let mut candidates = Vec::new();
+        for entrypoint in &self.config.entrypoints {
+            match tokio::net::lookup_host(entrypoint).await.map(|mut addrs| addrs.next()) {
+                Ok(Some(addr)) => candidates.push(addr),
+                Ok(None) => warn!("📡 Entrypoint {} has no address", entrypoint),
+                Err(e) => warn!("📡 Cannot resolve entrypoint {}: {}", entrypoint, e),
             }
         }
-        
-        for addr in to_remove {
-            peers.remove(&addr);
+        {
+            let table = self.table.read().await;
+            candidates.retain(|addr| {
+                !connected.contains(addr)
+                    && !table.get(addr).and_then(|entry| entry.node_id.as_ref()).is_some_and(|id| skip_ids.contains(id))
+            });
+            candidates.extend(table.candidates(&connected, &skip_ids));
         }
         
-        Ok(())
+        let mut dialed = HashSet::new();
+        for addr in candidates.into_iter().filter(|addr| dialed.insert(*addr)).take(wanted) {
+            if let Err(e) = self.connect_to_peer(addr, inbound.clone()).await {
+                debug!("📡 Could not reach {}: {}", addr, e);
+                self.table.write().await.failed(addr);
+            }
+        }
     }
 
-    pub async fn connect_to_peer(&self, addr: SocketAddr) -> Result<()> {
-        info!("📡 Connecting to peer: {}", addr);
-        let mut stream = TcpStream::connect(addr).await?;
-        
-        // Send announcement
-        let announce = GossipMessage::Announce {
-            node_id: self.config.identity.pubkey().to_string(),
-            addr: format!("0.0.0.0:{}", self.config.gossip_port).parse()?,
+    /// Re-sign our contact and stake info, expire old values and ask one
+    /// peer, picked by stake, for what we lack
+    async fn crds_loop(&self) {
+        let mut pull_interval = interval(Duration::from_millis(self.config.pull_interval_ms));
+        let mut published_at = None;
+        loop {
+            pull_interval.tick().await;
+            let now = wallclock();
+            
+            // Well before they would expire anywhere
+            if published_at.is_none_or(|at| now.saturating_sub(at) >= self.config.crds_timeout_ms / 2) {
+                self.publish_self().await;
+                published_at = Some(now);
+            }
+            
+            let expired = self.crds.write().await.purge_older_than(now.saturating_sub(self.config.crds_timeout_ms));
+            if expired > 0 {
+                debug!("📡 Expired {} gossip values", expired);
+            }
+            
+            let Some(&addr) = PushGossip::new(1).select_peers(&self.peer_stakes().await, None).first() else {
+                continue;
+            };
+            let filter = push_pull::build_filter(&*self.crds.read().await);
+            if let Err(e) = self.send(addr, &GossipMessage::PullRequest(filter)).await {
+                debug!("📡 Pull request to {} failed: {}", addr, e);
+            }
+        }
+    }
+
+    /// Our contact info and the stake the governance program shows for us
+    async fn publish_self(&self) {
+        let pubkey = self.config.identity.pubkey();
+        // Without a configured public IP, peers use the one we connect from
+        let ip = self.config.public_ip.unwrap_or(std::net::Ipv4Addr::UNSPECIFIED.into());
+        self.publish(CrdsValue::ContactInfo(ContactInfo {
+            pubkey,
+            gossip_addr: SocketAddr::new(ip, self.config.gossip_port),
+            api_addr: SocketAddr::new(ip, self.config.api_port),
+            version: wire::PROTOCOL_VERSION as u64,
+        })).await;
+        let stake = self.stake_of(&pubkey).await;
+        self.publish(CrdsValue::StakeInfo(StakeInfo { pubkey, stake, is_active: stake > 0 })).await;
+    }
+
+    /// Sign `value` as ours, store it and push it
+    async fn publish(&self, value: CrdsValue) {
+        let value = match VersionedCrdsValue::new_signed(value, wallclock(), &self.config.identity) {
+            Ok(value) => value,
+            Err(e) => {
+                error!("📡 Failed to sign gossip value: {}", e);
+                return;
+            }
         };
+        if let Err(e) = self.crds.write().await.insert(value.clone(), true) {
+            // Published twice within a millisecond; the first is as good
+            debug!("📡 Not publishing {:?}: {:?}", value.value.label(), e);
+            return;
+        }
+        self.push_values(vec![value], None).await;
+    }
+
+    /// Store the values that are new and may be trusted, hand prices and votes
+    /// to the node, and return the stored values. `session` is the identity of
+    /// the peer that sent them, connected from `from`.
+    async fn accept(&self, values: Vec<VersionedCrdsValue>, from: SocketAddr, session: &Pubkey, inbound: &GossipInbound) -> Vec<VersionedCrdsValue> {
+        let now = wallclock();
+        let oldest = now.saturating_sub(self.config.crds_timeout_ms);
+        let mut accepted = Vec::new();
         
-        let data = serde_json::to_vec(&announce)?;
-        stream.write_all(&data).await?;
+        for value in values.into_iter().take(MAX_VALUES_PER_MESSAGE) {
+            if value.wallclock < oldest || value.wallclock > now + MAX_CLOCK_DRIFT_MS {
+                continue;
+            }
+            // Prices and votes count only from staked validators; anything else
+            // from an unstaked node only straight from that node, so a pile of
+            // fresh keypairs cannot be relayed in
+            let owner = value.value.pubkey();
+            let staked = self.is_staked(&owner).await;
+            let needs_stake = matches!(value.value, CrdsValue::PriceData(_) | CrdsValue::Vote(_));
+            if !staked && (needs_stake || owner != *session) {
+                debug!("📡 Ignoring {:?} from an unstaked node", value.value.label());
+                continue;
+            }
+            // Decided from what the insert replaced, under the same lock, so
+            // two copies arriving together cannot both be passed on
+            let replaced = match self.crds.write().await.insert(value.clone(), staked) {
+                Ok(replaced) => replaced,
+                Err(crds::CrdsError::InsertFailed) => continue,
+                Err(crds::CrdsError::InvalidSignature) => {
+                    warn!("📡 Bad signature on {:?}", value.value.label());
+                    continue;
+                }
+            };
+            // An observation we already passed on, re-signed, is kept but not passed on again
+            let passed_on = match replaced.map(|held| held.value) {
+                Some(CrdsValue::PriceData(held)) => Some(held.timestamp),
+                _ => None,
+            };
+            
+            match &value.value {
+                CrdsValue::PriceData(data) => {
+                    if passed_on.is_none_or(|timestamp| data.timestamp > timestamp) {
+                        inbound.price_tx.send(data.to_update()).await.ok();
+                    }
+                }
+                CrdsValue::Vote(vote) => {
+                    inbound.vote_tx.send(vote.to_consensus()).await.ok();
+                }
+                CrdsValue::ContactInfo(info) => {
+                    if let Some(addr) = self.dialable_contact(info, from, session) {
+                        if self.table.write().await.add(addr, from.ip()) {
+                            debug!("📡 Learned {} at {} from its contact info", info.pubkey, addr);
+                            self.dial_now.notify_one();
+                        }
+                    }
+                }
+                CrdsValue::StakeInfo(_) => {}
+            }
+            accepted.push(value);
+        }
+        accepted
+    }
+
+    /// Where to dial the node `info` is about. A node that did not advertise
+    /// an IP is reachable at the one its own session comes from; relayed
+    /// without one, it cannot be dialed.
+    fn dialable_contact(&self, info: &ContactInfo, from: SocketAddr, session: &Pubkey) -> Option<SocketAddr> {
+        if info.pubkey == self.config.identity.pubkey() {
+            return None;
+        }
+        if !info.gossip_addr.ip().is_unspecified() {
+            return Some(info.gossip_addr);
+        }
+        (info.pubkey == *session).then(|| SocketAddr::new(from.ip(), info.gossip_addr.port()))
+    }
+
+    /// Send `values` to a stake-weighted sample of peers other than `from`
+    async fn push_values(&self, values: Vec<VersionedCrdsValue>, from: Option<SocketAddr>) {
+        let targets = self.push.select_peers(&self.peer_stakes().await, from.as_ref());
+        for addr in targets {
+            for chunk in values.chunks(MAX_VALUES_PER_MESSAGE) {
+                if let Err(e) = self.send(addr, &GossipMessage::Push(chunk.to_vec())).await {
+                    debug!("📡 Push to {} failed: {}", addr, e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Connected peers with their stake
+    async fn peer_stakes(&self) -> Vec<(SocketAddr, u64)> {
+        let identities: Vec<_> = self.peers.read().await.iter()
+            .map(|(addr, peer)| (*addr, peer.identity))
+            .collect();
+        let mut stakes = Vec::with_capacity(identities.len());
+        for (addr, identity) in identities {
+            stakes.push((addr, self.stake_of(&identity).await));
+        }
+        stakes
+    }
+
+    /// Track one change to the validator set
+    async fn apply_stake_change(&self, change: ValidatorSetChange) {
+        let mut stakes = self.stakes.write().await;
+        match change {
+            ValidatorSetChange::Added { staker, stake, .. } => {
+                stakes.insert(staker, stake);
+            }
+            ValidatorSetChange::StakeChanged { staker, new_stake, .. } => {
+                stakes.insert(staker, new_stake);
+            }
+            ValidatorSetChange::Removed { staker, .. } => {
+                stakes.remove(&staker);
+            }
+        }
+        debug!("📡 {} staked validators may gossip prices and votes", stakes.len());
+    }
+
+    /// Reload stake from the governance program when we fell behind the
+    /// validator set's changes; keeps the last stakes if the cluster cannot
+    /// be reached
+    async fn refresh_stakes(&self) {
+        let cluster = Arc::clone(&self.cluster);
+        let governance_program = self.governance_program;
+        match tokio::task::spawn_blocking(move || cluster.staker_accounts(&governance_program)).await {
+            Ok(Ok(accounts)) => {
+                let stakes: HashMap<Pubkey, u64> = accounts.into_iter()
+                    .filter(|(_, info)| info.staked_amount > 0)
+                    .map(|(address, info)| (address, info.staked_amount))
+                    .collect();
+                debug!("📡 {} staked validators may gossip prices and votes", stakes.len());
+                *self.stakes.write().await = stakes;
+            }
+            Ok(Err(e)) => warn!("📡 Failed to refresh stakes: {}", e),
+            Err(e) => warn!("📡 Stake refresh panicked: {}", e),
+        }
+    }
+
+    /// Whether `identity` has stake in the governance program
+    async fn is_staked(&self, identity: &Pubkey) -> bool {
+        self.stake_of(identity).await > 0
+    }
+
+    async fn stake_of(&self, identity: &Pubkey) -> u64 {
+        let address = staker_info_address(identity, &self.governance_program);
+        self.stakes.read().await.get(&address).copied().unwrap_or(0)
+    }
+
+    async fn save_table(&self) {
+        if let Err(e) = self.table.read().await.save(&self.table_path) {
+            warn!("📡 Failed to save peer table: {}", e);
+        }
+    }
+
+    /// Queue a message for one connected peer; fails if its queue is full
+    async fn send(&self, addr: SocketAddr, msg: &GossipMessage) -> Result<()> {
+        let outbound = self.peers.read().await.get(&addr)
+            .map(|peer| peer.outbound.clone())
+            .ok_or_else(|| anyhow::anyhow!("Not connected to {}", addr))?;
+        outbound.try_send(msg.clone()).map_err(|e| anyhow::anyhow!("Cannot queue for {}: {}", addr, e))
+    }
+
+    /// Queue a message for every connected peer, skipping those that are behind
+    async fn broadcast(peers: &PeerMap, msg: &GossipMessage) {
+        let outbound: Vec<_> = peers.read().await.iter()
+            .map(|(addr, peer)| (*addr, peer.outbound.clone()))
+            .collect();
         
-        self.peers.write().await.insert(addr, stream);
-        self.known_peers.write().await.push(addr);
+        for (addr, outbound) in outbound {
+            if let Err(e) = outbound.try_send(msg.clone()) {
+                debug!("📡 Dropped a message to {}: {}", addr, e);
+            }
+        }
+    }
+
+    /// Publish our aggregated price for `update.asset` as a signed CRDS value
+    pub async fn broadcast_price_update(&self, update: &PriceUpdate) -> Result<()> {
+        let data = PriceData::from_update(self.config.identity.pubkey(), update);
+        self.publish(CrdsValue::PriceData(data)).await;
+        Ok(())
+    }
+
+    /// Current CRDS value under `label`, if we hold one
+    pub async fn crds_value(&self, label: &CrdsLabel) -> Option<VersionedCrdsValue> {
+        self.crds.read().await.get(label).cloned()
+    }
+
+    pub async fn connect_to_peer(&self, addr: SocketAddr, inbound: GossipInbound) -> Result<()> {
+        info!("📡 Connecting to peer: {}", addr);
+        let stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await??;
+        self.register(addr, stream, true, inbound).await?;
         
         info!("✅ Connected to peer: {}", addr);
         Ok(())
     }
+
+    /// Listen addresses of the connected peers that announced one
+    pub async fn connected_peers(&self) -> Vec<SocketAddr> {
+        self.peers.read().await.values().filter_map(|peer| peer.listen_addr).collect()
+    }
+}
+
+/// Write a peer's queued messages until the queue closes or a write fails
+/// or stalls past `WRITE_TIMEOUT`
+async fn write_queued(mut writer: PeerWriter, mut queue: mpsc::Receiver<GossipMessage>) -> Result<()> {
+    while let Some(msg) = queue.recv().await {
+        timeout(WRITE_TIMEOUT, writer.send(msg)).await
+            .map_err(|_| anyhow::anyhow!("write timed out"))??;
+    }
+    Ok(())
+}
+
+/// Unix time in milliseconds, as CRDS wallclocks are kept
+fn wallclock() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
 }
 
 pub async fn start_gossip_network(
     config: Arc<NodeConfig>,
-    gossip_tx: mpsc::Sender<PriceUpdate>,
+    cluster: Arc<dyn Cluster>,
+    stake_changes: broadcast::Receiver<ValidatorSetChange>,
+    inbound: GossipInbound,
+    outbound: GossipOutbound,
+    status: Arc<RwLock<NodeStatus>>,
     shutdown: tokio::sync::broadcast::Receiver<()>,
 ) -> Result<()> {
-    let network = GossipNetwork::new(config);
-    network.start(gossip_tx, shutdown).await
+    let listener = TcpListener::bind(("0.0.0.0", config.gossip_port)).await?;
+    let network = GossipNetwork::new(config, cluster, status)?;
+    network.start(listener, stake_changes, inbound, outbound, shutdown).await
 }
 
 // Helper to broadcast custom price data via gossip
@@ -233,3 +760,285 @@ pub async fn broadcast_price_update(
 ) -> Result<()> {
     network.broadcast_price_update(update).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::validator_set::StakerInfo;
+    use std::path::Path;
+    use tachyon_merkle::Price;
+    use tokio::sync::broadcast;
+
+    /// Cluster in which the listed identities have stake
+    #[derive(Default)]
+    struct TestCluster {
+        staked: std::sync::RwLock<Vec<Pubkey>>,
+    }
+
+    impl Cluster for TestCluster {
+        fn slot(&self) -> Result<u64> {
+            Ok(0)
+        }
+
+        fn epoch(&self) -> Result<u64> {
+            Ok(0)
+        }
+
+        fn staker_accounts(&self, governance_program: &Pubkey) -> Result<Vec<(Pubkey, StakerInfo)>> {
+            Ok(self.staked.read().unwrap().iter()
+                .map(|identity| {
+                    let staker = StakerInfo { staked_amount: 100, ..StakerInfo::default() };
+                    (staker_info_address(identity, governance_program), staker)
+                })
+                .collect())
+        }
+    }
+
+    struct TestNode {
+        network: GossipNetwork,
+        addr: SocketAddr,
+        prices: mpsc::Receiver<PriceUpdate>,
+        votes: mpsc::Receiver<Vote>,
+        /// Our prices and votes, as the fetcher and consensus hand them to gossip
+        our_prices: mpsc::Sender<PriceUpdate>,
+        our_votes: mpsc::Sender<Vote>,
+        /// Validator set changes, as consensus sends them
+        stake_changes: broadcast::Sender<ValidatorSetChange>,
+        shutdown: broadcast::Sender<()>,
+        handle: tokio::task::JoinHandle<Result<()>>,
+    }
+
+    impl TestNode {
+        fn identity(&self) -> Pubkey {
+            self.network.config.identity.pubkey()
+        }
+
+        /// Announce `identity` as a new staker and wait until gossip took it in
+        async fn add_stake(&self, identity: Pubkey) {
+            let staker = staker_info_address(&identity, &self.network.governance_program);
+            self.stake_changes.send(ValidatorSetChange::Added { staker, identity: None, stake: 100 }).unwrap();
+            timeout(Duration::from_secs(5), async {
+                while !self.network.is_staked(&identity).await {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            }).await.unwrap();
+        }
+    }
+
+    /// A gossip node on a free loopback port, with nobody staked
+    async fn start_node(entrypoints: &[SocketAddr], peers_path: &Path) -> TestNode {
+        start_node_in(entrypoints, peers_path, Arc::new(TestCluster::default())).await
+    }
+
+    async fn start_node_in(entrypoints: &[SocketAddr], peers_path: &Path, cluster: Arc<TestCluster>) -> TestNode {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut config: NodeConfig = toml::from_str(&format!(r#"
+            keypair_path = "unused"
+            rpc_url = "http://127.0.0.1:1"
+            program_id = "TACH9r2uZzoFM6daofesADjeDn9NqB1pKFWP5mfByb1"
+            l2_program_id = "L2TA7eVsDyXx7nxF4p2Xay3iWgdCHuMPx6YV5odwMTx"
+            gossip_port = {}
+            api_port = 0
+            update_interval_ms = 1000
+            batch_interval_ms = 100
+            min_publishers = 1
+            assets = []
+            exchanges = {{}}
+        "#, addr.port())).unwrap();
+        config.entrypoints = entrypoints.iter().map(|addr| addr.to_string()).collect();
+        config.peers_path = peers_path.to_string_lossy().into_owned();
+        config.pull_interval_ms = 100;
+        let config = Arc::new(config);
+
+        let network = GossipNetwork::new(Arc::clone(&config), cluster, Arc::new(RwLock::new(NodeStatus::new(&config)))).unwrap();
+        let (price_tx, prices) = mpsc::channel(10);
+        let (vote_tx, votes) = mpsc::channel(10);
+        let (our_votes, vote_rx) = mpsc::channel(10);
+        let (our_prices, price_rx) = mpsc::channel(10);
+        let (stake_changes, stake_rx) = broadcast::channel(16);
+        let (shutdown, shutdown_rx) = broadcast::channel(1);
+        let handle = tokio::spawn({
+            let network = network.clone();
+            async move {
+                let (inbound, outbound) = (GossipInbound { price_tx, vote_tx }, GossipOutbound { price_rx, vote_rx });
+                network.start(listener, stake_rx, inbound, outbound, shutdown_rx).await
+            }
+        });
+        TestNode { network, addr, prices, votes, our_prices, our_votes, stake_changes, shutdown, handle }
+    }
+
+    /// Wait until `node` is connected to every one of `addrs`
+    async fn wait_connected(node: &TestNode, addrs: &[SocketAddr]) {
+        let connected = async {
+            loop {
+                let peers = node.network.connected_peers().await;
+                if addrs.iter().all(|addr| peers.contains(addr)) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        timeout(Duration::from_secs(5), connected).await
+            .unwrap_or_else(|_| panic!("{} never connected to {:?}", node.addr, addrs));
+    }
+
+    #[tokio::test]
+    async fn test_nodes_discover_each_other_through_an_entrypoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = start_node(&[], &dir.path().join("a.json")).await;
+        let b = start_node(&[a.addr], &dir.path().join("b.json")).await;
+        wait_connected(&a, &[b.addr]).await;
+
+        // C only knows A, and learns B from A's `Peers`
+        let c = start_node(&[a.addr], &dir.path().join("c.json")).await;
+        wait_connected(&c, &[a.addr, b.addr]).await;
+        wait_connected(&b, &[c.addr]).await;
+
+        // Restarted without entrypoints, C rejoins from its saved table
+        c.shutdown.send(()).unwrap();
+        c.handle.await.unwrap().unwrap();
+        let saved = PeerTable::load(dir.path().join("c.json")).unwrap();
+        assert!(saved.get(&b.addr).is_some_and(|entry| entry.last_seen.is_some()));
+
+        let restarted = start_node(&[], &dir.path().join("c.json")).await;
+        wait_connected(&restarted, &[a.addr, b.addr]).await;
+    }
+
+    #[tokio::test]
+    async fn test_dialing_our_own_address_is_remembered() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = start_node(&[], &dir.path().join("self.json")).await;
+        node.network.connect_to_peer(node.addr, GossipInbound {
+            price_tx: mpsc::channel(1).0,
+            vote_tx: mpsc::channel(1).0,
+        }).await.unwrap();
+
+        let remembered = async {
+            loop {
+                if node.network.table.read().await.get(&node.addr).is_some_and(|entry| entry.node_id.is_some()) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        timeout(Duration::from_secs(5), remembered).await.unwrap();
+
+        let skip = HashSet::from([node.network.config.identity.pubkey().to_string()]);
+        assert!(node.network.table.read().await.candidates(&HashSet::new(), &skip).is_empty());
+        assert!(node.network.connected_peers().await.is_empty());
+    }
+
+    /// Wait until `node` holds a value under `label`
+    async fn wait_for_value(node: &TestNode, label: &CrdsLabel) {
+        let held = async {
+            while node.network.crds_value(label).await.is_none() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        timeout(Duration::from_secs(5), held).await
+            .unwrap_or_else(|_| panic!("{} never got {:?}", node.addr, label));
+    }
+
+    fn price_update(node_pubkey: Pubkey, mantissa: i64) -> PriceUpdate {
+        PriceUpdate {
+            asset: "BTC/USD".to_string(),
+            price: Price::new(mantissa, -2),
+            confidence: Price::new(5, -2),
+            timestamp: 1_700_000_000,
+            exchange: "aggregated".to_string(),
+            node_pubkey: node_pubkey.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_only_staked_validators_reach_the_aggregator() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut a = start_node(&[], &dir.path().join("a.json")).await;
+        let staked = start_node(&[a.addr], &dir.path().join("staked.json")).await;
+        let unstaked = start_node(&[a.addr], &dir.path().join("unstaked.json")).await;
+        wait_connected(&a, &[staked.addr, unstaked.addr]).await;
+        a.add_stake(staked.identity()).await;
+
+        unstaked.network.broadcast_price_update(&price_update(unstaked.identity(), 100)).await.unwrap();
+        staked.network.broadcast_price_update(&price_update(staked.identity(), 300)).await.unwrap();
+
+        let received = timeout(Duration::from_secs(5), a.prices.recv()).await.unwrap().unwrap();
+        assert_eq!((received.node_pubkey, received.price.mantissa), (staked.identity().to_string(), 300));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(a.prices.try_recv().is_err());
+        assert!(a.network.crds_value(&CrdsLabel::PriceData(unstaked.identity(), "BTC/USD".to_string())).await.is_none());
+    }
+
+    fn signed_contact(node: &TestNode, gossip_addr: SocketAddr) -> VersionedCrdsValue {
+        let contact = ContactInfo {
+            pubkey: node.identity(),
+            gossip_addr,
+            api_addr: SocketAddr::new(gossip_addr.ip(), 0),
+            version: wire::PROTOCOL_VERSION as u64,
+        };
+        VersionedCrdsValue::new_signed(CrdsValue::ContactInfo(contact), wallclock(), &node.network.config.identity).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_peers_learned_from_contact_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let cluster = Arc::new(TestCluster::default());
+        let a = start_node_in(&[], &dir.path().join("a.json"), Arc::clone(&cluster)).await;
+        let b = start_node(&[], &dir.path().join("b.json")).await;
+        let node = start_node_in(&[], &dir.path().join("node.json"), Arc::clone(&cluster)).await;
+        cluster.staked.write().unwrap().push(a.identity());
+        node.network.refresh_stakes().await;
+        let inbound = GossipInbound { price_tx: mpsc::channel(1).0, vote_tx: mpsc::channel(1).0 };
+        let relay = (SocketAddr::from(([127, 0, 0, 9], 4000)), Pubkey::new_unique());
+
+        // Nobody told the node about A but A's own contact info, relayed
+        let accepted = node.network.accept(vec![signed_contact(&a, a.addr)], relay.0, &relay.1, &inbound).await;
+        assert_eq!(accepted.len(), 1);
+        wait_connected(&node, &[a.addr]).await;
+
+        // B only advertises its port: dialable at the IP of B's own session,
+        // and not taken from a relay at all since B has no stake
+        let contact = signed_contact(&b, SocketAddr::from(([0, 0, 0, 0], b.addr.port())));
+        assert!(node.network.accept(vec![contact.clone()], relay.0, &relay.1, &inbound).await.is_empty());
+        let session = SocketAddr::from(([127, 0, 0, 1], 4001));
+        assert_eq!(node.network.accept(vec![contact], session, &b.identity(), &inbound).await.len(), 1);
+        wait_connected(&node, &[b.addr]).await;
+    }
+
+    #[tokio::test]
+    async fn test_late_joiner_pulls_what_it_missed() {
+        let dir = tempfile::tempdir().unwrap();
+        let cluster = Arc::new(TestCluster::default());
+        let a = start_node_in(&[], &dir.path().join("a.json"), Arc::clone(&cluster)).await;
+        let mut b = start_node_in(&[a.addr], &dir.path().join("b.json"), Arc::clone(&cluster)).await;
+        wait_connected(&b, &[a.addr]).await;
+        cluster.staked.write().unwrap().push(a.identity());
+        b.network.refresh_stakes().await;
+
+        // Pushed to B as A publishes them
+        a.our_prices.send(price_update(a.identity(), 6_500_000)).await.unwrap();
+        let vote = Vote::new_signed(&a.network.config.identity, 7, &"ab".repeat(32), 100).unwrap();
+        a.our_votes.send(vote.clone()).await.unwrap();
+        let price = timeout(Duration::from_secs(5), b.prices.recv()).await.unwrap().unwrap();
+        assert_eq!(price.price.mantissa, 6_500_000);
+        let received = timeout(Duration::from_secs(5), b.votes.recv()).await.unwrap().unwrap();
+        assert_eq!((received.batch_number, &received.root_hash), (7, &vote.root_hash));
+        assert!(received.verify());
+
+        // The same observation, published again, is passed on once
+        a.our_prices.send(price_update(a.identity(), 6_500_000)).await.unwrap();
+        a.our_prices.send(PriceUpdate { timestamp: 1_700_000_001, ..price_update(a.identity(), 6_500_100) }).await.unwrap();
+        let price = timeout(Duration::from_secs(5), b.prices.recv()).await.unwrap().unwrap();
+        assert_eq!((price.timestamp, price.price.mantissa), (1_700_000_001, 6_500_100));
+
+        // C was not there for the push; it gets everything by pulling
+        let mut c = start_node_in(&[b.addr], &dir.path().join("c.json"), Arc::clone(&cluster)).await;
+        c.add_stake(a.identity()).await;
+        let price = timeout(Duration::from_secs(5), c.prices.recv()).await.unwrap().unwrap();
+        assert_eq!((price.node_pubkey, price.price.mantissa), (a.identity().to_string(), 6_500_100));
+        assert_eq!(timeout(Duration::from_secs(5), c.votes.recv()).await.unwrap().unwrap().batch_number, 7);
+        wait_for_value(&c, &CrdsLabel::ContactInfo(a.identity())).await;
+        wait_for_value(&c, &CrdsLabel::StakeInfo(a.identity())).await;
+    }
+}
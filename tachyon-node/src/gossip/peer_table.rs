@@ -0,0 +1,256 @@
+// Peer Table - gossip addresses this node can dial
+//
+// Filled from entrypoints, `Peers` answers and the listen address each peer
+// announces. Addresses that keep failing are dropped; the rest are written to
+// `peers_path` so a restarted node rejoins without its entrypoints. Addresses
+// we never connected to are only hearsay: each peer may hand us a bounded
+// number, and they are the first to go when the table is full.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Consecutive failed dials after which an address is forgotten
+const MAX_FAILURES: u32 = 5;
+
+/// Most addresses kept; beyond it never-connected ones make room
+const MAX_PEERS: usize = 1024;
+
+/// Most never-connected addresses kept from any one source
+const MAX_PEERS_PER_SOURCE: usize = 32;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerEntry {
+    /// Identity the peer announced, once it has
+    pub node_id: Option<String>,
+    /// Last time we were connected to it (unix seconds)
+    pub last_seen: Option<i64>,
+    /// Failed dials since it was last seen
+    pub failures: u32,
+    /// IP of the peer that told us about it, unless we found it ourselves
+    #[serde(default)]
+    pub source: Option<IpAddr>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerTable {
+    peers: BTreeMap<SocketAddr, PeerEntry>,
+}
+
+impl PeerTable {
+    /// Table saved at `path`; empty if there is none yet
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read peer table: {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Invalid peer table: {}", path.display()))
+    }
+
+    /// Written to a temporary file first, so a crash never leaves half a table
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Learn an address from the peer at `source`; returns whether it was
+    /// kept. A source past its share, or a table full of addresses we were
+    /// connected to, leaves it out.
+    pub fn add(&mut self, addr: SocketAddr, source: IpAddr) -> bool {
+        if !is_dialable(&addr) || self.peers.contains_key(&addr) {
+            return false;
+        }
+        let from_source = self.peers.values()
+            .filter(|entry| entry.last_seen.is_none() && entry.source == Some(source))
+            .count();
+        if from_source >= MAX_PEERS_PER_SOURCE {
+            return false;
+        }
+        if self.peers.len() >= MAX_PEERS && !self.evict_unseen() {
+            return false;
+        }
+        self.peers.insert(addr, PeerEntry { source: Some(source), ..PeerEntry::default() });
+        true
+    }
+
+    /// Connected to `addr`, which announced `node_id`
+    pub fn seen(&mut self, addr: SocketAddr, node_id: &str, now: i64) {
+        if !is_dialable(&addr) {
+            return;
+        }
+        if !self.peers.contains_key(&addr) && self.peers.len() >= MAX_PEERS && !self.evict_unseen() {
+            // Every entry was connected to: the one seen longest ago goes
+            let oldest = self.peers.iter().min_by_key(|(_, entry)| entry.last_seen).map(|(addr, _)| *addr);
+            if let Some(oldest) = oldest {
+                self.peers.remove(&oldest);
+            }
+        }
+        let entry = self.peers.entry(addr).or_default();
+        entry.node_id = Some(node_id.to_string());
+        entry.last_seen = Some(now);
+        entry.failures = 0;
+    }
+
+    /// A dial to `addr` failed; forgets it after too many in a row
+    pub fn failed(&mut self, addr: SocketAddr) {
+        let Some(entry) = self.peers.get_mut(&addr) else {
+            return;
+        };
+        entry.failures += 1;
+        if entry.failures >= MAX_FAILURES {
+            self.peers.remove(&addr);
+        }
+    }
+
+    /// Forget one never-connected address, from the source that gave us the
+    /// most and then the one that failed most; false if there is none
+    fn evict_unseen(&mut self) -> bool {
+        let mut per_source: HashMap<Option<IpAddr>, usize> = HashMap::new();
+        for entry in self.peers.values().filter(|entry| entry.last_seen.is_none()) {
+            *per_source.entry(entry.source).or_default() += 1;
+        }
+        let evicted = self.peers.iter()
+            .filter(|(_, entry)| entry.last_seen.is_none())
+            .max_by_key(|(addr, entry)| (per_source[&entry.source], entry.failures, std::cmp::Reverse(**addr)))
+            .map(|(addr, _)| *addr);
+        evicted.is_some_and(|addr| self.peers.remove(&addr).is_some())
+    }
+
+    pub fn get(&self, addr: &SocketAddr) -> Option<&PeerEntry> {
+        self.peers.get(addr)
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// Addresses worth dialing, skipping `connected` ones, known identities in
+    /// `skip_ids` (ourselves, peers already connected elsewhere); fewest
+    /// failures first, then most recently seen
+    pub fn candidates(&self, connected: &HashSet<SocketAddr>, skip_ids: &HashSet<String>) -> Vec<SocketAddr> {
+        let mut candidates: Vec<_> = self.peers.iter()
+            .filter(|(addr, _)| !connected.contains(addr))
+            .filter(|(_, entry)| !entry.node_id.as_ref().is_some_and(|id| skip_ids.contains(id)))
+            .collect();
+        candidates.sort_by_key(|(addr, entry)| (entry.failures, std::cmp::Reverse(entry.last_seen), **addr));
+        candidates.into_iter().map(|(addr, _)| *addr).collect()
+    }
+
+    /// Up to `limit` addresses we have been connected to, most recent first,
+    /// for a `Peers` answer
+    pub fn shareable(&self, limit: usize) -> Vec<SocketAddr> {
+        let mut seen: Vec<_> = self.peers.iter()
+            .filter_map(|(addr, entry)| Some((entry.last_seen?, *addr)))
+            .collect();
+        seen.sort_by_key(|&(last_seen, addr)| (std::cmp::Reverse(last_seen), addr));
+        seen.into_iter().take(limit).map(|(_, addr)| addr).collect()
+    }
+}
+
+/// Only concrete addresses can be dialed
+fn is_dialable(addr: &SocketAddr) -> bool {
+    !addr.ip().is_unspecified() && !addr.ip().is_multicast() && addr.port() != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn source(n: u8) -> IpAddr {
+        IpAddr::from([10, 0, 0, n])
+    }
+
+    #[test]
+    fn test_candidates_and_failures() {
+        let mut table = PeerTable::default();
+        assert!(table.add(addr(9001), source(1)));
+        assert!(!table.add(addr(9001), source(1)));
+        assert!(!table.add("0.0.0.0:9000".parse().unwrap(), source(1)));
+        table.add(addr(9002), source(1));
+        table.seen(addr(9003), "node3", 100);
+        table.seen(addr(9004), "node4", 200);
+        table.failed(addr(9001));
+
+        assert_eq!(table.candidates(&HashSet::new(), &HashSet::new()), vec![addr(9004), addr(9003), addr(9002), addr(9001)]);
+        let connected = HashSet::from([addr(9004)]);
+        let skip = HashSet::from(["node3".to_string()]);
+        assert_eq!(table.candidates(&connected, &skip), vec![addr(9002), addr(9001)]);
+
+        // Only addresses we were connected to are passed on
+        assert_eq!(table.shareable(10), vec![addr(9004), addr(9003)]);
+        assert_eq!(table.shareable(1), vec![addr(9004)]);
+
+        for _ in 1..MAX_FAILURES {
+            table.failed(addr(9001));
+        }
+        assert!(table.get(&addr(9001)).is_none());
+        assert_eq!(table.len(), 3);
+    }
+
+    #[test]
+    fn test_sources_are_capped_and_hearsay_goes_first() {
+        let mut table = PeerTable::default();
+        for port in 0..MAX_PEERS_PER_SOURCE as u16 {
+            assert!(table.add(addr(10_000 + port), source(1)));
+        }
+        // One peer cannot flood the table
+        assert!(!table.add(addr(9001), source(1)));
+        assert!(table.add(addr(9001), source(2)));
+
+        // Fill the rest with addresses we were connected to
+        let mut port = 20_000;
+        while table.len() < MAX_PEERS {
+            table.seen(addr(port), "node", 100);
+            port += 1;
+        }
+
+        // A full table makes room from the source that gave us the most
+        assert!(table.add(addr(9002), source(3)));
+        assert_eq!(table.len(), MAX_PEERS);
+        assert!(table.get(&addr(10_000)).is_none());
+        assert!(table.get(&addr(9001)).is_some());
+
+        // Newly connected peers push out hearsay, never connected peers
+        for port in 30_000..30_000 + MAX_PEERS_PER_SOURCE as u16 + 1 {
+            table.seen(addr(port), "node", 200);
+        }
+        assert_eq!(table.len(), MAX_PEERS);
+        assert!(table.peers.values().all(|entry| entry.last_seen.is_some()));
+        assert!(!table.add(addr(9003), source(4)));
+    }
+
+    #[test]
+    fn test_table_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peers.json");
+        assert!(PeerTable::load(&path).unwrap().is_empty());
+
+        let mut table = PeerTable::default();
+        table.seen(addr(9001), "node1", 100);
+        table.add(addr(9002), source(1));
+        table.save(&path).unwrap();
+
+        let loaded = PeerTable::load(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.get(&addr(9001)).unwrap().node_id.as_deref(), Some("node1"));
+    }
+}
@@ -1,63 +1,96 @@
 #![allow(dead_code)]
 /// Push/Pull Gossip Protocol
-/// 
+///
 /// Inspired by Solana's gossip push/pull mechanism.
-/// - Push: Broadcast new data to random peers
-/// - Pull: Request missing data from peers
+/// - Push: Send new values on to a stake-weighted sample of peers
+/// - Pull: Periodically send a peer a bloom filter of what we hold and take
+///   back what it has that we lack
 
-use super::crds::{Crds, VersionedCrdsValue, CrdsLabel};
-use std::collections::HashSet;
+use super::crds::{Crds, VersionedCrdsValue};
 use std::net::SocketAddr;
 use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 
-/// Gossip message types
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum GossipMessage {
-    /// Push new values to peers
-    Push(Vec<VersionedCrdsValue>),
-    /// Pull request with bloom filter
-    PullRequest {
-        filter: BloomFilter,
-        from: SocketAddr,
-    },
-    /// Pull response with values
-    PullResponse(Vec<VersionedCrdsValue>),
-    /// Ping/Pong for liveness
-    Ping(u64),
-    Pong(u64),
-}
+/// Most values sent in one push or pull response, so a message fits a frame
+pub const MAX_VALUES_PER_MESSAGE: usize = 64;
+
+/// Most values sent back for one pull request; the requester's next filter
+/// covers what it got, so it catches up over a few pulls
+pub const MAX_PULL_RESPONSE_VALUES: usize = 4 * MAX_VALUES_PER_MESSAGE;
+
+/// Largest filter we build or answer, in bits
+const MAX_FILTER_BITS: usize = 256 * 1024;
 
-/// Simple bloom filter for pull requests
+/// Most hash functions a filter may ask us to evaluate per value
+const MAX_FILTER_HASHES: u32 = 16;
+
+/// Chance that a pull misses a value the requester lacks
+const FALSE_POSITIVE_RATE: f64 = 0.1;
+
+/// Bloom filter over value hashes for pull requests
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BloomFilter {
-    /// Set of labels we already have
-    pub known_labels: HashSet<String>,
+    bits: Vec<u64>,
+    num_hashes: u32,
+    /// Mixed into every bit position. A fresh seed per request gives each
+    /// pull different false positives, so a value one pull misses is
+    /// unlikely to be missed by the next.
+    seed: u64,
 }
 
 impl BloomFilter {
-    pub fn new() -> Self {
+    /// Filter sized for `num_items` at `FALSE_POSITIVE_RATE`
+    pub fn new(num_items: usize, seed: u64) -> Self {
+        let num_items = num_items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-num_items * FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil() as usize;
+        let num_bits = num_bits.clamp(64, MAX_FILTER_BITS);
+        let num_hashes = ((num_bits as f64 / num_items) * ln2).round() as u32;
+
         Self {
-            known_labels: HashSet::new(),
+            bits: vec![0; num_bits.div_ceil(64)],
+            num_hashes: num_hashes.clamp(1, MAX_FILTER_HASHES),
+            seed,
         }
     }
 
-    pub fn add(&mut self, label: &CrdsLabel) {
-        self.known_labels.insert(format!("{:?}", label));
+    /// A filter from a peer is only evaluated if it is one we could have built
+    pub fn is_valid(&self) -> bool {
+        !self.bits.is_empty()
+            && self.bits.len() * 64 <= MAX_FILTER_BITS
+            && (1..=MAX_FILTER_HASHES).contains(&self.num_hashes)
     }
 
-    pub fn contains(&self, label: &CrdsLabel) -> bool {
-        self.known_labels.contains(&format!("{:?}", label))
+    pub fn add(&mut self, hash: &[u8; 32]) {
+        for index in self.indexes(hash) {
+            self.bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.indexes(hash).all(|index| self.bits[index / 64] & (1 << (index % 64)) != 0)
     }
-}
 
-impl Default for BloomFilter {
-    fn default() -> Self {
-        Self::new()
+    /// Bit positions for `hash`, by double hashing its two leading words
+    /// mixed with the seed. The size is a multiple of 64, so the step is kept
+    /// odd: an even one would revisit the same few bits.
+    fn indexes(&self, hash: &[u8; 32]) -> impl Iterator<Item = usize> {
+        let num_bits = (self.bits.len() * 64) as u64;
+        let h1 = mix(u64::from_le_bytes(hash[0..8].try_into().unwrap()) ^ self.seed);
+        let h2 = mix(u64::from_le_bytes(hash[8..16].try_into().unwrap()) ^ self.seed.rotate_left(32)) | 1;
+        (0..self.num_hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
     }
 }
 
+/// SplitMix64 finalizer: every input bit moves about half the output bits
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
 /// Push gossip manager
+#[derive(Debug, Clone, Copy)]
 pub struct PushGossip {
     /// Number of peers to push to
     fanout: usize,
@@ -68,167 +101,147 @@ impl PushGossip {
         Self { fanout }
     }
 
-    /// Select random peers for push
-    pub fn select_peers<'a>(
+    /// Pick up to `fanout` peers, each as likely as its stake; unstaked
+    /// peers count as one lamport so they are only picked when few are staked
+    pub fn select_peers(
         &self,
-        peers: &'a [SocketAddr],
+        peers: &[(SocketAddr, u64)],
         exclude: Option<&SocketAddr>,
-    ) -> Vec<&'a SocketAddr> {
+    ) -> Vec<SocketAddr> {
         let mut rng = rand::thread_rng();
-        let mut available: Vec<_> = peers
+        let available: Vec<_> = peers
             .iter()
-            .filter(|p| Some(*p) != exclude)
+            .filter(|(addr, _)| Some(addr) != exclude)
             .collect();
-        
-        available.shuffle(&mut rng);
-        available.into_iter().take(self.fanout).collect()
-    }
 
-    /// Create push message with recent updates
-    pub fn create_push_message(&self, crds: &Crds, since: u64) -> GossipMessage {
-        let values: Vec<_> = crds
-            .values()
-            .filter(|v| v.wallclock > since)
-            .cloned()
-            .collect();
-        
-        GossipMessage::Push(values)
-    }
-}
-
-/// Pull gossip manager
-pub struct PullGossip {
-    /// Minimum time between pull requests (ms)
-    pull_interval_ms: u64,
-    /// Last pull timestamp
-    last_pull: u64,
-}
-
-impl PullGossip {
-    pub fn new(pull_interval_ms: u64) -> Self {
-        Self {
-            pull_interval_ms,
-            last_pull: 0,
-        }
-    }
-
-    /// Check if it's time to pull
-    pub fn should_pull(&self, now: u64) -> bool {
-        now - self.last_pull >= self.pull_interval_ms
-    }
-
-    /// Create pull request with bloom filter
-    pub fn create_pull_request(
-        &mut self,
-        crds: &Crds,
-        from: SocketAddr,
-        now: u64,
-    ) -> GossipMessage {
-        self.last_pull = now;
-        
-        // Build bloom filter of what we have
-        let mut filter = BloomFilter::new();
-        for value in crds.values() {
-            filter.add(&value.value.label());
+        match available.choose_multiple_weighted(&mut rng, self.fanout, |(_, stake)| (*stake).max(1) as f64) {
+            Ok(selected) => selected.map(|(addr, _)| *addr).collect(),
+            Err(_) => Vec::new(),
         }
-        
-        GossipMessage::PullRequest { filter, from }
     }
+}
 
-    /// Process pull request and create response
-    pub fn process_pull_request(
-        &self,
-        crds: &Crds,
-        filter: &BloomFilter,
-    ) -> GossipMessage {
-        // Send values that the requester doesn't have
-        let values: Vec<_> = crds
-            .values()
-            .filter(|v| !filter.contains(&v.value.label()))
-            .cloned()
-            .collect();
-        
-        GossipMessage::PullResponse(values)
+/// Filter of every value in `crds`, for a pull request, under a fresh seed
+pub fn build_filter(crds: &Crds) -> BloomFilter {
+    let mut filter = BloomFilter::new(crds.len(), rand::random());
+    for value in crds.values() {
+        filter.add(&value.hash());
     }
+    filter
+}
 
-    /// Process pull response and insert into CRDS
-    pub fn process_pull_response(
-        &self,
-        crds: &mut Crds,
-        values: Vec<VersionedCrdsValue>,
-    ) -> usize {
-        let mut inserted = 0;
-        for value in values {
-            if crds.insert(value).is_ok() {
-                inserted += 1;
-            }
-        }
-        inserted
+/// Up to `MAX_PULL_RESPONSE_VALUES` values in `crds` that the requester's
+/// filter says it lacks, newest first
+pub fn missing_values(crds: &Crds, filter: &BloomFilter) -> Vec<VersionedCrdsValue> {
+    if !filter.is_valid() {
+        return Vec::new();
     }
+    let mut missing: Vec<_> = crds.values()
+        .filter(|value| !filter.contains(&value.hash()))
+        .collect();
+    missing.sort_by_key(|value| std::cmp::Reverse(value.wallclock));
+    missing.into_iter().take(MAX_PULL_RESPONSE_VALUES).cloned().collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use super::super::crds::{CrdsValue, ContactInfo};
-    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+
+    fn contact(keypair: &Keypair, wallclock: u64) -> VersionedCrdsValue {
+        let contact = ContactInfo {
+            pubkey: keypair.pubkey(),
+            gossip_addr: "127.0.0.1:7777".parse().unwrap(),
+            api_addr: "127.0.0.1:8080".parse().unwrap(),
+            version: 1,
+        };
+        VersionedCrdsValue::new_signed(CrdsValue::ContactInfo(contact), wallclock, keypair).unwrap()
+    }
 
     #[test]
     fn test_push_select_peers() {
         let push = PushGossip::new(3);
-        let peers: Vec<SocketAddr> = vec![
-            "127.0.0.1:7777".parse().unwrap(),
-            "127.0.0.1:7778".parse().unwrap(),
-            "127.0.0.1:7779".parse().unwrap(),
-            "127.0.0.1:7780".parse().unwrap(),
+        let peers: Vec<(SocketAddr, u64)> = vec![
+            ("127.0.0.1:7777".parse().unwrap(), 0),
+            ("127.0.0.1:7778".parse().unwrap(), 0),
+            ("127.0.0.1:7779".parse().unwrap(), 1_000_000),
+            ("127.0.0.1:7780".parse().unwrap(), 0),
         ];
-        
+
         let selected = push.select_peers(&peers, None);
         assert_eq!(selected.len(), 3);
+
+        // The heavily staked peer is all but always in a sample of one
+        let push = PushGossip::new(1);
+        let picked = (0..20).filter(|_| push.select_peers(&peers, None) == vec![peers[2].0]).count();
+        assert!(picked >= 18);
+        assert!(!push.select_peers(&peers, Some(&peers[2].0)).contains(&peers[2].0));
     }
 
     #[test]
     fn test_bloom_filter() {
-        let mut filter = BloomFilter::new();
-        let pubkey = Pubkey::new_unique();
-        let label = CrdsLabel::ContactInfo(pubkey);
-        
-        assert!(!filter.contains(&label));
-        filter.add(&label);
-        assert!(filter.contains(&label));
+        let values: Vec<_> = (0..500).map(|i| contact(&Keypair::new(), i).hash()).collect();
+        let mut filter = BloomFilter::new(values.len(), 7);
+        for hash in &values {
+            filter.add(hash);
+        }
+
+        // Never a false negative, and about the configured rate of false positives
+        assert!(values.iter().all(|hash| filter.contains(hash)));
+        let others = (0..500).filter(|i| filter.contains(&contact(&Keypair::new(), *i).hash())).count();
+        assert!(others < 100, "{} false positives", others);
+
+        assert!(filter.is_valid());
+        assert!(!BloomFilter { bits: Vec::new(), num_hashes: 1, seed: 0 }.is_valid());
+        assert!(!BloomFilter { bits: vec![0; 4], num_hashes: 1_000, seed: 0 }.is_valid());
+    }
+
+    #[test]
+    fn test_filters_miss_different_values() {
+        let mut crds = Crds::new(1000, 1000);
+        for i in 0..200 {
+            crds.insert(contact(&Keypair::new(), i), true).unwrap();
+        }
+        let (first, second) = (build_filter(&crds), build_filter(&crds));
+        assert_ne!(first.seed, second.seed);
+
+        // The same table, but each filter lets through a different set of others
+        let others: Vec<[u8; 32]> = (0..2_000).map(|_| rand::random()).collect();
+        let false_positives = |filter: &BloomFilter| others.iter()
+            .filter(|hash| filter.contains(hash))
+            .collect::<Vec<_>>();
+        let (first, second) = (false_positives(&first), false_positives(&second));
+        assert!(!first.is_empty() && !second.is_empty());
+        assert_ne!(first, second);
     }
 
     #[test]
     fn test_pull_request_response() {
-        let mut crds = Crds::new(1000);
-        let pubkey = Pubkey::new_unique();
-        
-        let contact = ContactInfo {
-            pubkey,
-            gossip_addr: "127.0.0.1:7777".parse().unwrap(),
-            api_addr: "127.0.0.1:8080".parse().unwrap(),
-            version: 1,
-        };
-        
-        let value = VersionedCrdsValue {
-            value: CrdsValue::ContactInfo(contact),
-            wallclock: 100,
-            signature: [0; 64],
-        };
-        
-        crds.insert(value).unwrap();
-        
-        let pull = PullGossip::new(5000);
-        let filter = BloomFilter::new(); // Empty filter
-        
-        let response = pull.process_pull_request(&crds, &filter);
-        
-        match response {
-            GossipMessage::PullResponse(values) => {
-                assert_eq!(values.len(), 1);
-            }
-            _ => panic!("Expected PullResponse"),
+        let (alice, bob) = (Keypair::new(), Keypair::new());
+        let mut ours = Crds::new(1000, 1000);
+        let mut theirs = Crds::new(1000, 1000);
+
+        ours.insert(contact(&alice, 100), true).unwrap();
+        theirs.insert(contact(&alice, 200), true).unwrap();
+        theirs.insert(contact(&bob, 100), true).unwrap();
+
+        // They send what we lack: Bob, and Alice's newer version
+        let missing = missing_values(&theirs, &build_filter(&ours));
+        assert_eq!(missing.len(), 2);
+        for value in missing {
+            ours.insert(value, true).unwrap();
         }
+        assert!(missing_values(&theirs, &build_filter(&ours)).is_empty());
+
+        // An empty filter gets everything, up to the cap, newest first
+        assert_eq!(missing_values(&theirs, &BloomFilter::new(0, 0)).len(), 2);
+        for i in 0..MAX_PULL_RESPONSE_VALUES as u64 {
+            theirs.insert(contact(&Keypair::new(), 1_000 + i), true).unwrap();
+        }
+        let missing = missing_values(&theirs, &BloomFilter::new(0, 0));
+        assert_eq!(missing.len(), MAX_PULL_RESPONSE_VALUES);
+        assert!(missing.iter().all(|value| value.wallclock >= 1_000));
     }
 }
-
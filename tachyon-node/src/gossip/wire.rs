@@ -0,0 +1,405 @@
+// Wire - how gossip messages travel over TCP
+//
+// Every frame is a big-endian u32 length followed by that many bytes of
+// bincode. A connection opens with each side sending a `Hello` frame carrying
+// the protocol magic and version; only peers speaking our version get past
+// it. A Noise_XX handshake follows, after which every frame is a Noise
+// transport message, and each side then signs the Noise handshake hash with
+// its validator identity so the session is bound to that pubkey. Frames
+// larger than `MAX_FRAME_LEN`, and frames that do not decrypt or decode, are
+// errors that close the connection.
+
+use std::marker::PhantomData;
+use bytes::{Buf, BufMut, BytesMut};
+use futures::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::{timeout, Duration};
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+use super::GossipMessage;
+
+/// Identifies a Tachyon gossip connection
+pub const PROTOCOL_MAGIC: [u8; 4] = *b"TACH";
+
+/// Bumped whenever the encoding of `GossipMessage` changes
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// Largest frame body either side sends or accepts: the largest Noise message
+pub const MAX_FRAME_LEN: usize = 65_535;
+
+/// How long a peer has to get through the whole handshake
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+
+/// Authentication tag Noise adds to every transport message
+const NOISE_TAG_LEN: usize = 16;
+
+/// Prefix of what each side signs to prove its identity
+const AUTH_DOMAIN: &[u8] = b"tachyon-gossip-auth-v1";
+
+/// Length prefix
+const HEADER_LEN: usize = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WireError {
+    #[error("i/o error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("frame of {len} bytes exceeds the {max} byte limit")]
+    FrameTooLarge { len: usize, max: usize },
+    #[error("malformed frame: {0}")]
+    Malformed(#[from] bincode::Error),
+    #[error("noise: {0}")]
+    Noise(#[from] snow::Error),
+    #[error("peer could not prove it holds identity {0}")]
+    Unauthenticated(Pubkey),
+    #[error("not a Tachyon gossip peer (magic {0:02x?})")]
+    BadMagic([u8; 4]),
+    #[error("peer speaks protocol version {theirs}, we speak {ours}")]
+    VersionMismatch { ours: u16, theirs: u16 },
+    #[error("no handshake within {0:?}")]
+    HandshakeTimeout(Duration),
+    #[error("connection closed during handshake")]
+    Closed,
+}
+
+/// First frame on every connection, in both directions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    pub magic: [u8; 4],
+    pub version: u16,
+}
+
+impl Hello {
+    pub fn ours() -> Self {
+        Self { magic: PROTOCOL_MAGIC, version: PROTOCOL_VERSION }
+    }
+}
+
+/// Second handshake step: each side's identity and its signature over the
+/// Noise handshake hash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Auth {
+    identity: Pubkey,
+    signature: Signature,
+}
+
+/// Length-prefixed bincode frames of `T`, encrypted once the Noise
+/// handshake is done
+pub struct WireCodec<T> {
+    max_frame_len: usize,
+    cipher: Option<Box<snow::TransportState>>,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T> WireCodec<T> {
+    pub fn new() -> Self {
+        Self::with_max_frame_len(MAX_FRAME_LEN)
+    }
+
+    pub fn with_max_frame_len(max_frame_len: usize) -> Self {
+        Self { max_frame_len, cipher: None, _item: PhantomData }
+    }
+
+    /// Same framing and cipher, carrying `U`
+    fn carrying<U>(self) -> WireCodec<U> {
+        WireCodec { max_frame_len: self.max_frame_len, cipher: self.cipher, _item: PhantomData }
+    }
+}
+
+impl<T> Default for WireCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DeserializeOwned> Decoder for WireCodec<T> {
+    type Item = T;
+    type Error = WireError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, WireError> {
+        if src.len() < HEADER_LEN {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes([src[0], src[1], src[2], src[3]]) as usize;
+        // Refuse before buffering a body we would never accept
+        if len > self.max_frame_len {
+            return Err(WireError::FrameTooLarge { len, max: self.max_frame_len });
+        }
+        if src.len() < HEADER_LEN + len {
+            src.reserve(HEADER_LEN + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(HEADER_LEN);
+        let body = src.split_to(len);
+        match &mut self.cipher {
+            Some(cipher) => {
+                let mut plain = vec![0; len];
+                let plain_len = cipher.read_message(&body, &mut plain)?;
+                Ok(Some(bincode::deserialize(&plain[..plain_len])?))
+            }
+            None => Ok(Some(bincode::deserialize(&body)?)),
+        }
+    }
+}
+
+impl<T: Serialize> Encoder<T> for WireCodec<T> {
+    type Error = WireError;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), WireError> {
+        let mut body = bincode::serialize(&item)?;
+        let len = body.len() + if self.cipher.is_some() { NOISE_TAG_LEN } else { 0 };
+        if len > self.max_frame_len {
+            return Err(WireError::FrameTooLarge { len, max: self.max_frame_len });
+        }
+        if let Some(cipher) = &mut self.cipher {
+            let mut sealed = vec![0; len];
+            cipher.write_message(&body, &mut sealed)?;
+            body = sealed;
+        }
+        dst.reserve(HEADER_LEN + body.len());
+        dst.put_u32(body.len() as u32);
+        dst.extend_from_slice(&body);
+        Ok(())
+    }
+}
+
+/// A connection past the handshake, carrying `GossipMessage`s
+pub type GossipFramed<S> = Framed<S, WireCodec<GossipMessage>>;
+
+/// Take a fresh connection through the whole handshake: versions, Noise and
+/// identities. Returns the encrypted connection and the peer's identity.
+pub async fn handshake<S>(stream: S, identity: &Keypair, initiator: bool) -> Result<(GossipFramed<S>, Pubkey), WireError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let handshake = async {
+        let framed = hello(Framed::new(stream, WireCodec::new())).await?;
+        let (framed, handshake_hash) = secure(framed, initiator).await?;
+        authenticate(framed, identity, initiator, &handshake_hash).await
+    };
+    timeout(HANDSHAKE_TIMEOUT, handshake).await
+        .map_err(|_| WireError::HandshakeTimeout(HANDSHAKE_TIMEOUT))?
+}
+
+/// Next frame, where the handshake needs one
+async fn receive<S, T>(framed: &mut Framed<S, WireCodec<T>>) -> Result<T, WireError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: DeserializeOwned,
+{
+    framed.next().await.ok_or(WireError::Closed)?
+}
+
+/// Exchange `Hello`s and check the peer's
+async fn hello<S>(mut framed: Framed<S, WireCodec<Hello>>) -> Result<Framed<S, WireCodec<Vec<u8>>>, WireError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    framed.send(Hello::ours()).await?;
+    let theirs = receive(&mut framed).await?;
+    if theirs.magic != PROTOCOL_MAGIC {
+        return Err(WireError::BadMagic(theirs.magic));
+    }
+    if theirs.version != PROTOCOL_VERSION {
+        return Err(WireError::VersionMismatch { ours: PROTOCOL_VERSION, theirs: theirs.version });
+    }
+
+    // Frames the peer sent right after its `Hello` stay buffered
+    Ok(framed.map_codec(WireCodec::carrying))
+}
+
+/// Run Noise_XX and switch the connection to its transport keys. The Noise
+/// static keys are per connection; identity comes from `authenticate`.
+async fn secure<S>(mut framed: Framed<S, WireCodec<Vec<u8>>>, initiator: bool) -> Result<(Framed<S, WireCodec<Auth>>, Vec<u8>), WireError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let builder = snow::Builder::new(NOISE_PARAMS.parse()?);
+    let static_key = builder.generate_keypair()?;
+    let builder = builder.local_private_key(&static_key.private);
+    let mut noise = if initiator { builder.build_initiator()? } else { builder.build_responder()? };
+
+    // -> e; <- e, ee, s, es; -> s, se
+    let mut buf = vec![0; MAX_FRAME_LEN];
+    for step in 0..3 {
+        if (step % 2 == 0) == initiator {
+            let len = noise.write_message(&[], &mut buf)?;
+            framed.send(buf[..len].to_vec()).await?;
+        } else {
+            let message = receive(&mut framed).await?;
+            noise.read_message(&message, &mut buf)?;
+        }
+    }
+
+    let handshake_hash = noise.get_handshake_hash().to_vec();
+    let cipher = noise.into_transport_mode()?;
+    let framed = framed.map_codec(|codec| WireCodec {
+        cipher: Some(Box::new(cipher)),
+        ..codec.carrying()
+    });
+    Ok((framed, handshake_hash))
+}
+
+/// Each side signs the handshake hash, which both sides' fresh ephemeral keys
+/// went into, so a signature proves the key is held now, for this session
+async fn authenticate<S>(
+    mut framed: Framed<S, WireCodec<Auth>>,
+    identity: &Keypair,
+    initiator: bool,
+    handshake_hash: &[u8],
+) -> Result<(GossipFramed<S>, Pubkey), WireError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    framed.send(Auth {
+        identity: identity.pubkey(),
+        signature: identity.sign_message(&auth_message(initiator, handshake_hash)),
+    }).await?;
+
+    let theirs = receive(&mut framed).await?;
+    if !theirs.signature.verify(theirs.identity.as_ref(), &auth_message(!initiator, handshake_hash)) {
+        return Err(WireError::Unauthenticated(theirs.identity));
+    }
+    Ok((framed.map_codec(WireCodec::carrying), theirs.identity))
+}
+
+/// What the initiator or responder of a session signs; the role keeps a
+/// signature from being reflected back at the side that made it
+fn auth_message(initiator: bool, handshake_hash: &[u8]) -> Vec<u8> {
+    let mut message = AUTH_DOMAIN.to_vec();
+    message.push(initiator as u8);
+    message.extend_from_slice(handshake_hash);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    fn frames(messages: &[GossipMessage]) -> BytesMut {
+        let mut buf = BytesMut::new();
+        for msg in messages {
+            WireCodec::new().encode(msg.clone(), &mut buf).unwrap();
+        }
+        buf
+    }
+
+    #[test]
+    fn test_split_and_concatenated_frames() {
+        let messages = [
+            GossipMessage::Heartbeat,
+            GossipMessage::Peers(vec!["127.0.0.1:9000".parse().unwrap()]),
+            GossipMessage::GetPeers,
+        ];
+        let wire = frames(&messages);
+        let mut codec = WireCodec::<GossipMessage>::new();
+
+        // Byte by byte: nothing until a frame is complete
+        let mut buf = BytesMut::new();
+        let mut decoded = Vec::new();
+        for byte in wire.iter() {
+            buf.put_u8(*byte);
+            while let Some(msg) = codec.decode(&mut buf).unwrap() {
+                decoded.push(msg);
+            }
+        }
+        assert_eq!(decoded.len(), 3);
+        assert!(matches!(&decoded[1], GossipMessage::Peers(addrs) if addrs.len() == 1));
+
+        // All at once
+        let mut buf = wire.clone();
+        let decoded: Vec<_> = std::iter::from_fn(|| codec.decode(&mut buf).unwrap()).collect();
+        assert_eq!(decoded.len(), 3);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_bad_frames_are_typed_errors() {
+        let mut codec = WireCodec::<GossipMessage>::with_max_frame_len(16);
+
+        let mut oversized = BytesMut::new();
+        oversized.put_u32(17);
+        assert!(matches!(codec.decode(&mut oversized), Err(WireError::FrameTooLarge { len: 17, max: 16 })));
+
+        let mut garbage = BytesMut::new();
+        garbage.put_u32(4);
+        garbage.put_u32(u32::MAX);
+        assert!(matches!(codec.decode(&mut garbage), Err(WireError::Malformed(_))));
+
+        let too_many = GossipMessage::Peers(vec!["127.0.0.1:9000".parse().unwrap(); 4]);
+        assert!(matches!(codec.encode(too_many, &mut BytesMut::new()), Err(WireError::FrameTooLarge { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_handshake() {
+        let (alice, bob) = (Keypair::new(), Keypair::new());
+        let (a, b) = tokio::io::duplex(1024);
+        let (a, b) = tokio::join!(handshake(a, &alice, true), handshake(b, &bob, false));
+        let ((mut a, a_peer), (mut b, b_peer)) = (a.unwrap(), b.unwrap());
+        assert_eq!((a_peer, b_peer), (bob.pubkey(), alice.pubkey()));
+
+        a.send(GossipMessage::GetPeers).await.unwrap();
+        b.send(GossipMessage::Heartbeat).await.unwrap();
+        assert!(matches!(b.next().await, Some(Ok(GossipMessage::GetPeers))));
+        assert!(matches!(a.next().await, Some(Ok(GossipMessage::Heartbeat))));
+
+        // A peer on another version is turned away
+        let (ours, mut theirs) = tokio::io::duplex(1024);
+        let mut hello = BytesMut::new();
+        WireCodec::new().encode(Hello { version: PROTOCOL_VERSION + 1, ..Hello::ours() }, &mut hello).unwrap();
+        theirs.write_all(&hello).await.unwrap();
+        assert!(matches!(
+            handshake(ours, &alice, false).await,
+            Err(WireError::VersionMismatch { ours: PROTOCOL_VERSION, theirs }) if theirs == PROTOCOL_VERSION + 1
+        ));
+
+        // And so is something that is not a gossip peer at all
+        let (ours, mut theirs) = tokio::io::duplex(1024);
+        theirs.write_all(b"\0\0\0\x06GET / ").await.unwrap();
+        assert!(matches!(handshake(ours, &alice, false).await, Err(WireError::BadMagic(_))));
+
+        // Silence runs into the timeout
+        let (ours, _theirs) = tokio::io::duplex(1024);
+        tokio::time::pause();
+        assert!(matches!(handshake(ours, &alice, false).await, Err(WireError::HandshakeTimeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_identity_must_be_proven() {
+        let (alice, bob, mallory) = (Keypair::new(), Keypair::new(), Keypair::new());
+        let (ours, theirs) = tokio::io::duplex(1024);
+
+        // Mallory completes Noise but claims Alice's identity with its own signature
+        let impostor = async {
+            let framed = hello(Framed::new(theirs, WireCodec::new())).await?;
+            let (mut framed, handshake_hash) = secure(framed, false).await?;
+            framed.send(Auth {
+                identity: alice.pubkey(),
+                signature: mallory.sign_message(&auth_message(false, &handshake_hash)),
+            }).await?;
+            Ok::<_, WireError>(framed)
+        };
+        let (ours, _impostor) = tokio::join!(handshake(ours, &bob, true), impostor);
+        assert!(matches!(ours, Err(WireError::Unauthenticated(claimed)) if claimed == alice.pubkey()));
+
+        // Nor can it reflect a signature back at the side that made it
+        let (ours, theirs) = tokio::io::duplex(1024);
+        let reflector = async {
+            let framed = hello(Framed::new(theirs, WireCodec::new())).await?;
+            let (mut framed, _) = secure(framed, false).await?;
+            let auth = receive(&mut framed).await?;
+            framed.send(auth).await?;
+            Ok::<_, WireError>(framed)
+        };
+        let (ours, _reflector) = tokio::join!(handshake(ours, &alice, true), reflector);
+        assert!(matches!(ours, Err(WireError::Unauthenticated(claimed)) if claimed == alice.pubkey()));
+    }
+}
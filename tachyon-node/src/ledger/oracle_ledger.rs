@@ -3,19 +3,62 @@
 // Simplified from Solana Ledger for Tachyon Oracle Network
 
 use std::sync::Arc;
-use rocksdb::{DB, Options, IteratorMode};
+use rocksdb::{DB, Direction, Options, IteratorMode, WriteBatch};
 use anyhow::Result;
 use serde::{Serialize, Deserialize};
+use tachyon_merkle::Price;
+use tokio::time::{interval, Duration};
+use tracing::{info, warn};
+
+use crate::aggregator::{batch_number_at, FeedData, MerkleBatch};
+use crate::config::NodeConfig;
+use crate::consensus::{root_bytes, ConsensusResult};
+
+/// How often the retention window is enforced
+const RETENTION_SWEEP_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Key prefixes for per-batch records, all keyed by zero-padded batch number
+const BATCH_PREFIXES: [&str; 3] = ["root:", "consensus:", "submission:"];
 
 /// Price entry for historical storage
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PriceRecord {
     pub symbol: String,
-    pub price: f64,
+    /// Mantissa at `expo`, exactly as committed
+    pub price: i64,
+    /// Confidence interval around `price`, same exponent
+    pub confidence: i64,
+    pub expo: i32,
     pub timestamp: i64,
     pub batch_number: u64,
     pub merkle_root: [u8; 32],
     pub submitter: [u8; 32],
+    pub publishers: Vec<String>,
+}
+
+impl PriceRecord {
+    /// History entry for a committed feed, read at the feed's exponent
+    pub fn from_feed(feed: &FeedData, batch_number: u64, merkle_root: [u8; 32], submitter: [u8; 32]) -> Self {
+        Self {
+            symbol: feed.asset_id.clone(),
+            price: feed.price,
+            confidence: feed.confidence,
+            expo: feed.expo,
+            timestamp: feed.timestamp,
+            batch_number,
+            merkle_root,
+            submitter,
+            publishers: feed.publishers.clone(),
+        }
+    }
+
+    pub fn price(&self) -> Price {
+        Price::new(self.price, self.expo)
+    }
+
+    pub fn confidence(&self) -> Price {
+        Price::new(self.confidence, self.expo)
+    }
 }
 
 /// Merkle root record
@@ -26,6 +69,59 @@ pub struct MerkleRootRecord {
     pub feed_count: u32,
     pub timestamp: i64,
     pub submitter: [u8; 32],
+    /// Every tree node, leaves first and root last
+    pub tree: Vec<[u8; 32]>,
+    /// Committed feeds in leaf order
+    pub feeds: Vec<FeedData>,
+}
+
+impl MerkleRootRecord {
+    /// The batch as the aggregator built it
+    pub fn to_batch(&self) -> MerkleBatch {
+        MerkleBatch {
+            batch_number: self.batch_number,
+            root: hex::encode(self.root),
+            timestamp: self.timestamp,
+            feeds: self.feeds.clone(),
+            tree: self.tree.iter().map(hex::encode).collect(),
+        }
+    }
+}
+
+/// Outcome of the consensus round for a batch
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConsensusRecord {
+    pub batch_number: u64,
+    pub consensus_root: Option<[u8; 32]>,
+    pub agreeing_stake: u64,
+    pub total_stake: u64,
+    /// Validators whose votes were counted
+    pub voters: Vec<String>,
+    pub is_leader: bool,
+}
+
+impl ConsensusRecord {
+    pub fn from_result(result: &ConsensusResult) -> Result<Self> {
+        let mut voters: Vec<String> = result.votes.keys().cloned().collect();
+        voters.sort();
+        
+        Ok(Self {
+            batch_number: result.batch.batch_number,
+            consensus_root: result.consensus_root.as_deref().map(root_bytes).transpose()?,
+            agreeing_stake: result.agreeing_stake,
+            total_stake: result.total_stake,
+            voters,
+            is_leader: result.is_leader,
+        })
+    }
+}
+
+/// Transaction that committed a batch root on chain
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmissionRecord {
+    pub batch_number: u64,
+    pub signature: String,
+    pub submitted_at: i64,
 }
 
 /// Historical ledger for price data
@@ -43,6 +139,7 @@ impl OracleLedger {
         opts.set_target_file_size_base(128 * 1024 * 1024); // 128MB
         opts.set_compression_type(rocksdb::DBCompressionType::Lz4);
         
+        std::fs::create_dir_all(path)?;
         let db = DB::open(&opts, path)?;
         
         Ok(Self {
@@ -52,8 +149,7 @@ impl OracleLedger {
 
     /// Store a price record
     pub fn store_price(&self, record: &PriceRecord) -> Result<()> {
-        // Key: symbol:timestamp
-        let key = format!("price:{}:{}", record.symbol, record.timestamp);
+        let key = price_key(&record.symbol, record.timestamp, record.batch_number);
         let data = bincode::serialize(record)?;
         self.db.put(key.as_bytes(), &data)?;
         Ok(())
@@ -62,69 +158,128 @@ impl OracleLedger {
     /// Store a Merkle root record
     pub fn store_merkle_root(&self, record: &MerkleRootRecord) -> Result<()> {
         // Key: root:batch_number
-        let key = format!("root:{}", record.batch_number);
+        let key = batch_key("root:", record.batch_number);
+        let data = bincode::serialize(record)?;
+        self.db.put(key.as_bytes(), &data)?;
+        Ok(())
+    }
+
+    /// Store a built batch: one price record per feed plus the root with its full tree,
+    /// written atomically
+    pub fn store_batch(&self, batch: &MerkleBatch, submitter: [u8; 32]) -> Result<()> {
+        let merkle_root = root_bytes(&batch.root)?;
+        let tree = batch.tree.iter()
+            .map(|node| root_bytes(node))
+            .collect::<Result<Vec<_>>>()?;
+        
+        let mut write = WriteBatch::default();
+        for feed in &batch.feeds {
+            let record = PriceRecord::from_feed(feed, batch.batch_number, merkle_root, submitter);
+            write.put(price_key(&record.symbol, record.timestamp, record.batch_number).as_bytes(), bincode::serialize(&record)?);
+        }
+        
+        let root_record = MerkleRootRecord {
+            root: merkle_root,
+            batch_number: batch.batch_number,
+            feed_count: batch.feeds.len() as u32,
+            timestamp: batch.timestamp,
+            submitter,
+            tree,
+            feeds: batch.feeds.clone(),
+        };
+        write.put(batch_key("root:", batch.batch_number).as_bytes(), bincode::serialize(&root_record)?);
+        
+        self.db.write(write)?;
+        Ok(())
+    }
+
+    /// Store the consensus outcome for a batch
+    pub fn store_consensus(&self, record: &ConsensusRecord) -> Result<()> {
+        let key = batch_key("consensus:", record.batch_number);
+        let data = bincode::serialize(record)?;
+        self.db.put(key.as_bytes(), &data)?;
+        Ok(())
+    }
+
+    /// Store the on-chain submission of a batch
+    pub fn store_submission(&self, record: &SubmissionRecord) -> Result<()> {
+        let key = batch_key("submission:", record.batch_number);
         let data = bincode::serialize(record)?;
         self.db.put(key.as_bytes(), &data)?;
         Ok(())
     }
 
-    /// Get price history for a symbol
+    pub fn get_consensus(&self, batch_number: u64) -> Result<Option<ConsensusRecord>> {
+        match self.db.get(batch_key("consensus:", batch_number).as_bytes())? {
+            Some(data) => Ok(Some(bincode::deserialize(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_submission(&self, batch_number: u64) -> Result<Option<SubmissionRecord>> {
+        match self.db.get(batch_key("submission:", batch_number).as_bytes())? {
+            Some(data) => Ok(Some(bincode::deserialize(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The newest `limit` prices of `symbol` from `start_time` to `end_time`,
+    /// oldest first. With an `interval`, only the last price of every
+    /// `interval`-second bucket is kept. Reads backwards from `end_time` and
+    /// stops once it has `limit` points.
     pub fn get_price_history(
         &self,
         symbol: &str,
         start_time: i64,
         end_time: i64,
+        interval: Option<i64>,
+        limit: usize,
     ) -> Result<Vec<PriceRecord>> {
-        let mut records = Vec::new();
+        let mut records: Vec<PriceRecord> = Vec::new();
+        if limit == 0 {
+            return Ok(records);
+        }
         
         let prefix = format!("price:{}:", symbol);
-        let iter = self.db.prefix_iterator(prefix.as_bytes());
+        let end = price_key(symbol, end_time, u64::MAX);
+        let iter = self.db.iterator(IteratorMode::From(end.as_bytes(), Direction::Reverse));
         
         for item in iter {
-            let (_key, value) = item?;
+            let (key, value) = item?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
             let record: PriceRecord = bincode::deserialize(&value)?;
-            
-            if record.timestamp >= start_time && record.timestamp <= end_time {
-                records.push(record.clone());
+            if record.timestamp > end_time {
+                continue;
+            }
+            if record.timestamp < start_time {
+                break;
             }
             
-            // Stop if we've passed the end time
-            if record.timestamp > end_time {
+            // Going backwards, the first record of a bucket is its last
+            let bucket = |record: &PriceRecord| interval.map(|interval| record.timestamp.div_euclid(interval));
+            if interval.is_some() && records.last().is_some_and(|newer| bucket(newer) == bucket(&record)) {
+                continue;
+            }
+            records.push(record);
+            if records.len() >= limit {
                 break;
             }
         }
         
+        records.reverse();
         Ok(records)
     }
 
     /// Get latest price for a symbol
     pub fn get_latest_price(&self, symbol: &str) -> Result<Option<PriceRecord>> {
-        let prefix = format!("price:{}:", symbol);
-        let iter = self.db.prefix_iterator(prefix.as_bytes());
-        
-        let mut latest: Option<PriceRecord> = None;
-        
-        for item in iter {
-            let (_key, value) = item?;
-            let record: PriceRecord = bincode::deserialize(&value)?;
-            
-            let should_update = if let Some(ref current_latest) = latest {
-                record.timestamp > current_latest.timestamp
-            } else {
-                true
-            };
-            
-            if should_update {
-                latest = Some(record);
-            }
-        }
-        
-        Ok(latest)
+        Ok(self.get_price_history(symbol, i64::MIN, i64::MAX, None, 1)?.pop())
     }
 
     /// Get Merkle root by batch number
     pub fn get_merkle_root(&self, batch_number: u64) -> Result<Option<MerkleRootRecord>> {
-        let key = format!("root:{}", batch_number);
+        let key = batch_key("root:", batch_number);
         
         if let Some(data) = self.db.get(key.as_bytes())? {
             let record: MerkleRootRecord = bincode::deserialize(&data)?;
@@ -151,6 +306,26 @@ impl OracleLedger {
         Ok(records)
     }
 
+    /// Up to `limit` Merkle roots from `start_batch` on, in batch order
+    pub fn get_merkle_roots_since(&self, start_batch: u64, limit: usize) -> Result<Vec<MerkleRootRecord>> {
+        let mut records = Vec::new();
+        let start = batch_key("root:", start_batch);
+
+        let iter = self.db.iterator(IteratorMode::From(start.as_bytes(), Direction::Forward));
+        for item in iter {
+            if records.len() >= limit {
+                break;
+            }
+            let (key, value) = item?;
+            if !key.starts_with(b"root:") {
+                break;
+            }
+            records.push(bincode::deserialize(&value)?);
+        }
+
+        Ok(records)
+    }
+
     /// Get all symbols with price data
     pub fn get_symbols(&self) -> Result<Vec<String>> {
         let mut symbols = std::collections::HashSet::new();
@@ -161,7 +336,7 @@ impl OracleLedger {
             let key_str = String::from_utf8_lossy(&key);
             
             if key_str.starts_with("price:") {
-                // Extract symbol from key "price:SYMBOL:timestamp"
+                // Extract symbol from key "price:SYMBOL:timestamp:batch"
                 if let Some(symbol) = key_str.split(':').nth(1) {
                     symbols.insert(symbol.to_string());
                 }
@@ -210,33 +385,124 @@ impl OracleLedger {
         Ok(())
     }
 
-    /// Delete old price data (cleanup)
+    /// Delete price records older than `before_timestamp`. Keys sort by time
+    /// within a symbol, so only the deleted keys and one per symbol are read.
     pub fn delete_old_prices(&self, before_timestamp: i64) -> Result<u64> {
         let mut deleted = 0u64;
-        let mut keys_to_delete = Vec::new();
+        let mut write = WriteBatch::default();
+        let mut cursor = b"price:".to_vec();
         
-        let iter = self.db.iterator(IteratorMode::Start);
-        for item in iter {
-            let (key, value) = item?;
-            let key_str = String::from_utf8_lossy(&key);
+        loop {
+            let mut iter = self.db.iterator(IteratorMode::From(&cursor, Direction::Forward));
+            let Some(item) = iter.next() else {
+                break;
+            };
+            let (first, _) = item?;
+            if !first.starts_with(b"price:") {
+                break;
+            }
+            let Some(symbol) = String::from_utf8_lossy(&first).split(':').nth(1).map(str::to_string) else {
+                break;
+            };
             
-            if key_str.starts_with("price:") {
-                let record: PriceRecord = bincode::deserialize(&value)?;
-                if record.timestamp < before_timestamp {
-                    keys_to_delete.push(key.to_vec());
+            let prefix = format!("price:{}:", symbol);
+            let end = price_key(&symbol, before_timestamp, 0);
+            for key in std::iter::once(Ok(first)).chain(iter.map(|item| item.map(|(key, _)| key))) {
+                let key = key?;
+                if !key.starts_with(prefix.as_bytes()) || *key >= *end.as_bytes() {
+                    break;
                 }
+                write.delete(&key);
+                deleted += 1;
             }
+            
+            // On to the next symbol: ';' sorts right after ':'
+            cursor = format!("price:{};", symbol).into_bytes();
         }
         
-        for key in keys_to_delete {
-            self.db.delete(&key)?;
-            deleted += 1;
+        self.db.write(write)?;
+        Ok(deleted)
+    }
+
+    /// Delete root, consensus and submission records of batches before
+    /// `before_batch`, in one write
+    pub fn delete_old_batches(&self, before_batch: u64) -> Result<u64> {
+        let mut deleted = 0u64;
+        let mut write = WriteBatch::default();
+        
+        for prefix in BATCH_PREFIXES {
+            let end = batch_key(prefix, before_batch);
+            
+            let iter = self.db.iterator(IteratorMode::From(prefix.as_bytes(), Direction::Forward));
+            for item in iter {
+                let (key, _value) = item?;
+                if !key.starts_with(prefix.as_bytes()) || *key >= *end.as_bytes() {
+                    break;
+                }
+                write.delete(&key);
+                deleted += 1;
+            }
         }
         
+        self.db.write(write)?;
         Ok(deleted)
     }
 }
 
+// Zero-padded so keys sort numerically; the batch number keeps every
+// batch's record when several land in the same second. The timestamp is
+// offset by 2^63, flipping its sign bit, so negative times sort first.
+fn price_key(symbol: &str, timestamp: i64, batch_number: u64) -> String {
+    let offset = (timestamp as u64) ^ (1 << 63);
+    format!("price:{}:{:020}:{:020}", symbol, offset, batch_number)
+}
+
+fn batch_key(prefix: &str, batch_number: u64) -> String {
+    format!("{}{:020}", prefix, batch_number)
+}
+
+/// Periodically drop ledger data older than the configured retention window
+pub async fn start_retention(
+    ledger: Arc<OracleLedger>,
+    config: Arc<NodeConfig>,
+    mut shutdown: tokio::sync::broadcast::Receiver<()>,
+) -> Result<()> {
+    info!("🗄️  Ledger retention: keeping {}s of history", config.ledger_retention_secs);
+    
+    let mut ticker = interval(RETENTION_SWEEP_INTERVAL);
+    
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                let cutoff = chrono::Utc::now().timestamp() - config.ledger_retention_secs as i64;
+                let cutoff_batch = batch_number_at(cutoff.saturating_mul(1000), config.batch_interval_ms);
+                
+                // Deletes can touch many keys; keep them off the runtime
+                let sweep = Arc::clone(&ledger);
+                let result = tokio::task::spawn_blocking(move || {
+                    sweep.delete_old_prices(cutoff)
+                        .and_then(|prices| Ok((prices, sweep.delete_old_batches(cutoff_batch)?)))
+                }).await.map_err(anyhow::Error::from).and_then(|result| result);
+                match result {
+                    Ok((prices, batches)) if prices + batches > 0 => {
+                        info!("🗄️  Pruned {} price and {} batch records", prices, batches);
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Ledger retention failed: {}", e),
+                }
+            }
+            _ = shutdown.recv() => {
+                if let Err(e) = ledger.flush() {
+                    warn!("Failed to flush ledger: {}", e);
+                }
+                break;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
 /// Ledger statistics
 #[derive(Debug, Clone)]
 pub struct LedgerStats {
@@ -257,18 +523,21 @@ mod tests {
         
         let record = PriceRecord {
             symbol: "BTC/USD".to_string(),
-            price: 50000.0,
+            price: 5_000_000_000_000,
+            confidence: 99_000_000,
+            expo: -8,
             timestamp: 1000,
             batch_number: 1,
             merkle_root: [1u8; 32],
             submitter: [0u8; 32],
+            publishers: vec!["node1".to_string()],
         };
         
         ledger.store_price(&record).unwrap();
         
-        let history = ledger.get_price_history("BTC/USD", 0, 2000).unwrap();
+        let history = ledger.get_price_history("BTC/USD", 0, 2000, None, 100).unwrap();
         assert_eq!(history.len(), 1);
-        assert_eq!(history[0].price, 50000.0);
+        assert_eq!(history[0].price(), Price::new(50_000, 0));
     }
 
     #[test]
@@ -282,6 +551,8 @@ mod tests {
             feed_count: 10,
             timestamp: 1000,
             submitter: [0u8; 32],
+            tree: vec![[42u8; 32]],
+            feeds: vec![],
         };
         
         ledger.store_merkle_root(&record).unwrap();
@@ -301,11 +572,14 @@ mod tests {
         for (i, symbol) in symbols.iter().enumerate() {
             let record = PriceRecord {
                 symbol: symbol.to_string(),
-                price: 1000.0 * (i as f64 + 1.0),
+                price: 1000 * (i as i64 + 1),
+                confidence: 1,
+                expo: 0,
                 timestamp: 1000 + i as i64,
                 batch_number: i as u64,
                 merkle_root: [i as u8; 32],
                 submitter: [0u8; 32],
+                publishers: vec![],
             };
             ledger.store_price(&record).unwrap();
         }
@@ -323,11 +597,14 @@ mod tests {
         for i in 0..10 {
             let record = PriceRecord {
                 symbol: "BTC/USD".to_string(),
-                price: 50000.0 + i as f64,
+                price: 50_000 + i,
+                confidence: 1,
+                expo: 0,
                 timestamp: 1000 + i,
                 batch_number: i as u64,
                 merkle_root: [i as u8; 32],
                 submitter: [0u8; 32],
+                publishers: vec![],
             };
             ledger.store_price(&record).unwrap();
         }
@@ -335,5 +612,128 @@ mod tests {
         let stats = ledger.get_stats().unwrap();
         assert_eq!(stats.price_count, 10);
     }
-}
 
+    #[test]
+    fn test_history_is_per_symbol_and_ordered() {
+        let temp_dir = TempDir::new().unwrap();
+        let ledger = OracleLedger::new(temp_dir.path().to_str().unwrap()).unwrap();
+        
+        // Two batches can land in the same second
+        for (symbol, timestamp, batch_number) in [("BTC/USD", 999, 0), ("BTC/USD", 1000, 1), ("BTC/USD", 1000, 2), ("BTC/USDT", 1001, 3), ("ETH/USD", 1002, 4)] {
+            ledger.store_price(&PriceRecord {
+                symbol: symbol.to_string(),
+                price: 1,
+                confidence: 1,
+                expo: 0,
+                timestamp,
+                batch_number,
+                merkle_root: [0u8; 32],
+                submitter: [0u8; 32],
+                publishers: vec![],
+            }).unwrap();
+        }
+        
+        let history = ledger.get_price_history("BTC/USD", 0, 2000, None, 100).unwrap();
+        assert_eq!(history.iter().map(|r| r.batch_number).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(ledger.get_latest_price("BTC/USD").unwrap().unwrap().batch_number, 2);
+        
+        assert_eq!(ledger.delete_old_prices(1001).unwrap(), 3);
+        assert!(ledger.get_price_history("BTC/USD", 0, 2000, None, 100).unwrap().is_empty());
+        assert_eq!(ledger.get_stats().unwrap().price_count, 2);
+    }
+
+    #[test]
+    fn test_history_orders_negative_timestamps() {
+        let temp_dir = TempDir::new().unwrap();
+        let ledger = OracleLedger::new(temp_dir.path().to_str().unwrap()).unwrap();
+        
+        for (batch_number, timestamp) in [(0, i64::MIN), (1, -1000), (2, -1), (3, 0), (4, 1), (5, i64::MAX)] {
+            ledger.store_price(&PriceRecord {
+                symbol: "BTC/USD".to_string(),
+                price: 1,
+                confidence: 1,
+                expo: 0,
+                timestamp,
+                batch_number,
+                merkle_root: [0u8; 32],
+                submitter: [0u8; 32],
+                publishers: vec![],
+            }).unwrap();
+        }
+        let batches = |records: Vec<PriceRecord>| records.iter().map(|r| r.batch_number).collect::<Vec<_>>();
+        
+        assert_eq!(batches(ledger.get_price_history("BTC/USD", i64::MIN, i64::MAX, None, 100).unwrap()), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(batches(ledger.get_price_history("BTC/USD", -1000, 0, None, 100).unwrap()), vec![1, 2, 3]);
+        assert_eq!(ledger.get_latest_price("BTC/USD").unwrap().unwrap().batch_number, 5);
+        
+        assert_eq!(ledger.delete_old_prices(0).unwrap(), 3);
+        assert_eq!(batches(ledger.get_price_history("BTC/USD", i64::MIN, i64::MAX, None, 100).unwrap()), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_history_keeps_newest_and_last_in_bucket() {
+        let temp_dir = TempDir::new().unwrap();
+        let ledger = OracleLedger::new(temp_dir.path().to_str().unwrap()).unwrap();
+        
+        for timestamp in [100, 105, 159, 160, 230] {
+            ledger.store_price(&PriceRecord {
+                symbol: "BTC/USD".to_string(),
+                price: timestamp,
+                confidence: 1,
+                expo: 0,
+                timestamp,
+                batch_number: 0,
+                merkle_root: [0u8; 32],
+                submitter: [0u8; 32],
+                publishers: vec![],
+            }).unwrap();
+        }
+        let timestamps = |records: Vec<PriceRecord>| records.iter().map(|r| r.timestamp).collect::<Vec<_>>();
+        
+        assert_eq!(timestamps(ledger.get_price_history("BTC/USD", 0, 1000, Some(60), 100).unwrap()), vec![105, 160, 230]);
+        assert_eq!(timestamps(ledger.get_price_history("BTC/USD", 0, 1000, Some(60), 2).unwrap()), vec![160, 230]);
+        assert_eq!(timestamps(ledger.get_price_history("BTC/USD", 101, 200, None, 100).unwrap()), vec![105, 159, 160]);
+        assert_eq!(timestamps(ledger.get_price_history("BTC/USD", 0, 1000, None, 2).unwrap()), vec![160, 230]);
+    }
+
+    #[test]
+    fn test_delete_old_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        let ledger = OracleLedger::new(temp_dir.path().to_str().unwrap()).unwrap();
+        
+        for batch_number in [5, 10, 100] {
+            ledger.store_merkle_root(&MerkleRootRecord {
+                root: [1u8; 32],
+                batch_number,
+                feed_count: 1,
+                timestamp: 1000,
+                submitter: [0u8; 32],
+                tree: vec![[1u8; 32]],
+                feeds: vec![],
+            }).unwrap();
+            ledger.store_consensus(&ConsensusRecord {
+                batch_number,
+                consensus_root: Some([1u8; 32]),
+                agreeing_stake: 10,
+                total_stake: 10,
+                voters: vec!["node1".to_string()],
+                is_leader: true,
+            }).unwrap();
+        }
+        ledger.store_submission(&SubmissionRecord {
+            batch_number: 5,
+            signature: "sig".to_string(),
+            submitted_at: 1000,
+        }).unwrap();
+        
+        let since: Vec<u64> = ledger.get_merkle_roots_since(6, 10).unwrap().iter().map(|r| r.batch_number).collect();
+        assert_eq!(since, vec![10, 100]);
+        assert_eq!(ledger.get_merkle_roots_since(6, 1).unwrap().len(), 1);
+        
+        assert_eq!(ledger.delete_old_batches(11).unwrap(), 5);
+        assert!(ledger.get_merkle_root(10).unwrap().is_none());
+        assert!(ledger.get_submission(5).unwrap().is_none());
+        assert!(ledger.get_consensus(100).unwrap().is_some());
+        assert_eq!(ledger.get_merkle_root(100).unwrap().unwrap().tree, vec![[1u8; 32]]);
+    }
+}
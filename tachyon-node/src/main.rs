@@ -15,6 +15,8 @@ mod api;
 mod crypto;
 mod metrics;
 mod price_feeds;
+mod ledger;
+mod replay;
 
 // Solana components adapted for production-grade oracle network
 // These modules contain infrastructure code that will be used in future features
@@ -28,8 +30,6 @@ mod poh;         // ✅ Proof of History ordering
 mod vote;        // ✅ Validator voting
 #[allow(dead_code)]
 mod accounts_db; // ✅ High-performance storage
-#[allow(dead_code)]
-mod ledger;      // ✅ Historical data storage
 
 use config::NodeConfig;
 
@@ -68,6 +68,24 @@ enum Commands {
         /// Path to config file
         #[arg(long, default_value = "~/.config/tachyon/node-config.toml")]
         config: String,
+        
+        /// Record every source response and price update to this file
+        #[arg(long)]
+        record: Option<String>,
+    },
+    
+    /// Replay a recording through the aggregator and consensus, offline
+    Replay {
+        /// Recording written by `start --record`
+        file: String,
+        
+        /// Path to config file
+        #[arg(long, default_value = "~/.config/tachyon/node-config.toml")]
+        config: String,
+        
+        /// Write the batches here instead of stdout, one JSON object per line
+        #[arg(long)]
+        output: Option<String>,
     },
     
     /// Show node status
@@ -185,9 +203,13 @@ async fn main() -> Result<()> {
             info!("🚀 Initializing Tachyon Node...");
             config::init_node(keypair, rpc_url, gossip_port, api_port).await?;
         }
-        Commands::Start { config } => {
+        Commands::Start { config, record } => {
             info!("🚀 Starting Tachyon Node...");
-            start_node(config).await?;
+            start_node(config, record).await?;
+        }
+        Commands::Replay { file, config, output } => {
+            info!("⏯️  Replaying {}...", file);
+            replay_recording(file, config, output)?;
         }
         Commands::Status { api } => {
             info!("📊 Fetching node status...");
@@ -250,7 +272,10 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn start_node(config_path: String) -> Result<()> {
+async fn start_node(config_path: String, record_path: Option<String>) -> Result<()> {
+    use solana_sdk::pubkey::Pubkey;
+    use std::str::FromStr;
+    
     let config = Arc::new(NodeConfig::load(&config_path)?);
     
     info!("🔑 Node Identity: {}", config.identity.pubkey());
@@ -258,6 +283,17 @@ async fn start_node(config_path: String) -> Result<()> {
     info!("📡 Gossip Port: {}", config.gossip_port);
     info!("🔌 API Port: {}", config.api_port);
     
+    // Open the price and batch history ledger
+    let ledger_path = shellexpand::tilde(&config.ledger_path).to_string();
+    let ledger = Arc::new(ledger::oracle_ledger::OracleLedger::new(&ledger_path)?);
+    info!("🗄️  Ledger: {}", ledger_path);
+    
+    // Shared with the API
+    let status = Arc::new(tokio::sync::RwLock::new(api::NodeStatus::new(&config)));
+    let aggregator_state = Arc::new(tokio::sync::RwLock::new(aggregator::AggregatorState::default()));
+    let fetcher_stats = Arc::new(tokio::sync::RwLock::new(fetcher::stats::FetcherStats::default()));
+    let (stream_tx, _) = tokio::sync::broadcast::channel(api::stream::STREAM_CHANNEL_CAPACITY);
+    
     // Start all subsystems
     let (shutdown_tx, mut shutdown_rx) = tokio::sync::broadcast::channel(1);
     
@@ -270,23 +306,54 @@ async fn start_node(config_path: String) -> Result<()> {
         }
     });
     
+    // Gossip and consensus both read stake from the cluster
+    let cluster: Arc<dyn consensus::Cluster> = Arc::new(solana_client::rpc_client::RpcClient::new(&config.rpc_url));
+    // Consensus keeps the validator set; gossip follows its changes
+    let validator_cache = consensus::validator_set::ValidatorSetCache::new(
+        Pubkey::from_str(&config.program_id)?,
+        std::time::Duration::from_secs(config.validator_refresh_secs),
+    );
+    let stake_changes = validator_cache.subscribe();
+    
     // 2. Start P2P gossip network
     let (gossip_tx, gossip_rx) = tokio::sync::mpsc::channel(1000);
+    let (peer_vote_tx, peer_vote_rx) = tokio::sync::mpsc::channel(1000);
+    let (vote_broadcast_tx, vote_broadcast_rx) = tokio::sync::mpsc::channel(100);
+    let (price_broadcast_tx, price_broadcast_rx) = tokio::sync::mpsc::channel(1000);
     let gossip_handle = tokio::spawn({
         let config = Arc::clone(&config);
+        let cluster = Arc::clone(&cluster);
+        let status = Arc::clone(&status);
         let shutdown = shutdown_tx.subscribe();
+        let inbound = gossip::GossipInbound {
+            price_tx: gossip_tx,
+            vote_tx: peer_vote_tx,
+        };
+        let outbound = gossip::GossipOutbound {
+            price_rx: price_broadcast_rx,
+            vote_rx: vote_broadcast_rx,
+        };
         async move {
-            gossip::start_gossip_network(config, gossip_tx, shutdown).await
+            gossip::start_gossip_network(config, cluster, stake_changes, inbound, outbound, status, shutdown).await
         }
     });
     
     // 3. Start price fetcher
+    let recorder = match record_path {
+        Some(path) => {
+            let path = shellexpand::tilde(&path).to_string();
+            info!("⏺️  Recording to {}", path);
+            Some(Arc::new(replay::recording::Recorder::create(&path)?))
+        }
+        None => None,
+    };
     let (price_tx, price_rx) = tokio::sync::mpsc::channel(1000);
     let fetcher_handle = tokio::spawn({
         let config = Arc::clone(&config);
+        let fetcher_stats = Arc::clone(&fetcher_stats);
         let shutdown = shutdown_tx.subscribe();
         async move {
-            fetcher::start_price_fetcher(config, price_tx, shutdown).await
+            fetcher::start_price_fetcher(config, price_tx, price_broadcast_tx, fetcher_stats, recorder, shutdown).await
         }
     });
     
@@ -294,41 +361,70 @@ async fn start_node(config_path: String) -> Result<()> {
     let (batch_tx, batch_rx) = tokio::sync::mpsc::channel(100);
     let aggregator_handle = tokio::spawn({
         let config = Arc::clone(&config);
+        let outbound = aggregator::AggregatorOutbound {
+            batch_tx,
+            ledger: Arc::clone(&ledger),
+            state: Arc::clone(&aggregator_state),
+            status: Arc::clone(&status),
+            events: stream_tx.clone(),
+        };
         #[allow(unused_mut)]
         let mut shutdown = shutdown_tx.subscribe();
         async move {
-            aggregator::start_aggregator(config, price_rx, gossip_rx, batch_tx, shutdown).await
+            aggregator::start_aggregator(config, replay::Clock::System, price_rx, gossip_rx, outbound, shutdown).await
         }
     });
     
     // 5. Start consensus module (votes on batches)
     let (consensus_tx, consensus_rx) = tokio::sync::mpsc::channel(100);
+    let (evidence_tx, evidence_rx) = tokio::sync::mpsc::channel(100);
+    let tower_stats = Arc::new(tokio::sync::RwLock::new(consensus::oracle_tower::TowerStats::default()));
     let consensus_handle = tokio::spawn({
         let config = Arc::clone(&config);
+        let cluster = Arc::clone(&cluster);
+        let tower_stats = Arc::clone(&tower_stats);
+        let outbound = consensus::ConsensusOutbound {
+            vote_tx: vote_broadcast_tx,
+            result_tx: consensus_tx,
+            evidence_tx,
+        };
         #[allow(unused_mut)]
         let mut shutdown = shutdown_tx.subscribe();
         async move {
-            consensus::start_consensus(config, batch_rx, consensus_tx, shutdown).await
+            consensus::start_consensus(config, cluster, validator_cache, batch_rx, peer_vote_rx, outbound, tower_stats, shutdown).await
         }
     });
     
     // 6. Start sequencer (submits to X1)
     let sequencer_handle = tokio::spawn({
         let config = Arc::clone(&config);
+        let ledger = Arc::clone(&ledger);
+        let status = Arc::clone(&status);
+        let events = stream_tx.clone();
         #[allow(unused_mut)]
         let mut shutdown = shutdown_tx.subscribe();
         async move {
-            sequencer::start_sequencer(config, consensus_rx, shutdown).await
+            sequencer::start_sequencer(config, consensus_rx, evidence_rx, ledger, status, events, shutdown).await
+        }
+    });
+    
+    // 7. Enforce ledger retention
+    let retention_handle = tokio::spawn({
+        let config = Arc::clone(&config);
+        let ledger = Arc::clone(&ledger);
+        let shutdown = shutdown_tx.subscribe();
+        async move {
+            ledger::oracle_ledger::start_retention(ledger, config, shutdown).await
         }
     });
     
-    // 7. Start API server
+    // 8. Start API server
     let api_handle = tokio::spawn({
         let config = Arc::clone(&config);
         #[allow(unused_mut)]
         let mut shutdown = shutdown_tx.subscribe();
         async move {
-            api::start_api_server(config, shutdown).await
+            api::start_api_server(config, status, tower_stats, fetcher_stats, aggregator_state, ledger, stream_tx, shutdown).await
         }
     });
     
@@ -356,6 +452,7 @@ async fn start_node(config_path: String) -> Result<()> {
         aggregator_handle,
         consensus_handle,
         sequencer_handle,
+        retention_handle,
         api_handle,
     );
     
@@ -363,6 +460,29 @@ async fn start_node(config_path: String) -> Result<()> {
     Ok(())
 }
 
+/// Replay a recording offline and print the batches it produces
+fn replay_recording(file: String, config_path: String, output: Option<String>) -> Result<()> {
+    let config = NodeConfig::load(&config_path)?;
+    let file = shellexpand::tilde(&file).to_string();
+    let batches = replay::run(config, std::path::Path::new(&file))?;
+    
+    let mut lines = String::new();
+    for batch in &batches {
+        lines.push_str(&serde_json::to_string(batch)?);
+        lines.push('\n');
+    }
+    
+    match output {
+        Some(path) => {
+            std::fs::write(shellexpand::tilde(&path).to_string(), lines)?;
+            info!("✅ Wrote {} batches to {}", batches.len(), path);
+        }
+        None => print!("{}", lines),
+    }
+    
+    Ok(())
+}
+
 async fn show_status(api: String) -> Result<()> {
     let client = reqwest::Client::new();
     let response = client.get(format!("{}/status", api)).send().await?;
@@ -82,9 +82,8 @@ async fn submit_single_feed(
     feed.price.serialize(&mut data)?;
     (feed.confidence.abs() as u64).serialize(&mut data)?;
     
-    // Determine decimals based on symbol
-    let expo = get_expo_for_symbol(symbol);
-    expo.serialize(&mut data)?;
+    // The program rescales to the feed's own decimals
+    feed.expo.serialize(&mut data)?;
     
     keypair.pubkey().serialize(&mut data)?;
     
@@ -119,31 +118,3 @@ async fn submit_single_feed(
     
     Ok(signature.to_string())
 }
-
-/// Get the exponent (decimal places) for a symbol
-/// This is the only place that needs updating when adding new symbols
-fn get_expo_for_symbol(symbol: &str) -> i32 {
-    match symbol {
-        // Crypto (8 decimals)
-        s if s.starts_with("BTC/") => -8,
-        s if s.starts_with("ETH/") => -8,
-        s if s.starts_with("SOL/") => -8,
-        
-        // Native tokens (9 decimals)
-        s if s.starts_with("XNT/") => -9,
-        s if s.starts_with("TACH/") => -9,
-        
-        // Forex (6 decimals)
-        s if s.starts_with("EUR/") => -6,
-        s if s.starts_with("GBP/") => -6,
-        s if s.starts_with("JPY/") => -6,
-        
-        // Commodities (6 decimals)
-        s if s.starts_with("XAU/") => -6, // Gold
-        s if s.starts_with("XAG/") => -6, // Silver
-        s if s.starts_with("WTI/") => -6, // Oil
-        
-        // Default: 8 decimals
-        _ => -8,
-    }
-}
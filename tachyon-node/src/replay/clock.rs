@@ -0,0 +1,186 @@
+// Clock - wall time for the pipeline, real or simulated
+//
+// The aggregator timestamps batches, numbers them and ticks from this clock.
+// A live node reads the system time and ticks on tokio timers. Replay starts
+// a simulated clock at the first recorded event and moves it itself: time
+// only passes when replay advances it, one batch tick at a time, so a
+// recording produces the same batches however fast it is replayed.
+
+use std::sync::Arc;
+use anyhow::Result;
+use tokio::sync::watch;
+use tokio::time::{interval_at, Duration, Instant, Interval};
+
+#[derive(Debug, Clone)]
+pub enum Clock {
+    System,
+    /// Unix time that replay moves forward
+    Simulated(Arc<SimulatedTime>),
+}
+
+/// Simulated unix time and the tick the pipeline is waiting for
+#[derive(Debug)]
+pub struct SimulatedTime {
+    now_ms: watch::Sender<i64>,
+    /// Next tick's time, once a ticker waits on it
+    waiting: watch::Sender<Option<i64>>,
+}
+
+impl Clock {
+    /// Clock reading `start_ms` until it is advanced
+    pub fn simulated(start_ms: i64) -> Self {
+        Clock::Simulated(Arc::new(SimulatedTime {
+            now_ms: watch::channel(start_ms).0,
+            waiting: watch::channel(None).0,
+        }))
+    }
+
+    /// Unix time in milliseconds
+    pub fn now_ms(&self) -> i64 {
+        match self {
+            Clock::System => chrono::Utc::now().timestamp_millis(),
+            Clock::Simulated(time) => *time.now_ms.borrow(),
+        }
+    }
+
+    /// Unix time in seconds
+    pub fn now(&self) -> i64 {
+        self.now_ms().div_euclid(1000)
+    }
+
+    /// Ticks on every boundary of `period_ms` in this clock's time,
+    /// starting with the next one
+    pub fn interval(&self, period_ms: u64) -> Ticker {
+        let period_ms = period_ms.max(1);
+        let to_boundary = period_ms - self.now_ms().rem_euclid(period_ms as i64) as u64;
+
+        match self {
+            Clock::System => Ticker::System(interval_at(
+                Instant::now() + Duration::from_millis(to_boundary),
+                Duration::from_millis(period_ms),
+            )),
+            Clock::Simulated(time) => Ticker::Simulated {
+                time: Arc::clone(time),
+                next_ms: self.now_ms() + to_boundary as i64,
+                period_ms: period_ms as i64,
+            },
+        }
+    }
+
+    /// Time of the tick the pipeline waits for, once it waits. `None` on the
+    /// system clock, which nobody advances.
+    pub async fn next_tick(&self) -> Option<i64> {
+        let Clock::Simulated(time) = self else {
+            return None;
+        };
+        let mut waiting = time.waiting.subscribe();
+        waiting.wait_for(Option::is_some).await.ok().and_then(|next| *next)
+    }
+
+    /// Move simulated time to `to_ms` and, if that reaches the waiting tick,
+    /// wait until the ticker has handled it and waits for the next one
+    pub async fn advance(&self, to_ms: i64) -> Result<()> {
+        let Clock::Simulated(time) = self else {
+            return Err(anyhow::anyhow!("The system clock cannot be advanced"));
+        };
+        let mut waiting = time.waiting.subscribe();
+        let tick = *waiting.borrow_and_update();
+        time.now_ms.send_if_modified(|now_ms| {
+            let moved = to_ms > *now_ms;
+            *now_ms = (*now_ms).max(to_ms);
+            moved
+        });
+
+        if let Some(tick) = tick.filter(|tick| *tick <= to_ms) {
+            waiting.wait_for(|waiting| waiting.is_some_and(|next| next > tick)).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Batch ticks from a `Clock`
+pub enum Ticker {
+    System(Interval),
+    Simulated {
+        time: Arc<SimulatedTime>,
+        next_ms: i64,
+        period_ms: i64,
+    },
+}
+
+impl Ticker {
+    /// Wait for the next tick. Cancel safe: a tick that was not taken is
+    /// still pending on the next call.
+    pub async fn tick(&mut self) {
+        match self {
+            Ticker::System(interval) => {
+                interval.tick().await;
+            }
+            Ticker::Simulated { time, next_ms, period_ms } => {
+                time.waiting.send_replace(Some(*next_ms));
+                let mut now_ms = time.now_ms.subscribe();
+                let deadline = *next_ms;
+                // The clock lives as long as this ticker holds it
+                let _ = now_ms.wait_for(|now_ms| *now_ms >= deadline).await;
+                *next_ms += *period_ms;
+            }
+        }
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        // Nothing will wait on this clock again; don't keep replay waiting
+        if let Ticker::Simulated { time, .. } = self {
+            time.waiting.send_replace(Some(i64::MAX));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_simulated_clock_moves_when_advanced() {
+        let clock = Clock::simulated(1_700_000_000_500);
+        assert_eq!((clock.now_ms(), clock.now()), (1_700_000_000_500, 1_700_000_000));
+
+        // Nobody waits on a tick: the time just moves
+        clock.advance(1_700_000_001_100).await.unwrap();
+        assert_eq!((clock.now_ms(), clock.now()), (1_700_000_001_100, 1_700_000_001));
+
+        // Never backwards
+        clock.advance(1_700_000_000_000).await.unwrap();
+        assert_eq!(clock.now_ms(), 1_700_000_001_100);
+        assert!(Clock::System.advance(0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_advance_waits_for_each_tick() {
+        let clock = Clock::simulated(1_050);
+        let mut ticker = clock.interval(100);
+        let (tick_tx, mut tick_rx) = tokio::sync::mpsc::unbounded_channel();
+        let ticking = {
+            let clock = clock.clone();
+            tokio::spawn(async move {
+                loop {
+                    ticker.tick().await;
+                    tick_tx.send(clock.now_ms()).unwrap();
+                }
+            })
+        };
+
+        // Ticks land on the boundaries, and each is handled before advance returns
+        assert_eq!(clock.next_tick().await, Some(1_100));
+        clock.advance(1_100).await.unwrap();
+        assert_eq!(tick_rx.try_recv().unwrap(), 1_100);
+
+        assert_eq!(clock.next_tick().await, Some(1_200));
+        clock.advance(1_150).await.unwrap();
+        assert!(tick_rx.try_recv().is_err());
+        clock.advance(1_200).await.unwrap();
+        assert_eq!(tick_rx.try_recv().unwrap(), 1_200);
+        ticking.abort();
+    }
+}
@@ -0,0 +1,163 @@
+// Recording - what the fetcher saw and sent, one JSON object per line
+//
+// Every source answer (the response as the source sent it, the ticker read
+// from it or why there was none) and every `PriceUpdate` handed to the
+// aggregator is written with the wall time it happened at. Replay feeds the
+// updates back in; the source answers explain where each update came from.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::fetcher::stats::FetchOutcome;
+use crate::fetcher::{PriceUpdate, SourceFetch};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Record {
+    /// One source's answer for one asset
+    Source {
+        /// Unix time in milliseconds
+        at_ms: i64,
+        asset: String,
+        source: String,
+        /// Currency of the ticker's prices
+        quote: String,
+        latency_ms: u64,
+        /// Response as the source sent it, if it keeps one
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        body: Option<String>,
+        outcome: FetchOutcome,
+    },
+    /// An update sent to the aggregator
+    Update {
+        /// Unix time in milliseconds
+        at_ms: i64,
+        update: PriceUpdate,
+    },
+}
+
+/// Appends records to a file as they happen
+pub struct Recorder {
+    file: Mutex<BufWriter<File>>,
+}
+
+impl Recorder {
+    /// Start a new recording at `path`, replacing any file there
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path).with_context(|| format!("Cannot create recording {}", path.display()))?;
+        Ok(Self { file: Mutex::new(BufWriter::new(file)) })
+    }
+
+    /// Every source answer for `asset` from one fetch
+    pub fn sources(&self, asset: &str, fetches: &[SourceFetch]) -> Result<()> {
+        let at_ms = chrono::Utc::now().timestamp_millis();
+        for fetch in fetches {
+            self.write(&Record::Source {
+                at_ms,
+                asset: asset.to_string(),
+                source: fetch.source.clone(),
+                quote: fetch.quote.clone(),
+                latency_ms: fetch.latency.as_millis() as u64,
+                body: fetch.body.clone(),
+                outcome: fetch.outcome.clone(),
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn update(&self, update: &PriceUpdate) -> Result<()> {
+        self.write(&Record::Update {
+            at_ms: chrono::Utc::now().timestamp_millis(),
+            update: update.clone(),
+        })
+    }
+
+    /// Written through, so a crash loses at most the line being written
+    fn write(&self, record: &Record) -> Result<()> {
+        let line = serde_json::to_string(record)?;
+        let mut file = self.file.lock().map_err(|_| anyhow::anyhow!("Recording lock poisoned"))?;
+        writeln!(file, "{}", line)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Every record in a recording, in the order they were written
+pub fn read_recording(path: impl AsRef<Path>) -> Result<Vec<Record>> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("Cannot open recording {}", path.display()))?;
+
+    let mut records = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .with_context(|| format!("{}:{}: invalid record", path.display(), number + 1))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::source::{px, Ticker};
+    use tachyon_merkle::Price;
+    use tokio::time::Duration;
+
+    #[test]
+    fn test_recording_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording.jsonl");
+        let recorder = Recorder::create(&path).unwrap();
+
+        let ticker = Ticker { bid: Some(px("64999.5")), ask: Some(px("65000.5")), ..Ticker::default() };
+        let body = r#"{"result":{"XXBTZUSD":{"b":["64999.5"],"a":["65000.5"]}}}"#;
+        recorder.sources("BTC/USD", &[
+            SourceFetch {
+                source: "kraken".to_string(),
+                quote: "USD".to_string(),
+                latency: Duration::from_millis(42),
+                received_at: 1_700_000_000,
+                body: Some(body.to_string()),
+                outcome: FetchOutcome::Ticker(ticker),
+            },
+            SourceFetch {
+                source: "binance".to_string(),
+                quote: "USDT".to_string(),
+                latency: Duration::from_millis(800),
+                received_at: 1_700_000_001,
+                body: None,
+                outcome: FetchOutcome::TimedOut,
+            },
+        ]).unwrap();
+        let update = PriceUpdate {
+            asset: "BTC/USD".to_string(),
+            price: Price::new(6_500_000_000_000, -8),
+            confidence: Price::new(50_000_000, -8),
+            timestamp: 1_700_000_000,
+            exchange: "aggregated".to_string(),
+            node_pubkey: "node1".to_string(),
+        };
+        recorder.update(&update).unwrap();
+        drop(recorder);
+
+        let records = read_recording(&path).unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(matches!(&records[0], Record::Source { source, latency_ms: 42, body: Some(sent), outcome: FetchOutcome::Ticker(t), .. }
+            if source == "kraken" && sent == body && *t == ticker));
+        assert!(matches!(&records[1], Record::Source { body: None, outcome: FetchOutcome::TimedOut, .. }));
+        let (Record::Source { at_ms: fetched_at, .. }, Record::Update { at_ms: sent_at, update: read }) = (&records[0], &records[2]) else {
+            panic!("not a source answer and an update");
+        };
+        assert_eq!((read.price, read.confidence, read.timestamp), (update.price, update.confidence, update.timestamp));
+        assert!(fetched_at <= sent_at);
+    }
+}
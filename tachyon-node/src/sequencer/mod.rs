@@ -1,50 +1,125 @@
 use std::sync::Arc;
+use solana_address_lookup_table_interface::instruction as lookup_table;
+use solana_address_lookup_table_interface::program as lookup_table_program;
+use solana_address_lookup_table_interface::state::{AddressLookupTable, LOOKUP_TABLE_MAX_ADDRESSES};
 use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcProgramAccountsConfig;
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_message::{v0, AddressLookupTableAccount, VersionedMessage};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    ed25519_program,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
-    signature::Signer,
-    transaction::Transaction,
+    signature::{Keypair, Signer},
+    sysvar,
+    transaction::{Transaction, VersionedTransaction},
 };
 use std::str::FromStr;
-use tokio::sync::mpsc;
-use tracing::{info, error};
+use tokio::sync::{mpsc, RwLock};
+use tracing::{info, error, warn};
 use borsh::BorshSerialize;
 
+use crate::api::NodeStatus;
+use crate::api::stream::StreamEvent;
 use crate::config::NodeConfig;
-use crate::consensus::ConsensusResult;
+use crate::consensus::{ConsensusResult, Vote};
+use crate::consensus::equivocation::EquivocationProof;
+use crate::consensus::validator_set::staker_info_address;
+use crate::ledger::oracle_ledger::{ConsensusRecord, OracleLedger, SubmissionRecord};
+use tachyon_merkle::ed25519::vote_signatures_data;
+
+/// Where the authority sits in a lookup table account: after the u32 state
+/// tag, two slots, a start index and the `Option` tag
+const LOOKUP_TABLE_AUTHORITY_OFFSET: usize = 22;
+
+/// Addresses added per extend transaction, well inside the packet limit
+const LOOKUP_TABLE_EXTEND_CHUNK: usize = 20;
 
 pub async fn start_sequencer(
     config: Arc<NodeConfig>,
     mut consensus_rx: mpsc::Receiver<ConsensusResult>,
+    mut evidence_rx: mpsc::Receiver<EquivocationProof>,
+    ledger: Arc<OracleLedger>,
+    status: Arc<RwLock<NodeStatus>>,
+    events: tokio::sync::broadcast::Sender<StreamEvent>,
     mut shutdown: tokio::sync::broadcast::Receiver<()>,
 ) -> anyhow::Result<()> {
     info!("🚀 Starting sequencer...");
     
-    let rpc_client = RpcClient::new_with_commitment(
+    // The RPC client blocks, so every chain call runs through spawn_blocking
+    let rpc_client = Arc::new(RpcClient::new_with_commitment(
         config.rpc_url.clone(),
         CommitmentConfig::confirmed(),
-    );
+    ));
     
     let program_id = Pubkey::from_str(&config.l2_program_id)?;
+    let mut lookup_table = None;
     
     loop {
         tokio::select! {
             Some(result) = consensus_rx.recv() => {
+                if let Err(e) = ConsensusRecord::from_result(&result)
+                    .and_then(|record| ledger.store_consensus(&record))
+                {
+                    warn!("Failed to write consensus for batch {} to ledger: {}", result.batch.batch_number, e);
+                }
+                
+                status.write().await.is_leader = result.is_leader;
+                
                 // Only submit if this node is the leader
                 if !result.is_leader {
+                    publish_finalized(&events, &result, None);
+                    continue;
+                }
+                
+                // Without 2/3 of the stake the program would reject the root,
+                // and we can only submit a root whose feeds we hold
+                if result.consensus_root.as_ref() != Some(&result.batch.root) {
+                    publish_finalized(&events, &result, None);
                     continue;
                 }
                 
                 info!("🚀 Submitting Merkle root to X1: {}", &result.batch.root[..8]);
                 
-                // Submit Merkle root
-                match submit_to_chain(&rpc_client, &config, &program_id, &result).await {
+                // Submit Merkle root, handing the cached lookup table to the
+                // blocking task and taking it back afterwards
+                let result = Arc::new(result);
+                let submission = {
+                    let (rpc_client, config, result) = (Arc::clone(&rpc_client), Arc::clone(&config), Arc::clone(&result));
+                    let mut table = lookup_table.take();
+                    tokio::task::spawn_blocking(move || {
+                        let submitted = submit_to_chain(&rpc_client, &config, &program_id, &result, &mut table);
+                        (submitted, table)
+                    }).await
+                };
+                let submitted = match submission {
+                    Ok((submitted, table)) => {
+                        lookup_table = table;
+                        submitted
+                    }
+                    Err(e) => Err(anyhow::anyhow!("Submission task failed: {}", e)),
+                };
+                
+                match submitted {
                     Ok(signature) => {
                         info!("✅ Merkle root submitted! Tx: {}", signature);
+                        status.write().await.batches_submitted += 1;
+                        publish_finalized(&events, &result, Some(signature.clone()));
+                        
+                        let record = SubmissionRecord {
+                            batch_number: result.batch.batch_number,
+                            signature,
+                            submitted_at: chrono::Utc::now().timestamp(),
+                        };
+                        if let Err(e) = ledger.store_submission(&record) {
+                            warn!("Failed to write submission for batch {} to ledger: {}", record.batch_number, e);
+                        }
                     }
                     Err(e) => {
                         error!("❌ Failed to submit Merkle root: {}", e);
+                        publish_finalized(&events, &result, None);
                     }
                 }
                 
@@ -65,6 +140,22 @@ pub async fn start_sequencer(
                     }
                 }
             }
+            Some(proof) = evidence_rx.recv() => {
+                info!("🚨 Submitting equivocation proof for {} at batch {}", proof.validator(), proof.batch_number());
+                
+                let submission = {
+                    let (rpc_client, config) = (Arc::clone(&rpc_client), Arc::clone(&config));
+                    tokio::task::spawn_blocking(move || submit_equivocation_proof(&rpc_client, &config, &proof)).await
+                };
+                match submission.unwrap_or_else(|e| Err(anyhow::anyhow!("Submission task failed: {}", e))) {
+                    Ok(signature) => {
+                        info!("✅ Equivocation slashed! Tx: {}", signature);
+                    }
+                    Err(e) => {
+                        error!("❌ Failed to submit equivocation proof: {}", e);
+                    }
+                }
+            }
             _ = shutdown.recv() => {
                 info!("🚀 Sequencer shutting down...");
                 break;
@@ -75,12 +166,60 @@ pub async fn start_sequencer(
     Ok(())
 }
 
-async fn submit_to_chain(
+/// Tell stream subscribers, and the aggregator, that consensus closed on a batch
+pub fn publish_finalized(
+    events: &tokio::sync::broadcast::Sender<StreamEvent>,
+    result: &ConsensusResult,
+    signature: Option<String>,
+) {
+    let Some(consensus_root) = &result.consensus_root else {
+        return;
+    };
+    
+    // No stream subscribers is fine
+    let _ = events.send(StreamEvent::BatchFinalized {
+        batch_number: result.batch.batch_number,
+        consensus_root: consensus_root.clone(),
+        agreeing_stake: result.agreeing_stake,
+        total_stake: result.total_stake,
+        signature,
+    });
+}
+
+fn submit_to_chain(
     rpc_client: &RpcClient,
     config: &NodeConfig,
     program_id: &Pubkey,
     result: &ConsensusResult,
+    lookup_table: &mut Option<AddressLookupTableAccount>,
 ) -> anyhow::Result<String> {
+    let governance_program = Pubkey::from_str(&config.program_id)?;
+    let instructions = consensus_instructions(&config.identity.pubkey(), program_id, &governance_program, result)?;
+    
+    // Staker and state accounts go through our lookup table, which is what
+    // lets a 2/3 quorum fit in one transaction
+    let table = sync_lookup_table(rpc_client, &config.identity, lookup_table, &lookup_addresses(&instructions))?;
+    
+    // Get recent blockhash
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    
+    // Create and sign transaction
+    let transaction = consensus_transaction(&config.identity, &instructions, table.as_slice(), recent_blockhash)?;
+    
+    // Send transaction
+    let signature = rpc_client.send_and_confirm_transaction(&transaction)?;
+    
+    Ok(signature.to_string())
+}
+
+/// The Ed25519 instruction carrying the quorum's signatures, followed by
+/// `submit_root_with_consensus`
+fn consensus_instructions(
+    identity: &Pubkey,
+    program_id: &Pubkey,
+    governance_program: &Pubkey,
+    result: &ConsensusResult,
+) -> anyhow::Result<Vec<Instruction>> {
     // Convert root hash to bytes
     let root_bytes = hex::decode(&result.batch.root)?;
     if root_bytes.len() != 32 {
@@ -97,80 +236,310 @@ async fn submit_to_chain(
     );
     
     // Find governance state PDA
-    let governance_program = Pubkey::from_str(&config.program_id)?;
     let (governance_state_pda, _) = Pubkey::find_program_address(
         &[b"governance"],
-        &governance_program,
+        governance_program,
     );
     
     // Build instruction data for submit_root_with_consensus
-    // Parameters: root (32), feed_count (4), timestamp (8), total_stake (8), votes (Vec<ConsensusVote>)
-    
-    // Serialize using Borsh format (Anchor's default)
-    use borsh::BorshSerialize;
-    
-    #[derive(BorshSerialize)]
-    struct ConsensusVote {
-        validator: [u8; 32],
-        root: [u8; 32],
-        stake: u64,
-        signature: [u8; 64],
-    }
+    // Parameters: batch_number (8), root (32), feed_count (4), timestamp (8)
     
     #[derive(BorshSerialize)]
     struct SubmitRootParams {
+        batch_number: u64,
         root: [u8; 32],
         feed_count: u32,
         timestamp: i64,
-        total_stake: u64,
-        votes: Vec<ConsensusVote>,
     }
     
-    // Create our own vote (single validator)
-    let our_vote = ConsensusVote {
-        validator: config.identity.pubkey().to_bytes(),
-        root: root_array,
-        stake: result.total_stake,
-        signature: [0u8; 64], // TODO: Sign the root in production
+    // Every vote signs the same message, so one Ed25519 instruction carries
+    // them all; the program takes each signer's staker account in the same order
+    let mut signers = Vec::new();
+    let mut staker_accounts = Vec::new();
+    
+    for vote in quorum_votes(result) {
+        let validator = Pubkey::from_str(&vote.node_pubkey)?;
+        let signature: [u8; 64] = vote.signature.as_slice().try_into()
+            .map_err(|_| anyhow::anyhow!("Invalid signature length from {}", vote.node_pubkey))?;
+        
+        signers.push((validator.to_bytes(), signature));
+        staker_accounts.push(staker_info_address(&validator, governance_program));
+    }
+    
+    let message = tachyon_merkle::vote_message(result.batch.batch_number, &root_array);
+    let signatures = Instruction {
+        program_id: ed25519_program::ID,
+        accounts: vec![],
+        data: vote_signatures_data(&message, &signers)
+            .ok_or_else(|| anyhow::anyhow!("{} votes do not fit one Ed25519 instruction", signers.len()))?,
     };
     
     let params = SubmitRootParams {
+        batch_number: result.batch.batch_number,
         root: root_array,
         feed_count: result.batch.feeds.len() as u32,
         timestamp: result.batch.timestamp,
-        total_stake: result.total_stake,
-        votes: vec![our_vote], // Include our vote
     };
     
     let mut instruction_data = vec![0xd7, 0x29, 0x2e, 0xe9, 0x2c, 0x1b, 0x83, 0x05]; // Discriminator
     params.serialize(&mut instruction_data)?;
     
+    let mut accounts = vec![
+        AccountMeta::new(l2_state_pda, false),
+        AccountMeta::new_readonly(governance_state_pda, false),
+        AccountMeta::new_readonly(*identity, true),
+        AccountMeta::new_readonly(sysvar::instructions::ID, false),
+    ];
+    accounts.extend(staker_accounts.into_iter().map(|address| AccountMeta::new_readonly(address, false)));
+    
     // Create instruction for submit_root_with_consensus
-    let instruction = solana_sdk::instruction::Instruction {
+    let submit = Instruction {
         program_id: *program_id,
-        accounts: vec![
-            solana_sdk::instruction::AccountMeta::new(l2_state_pda, false),
-            solana_sdk::instruction::AccountMeta::new_readonly(governance_state_pda, false),
-            solana_sdk::instruction::AccountMeta::new_readonly(config.identity.pubkey(), true),
-            solana_sdk::instruction::AccountMeta::new_readonly(governance_program, false),
-        ],
+        accounts,
         data: instruction_data,
     };
     
-    // Get recent blockhash
-    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    Ok(vec![signatures, submit])
+}
+
+/// Signed v0 transaction for `instructions`, loading whatever accounts it can
+/// from `lookup_tables`
+fn consensus_transaction(
+    identity: &Keypair,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+) -> anyhow::Result<VersionedTransaction> {
+    let message = v0::Message::try_compile(&identity.pubkey(), instructions, lookup_tables, recent_blockhash)?;
+    Ok(VersionedTransaction::try_new(VersionedMessage::V0(message), &[identity])?)
+}
+
+/// Accounts of `instructions` a lookup table can supply: signers and the
+/// programs being invoked have to be in the transaction itself
+fn lookup_addresses(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut addresses: Vec<Pubkey> = Vec::new();
+    for meta in instructions.iter().flat_map(|ix| &ix.accounts) {
+        if !meta.is_signer && !addresses.contains(&meta.pubkey) {
+            addresses.push(meta.pubkey);
+        }
+    }
+    addresses.retain(|address| !instructions.iter().any(|ix| ix.program_id == *address));
+    addresses
+}
+
+/// Our lookup table as it can be used right now, extended on chain with
+/// whatever `wanted` it still lacks so later submissions find it there.
+/// Creates the table on first use; `cached` saves the lookups while the
+/// quorum's accounts are all in it.
+fn sync_lookup_table(
+    rpc_client: &RpcClient,
+    identity: &Keypair,
+    cached: &mut Option<AddressLookupTableAccount>,
+    wanted: &[Pubkey],
+) -> anyhow::Result<Option<AddressLookupTableAccount>> {
+    if let Some(table) = cached.as_ref() {
+        if wanted.iter().all(|address| table.addresses.contains(address)) {
+            return Ok(Some(table.clone()));
+        }
+    }
     
-    // Create and sign transaction
+    let authority = identity.pubkey();
+    let current = match cached.as_ref() {
+        Some(table) => Some(fetch_lookup_table(rpc_client, &table.key)?),
+        None => find_lookup_table(rpc_client, &authority)?,
+    };
+    
+    let present = current.as_ref().map_or(&[][..], |table| &table.addresses[..]);
+    let missing: Vec<Pubkey> = wanted.iter()
+        .filter(|address| !present.contains(address))
+        .copied()
+        .collect();
+    
+    // The extended table is what later submissions will find on chain
+    *cached = current.clone();
+    if !missing.is_empty() {
+        if present.len() + missing.len() > LOOKUP_TABLE_MAX_ADDRESSES {
+            warn!("Lookup table is full, {} accounts stay in the transaction", missing.len());
+        } else {
+            let mut addresses = present.to_vec();
+            addresses.extend_from_slice(&missing);
+            let key = extend_lookup_table(rpc_client, identity, current.as_ref().map(|table| table.key), missing)?;
+            *cached = Some(AddressLookupTableAccount { key, addresses });
+        }
+    }
+    
+    Ok(current)
+}
+
+/// Create our lookup table if `table` is `None`, then add `addresses` to it.
+/// Returns the table's address.
+fn extend_lookup_table(
+    rpc_client: &RpcClient,
+    identity: &Keypair,
+    table: Option<Pubkey>,
+    addresses: Vec<Pubkey>,
+) -> anyhow::Result<Pubkey> {
+    let authority = identity.pubkey();
+    let mut instructions = Vec::new();
+    let table = match table {
+        Some(table) => table,
+        None => {
+            let (create, table) = lookup_table::create_lookup_table(authority, authority, rpc_client.get_slot()?);
+            instructions.push(create);
+            table
+        }
+    };
+    
+    let count = addresses.len();
+    for chunk in addresses.chunks(LOOKUP_TABLE_EXTEND_CHUNK) {
+        instructions.push(lookup_table::extend_lookup_table(table, authority, Some(authority), chunk.to_vec()));
+        
+        let recent_blockhash = rpc_client.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&authority),
+            &[identity],
+            recent_blockhash,
+        );
+        rpc_client.send_and_confirm_transaction(&transaction)?;
+        instructions.clear();
+    }
+    
+    info!("🚀 Added {} accounts to lookup table {}", count, table);
+    Ok(table)
+}
+
+/// Active lookup table `authority` controls that still has room, if any
+fn find_lookup_table(rpc_client: &RpcClient, authority: &Pubkey) -> anyhow::Result<Option<AddressLookupTableAccount>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(LOOKUP_TABLE_AUTHORITY_OFFSET, authority.as_ref())),
+        ]),
+        ..Default::default()
+    };
+    
+    let accounts = rpc_client.get_program_accounts_with_config(&lookup_table_program::ID, config)?;
+    for (key, account) in accounts {
+        let table = AddressLookupTable::deserialize(&account.data)?;
+        if table.meta.deactivation_slot == u64::MAX && table.addresses.len() < LOOKUP_TABLE_MAX_ADDRESSES {
+            return Ok(Some(AddressLookupTableAccount { key, addresses: table.addresses.to_vec() }));
+        }
+    }
+    
+    Ok(None)
+}
+
+fn fetch_lookup_table(rpc_client: &RpcClient, key: &Pubkey) -> anyhow::Result<AddressLookupTableAccount> {
+    let account = rpc_client.get_account(key)?;
+    let table = AddressLookupTable::deserialize(&account.data)?;
+    Ok(AddressLookupTableAccount { key: *key, addresses: table.addresses.to_vec() })
+}
+
+fn submit_equivocation_proof(
+    rpc_client: &RpcClient,
+    config: &NodeConfig,
+    proof: &EquivocationProof,
+) -> anyhow::Result<String> {
+    if !proof.verify() {
+        return Err(anyhow::anyhow!("Equivocation proof does not verify"));
+    }
+    
+    let governance_program = Pubkey::from_str(&config.program_id)?;
+    let instructions = proof.slash_instructions(&governance_program, &config.identity.pubkey())?;
+    
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
     let transaction = Transaction::new_signed_with_payer(
-        &[instruction],
+        &instructions,
         Some(&config.identity.pubkey()),
         &[&config.identity],
         recent_blockhash,
     );
     
-    // Send transaction
     let signature = rpc_client.send_and_confirm_transaction(&transaction)?;
-    
     Ok(signature.to_string())
 }
 
+/// Highest-stake signed votes for the consensus root, just enough for 2/3.
+/// Every vote costs its signature and a staker account, so we keep the
+/// transaction small.
+fn quorum_votes(result: &ConsensusResult) -> Vec<&Vote> {
+    let mut votes: Vec<&Vote> = result.votes.values()
+        .filter(|vote| vote.batch_number == result.batch.batch_number)
+        .filter(|vote| Some(&vote.root_hash) == result.consensus_root.as_ref() && vote.verify())
+        .collect();
+    votes.sort_by(|a, b| b.stake.cmp(&a.stake).then_with(|| a.node_pubkey.cmp(&b.node_pubkey)));
+    
+    let quorum = (result.total_stake as u128 * 2).div_ceil(3);
+    let mut selected = Vec::new();
+    let mut stake = 0u128;
+    for vote in votes {
+        if stake >= quorum {
+            break;
+        }
+        stake += vote.stake as u128;
+        selected.push(vote);
+    }
+    
+    selected
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregator::MerkleBatch;
+    use solana_sdk::signature::Signature;
+    use tachyon_merkle::ed25519::parse_vote_signatures;
+    
+    /// Largest serialized transaction the network accepts
+    const PACKET_DATA_SIZE: usize = 1232;
+    
+    #[test]
+    fn test_quorum_of_ten_fits_one_transaction() {
+        let validators: Vec<Keypair> = (0..10).map(|_| Keypair::new()).collect();
+        let root = hex::encode([7u8; 32]);
+        let result = ConsensusResult {
+            batch: MerkleBatch {
+                batch_number: 42,
+                root: root.clone(),
+                timestamp: 1_700_000_000,
+                feeds: vec![],
+                tree: vec![],
+            },
+            votes: validators.iter()
+                .map(|keypair| (keypair.pubkey().to_string(), Vote::new_signed(keypair, 42, &root, 100).unwrap()))
+                .collect(),
+            consensus_root: Some(root),
+            agreeing_stake: 1_000,
+            total_stake: 1_000,
+            is_leader: true,
+        };
+        
+        let identity = Keypair::new();
+        let instructions = consensus_instructions(
+            &identity.pubkey(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &result,
+        ).unwrap();
+        
+        // Two thirds of ten equal stakes takes seven votes, all in one instruction
+        let mut signed = Vec::new();
+        parse_vote_signatures(&instructions[0].data, &mut signed).unwrap();
+        assert_eq!(signed.len(), 7);
+        assert!(signed.iter().all(|vote| Signature::from(vote.signature).verify(&vote.pubkey, &vote.message)));
+        assert_eq!(instructions[1].accounts.len(), 4 + 7);
+        
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: lookup_addresses(&instructions),
+        };
+        let transaction = consensus_transaction(&identity, &instructions, &[table], Hash::default()).unwrap();
+        let size = bincode::serialize(&transaction).unwrap().len();
+        assert!(size <= PACKET_DATA_SIZE, "quorum transaction is {} bytes", size);
+        
+        // Until the table holds the accounts the quorum does not fit
+        let transaction = consensus_transaction(&identity, &instructions, &[], Hash::default()).unwrap();
+        assert!(bincode::serialize(&transaction).unwrap().len() > PACKET_DATA_SIZE);
+    }
+}